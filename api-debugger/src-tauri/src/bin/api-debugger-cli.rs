@@ -0,0 +1,150 @@
+//! API Debugger 命令行运行器 - 在 CI 中执行 GUI 中保存的请求集合
+//!
+//! 退出码：0 全部通过，1 存在失败的请求或断言，2 参数或文件错误。
+
+use api_debugger_lib::runner::{
+    run_collection, CollectionFile, Environment, ReportFormat, RunOptions, RunReport, Variables,
+};
+use clap::Parser;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// 在无界面环境中运行 API Debugger 集合
+#[derive(Parser, Debug)]
+#[command(name = "api-debugger-cli", version, about)]
+struct Cli {
+    /// 集合文件（单个集合、集合数组或应用导出的状态文件）
+    collection: PathBuf,
+
+    /// 仅运行指定名称的集合
+    #[arg(short = 'c', long = "collection-name")]
+    collection_name: Option<String>,
+
+    /// 仅运行指定名称的请求（可重复）
+    #[arg(short = 'r', long = "request")]
+    requests: Vec<String>,
+
+    /// 环境文件（可重复，后者覆盖前者）
+    #[arg(short = 'e', long = "env")]
+    env_files: Vec<PathBuf>,
+
+    /// 使用集合文件中指定名称的环境
+    #[arg(long = "env-name")]
+    env_name: Option<String>,
+
+    /// 变量覆盖，格式为 KEY=VALUE（可重复，优先级最高）
+    #[arg(long = "var", value_parser = parse_var)]
+    vars: Vec<(String, String)>,
+
+    /// 输出格式：text、json 或 junit
+    #[arg(short = 'f', long = "format", default_value = "text")]
+    format: ReportFormat,
+
+    /// 将报告写入文件而不是标准输出
+    #[arg(short = 'o', long = "output")]
+    output: Option<PathBuf>,
+
+    /// 覆盖所有请求的超时时间（毫秒）
+    #[arg(long = "timeout")]
+    timeout: Option<u64>,
+
+    /// 首个失败后停止运行
+    #[arg(long = "bail")]
+    bail: bool,
+}
+
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("变量格式应为 KEY=VALUE: {}", s))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<bool, Box<dyn std::error::Error>> {
+    let file = CollectionFile::load(&cli.collection)?;
+
+    // 变量优先级：集合文件中的环境 < 环境文件 < --var
+    let mut variables = Variables::new();
+    if let Some(name) = &cli.env_name {
+        let env = file
+            .environments
+            .iter()
+            .find(|e| &e.name == name)
+            .ok_or_else(|| format!("集合文件中不存在环境: {}", name))?;
+        env.apply_to(&mut variables);
+    }
+    for path in &cli.env_files {
+        Environment::load(path)?.apply_to(&mut variables);
+    }
+    variables.extend(cli.vars.iter().cloned());
+
+    let collections: Vec<_> = file
+        .collections
+        .iter()
+        .filter(|c| match &cli.collection_name {
+            Some(name) => &c.name == name,
+            None => true,
+        })
+        .collect();
+    if collections.is_empty() {
+        return Err(match &cli.collection_name {
+            Some(name) => format!("集合文件中不存在集合: {}", name).into(),
+            None => "没有可运行的集合".into(),
+        });
+    }
+
+    // 过滤条件写错时不能以“没有运行任何请求”的结果通过
+    let missing: Vec<&str> = cli
+        .requests
+        .iter()
+        .filter(|name| {
+            !collections
+                .iter()
+                .any(|c| c.requests.iter().any(|r| &r.name == *name))
+        })
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("所选集合中不存在请求: {}", missing.join(", ")).into());
+    }
+    if collections.iter().all(|c| c.requests.is_empty()) {
+        return Err("所选集合中没有请求".into());
+    }
+
+    let options = RunOptions {
+        request_filter: cli.requests.clone(),
+        timeout: cli.timeout,
+        bail: cli.bail,
+    };
+
+    let mut reports = Vec::new();
+    for collection in collections {
+        let report = run_collection(collection, &variables, &options).await;
+        let failed = report.requests.iter().any(|r| !r.passed());
+        reports.push(report);
+        if failed && cli.bail {
+            break;
+        }
+    }
+
+    let report = RunReport::from_collections(reports);
+    let rendered = report.render(cli.format);
+    match &cli.output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => print!("{}", rendered),
+    }
+
+    Ok(report.passed())
+}
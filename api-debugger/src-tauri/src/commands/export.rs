@@ -120,7 +120,7 @@ pub fn export_as_postman(
 }
 
 /// 生成简单的 UUID v4
-fn uuid_v4() -> String {
+pub(crate) fn uuid_v4() -> String {
     use rand::Rng;
     let mut rng = rand::thread_rng();
     let bytes: [u8; 16] = rng.gen();
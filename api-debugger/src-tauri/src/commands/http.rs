@@ -1,55 +1,37 @@
-use reqwest::{header::HeaderMap, Client, Method};
-use serde::{Deserialize, Serialize};
+use reqwest::Client;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
-use thiserror::Error;
-
-/// HTTP 请求错误类型
-#[derive(Error, Debug)]
-pub enum HttpError {
-    #[error("请求失败: {0}")]
-    RequestError(#[from] reqwest::Error),
-
-    #[error("无效的 HTTP 方法: {0}")]
-    InvalidMethod(String),
-
-    #[error("URL 解析错误: {0}")]
-    UrlParseError(String),
-
-    #[error("超时")]
-    Timeout,
-
-    #[error("JSON 解析错误: {0}")]
-    JsonError(#[from] serde_json::Error),
-}
-
-impl Serialize for HttpError {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(&self.to_string())
-    }
-}
-
-/// 响应数据结构
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ResponseData {
-    pub status: u16,
-    #[serde(rename = "statusText")]
-    pub status_text: String,
-    pub headers: HashMap<String, String>,
-    pub body: String,
-    #[serde(rename = "bodySize")]
-    pub body_size: usize,
-    pub duration: u64,
-    pub timestamp: u64,
-    pub error: Option<String>,
+use tauri::{AppHandle, Emitter, State};
+
+use super::query::{body_preview, spool_response_body, LARGE_BODY_THRESHOLD};
+use super::registry::RequestRegistry;
+use crate::http::{
+    execute_request_with_progress, HttpError, HttpRequestOptions, HttpVersionPreference,
+    ProgressCallback, ResponseData, RetryPolicy, TransferProgress,
+};
+use crate::utils::generate_random_string;
+
+/// 前端收到的进度事件（`http:progress`）
+#[derive(Debug, Clone, Serialize)]
+struct RequestProgressEvent {
+    #[serde(rename = "requestId")]
+    request_id: String,
+    #[serde(flatten)]
+    progress: TransferProgress,
 }
 
 /// 发送 HTTP 请求
+///
+/// 请求在独立任务中执行并登记到 [`RequestRegistry`]，前端可用同一个
+/// `request_id` 调用 `cancel_request` 取消；传输进度通过 `http:progress` 事件推送。
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn send_http_request(
+    app: AppHandle,
+    registry: State<'_, RequestRegistry>,
+    request_id: Option<String>,
     method: String,
     url: String,
     headers: HashMap<String, String>,
@@ -57,101 +39,59 @@ pub async fn send_http_request(
     timeout: u64,
     follow_redirects: bool,
     verify_ssl: bool,
+    http_version: Option<HttpVersionPreference>,
+    retry: Option<RetryPolicy>,
 ) -> Result<ResponseData, HttpError> {
-    let start = std::time::Instant::now();
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
-
-    // 解析 HTTP 方法
-    let method = match method.to_uppercase().as_str() {
-        "GET" => Method::GET,
-        "POST" => Method::POST,
-        "PUT" => Method::PUT,
-        "DELETE" => Method::DELETE,
-        "PATCH" => Method::PATCH,
-        "HEAD" => Method::HEAD,
-        "OPTIONS" => Method::OPTIONS,
-        other => return Err(HttpError::InvalidMethod(other.to_string())),
+    let request_id = request_id.unwrap_or_else(|| generate_random_string(16));
+    let options = HttpRequestOptions {
+        method,
+        url,
+        headers,
+        body,
+        timeout,
+        follow_redirects,
+        verify_ssl,
+        http_version: http_version.unwrap_or_default(),
+        retry,
     };
 
-    // 构建客户端
-    let client = Client::builder()
-        .timeout(Duration::from_millis(timeout))
-        .redirect(if follow_redirects {
-            reqwest::redirect::Policy::limited(10)
-        } else {
-            reqwest::redirect::Policy::none()
-        })
-        .danger_accept_invalid_certs(!verify_ssl)
-        .gzip(true)
-        .brotli(true)
-        .build()?;
-
-    // 构建请求头
-    let mut header_map = HeaderMap::new();
-    for (key, value) in &headers {
-        if let (Ok(name), Ok(val)) = (
-            key.parse::<reqwest::header::HeaderName>(),
-            value.parse::<reqwest::header::HeaderValue>(),
-        ) {
-            header_map.insert(name, val);
+    let event_id = request_id.clone();
+    let event_app = app.clone();
+    let on_progress: ProgressCallback = Arc::new(move |progress| {
+        let event = RequestProgressEvent {
+            request_id: event_id.clone(),
+            progress,
+        };
+        event_app.emit("http:progress", event).ok();
+    });
+
+    let task = tokio::spawn(execute_request_with_progress(options, Some(on_progress)));
+    let generation = registry.register(request_id.clone(), task.abort_handle());
+    let result = task.await;
+    registry.remove_if(&request_id, generation);
+
+    match result {
+        Ok(Ok(mut response)) => {
+            // 大响应体写入缓存文件后只返回开头部分，缓存失败时仍返回完整内容
+            if response.body_size > LARGE_BODY_THRESHOLD {
+                if let Ok(path) = spool_response_body(&app, &request_id, &response.body) {
+                    response.body = body_preview(&response.body).to_string();
+                    response.body_file = Some(path.to_string_lossy().to_string());
+                    response.body_truncated = true;
+                }
+            }
+            Ok(response)
         }
+        Ok(Err(e)) => Err(e),
+        Err(e) if e.is_cancelled() => Err(HttpError::Cancelled),
+        Err(e) => Err(HttpError::TaskError(e.to_string())),
     }
-
-    // 构建请求
-    let mut request = client.request(method, &url).headers(header_map);
-
-    // 添加请求体
-    if let Some(body_content) = body {
-        request = request.body(body_content);
-    }
-
-    // 发送请求
-    let response = request.send().await?;
-
-    // 获取响应信息
-    let status = response.status().as_u16();
-    let status_text = response
-        .status()
-        .canonical_reason()
-        .unwrap_or("Unknown")
-        .to_string();
-
-    // 获取响应头
-    let mut response_headers = HashMap::new();
-    for (key, value) in response.headers() {
-        if let Ok(v) = value.to_str() {
-            response_headers.insert(key.to_string(), v.to_string());
-        }
-    }
-
-    // 获取响应体
-    let body_bytes = response.bytes().await?;
-    let body_size = body_bytes.len();
-    let body = String::from_utf8_lossy(&body_bytes).to_string();
-
-    let duration = start.elapsed().as_millis() as u64;
-
-    Ok(ResponseData {
-        status,
-        status_text,
-        headers: response_headers,
-        body,
-        body_size,
-        duration,
-        timestamp,
-        error: None,
-    })
 }
 
 /// 测试连接
 #[tauri::command]
 pub async fn test_connection(url: String) -> Result<bool, HttpError> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()?;
+    let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
 
     let response = client.head(&url).send().await;
     Ok(response.is_ok())
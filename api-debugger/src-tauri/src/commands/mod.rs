@@ -1,7 +1,11 @@
 pub mod export;
 pub mod http;
+pub mod query;
+pub mod registry;
 pub mod storage;
 
 pub use export::*;
 pub use http::*;
+pub use query::*;
+pub use registry::*;
 pub use storage::*;
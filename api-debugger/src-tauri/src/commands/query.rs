@@ -0,0 +1,172 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+
+use super::export::uuid_v4;
+use crate::query::{self, QueryEngine, QueryError, QueryMatch};
+
+/// 超过该大小的响应体会写入缓存目录，查询时直接读取文件
+pub const LARGE_BODY_THRESHOLD: usize = 1024 * 1024;
+
+/// 缓存大响应体后，经 IPC 返回给前端的预览长度
+pub const BODY_PREVIEW_SIZE: usize = 64 * 1024;
+
+/// 缓存响应体的保留时间
+const RESPONSE_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 默认返回的最大匹配数
+const DEFAULT_QUERY_LIMIT: usize = 1000;
+
+/// 查询结果
+#[derive(Debug, Serialize)]
+pub struct QueryResult {
+    pub matches: Vec<QueryMatch>,
+    /// 匹配总数（可能大于返回的数量）
+    pub total: usize,
+    pub truncated: bool,
+}
+
+/// 获取响应体缓存目录
+fn get_response_cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, QueryError> {
+    let path = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| QueryError::PathError(e.to_string()))?
+        .join("responses");
+
+    if !path.exists() {
+        fs::create_dir_all(&path)?;
+    }
+
+    Ok(path)
+}
+
+/// 将大响应体写入缓存目录，返回文件路径
+///
+/// 写入前顺带清理过期的缓存文件。
+pub fn spool_response_body(
+    app: &tauri::AppHandle,
+    request_id: &str,
+    body: &str,
+) -> Result<PathBuf, QueryError> {
+    let cache_dir = get_response_cache_dir(app)?;
+    prune_response_cache(&cache_dir);
+
+    let file_path = cache_dir.join(spool_file_name(request_id));
+    fs::write(&file_path, body)?;
+    Ok(file_path)
+}
+
+/// 缓存文件名：请求 ID 中的安全字符加随机后缀，同一请求 ID 的多次响应不会互相覆盖
+fn spool_file_name(request_id: &str) -> String {
+    let safe_id: String = request_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    format!("{}-{}.body", safe_id, uuid_v4())
+}
+
+/// 截取响应体开头作为预览，不会截断多字节字符
+pub fn body_preview(body: &str) -> &str {
+    let mut end = BODY_PREVIEW_SIZE.min(body.len());
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    &body[..end]
+}
+
+/// 删除超过保留时间的缓存文件
+fn prune_response_cache(cache_dir: &Path) {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(|modified| now.duration_since(modified).unwrap_or_default() > RESPONSE_CACHE_TTL)
+            .unwrap_or(false);
+        if expired {
+            fs::remove_file(entry.path()).ok();
+        }
+    }
+}
+
+/// 读取缓存的响应体，仅允许访问缓存目录内的文件
+fn read_cached_body(app: &tauri::AppHandle, body_file: &str) -> Result<String, QueryError> {
+    let cache_dir = get_response_cache_dir(app)?.canonicalize()?;
+    let file_path = Path::new(body_file).canonicalize()?;
+
+    if !file_path.starts_with(&cache_dir) {
+        return Err(QueryError::PathError(format!(
+            "不允许访问该文件: {}",
+            body_file
+        )));
+    }
+
+    Ok(fs::read_to_string(file_path)?)
+}
+
+/// 对响应体执行 JSONPath / JMESPath / jq 查询
+///
+/// `body` 与 `body_file` 二选一；大响应体应传入 `send_http_request` 返回的 `bodyFile`，
+/// 避免再次经由 IPC 传输整个响应体。
+#[tauri::command]
+pub async fn query_response(
+    app: tauri::AppHandle,
+    engine: QueryEngine,
+    expression: String,
+    body: Option<String>,
+    body_file: Option<String>,
+    limit: Option<usize>,
+) -> Result<QueryResult, QueryError> {
+    let body = match (body, body_file) {
+        (_, Some(file)) => read_cached_body(&app, &file)?,
+        (Some(body), None) => body,
+        (None, None) => return Err(QueryError::InvalidExpression("缺少响应体".to_string())),
+    };
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+
+    // 解析与查询可能耗时较长，放到阻塞线程池中执行
+    tokio::task::spawn_blocking(move || {
+        let document: Value = serde_json::from_str(&body)?;
+        let mut matches = query::query(engine, &expression, &document)?;
+        let total = matches.len();
+        matches.truncate(limit);
+        Ok(QueryResult {
+            matches,
+            total,
+            truncated: total > limit,
+        })
+    })
+    .await
+    .map_err(|e| QueryError::EvaluationError(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_preview() {
+        assert_eq!(body_preview("{}"), "{}");
+
+        // 预览边界落在多字节字符中间时向前退到字符边界
+        let body = format!("{}中文", "a".repeat(BODY_PREVIEW_SIZE - 1));
+        let preview = body_preview(&body);
+        assert_eq!(preview.len(), BODY_PREVIEW_SIZE - 1);
+        assert!(body.starts_with(preview));
+    }
+
+    #[test]
+    fn test_spool_file_name() {
+        let first = spool_file_name("req/1");
+        assert!(first.starts_with("req1-") && first.ends_with(".body"));
+        // 同一请求 ID 的响应写入不同的文件
+        assert_ne!(first, spool_file_name("req/1"));
+    }
+}
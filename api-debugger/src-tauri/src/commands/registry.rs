@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::State;
+use tokio::task::AbortHandle;
+
+/// 进行中的请求登记表（由 Tauri 管理）
+#[derive(Default)]
+pub struct RequestRegistry {
+    /// 请求 ID -> (登记序号, 任务句柄)
+    requests: Mutex<HashMap<String, (u64, AbortHandle)>>,
+    next_generation: AtomicU64,
+}
+
+impl RequestRegistry {
+    /// 登记请求任务，返回本次登记的序号
+    pub fn register(&self, request_id: String, handle: AbortHandle) -> u64 {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let mut requests = self.requests.lock().unwrap();
+        if let Some((_, previous)) = requests.insert(request_id, (generation, handle)) {
+            // 同一 ID 重复发送时，放弃旧请求
+            previous.abort();
+        }
+        generation
+    }
+
+    /// 请求结束后移除登记
+    ///
+    /// 仅当登记序号一致时移除，避免旧请求结束时移除同 ID 新请求的登记。
+    pub fn remove_if(&self, request_id: &str, generation: u64) -> bool {
+        let mut requests = self.requests.lock().unwrap();
+        match requests.get(request_id) {
+            Some((current, _)) if *current == generation => {
+                requests.remove(request_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 取消指定请求，返回是否找到该请求
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.requests.lock().unwrap().remove(request_id) {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 取消全部请求，返回取消的数量
+    pub fn cancel_all(&self) -> usize {
+        let mut requests = self.requests.lock().unwrap();
+        let count = requests.len();
+        for (_, (_, handle)) in requests.drain() {
+            handle.abort();
+        }
+        count
+    }
+
+    /// 当前进行中的请求 ID
+    pub fn active_ids(&self) -> Vec<String> {
+        self.requests.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+/// 取消进行中的请求
+#[tauri::command]
+pub fn cancel_request(registry: State<'_, RequestRegistry>, request_id: String) -> bool {
+    registry.cancel(&request_id)
+}
+
+/// 取消全部进行中的请求
+#[tauri::command]
+pub fn cancel_all_requests(registry: State<'_, RequestRegistry>) -> usize {
+    registry.cancel_all()
+}
+
+/// 列出进行中的请求
+#[tauri::command]
+pub fn list_active_requests(registry: State<'_, RequestRegistry>) -> Vec<String> {
+    registry.active_ids()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let registry = RequestRegistry::default();
+        let task = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        registry.register("req-1".to_string(), task.abort_handle());
+        assert_eq!(registry.active_ids(), vec!["req-1".to_string()]);
+
+        assert!(registry.cancel("req-1"));
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(!registry.cancel("req-1"));
+        assert!(registry.active_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_same_id_requests() {
+        let registry = RequestRegistry::default();
+        let first = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        let second = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        let first_generation = registry.register("req-1".to_string(), first.abort_handle());
+        let second_generation = registry.register("req-1".to_string(), second.abort_handle());
+        assert!(first.await.unwrap_err().is_cancelled());
+
+        // 旧请求的清理不能移除新请求的登记
+        assert!(!registry.remove_if("req-1", first_generation));
+        assert_eq!(registry.active_ids(), vec!["req-1".to_string()]);
+
+        assert!(registry.cancel("req-1"));
+        assert!(second.await.unwrap_err().is_cancelled());
+        assert!(!registry.remove_if("req-1", second_generation));
+    }
+}
@@ -0,0 +1,512 @@
+//! HTTP 请求执行 - 不依赖 Tauri，GUI 命令与命令行运行器共用
+
+use reqwest::{header::HeaderMap, Client, ClientBuilder, Method, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+mod retry;
+
+pub use retry::*;
+
+/// 传输进度的上报粒度（字节）
+const PROGRESS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// HTTP 请求错误类型
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error("请求失败: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("无效的 HTTP 方法: {0}")]
+    InvalidMethod(String),
+
+    #[error("URL 解析错误: {0}")]
+    UrlParseError(String),
+
+    #[error("请求超时（{0} 毫秒）")]
+    Timeout(u64),
+
+    #[error("JSON 解析错误: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("不支持的协议版本: {0}")]
+    UnsupportedVersion(String),
+
+    #[error("请求已取消")]
+    Cancelled,
+
+    #[error("请求任务异常: {0}")]
+    TaskError(String),
+
+    #[error("共尝试 {} 次仍失败: {message}", .attempts.len())]
+    RetryFailed {
+        attempts: Vec<RetryAttempt>,
+        message: String,
+    },
+}
+
+impl Serialize for HttpError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        match self {
+            // 重试失败时附带每次尝试的记录，前端据此展示重试历史
+            HttpError::RetryFailed { attempts, .. } => {
+                let mut state = serializer.serialize_struct("HttpError", 2)?;
+                state.serialize_field("message", &self.to_string())?;
+                state.serialize_field("attempts", attempts)?;
+                state.end()
+            }
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+/// 响应数据结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseData {
+    pub status: u16,
+    #[serde(rename = "statusText")]
+    pub status_text: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    #[serde(rename = "bodySize")]
+    pub body_size: usize,
+    pub duration: u64,
+    pub timestamp: u64,
+    pub error: Option<String>,
+    /// 实际协商的协议版本，如 `HTTP/1.1`、`HTTP/2`
+    #[serde(rename = "httpVersion", default)]
+    pub http_version: String,
+    /// 大响应体在缓存目录中的副本，供 `query_response` 直接读取
+    #[serde(rename = "bodyFile", default)]
+    pub body_file: Option<String>,
+    /// `body` 是否只是完整响应体的开头部分（完整内容见 `body_file`）
+    #[serde(rename = "bodyTruncated", default)]
+    pub body_truncated: bool,
+    /// 每次尝试的记录（含重试）
+    #[serde(default)]
+    pub attempts: Vec<RetryAttempt>,
+}
+
+/// 请求使用的 HTTP 协议版本
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HttpVersionPreference {
+    /// 自动协商（TLS 下通过 ALPN 选择 HTTP/2 或 HTTP/1.1）
+    #[default]
+    Auto,
+    /// 仅使用 HTTP/1.1
+    Http1,
+    /// HTTP/2 先验知识（h2c，不经过协商直接发送 HTTP/2 帧）
+    Http2PriorKnowledge,
+    /// 通过 ALPN 协商 HTTP/2，服务端不支持时报错
+    Http2,
+    /// HTTP/3（需要启用 `http3` 特性）
+    Http3,
+}
+
+/// HTTP 请求参数
+#[derive(Debug, Clone)]
+pub struct HttpRequestOptions {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+    pub timeout: u64,
+    pub follow_redirects: bool,
+    pub verify_ssl: bool,
+    pub http_version: HttpVersionPreference,
+    pub retry: Option<RetryPolicy>,
+}
+
+/// 传输阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferPhase {
+    Upload,
+    Download,
+}
+
+/// 传输进度
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferProgress {
+    pub phase: TransferPhase,
+    pub loaded: u64,
+    pub total: Option<u64>,
+}
+
+/// 进度回调
+pub type ProgressCallback = Arc<dyn Fn(TransferProgress) + Send + Sync>;
+
+/// 执行 HTTP 请求
+pub async fn execute_request(options: HttpRequestOptions) -> Result<ResponseData, HttpError> {
+    execute_request_with_progress(options, None).await
+}
+
+/// 执行 HTTP 请求并上报上传/下载进度
+///
+/// 按请求的重试策略处理连接失败、超时与指定状态码，每次尝试都记录在
+/// 响应的 `attempts` 中；重试后仍失败时记录随 `HttpError::RetryFailed` 返回。
+pub async fn execute_request_with_progress(
+    options: HttpRequestOptions,
+    on_progress: Option<ProgressCallback>,
+) -> Result<ResponseData, HttpError> {
+    let policy = options.retry.clone().unwrap_or_else(RetryPolicy::disabled);
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempts = Vec::new();
+    let mut attempt = 1;
+
+    loop {
+        let start = std::time::Instant::now();
+        let result = send_once(options.clone(), on_progress.clone())
+            .await
+            .map_err(|e| classify_timeout(e, options.timeout));
+        let duration = start.elapsed().as_millis() as u64;
+
+        let can_retry = attempt < max_attempts;
+        let delay = match &result {
+            Ok(response) if can_retry && policy.should_retry_status(response.status) => {
+                Some(policy.delay_for(attempt, Some(&response.headers)))
+            }
+            Err(e) if can_retry && policy.should_retry_error(e) => {
+                Some(policy.delay_for(attempt, None))
+            }
+            _ => None,
+        };
+
+        attempts.push(RetryAttempt {
+            attempt,
+            status: result.as_ref().ok().map(|r| r.status),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            duration,
+            delay: delay.map(|d| d.as_millis() as u64).unwrap_or(0),
+        });
+
+        match delay {
+            Some(delay) => {
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            None => {
+                return match result {
+                    Ok(mut response) => {
+                        response.attempts = attempts;
+                        Ok(response)
+                    }
+                    Err(e) if attempt > 1 => Err(HttpError::RetryFailed {
+                        attempts,
+                        message: e.to_string(),
+                    }),
+                    Err(e) => Err(e),
+                };
+            }
+        }
+    }
+}
+
+/// reqwest 的超时错误转换为 `HttpError::Timeout`，其他错误原样返回
+fn classify_timeout(error: HttpError, timeout: u64) -> HttpError {
+    match error {
+        HttpError::RequestError(e) if e.is_timeout() => HttpError::Timeout(timeout),
+        other => other,
+    }
+}
+
+/// 发送单次请求
+async fn send_once(
+    options: HttpRequestOptions,
+    on_progress: Option<ProgressCallback>,
+) -> Result<ResponseData, HttpError> {
+    let HttpRequestOptions {
+        method,
+        url,
+        headers,
+        body,
+        timeout,
+        follow_redirects,
+        verify_ssl,
+        http_version,
+        ..
+    } = options;
+
+    let start = std::time::Instant::now();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    // 解析 HTTP 方法
+    let method = match method.to_uppercase().as_str() {
+        "GET" => Method::GET,
+        "POST" => Method::POST,
+        "PUT" => Method::PUT,
+        "DELETE" => Method::DELETE,
+        "PATCH" => Method::PATCH,
+        "HEAD" => Method::HEAD,
+        "OPTIONS" => Method::OPTIONS,
+        other => return Err(HttpError::InvalidMethod(other.to_string())),
+    };
+
+    // 构建客户端
+    let client = Client::builder()
+        .timeout(Duration::from_millis(timeout))
+        .redirect(if follow_redirects {
+            reqwest::redirect::Policy::limited(10)
+        } else {
+            reqwest::redirect::Policy::none()
+        })
+        .danger_accept_invalid_certs(!verify_ssl)
+        .gzip(true)
+        .brotli(true);
+    let client = apply_http_version(client, http_version)?.build()?;
+
+    // 构建请求头
+    let mut header_map = HeaderMap::new();
+    for (key, value) in &headers {
+        if let (Ok(name), Ok(val)) = (
+            key.parse::<reqwest::header::HeaderName>(),
+            value.parse::<reqwest::header::HeaderValue>(),
+        ) {
+            header_map.insert(name, val);
+        }
+    }
+
+    // 构建请求
+    let mut request = client.request(method, &url).headers(header_map);
+    if http_version == HttpVersionPreference::Http2 {
+        // 要求连接层通过 ALPN 协商出 HTTP/2，否则请求失败
+        request = request.version(Version::HTTP_2);
+    }
+
+    // 添加请求体
+    if let Some(body_content) = body {
+        request = match &on_progress {
+            Some(callback) if body_content.len() > PROGRESS_CHUNK_SIZE => {
+                // 指定长度，避免流式请求体退化为 chunked 编码
+                request
+                    .header(reqwest::header::CONTENT_LENGTH, body_content.len())
+                    .body(upload_body(body_content.into_bytes(), Arc::clone(callback)))
+            }
+            _ => request.body(body_content),
+        };
+    }
+
+    // 发送请求
+    let mut response = request.send().await?;
+
+    // 获取响应信息
+    let negotiated_version = version_name(response.version()).to_string();
+    let status = response.status().as_u16();
+    let status_text = response
+        .status()
+        .canonical_reason()
+        .unwrap_or("Unknown")
+        .to_string();
+
+    // 获取响应头
+    let mut response_headers = HashMap::new();
+    for (key, value) in response.headers() {
+        if let Ok(v) = value.to_str() {
+            response_headers.insert(key.to_string(), v.to_string());
+        }
+    }
+
+    // 获取响应体
+    let total = response.content_length();
+    let mut body_bytes: Vec<u8> = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut reported = 0;
+    while let Some(chunk) = response.chunk().await? {
+        body_bytes.extend_from_slice(&chunk);
+        if let Some(callback) = &on_progress {
+            if body_bytes.len() - reported >= PROGRESS_CHUNK_SIZE {
+                reported = body_bytes.len();
+                callback(TransferProgress {
+                    phase: TransferPhase::Download,
+                    loaded: reported as u64,
+                    total,
+                });
+            }
+        }
+    }
+    if let Some(callback) = &on_progress {
+        callback(TransferProgress {
+            phase: TransferPhase::Download,
+            loaded: body_bytes.len() as u64,
+            total,
+        });
+    }
+    let body_size = body_bytes.len();
+    let body = String::from_utf8_lossy(&body_bytes).to_string();
+
+    let duration = start.elapsed().as_millis() as u64;
+
+    Ok(ResponseData {
+        status,
+        status_text,
+        headers: response_headers,
+        body,
+        body_size,
+        duration,
+        timestamp,
+        error: None,
+        http_version: negotiated_version,
+        body_file: None,
+        body_truncated: false,
+        attempts: Vec::new(),
+    })
+}
+
+/// 将请求体拆分为数据块，在每块交给连接层时上报上传进度
+fn upload_body(data: Vec<u8>, on_progress: ProgressCallback) -> reqwest::Body {
+    let total = data.len() as u64;
+    let chunks: Vec<Vec<u8>> = data
+        .chunks(PROGRESS_CHUNK_SIZE)
+        .map(|c| c.to_vec())
+        .collect();
+    let mut loaded = 0u64;
+
+    let stream = futures_util::stream::iter(chunks.into_iter().map(move |chunk| {
+        loaded += chunk.len() as u64;
+        on_progress(TransferProgress {
+            phase: TransferPhase::Upload,
+            loaded,
+            total: Some(total),
+        });
+        Ok::<_, std::io::Error>(chunk)
+    }));
+
+    reqwest::Body::wrap_stream(stream)
+}
+
+/// 协议版本的显示名称（`Version` 的 Debug 输出为 `HTTP/2.0`，与常用写法不一致）
+fn version_name(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+}
+
+/// 根据协议版本偏好配置客户端
+fn apply_http_version(
+    builder: ClientBuilder,
+    preference: HttpVersionPreference,
+) -> Result<ClientBuilder, HttpError> {
+    match preference {
+        HttpVersionPreference::Auto | HttpVersionPreference::Http2 => Ok(builder),
+        HttpVersionPreference::Http1 => Ok(builder.http1_only()),
+        HttpVersionPreference::Http2PriorKnowledge => Ok(builder.http2_prior_knowledge()),
+        #[cfg(feature = "http3")]
+        HttpVersionPreference::Http3 => Ok(builder.http3_prior_knowledge()),
+        #[cfg(not(feature = "http3"))]
+        HttpVersionPreference::Http3 => Err(HttpError::UnsupportedVersion(
+            "HTTP/3 需要使用 http3 特性编译".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 启动只处理一个连接的 HTTP/1.1 服务，延迟 `delay` 后返回固定响应
+    async fn serve_once(delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let read = socket.read(&mut buf).await.unwrap();
+            assert!(read > 0);
+            tokio::time::sleep(delay).await;
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+        });
+        format!("http://{}/", addr)
+    }
+
+    fn options(url: String) -> HttpRequestOptions {
+        HttpRequestOptions {
+            method: "GET".to_string(),
+            url,
+            headers: HashMap::new(),
+            body: None,
+            timeout: 5000,
+            follow_redirects: true,
+            verify_ssl: true,
+            http_version: HttpVersionPreference::Http1,
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn test_version_name() {
+        assert_eq!(version_name(Version::HTTP_10), "HTTP/1.0");
+        assert_eq!(version_name(Version::HTTP_11), "HTTP/1.1");
+        assert_eq!(version_name(Version::HTTP_2), "HTTP/2");
+        assert_eq!(version_name(Version::HTTP_3), "HTTP/3");
+    }
+
+    #[tokio::test]
+    async fn test_response_version_and_duration() {
+        let url = serve_once(Duration::from_millis(50)).await;
+        let response = execute_request(options(url)).await.unwrap();
+        assert_eq!((response.status, response.body.as_str()), (200, "ok"));
+        assert_eq!(response.http_version, "HTTP/1.1");
+        assert!(response.duration >= 50, "duration {}", response.duration);
+        assert!(response.timestamp > 0);
+        assert_eq!(response.attempts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_failure_keeps_attempts() {
+        // 绑定后立即释放端口，连接会被拒绝
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mut options = options(url);
+        options.retry = Some(RetryPolicy {
+            max_attempts: 2,
+            base_delay: 1,
+            jitter: false,
+            ..Default::default()
+        });
+        let error = execute_request(options).await.unwrap_err();
+        let HttpError::RetryFailed { attempts, .. } = &error else {
+            panic!("unexpected error: {error}");
+        };
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts.iter().all(|a| a.error.is_some()));
+
+        let payload = serde_json::to_value(&error).unwrap();
+        assert_eq!(payload["attempts"].as_array().map(Vec::len), Some(2));
+        assert_eq!(payload["message"], error.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_error() {
+        let url = serve_once(Duration::from_millis(500)).await;
+        let mut options = options(url);
+        options.timeout = 50;
+        let error = execute_request(options).await.unwrap_err();
+        assert!(
+            matches!(error, HttpError::Timeout(50)),
+            "unexpected error: {error}"
+        );
+    }
+}
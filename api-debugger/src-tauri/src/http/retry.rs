@@ -0,0 +1,249 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::HttpError;
+
+/// 请求重试策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// 总尝试次数上限（含首次请求）
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// 连接失败时重试
+    #[serde(default = "default_true")]
+    pub retry_on_connect_error: bool,
+    /// 超时时重试
+    #[serde(default = "default_true")]
+    pub retry_on_timeout: bool,
+    /// 触发重试的状态码
+    #[serde(default = "default_status_codes")]
+    pub status_codes: Vec<u16>,
+    /// 首次重试前的基础等待时间（毫秒），之后按指数增长
+    #[serde(default = "default_base_delay")]
+    pub base_delay: u64,
+    /// 单次等待时间上限（毫秒），同样约束服务端要求的等待时间
+    #[serde(default = "default_max_delay")]
+    pub max_delay: u64,
+    /// 是否为退避时间加入随机抖动
+    #[serde(default = "default_true")]
+    pub jitter: bool,
+    /// 是否遵循 `Retry-After` 与 `X-RateLimit-Reset` 响应头
+    #[serde(default = "default_true")]
+    pub respect_retry_after: bool,
+}
+
+/// 单次尝试记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryAttempt {
+    /// 第几次尝试（从 1 开始）
+    pub attempt: u32,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// 本次尝试耗时（毫秒）
+    pub duration: u64,
+    /// 本次失败后等待的时间（毫秒），最后一次为 0
+    pub delay: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_status_codes() -> Vec<u16> {
+    vec![408, 429, 500, 502, 503, 504]
+}
+
+fn default_base_delay() -> u64 {
+    500
+}
+
+fn default_max_delay() -> u64 {
+    30_000
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            retry_on_connect_error: true,
+            retry_on_timeout: true,
+            status_codes: default_status_codes(),
+            base_delay: default_base_delay(),
+            max_delay: default_max_delay(),
+            jitter: true,
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试（仅发送一次）
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// 状态码是否需要重试
+    pub fn should_retry_status(&self, status: u16) -> bool {
+        self.status_codes.contains(&status)
+    }
+
+    /// 请求错误是否需要重试
+    pub fn should_retry_error(&self, error: &HttpError) -> bool {
+        match error {
+            HttpError::Timeout(_) => self.retry_on_timeout,
+            HttpError::RequestError(e) => self.retry_on_connect_error && e.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// 计算第 `attempt` 次尝试失败后的等待时间
+    ///
+    /// 服务端通过响应头给出等待时间时优先使用，否则按指数退避。
+    pub fn delay_for(&self, attempt: u32, headers: Option<&HashMap<String, String>>) -> Duration {
+        let max_delay = Duration::from_millis(self.max_delay);
+
+        if self.respect_retry_after {
+            if let Some(delay) = headers.and_then(server_requested_delay) {
+                return delay.min(max_delay);
+            }
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .base_delay
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay);
+
+        // 抖动范围为退避时间的一半到全部，避免多个客户端同时重试
+        let backoff = if self.jitter && backoff > 1 {
+            rand::thread_rng().gen_range(backoff / 2..=backoff)
+        } else {
+            backoff
+        };
+        Duration::from_millis(backoff)
+    }
+}
+
+/// 从响应头解析服务端要求的等待时间
+fn server_requested_delay(headers: &HashMap<String, String>) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+    };
+
+    if let Some(value) = header("retry-after") {
+        // 秒数或 HTTP 日期
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+            let target = date.timestamp_millis().max(0) as u64;
+            return Some(Duration::from_millis(target.saturating_sub(now_millis())));
+        }
+    }
+
+    if let Some(value) = header("x-ratelimit-reset") {
+        let reset = value.parse::<f64>().ok()?;
+        // `inf`、`NaN` 与负数无意义，交给指数退避处理
+        if !reset.is_finite() || reset < 0.0 {
+            return None;
+        }
+        let now = now_millis() as f64 / 1000.0;
+        // 大于当前时间戳的值视为重置时刻，否则视为剩余秒数
+        let seconds = if reset > now { reset - now } else { reset };
+        // 超出 `Duration` 范围的值按最大值处理，由调用方按 `max_delay` 截断
+        return Some(Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX));
+    }
+
+    None
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            base_delay: 100,
+            max_delay: 1000,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay_for(1, None), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(3, None), Duration::from_millis(400));
+        assert_eq!(policy.delay_for(10, None), Duration::from_millis(1000));
+
+        let jittered = RetryPolicy {
+            jitter: true,
+            ..policy
+        }
+        .delay_for(3, None);
+        assert!(jittered >= Duration::from_millis(200) && jittered <= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_rate_limit_headers() {
+        let policy = RetryPolicy::default();
+
+        let mut headers = HashMap::new();
+        headers.insert("Retry-After".to_string(), "2".to_string());
+        assert_eq!(policy.delay_for(1, Some(&headers)), Duration::from_secs(2));
+
+        let mut headers = HashMap::new();
+        let reset = now_millis() / 1000 + 5;
+        headers.insert("x-ratelimit-reset".to_string(), reset.to_string());
+        let delay = policy.delay_for(1, Some(&headers));
+        assert!(delay > Duration::from_secs(3) && delay <= Duration::from_secs(5));
+
+        // 服务端要求的等待时间同样受上限约束
+        let mut headers = HashMap::new();
+        headers.insert("retry-after".to_string(), "3600".to_string());
+        assert_eq!(
+            policy.delay_for(1, Some(&headers)),
+            Duration::from_millis(policy.max_delay)
+        );
+    }
+
+    #[test]
+    fn test_invalid_rate_limit_headers() {
+        let policy = RetryPolicy {
+            jitter: false,
+            base_delay: 100,
+            max_delay: 1000,
+            ..Default::default()
+        };
+        let delay_with = |value: &str| {
+            let mut headers = HashMap::new();
+            headers.insert("X-RateLimit-Reset".to_string(), value.to_string());
+            policy.delay_for(2, Some(&headers))
+        };
+
+        // 非有限值与负数回退到指数退避
+        assert_eq!(delay_with("inf"), Duration::from_millis(200));
+        assert_eq!(delay_with("NaN"), Duration::from_millis(200));
+        assert_eq!(delay_with("-5"), Duration::from_millis(200));
+        // 超大值不会溢出，按上限截断
+        assert_eq!(delay_with("1e30"), Duration::from_millis(1000));
+    }
+}
@@ -1,21 +1,35 @@
+#[cfg(feature = "gui")]
 pub mod commands;
+pub mod http;
+pub mod query;
+pub mod runner;
 pub mod utils;
 
+#[cfg(feature = "gui")]
 use commands::{
     export_as_json, export_as_postman, generate_curl,
     http::{send_http_request, test_connection},
+    query::query_response,
+    registry::{cancel_all_requests, cancel_request, list_active_requests, RequestRegistry},
     storage::{delete_data, list_data_files, load_data, save_data},
 };
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .manage(RequestRegistry::default())
         .invoke_handler(tauri::generate_handler![
             // HTTP 命令
             send_http_request,
             test_connection,
+            cancel_request,
+            cancel_all_requests,
+            list_active_requests,
+            // 查询命令
+            query_response,
             // 存储命令
             save_data,
             load_data,
@@ -0,0 +1,304 @@
+//! 响应查询引擎 - 对 JSON 响应执行 JSONPath / JMESPath / jq 过滤
+//!
+//! 同一套引擎用于界面查询、断言取值以及命令行运行器的变量提取。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// 查询语言
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryEngine {
+    /// JSONPath（RFC 9535），结果带规范化路径
+    #[default]
+    JsonPath,
+    /// JMESPath，结果是单个值，不带路径
+    JmesPath,
+    /// jq 兼容过滤器，每个输出一个结果，不带路径（输出可能是新构造的值，没有对应的位置）
+    Jq,
+}
+
+/// 查询错误类型
+#[derive(Error, Debug)]
+pub enum QueryError {
+    #[error("表达式无效: {0}")]
+    InvalidExpression(String),
+
+    #[error("查询执行失败: {0}")]
+    EvaluationError(String),
+
+    #[error("JSON 解析错误: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("文件操作错误: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("路径错误: {0}")]
+    PathError(String),
+}
+
+impl Serialize for QueryError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// 单个匹配结果
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryMatch {
+    /// 规范化路径（如 `$['data']['items'][0]`），仅 JSONPath 提供，JMESPath 与 jq 始终为 `None`
+    pub path: Option<String>,
+    pub value: Value,
+}
+
+/// 对 JSON 文档执行查询
+pub fn query(
+    engine: QueryEngine,
+    expression: &str,
+    document: &Value,
+) -> Result<Vec<QueryMatch>, QueryError> {
+    match engine {
+        QueryEngine::JsonPath => query_json_path(&normalize_json_path(expression), document),
+        QueryEngine::JmesPath => query_jmespath(expression, document),
+        QueryEngine::Jq => query_jq(expression, document),
+    }
+}
+
+/// 返回第一个匹配值（用于断言与变量提取）
+pub fn query_first(
+    engine: QueryEngine,
+    expression: &str,
+    document: &Value,
+) -> Result<Option<Value>, QueryError> {
+    Ok(query(engine, expression, document)?
+        .into_iter()
+        .next()
+        .map(|m| m.value))
+}
+
+/// 将简写的点号路径（`data.items.0.id`）转换为 JSONPath（`$.data.items[0].id`）
+pub fn normalize_json_path(expression: &str) -> String {
+    let expression = expression.trim();
+    if expression.starts_with('$') {
+        return expression.to_string();
+    }
+    if expression.is_empty() {
+        return "$".to_string();
+    }
+
+    let mut path = String::from("$");
+    for segment in expression.split('.') {
+        if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+            path.push_str(&format!("[{}]", segment));
+        } else {
+            path.push('.');
+            path.push_str(segment);
+        }
+    }
+    path
+}
+
+fn query_json_path(expression: &str, document: &Value) -> Result<Vec<QueryMatch>, QueryError> {
+    let path = serde_json_path::JsonPath::parse(expression)
+        .map_err(|e| QueryError::InvalidExpression(e.to_string()))?;
+
+    Ok(path
+        .query_located(document)
+        .into_iter()
+        .map(|node| QueryMatch {
+            path: Some(node.location().to_string()),
+            value: node.node().clone(),
+        })
+        .collect())
+}
+
+fn query_jmespath(expression: &str, document: &Value) -> Result<Vec<QueryMatch>, QueryError> {
+    let expr =
+        jmespath::compile(expression).map_err(|e| QueryError::InvalidExpression(e.to_string()))?;
+    let result = expr
+        .search(document)
+        .map_err(|e| QueryError::EvaluationError(e.to_string()))?;
+    let value = serde_json::to_value(&*result)?;
+
+    // JMESPath 的结果是单个值；null 表示没有匹配
+    if value.is_null() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![QueryMatch { path: None, value }])
+}
+
+fn query_jq(expression: &str, document: &Value) -> Result<Vec<QueryMatch>, QueryError> {
+    use jaq_core::load::{Arena, File, Loader};
+    use jaq_core::{Compiler, Ctx, RcIter};
+    use jaq_json::Val;
+
+    let program = File {
+        code: expression,
+        path: (),
+    };
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = loader
+        .load(&arena, program)
+        .map_err(|errs| jq_load_error(expression, errs))?;
+    let filter = Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(jq_compile_error)?;
+
+    let inputs = RcIter::new(core::iter::empty());
+    let outputs = filter.run((Ctx::new([], &inputs), Val::from(document.clone())));
+
+    outputs
+        .map(|output| {
+            output
+                .map(|val| QueryMatch {
+                    path: None,
+                    value: Value::from(val),
+                })
+                .map_err(|e| QueryError::EvaluationError(e.to_string()))
+        })
+        .collect()
+}
+
+/// 将 jq 的词法、语法错误转换为可读的说明
+fn jq_load_error(code: &str, errors: jaq_core::load::Errors<&str, ()>) -> QueryError {
+    use jaq_core::load::Error;
+
+    let messages: Vec<String> = errors
+        .into_iter()
+        .flat_map(|(_, error)| match error {
+            Error::Io(errs) => errs
+                .into_iter()
+                .map(|(path, e)| format!("{}: {}", path, e))
+                .collect(),
+            Error::Lex(errs) => errs
+                .into_iter()
+                .map(|(expect, found)| jq_expected(code, expect.as_str(), found))
+                .collect(),
+            Error::Parse(errs) => errs
+                .into_iter()
+                .map(|(expect, found)| jq_expected(code, expect.as_str(), found))
+                .collect::<Vec<_>>(),
+        })
+        .collect();
+    QueryError::InvalidExpression(messages.join("; "))
+}
+
+/// 将 jq 的未定义符号错误转换为可读的说明
+fn jq_compile_error(errors: jaq_core::compile::Errors<&str, ()>) -> QueryError {
+    let messages: Vec<String> = errors
+        .into_iter()
+        .flat_map(|(_, errs)| errs)
+        .map(|(name, undefined)| format!("未定义的 {}: {}", undefined.as_str(), name))
+        .collect();
+    QueryError::InvalidExpression(messages.join("; "))
+}
+
+/// `found` 是表达式中出错位置之后的部分，据此给出字符位置
+fn jq_expected(code: &str, expected: &str, found: &str) -> String {
+    let offset = (found.as_ptr() as usize)
+        .checked_sub(code.as_ptr() as usize)
+        .filter(|offset| *offset <= code.len() && code.is_char_boundary(*offset));
+    match offset {
+        Some(offset) if offset < code.len() => format!(
+            "第 {} 个字符处应为 {}",
+            code[..offset].chars().count() + 1,
+            expected
+        ),
+        Some(_) => format!("表达式意外结束，应为 {}", expected),
+        None => format!("应为 {}", expected),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "data": {
+                "items": [
+                    { "id": 1, "name": "a", "price": 5 },
+                    { "id": 2, "name": "b", "price": 15 }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_normalize_json_path() {
+        assert_eq!(normalize_json_path("data.items.0.id"), "$.data.items[0].id");
+        assert_eq!(normalize_json_path("$.data"), "$.data");
+    }
+
+    #[test]
+    fn test_json_path_with_locations() {
+        let matches = query(
+            QueryEngine::JsonPath,
+            "$.data.items[?@.price > 10].name",
+            &document(),
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].value, json!("b"));
+        assert_eq!(
+            matches[0].path.as_deref(),
+            Some("$['data']['items'][1]['name']")
+        );
+    }
+
+    #[test]
+    fn test_jmespath() {
+        let matches = query(
+            QueryEngine::JmesPath,
+            "data.items[?price > `10`].id",
+            &document(),
+        )
+        .unwrap();
+        assert_eq!(matches[0].value, json!([2]));
+        assert_eq!(matches[0].path, None);
+    }
+
+    #[test]
+    fn test_jq() {
+        let matches = query(
+            QueryEngine::Jq,
+            ".data.items[] | select(.price < 10) | .name",
+            &document(),
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].value, json!("a"));
+    }
+
+    #[test]
+    fn test_invalid_expression() {
+        assert!(matches!(
+            query(QueryEngine::JsonPath, "$[", &document()),
+            Err(QueryError::InvalidExpression(_))
+        ));
+
+        // jq 的错误是可读说明，而不是调试输出
+        let error = query(QueryEngine::Jq, ".data | (", &document())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "表达式无效: 表达式意外结束，应为 closing parenthesis"
+        );
+        let error = query(QueryEngine::Jq, ".a | & .b", &document())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("第 6 个字符处"), "{}", error);
+        let error = query(QueryEngine::Jq, "nosuchfilter", &document())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "表达式无效: 未定义的 filter: nosuchfilter");
+    }
+}
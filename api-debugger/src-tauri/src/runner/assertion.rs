@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::http::ResponseData;
+use crate::query::{self, QueryEngine, QueryError};
+
+/// 断言数据来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AssertionSource {
+    /// 响应状态码
+    Status,
+    /// 响应头（property 为头名称）
+    Header,
+    /// 响应体原文
+    Body,
+    /// JSON 响应体中的字段（property 为查询表达式，如 `$.data.items[0].id`）
+    JsonBody,
+    /// 响应耗时（毫秒）
+    ResponseTime,
+}
+
+/// 断言比较方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AssertionOperator {
+    Equals,
+    NotEquals,
+    Contains,
+    NotContains,
+    Exists,
+    NotExists,
+    LessThan,
+    GreaterThan,
+}
+
+/// 请求断言
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assertion {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub source: AssertionSource,
+    #[serde(default)]
+    pub property: String,
+    /// JsonBody 断言使用的查询语言
+    #[serde(default)]
+    pub engine: QueryEngine,
+    pub operator: AssertionOperator,
+    #[serde(default)]
+    pub expected: String,
+}
+
+/// 断言执行结果
+#[derive(Debug, Clone, Serialize)]
+pub struct AssertionResult {
+    pub name: String,
+    pub passed: bool,
+    pub actual: Option<String>,
+    pub message: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Assertion {
+    /// 断言的可读描述
+    pub fn describe(&self) -> String {
+        let source = match self.source {
+            AssertionSource::Status => "status".to_string(),
+            AssertionSource::Header => format!("header[{}]", self.property),
+            AssertionSource::Body => "body".to_string(),
+            AssertionSource::JsonBody => format!("json[{}]", self.property),
+            AssertionSource::ResponseTime => "responseTime".to_string(),
+        };
+        let operator = match self.operator {
+            AssertionOperator::Equals => "==",
+            AssertionOperator::NotEquals => "!=",
+            AssertionOperator::Contains => "contains",
+            AssertionOperator::NotContains => "not contains",
+            AssertionOperator::Exists => return format!("{} exists", source),
+            AssertionOperator::NotExists => return format!("{} not exists", source),
+            AssertionOperator::LessThan => "<",
+            AssertionOperator::GreaterThan => ">",
+        };
+        format!("{} {} {}", source, operator, self.expected)
+    }
+
+    /// 对响应执行断言
+    pub fn evaluate(&self, response: &ResponseData) -> AssertionResult {
+        // 表达式无效时断言失败，并带上解析器给出的原因
+        let actual = match self.extract(response) {
+            Ok(actual) => actual,
+            Err(e) => {
+                return AssertionResult {
+                    name: self.describe(),
+                    passed: false,
+                    actual: None,
+                    message: Some(e.to_string()),
+                }
+            }
+        };
+        let passed = compare(self.operator, actual.as_deref(), &self.expected);
+        let message = if passed {
+            None
+        } else {
+            Some(format!(
+                "期望 {}，实际为 {}",
+                self.describe(),
+                actual.as_deref().unwrap_or("<不存在>")
+            ))
+        };
+
+        AssertionResult {
+            name: self.describe(),
+            passed,
+            actual,
+            message,
+        }
+    }
+
+    /// 从响应中提取实际值
+    fn extract(&self, response: &ResponseData) -> Result<Option<String>, QueryError> {
+        let actual = match self.source {
+            AssertionSource::Status => Some(response.status.to_string()),
+            AssertionSource::Header => response
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(&self.property))
+                .map(|(_, v)| v.clone()),
+            AssertionSource::Body => Some(response.body.clone()),
+            AssertionSource::JsonBody => {
+                // 响应体不是 JSON 时视为字段不存在
+                let Ok(json) = serde_json::from_str::<Value>(&response.body) else {
+                    return Ok(None);
+                };
+                query::query_first(self.engine, &self.property, &json)?.map(value_to_string)
+            }
+            AssertionSource::ResponseTime => Some(response.duration.to_string()),
+        };
+        Ok(actual)
+    }
+}
+
+/// 比较实际值与期望值
+fn compare(operator: AssertionOperator, actual: Option<&str>, expected: &str) -> bool {
+    match (operator, actual) {
+        (AssertionOperator::Exists, actual) => actual.is_some(),
+        (AssertionOperator::NotExists, actual) => actual.is_none(),
+        (_, None) => {
+            operator == AssertionOperator::NotEquals || operator == AssertionOperator::NotContains
+        }
+        (AssertionOperator::Equals, Some(a)) => a == expected,
+        (AssertionOperator::NotEquals, Some(a)) => a != expected,
+        (AssertionOperator::Contains, Some(a)) => a.contains(expected),
+        (AssertionOperator::NotContains, Some(a)) => !a.contains(expected),
+        (AssertionOperator::LessThan, Some(a)) => {
+            matches!((a.parse::<f64>(), expected.parse::<f64>()), (Ok(a), Ok(e)) if a < e)
+        }
+        (AssertionOperator::GreaterThan, Some(a)) => {
+            matches!((a.parse::<f64>(), expected.parse::<f64>()), (Ok(a), Ok(e)) if a > e)
+        }
+    }
+}
+
+/// 将查询结果转换为字符串（字符串值不带引号）
+pub fn value_to_string(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn response(status: u16, body: &str) -> ResponseData {
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        ResponseData {
+            status,
+            status_text: "OK".to_string(),
+            headers,
+            body: body.to_string(),
+            body_size: body.len(),
+            duration: 120,
+            timestamp: 0,
+            error: None,
+            http_version: "HTTP/1.1".to_string(),
+            body_file: None,
+            body_truncated: false,
+            attempts: Vec::new(),
+        }
+    }
+
+    fn assertion(
+        source: AssertionSource,
+        property: &str,
+        operator: AssertionOperator,
+        expected: &str,
+    ) -> Assertion {
+        Assertion {
+            enabled: true,
+            source,
+            property: property.to_string(),
+            engine: QueryEngine::JsonPath,
+            operator,
+            expected: expected.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_status_and_header() {
+        let resp = response(200, "{}");
+        assert!(
+            assertion(
+                AssertionSource::Status,
+                "",
+                AssertionOperator::Equals,
+                "200"
+            )
+            .evaluate(&resp)
+            .passed
+        );
+        assert!(
+            assertion(
+                AssertionSource::Header,
+                "Content-Type",
+                AssertionOperator::Contains,
+                "json"
+            )
+            .evaluate(&resp)
+            .passed
+        );
+        assert!(
+            !assertion(
+                AssertionSource::ResponseTime,
+                "",
+                AssertionOperator::LessThan,
+                "100"
+            )
+            .evaluate(&resp)
+            .passed
+        );
+    }
+
+    #[test]
+    fn test_json_body() {
+        let resp = response(200, r#"{"data": {"items": [{"id": 7, "name": "a"}]}}"#);
+        assert!(
+            assertion(
+                AssertionSource::JsonBody,
+                "$.data.items[0].id",
+                AssertionOperator::Equals,
+                "7"
+            )
+            .evaluate(&resp)
+            .passed
+        );
+        assert!(
+            assertion(
+                AssertionSource::JsonBody,
+                "data.items.0.name",
+                AssertionOperator::Equals,
+                "a"
+            )
+            .evaluate(&resp)
+            .passed
+        );
+        assert!(
+            assertion(
+                AssertionSource::JsonBody,
+                "data.missing",
+                AssertionOperator::NotExists,
+                ""
+            )
+            .evaluate(&resp)
+            .passed
+        );
+
+        let mut jq = assertion(
+            AssertionSource::JsonBody,
+            ".data.items | length",
+            AssertionOperator::Equals,
+            "1",
+        );
+        jq.engine = QueryEngine::Jq;
+        assert!(jq.evaluate(&resp).passed);
+    }
+
+    #[test]
+    fn test_invalid_expression_fails() {
+        let resp = response(200, r#"{"data": null}"#);
+        // 表达式写错时即使是“不存在”断言也不能通过
+        let result = assertion(
+            AssertionSource::JsonBody,
+            "$[",
+            AssertionOperator::NotExists,
+            "",
+        )
+        .evaluate(&resp);
+        assert!(!result.passed);
+        assert!(result.message.unwrap().starts_with("表达式无效: "));
+    }
+}
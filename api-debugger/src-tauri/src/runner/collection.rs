@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::assertion::{value_to_string, Assertion};
+use super::RunnerError;
+use crate::http::{HttpRequestOptions, HttpVersionPreference, ResponseData, RetryPolicy};
+use crate::query::{self, QueryEngine};
+
+/// 键值对（与前端 KeyValuePair 一致）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyValuePair {
+    #[serde(default)]
+    pub id: String,
+    pub key: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// 请求配置（与前端 RequestConfig 一致）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestConfig {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<KeyValuePair>,
+    #[serde(default)]
+    pub query_params: Vec<KeyValuePair>,
+    #[serde(default = "default_body_type")]
+    pub body_type: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub form_data: Vec<KeyValuePair>,
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default = "default_true")]
+    pub follow_redirects: bool,
+    #[serde(default = "default_true")]
+    pub verify_ssl: bool,
+    #[serde(default)]
+    pub http_version: HttpVersionPreference,
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    /// 从响应中提取变量，供后续请求通过 `{{变量名}}` 引用
+    #[serde(default)]
+    pub extract: Vec<VariableExtraction>,
+}
+
+/// 变量提取规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariableExtraction {
+    pub variable: String,
+    #[serde(default)]
+    pub engine: QueryEngine,
+    pub expression: String,
+}
+
+/// 集合
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub requests: Vec<RequestConfig>,
+}
+
+/// 环境
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Environment {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub variables: Vec<KeyValuePair>,
+}
+
+/// 集合文件内容
+///
+/// 支持三种格式：单个集合、集合数组，以及前端持久化的整份状态
+/// （`{ "state": { "collections": [...], "environments": [...] } }`）。
+#[derive(Debug, Default)]
+pub struct CollectionFile {
+    pub collections: Vec<Collection>,
+    pub environments: Vec<Environment>,
+}
+
+/// 运行时变量表
+pub type Variables = HashMap<String, String>;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_body_type() -> String {
+    "none".to_string()
+}
+
+fn default_timeout() -> u64 {
+    30000
+}
+
+impl CollectionFile {
+    /// 从文件加载集合
+    pub fn load(path: &Path) -> Result<Self, RunnerError> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// 解析集合文件内容
+    pub fn parse(content: &str) -> Result<Self, RunnerError> {
+        let mut value: Value = serde_json::from_str(content)?;
+
+        if let Some(state) = value.get_mut("state") {
+            value = state.take();
+        }
+
+        if value.is_array() {
+            return Ok(Self {
+                collections: serde_json::from_value(value)?,
+                environments: Vec::new(),
+            });
+        }
+
+        if value.get("collections").is_some() {
+            let collections = serde_json::from_value(value["collections"].take())?;
+            let environments = match value.get_mut("environments") {
+                Some(envs) => serde_json::from_value(envs.take())?,
+                None => Vec::new(),
+            };
+            return Ok(Self {
+                collections,
+                environments,
+            });
+        }
+
+        Ok(Self {
+            collections: vec![serde_json::from_value(value)?],
+            environments: Vec::new(),
+        })
+    }
+}
+
+impl Environment {
+    /// 从文件加载环境
+    ///
+    /// 既支持前端的 Environment 结构，也支持简单的 `{ "key": "value" }` 对象。
+    pub fn load(path: &Path) -> Result<Self, RunnerError> {
+        let content = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&content)?;
+
+        if value.get("variables").is_some() {
+            return Ok(serde_json::from_value(value)?);
+        }
+
+        let object = value.as_object().ok_or_else(|| {
+            RunnerError::InvalidInput(format!("环境文件格式错误: {}", path.display()))
+        })?;
+
+        let variables = object
+            .iter()
+            .map(|(key, value)| KeyValuePair {
+                id: String::new(),
+                key: key.clone(),
+                value: match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                },
+                enabled: true,
+                description: None,
+            })
+            .collect();
+
+        Ok(Self {
+            id: String::new(),
+            name: path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            variables,
+        })
+    }
+
+    /// 将启用的变量合并到变量表
+    pub fn apply_to(&self, variables: &mut Variables) {
+        for var in self
+            .variables
+            .iter()
+            .filter(|v| v.enabled && !v.key.is_empty())
+        {
+            variables.insert(var.key.clone(), var.value.clone());
+        }
+    }
+}
+
+impl VariableExtraction {
+    /// 对 JSON 响应体执行查询，将第一个匹配值写入变量表
+    pub fn apply(
+        &self,
+        response: &ResponseData,
+        variables: &mut Variables,
+    ) -> Result<String, String> {
+        let json: Value = serde_json::from_str(&response.body)
+            .map_err(|e| format!("响应体不是有效的 JSON: {}", e))?;
+        let value = query::query_first(self.engine, &self.expression, &json)
+            .map_err(|e| e.to_string())?
+            .map(value_to_string)
+            .ok_or_else(|| format!("表达式没有匹配: {}", self.expression))?;
+        variables.insert(self.variable.clone(), value.clone());
+        Ok(value)
+    }
+}
+
+/// 替换 `{{变量名}}` 占位符
+pub fn replace_variables(text: &str, variables: &Variables) -> String {
+    let mut result = text.to_string();
+    for (key, value) in variables {
+        result = result.replace(&format!("{{{{{}}}}}", key), value);
+    }
+    result
+}
+
+/// 将启用的键值对转换为映射
+fn enabled_pairs<'a>(
+    items: &'a [KeyValuePair],
+    variables: &'a Variables,
+) -> impl Iterator<Item = (String, String)> + 'a {
+    items
+        .iter()
+        .filter(|item| item.enabled && !item.key.trim().is_empty())
+        .map(move |item| (item.key.clone(), replace_variables(&item.value, variables)))
+}
+
+/// 构建带查询参数的完整 URL
+fn build_full_url(
+    base_url: &str,
+    query_params: Vec<(String, String)>,
+) -> Result<String, RunnerError> {
+    if query_params.is_empty() {
+        return Ok(base_url.to_string());
+    }
+
+    let raw = if base_url.starts_with("http") {
+        base_url.to_string()
+    } else {
+        format!("http://{}", base_url)
+    };
+    let mut url = reqwest::Url::parse(&raw)
+        .map_err(|e| RunnerError::InvalidInput(format!("URL 解析错误: {}", e)))?;
+    url.query_pairs_mut().extend_pairs(query_params);
+    Ok(url.to_string())
+}
+
+impl RequestConfig {
+    /// 按照前端发送请求的规则生成 HTTP 请求参数
+    pub fn to_http_options(
+        &self,
+        variables: &Variables,
+    ) -> Result<HttpRequestOptions, RunnerError> {
+        let url = replace_variables(&self.url, variables);
+        let url = build_full_url(&url, enabled_pairs(&self.query_params, variables).collect())?;
+        let mut headers: HashMap<String, String> =
+            enabled_pairs(&self.headers, variables).collect();
+
+        let method = self.method.to_uppercase();
+        let mut body = None;
+        if matches!(method.as_str(), "POST" | "PUT" | "PATCH") {
+            match self.body_type.as_str() {
+                "json" => {
+                    body = Some(replace_variables(&self.body, variables));
+                    headers.insert("Content-Type".to_string(), "application/json".to_string());
+                }
+                "x-www-form-urlencoded" => {
+                    let form = reqwest::Url::parse("http://localhost/")
+                        .map(|mut u| {
+                            u.query_pairs_mut()
+                                .extend_pairs(enabled_pairs(&self.form_data, variables));
+                            u.query().unwrap_or("").to_string()
+                        })
+                        .unwrap_or_default();
+                    body = Some(form);
+                    headers.insert(
+                        "Content-Type".to_string(),
+                        "application/x-www-form-urlencoded".to_string(),
+                    );
+                }
+                "raw" => body = Some(replace_variables(&self.body, variables)),
+                _ => {}
+            }
+        }
+
+        Ok(HttpRequestOptions {
+            method,
+            url,
+            headers,
+            body,
+            timeout: self.timeout,
+            follow_redirects: self.follow_redirects,
+            verify_ssl: self.verify_ssl,
+            http_version: self.http_version,
+            retry: self.retry.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_variables() {
+        let mut vars = Variables::new();
+        vars.insert("host".to_string(), "api.example.com".to_string());
+        assert_eq!(
+            replace_variables("https://{{host}}/users/{{id}}", &vars),
+            "https://api.example.com/users/{{id}}"
+        );
+    }
+
+    #[test]
+    fn test_parse_persisted_state() {
+        let content = r#"{
+            "state": {
+                "collections": [{ "id": "c1", "name": "Users", "requests": [
+                    { "name": "List", "method": "GET", "url": "{{base}}/users" }
+                ]}],
+                "environments": [{ "id": "e1", "name": "dev", "variables": [
+                    { "id": "v1", "key": "base", "value": "http://localhost", "enabled": true }
+                ]}]
+            },
+            "version": 0
+        }"#;
+        let file = CollectionFile::parse(content).unwrap();
+        assert_eq!(file.collections.len(), 1);
+        assert_eq!(file.environments[0].variables[0].key, "base");
+    }
+
+    #[test]
+    fn test_parse_gui_export() {
+        // 集合面板“导出”按钮生成的文件：完整的前端对象，带运行器不使用的字段
+        let content = r#"{
+            "state": {
+                "collections": [{
+                    "id": "c1", "name": "Users", "createdAt": 1, "updatedAt": 2,
+                    "requests": [{
+                        "id": "r1", "name": "Create", "method": "POST", "url": "{{base}}/users",
+                        "headers": [{ "id": "h1", "key": "Content-Type", "value": "application/json", "enabled": true }],
+                        "queryParams": [], "bodyType": "json", "body": "{}", "formData": [],
+                        "timeout": 5000, "followRedirects": true, "verifySsl": false, "httpVersion": "auto",
+                        "retry": { "maxAttempts": 3, "statusCodes": [503] },
+                        "encryption": { "enabled": false, "algorithm": "none" },
+                        "sse": { "enabled": false },
+                        "assertions": [{ "enabled": true, "source": "status", "operator": "equals", "expected": "201" }],
+                        "createdAt": 1, "updatedAt": 2
+                    }]
+                }],
+                "environments": [{ "id": "e1", "name": "dev", "variables": [], "isActive": true }]
+            }
+        }"#;
+        let file = CollectionFile::parse(content).unwrap();
+        let request = &file.collections[0].requests[0];
+        assert_eq!(
+            (
+                request.timeout,
+                request.verify_ssl,
+                request.assertions.len()
+            ),
+            (5000, false, 1)
+        );
+        assert_eq!(request.retry.as_ref().unwrap().max_attempts, 3);
+        assert_eq!(file.environments[0].name, "dev");
+    }
+
+    #[test]
+    fn test_to_http_options() {
+        let request: RequestConfig = serde_json::from_str(
+            r#"{
+                "name": "Create",
+                "method": "post",
+                "url": "{{base}}/users",
+                "queryParams": [{ "key": "v", "value": "2", "enabled": true }],
+                "bodyType": "json",
+                "body": "{\"name\": \"{{user}}\"}"
+            }"#,
+        )
+        .unwrap();
+        let mut vars = Variables::new();
+        vars.insert("base".to_string(), "http://localhost:8080".to_string());
+        vars.insert("user".to_string(), "alice".to_string());
+
+        let options = request.to_http_options(&vars).unwrap();
+        assert_eq!(options.method, "POST");
+        assert_eq!(options.url, "http://localhost:8080/users?v=2");
+        assert_eq!(options.body.as_deref(), Some("{\"name\": \"alice\"}"));
+        assert_eq!(options.headers["Content-Type"], "application/json");
+    }
+}
@@ -0,0 +1,127 @@
+//! 集合运行器 - 在无界面环境（如 CI）中执行保存的请求集合
+
+pub mod assertion;
+pub mod collection;
+pub mod report;
+
+pub use assertion::*;
+pub use collection::*;
+pub use report::*;
+
+use thiserror::Error;
+
+use crate::http::execute_request;
+
+/// 运行器错误类型
+#[derive(Error, Debug)]
+pub enum RunnerError {
+    #[error("文件操作错误: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON 解析错误: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("输入无效: {0}")]
+    InvalidInput(String),
+}
+
+/// 运行选项
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// 仅运行指定名称的请求（为空则全部运行）
+    pub request_filter: Vec<String>,
+    /// 覆盖请求自带的超时时间（毫秒）
+    pub timeout: Option<u64>,
+    /// 首个失败后停止运行
+    pub bail: bool,
+}
+
+/// 依次执行集合中的请求并执行断言
+pub async fn run_collection(
+    collection: &Collection,
+    variables: &Variables,
+    options: &RunOptions,
+) -> CollectionReport {
+    let start = std::time::Instant::now();
+    let mut requests = Vec::new();
+    // 前面请求提取的变量对后续请求可见
+    let mut variables = variables.clone();
+
+    let selected = collection.requests.iter().filter(|r| {
+        options.request_filter.is_empty() || options.request_filter.iter().any(|n| n == &r.name)
+    });
+
+    for request in selected {
+        let report = run_request(request, &mut variables, options).await;
+        let failed = !report.passed();
+        requests.push(report);
+        if failed && options.bail {
+            break;
+        }
+    }
+
+    CollectionReport {
+        name: collection.name.clone(),
+        duration: start.elapsed().as_millis() as u64,
+        requests,
+    }
+}
+
+/// 执行单个请求
+async fn run_request(
+    request: &RequestConfig,
+    variables: &mut Variables,
+    options: &RunOptions,
+) -> RequestReport {
+    let mut report = RequestReport {
+        name: request.name.clone(),
+        method: request.method.to_uppercase(),
+        url: replace_variables(&request.url, variables),
+        status: None,
+        status_text: None,
+        duration: 0,
+        error: None,
+        assertions: Vec::new(),
+    };
+
+    let mut http_options = match request.to_http_options(variables) {
+        Ok(http_options) => http_options,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    if let Some(timeout) = options.timeout {
+        http_options.timeout = timeout;
+    }
+    report.url = http_options.url.clone();
+
+    match execute_request(http_options).await {
+        Ok(response) => {
+            report.status = Some(response.status);
+            report.status_text = Some(response.status_text.clone());
+            report.duration = response.duration;
+            report.assertions = request
+                .assertions
+                .iter()
+                .filter(|a| a.enabled)
+                .map(|a| a.evaluate(&response))
+                .collect();
+
+            // 提取失败记为未通过的断言，便于在报告中定位
+            for extraction in &request.extract {
+                let name = format!("extract {{{{{}}}}}", extraction.variable);
+                let result = extraction.apply(&response, variables);
+                report.assertions.push(AssertionResult {
+                    name,
+                    passed: result.is_ok(),
+                    actual: result.as_ref().ok().cloned(),
+                    message: result.err(),
+                });
+            }
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+
+    report
+}
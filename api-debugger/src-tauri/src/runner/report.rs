@@ -0,0 +1,302 @@
+use serde::Serialize;
+use std::fmt::Write;
+
+use super::assertion::AssertionResult;
+
+/// 报告输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Junit,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "junit" => Ok(Self::Junit),
+            other => Err(format!("不支持的报告格式: {}", other)),
+        }
+    }
+}
+
+/// 单个请求的执行结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestReport {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    pub status: Option<u16>,
+    pub status_text: Option<String>,
+    pub duration: u64,
+    pub error: Option<String>,
+    pub assertions: Vec<AssertionResult>,
+}
+
+/// 单个集合的执行结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionReport {
+    pub name: String,
+    pub duration: u64,
+    pub requests: Vec<RequestReport>,
+}
+
+/// 整次运行的汇总报告
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunReport {
+    pub collections: Vec<CollectionReport>,
+    pub total_requests: usize,
+    pub failed_requests: usize,
+    pub total_assertions: usize,
+    pub failed_assertions: usize,
+    pub duration: u64,
+}
+
+impl RequestReport {
+    /// 请求成功且所有断言通过
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.assertions.iter().all(|a| a.passed)
+    }
+}
+
+impl RunReport {
+    /// 根据各集合结果生成汇总
+    pub fn from_collections(collections: Vec<CollectionReport>) -> Self {
+        let requests = || collections.iter().flat_map(|c| c.requests.iter());
+        let assertions = || requests().flat_map(|r| r.assertions.iter());
+
+        Self {
+            total_requests: requests().count(),
+            failed_requests: requests().filter(|r| !r.passed()).count(),
+            total_assertions: assertions().count(),
+            failed_assertions: assertions().filter(|a| !a.passed).count(),
+            duration: collections.iter().map(|c| c.duration).sum(),
+            collections,
+        }
+    }
+
+    /// 全部请求是否通过
+    pub fn passed(&self) -> bool {
+        self.failed_requests == 0
+    }
+
+    /// 按指定格式渲染报告
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+            ReportFormat::Junit => self.to_junit(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        for collection in &self.collections {
+            let _ = writeln!(out, "{}", collection.name);
+            for request in &collection.requests {
+                let mark = if request.passed() { "✓" } else { "✗" };
+                let outcome = match (&request.error, request.status) {
+                    (Some(error), _) => error.clone(),
+                    (None, Some(status)) => format!(
+                        "{} {}",
+                        status,
+                        request.status_text.as_deref().unwrap_or("")
+                    ),
+                    (None, None) => String::new(),
+                };
+                let _ = writeln!(
+                    out,
+                    "  {} {} {} ({}, {} ms)",
+                    mark,
+                    request.method,
+                    request.name,
+                    outcome.trim(),
+                    request.duration
+                );
+                for assertion in &request.assertions {
+                    let mark = if assertion.passed { "✓" } else { "✗" };
+                    let _ = writeln!(out, "      {} {}", mark, assertion.name);
+                    if let Some(message) = &assertion.message {
+                        let _ = writeln!(out, "        {}", message);
+                    }
+                }
+            }
+            let _ = writeln!(out);
+        }
+
+        let _ = writeln!(
+            out,
+            "请求: {} 个，失败 {} 个；断言: {} 个，失败 {} 个；耗时 {} ms",
+            self.total_requests,
+            self.failed_requests,
+            self.total_assertions,
+            self.failed_assertions,
+            self.duration
+        );
+        out
+    }
+
+    fn to_junit(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            out,
+            "<testsuites name=\"api-debugger\" tests=\"{}\" failures=\"{}\" time=\"{}\">",
+            self.total_requests,
+            self.failed_requests,
+            seconds(self.duration)
+        );
+
+        for collection in &self.collections {
+            let errors = collection
+                .requests
+                .iter()
+                .filter(|r| r.error.is_some())
+                .count();
+            let failures = collection
+                .requests
+                .iter()
+                .filter(|r| r.error.is_none() && !r.passed())
+                .count();
+            let _ = writeln!(
+                out,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">",
+                xml_escape(&collection.name),
+                collection.requests.len(),
+                failures,
+                errors,
+                seconds(collection.duration)
+            );
+
+            for request in &collection.requests {
+                let _ = write!(
+                    out,
+                    "    <testcase classname=\"{}\" name=\"{}\" time=\"{}\"",
+                    xml_escape(&collection.name),
+                    xml_escape(&format!("{} {}", request.method, request.name)),
+                    seconds(request.duration)
+                );
+
+                if request.passed() {
+                    out.push_str("/>\n");
+                    continue;
+                }
+                out.push_str(">\n");
+
+                if let Some(error) = &request.error {
+                    let _ = writeln!(
+                        out,
+                        "      <error message=\"{}\">{}</error>",
+                        xml_escape(error),
+                        xml_escape(&request.url)
+                    );
+                } else {
+                    let failed: Vec<&AssertionResult> =
+                        request.assertions.iter().filter(|a| !a.passed).collect();
+                    let details = failed
+                        .iter()
+                        .map(|a| a.message.clone().unwrap_or_else(|| a.name.clone()))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let _ = writeln!(
+                        out,
+                        "      <failure message=\"{} 个断言失败\">{}</failure>",
+                        failed.len(),
+                        xml_escape(&details)
+                    );
+                }
+                out.push_str("    </testcase>\n");
+            }
+            out.push_str("  </testsuite>\n");
+        }
+
+        out.push_str("</testsuites>\n");
+        out
+    }
+}
+
+fn seconds(millis: u64) -> String {
+    format!("{:.3}", millis as f64 / 1000.0)
+}
+
+/// 转义 XML 特殊字符
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> RunReport {
+        RunReport::from_collections(vec![CollectionReport {
+            name: "Users & Orders".to_string(),
+            duration: 250,
+            requests: vec![
+                RequestReport {
+                    name: "List".to_string(),
+                    method: "GET".to_string(),
+                    url: "http://localhost/users".to_string(),
+                    status: Some(200),
+                    status_text: Some("OK".to_string()),
+                    duration: 100,
+                    error: None,
+                    assertions: vec![AssertionResult {
+                        name: "status == 201".to_string(),
+                        passed: false,
+                        actual: Some("200".to_string()),
+                        message: Some("期望 status == 201，实际为 200".to_string()),
+                    }],
+                },
+                RequestReport {
+                    name: "Health".to_string(),
+                    method: "GET".to_string(),
+                    url: "http://localhost/health".to_string(),
+                    status: Some(200),
+                    status_text: Some("OK".to_string()),
+                    duration: 150,
+                    error: None,
+                    assertions: Vec::new(),
+                },
+            ],
+        }])
+    }
+
+    #[test]
+    fn test_summary() {
+        let report = sample();
+        assert_eq!(report.total_requests, 2);
+        assert_eq!(report.failed_requests, 1);
+        assert_eq!(report.failed_assertions, 1);
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_junit_output() {
+        let xml = sample().render(ReportFormat::Junit);
+        assert!(xml.contains(
+            "<testsuite name=\"Users &amp; Orders\" tests=\"2\" failures=\"1\" errors=\"0\""
+        ));
+        assert!(xml.contains("<failure message=\"1 个断言失败\">"));
+        assert!(xml.contains(
+            "<testcase classname=\"Users &amp; Orders\" name=\"GET Health\" time=\"0.150\"/>"
+        ));
+    }
+}
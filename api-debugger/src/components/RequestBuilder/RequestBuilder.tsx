@@ -1,9 +1,10 @@
-import React, { useState } from "react";
+import React, { useEffect, useState } from "react";
 import { useAppStore } from "../../stores/appStore";
-import { useMockRequest } from "../../hooks/useRequest";
-import type { HttpMethod, BodyType } from "../../types";
+import { useRequest } from "../../hooks/useRequest";
+import type { HttpMethod, BodyType, HttpVersion } from "../../types";
 import { methodColors, methodBgColors } from "../../utils/helpers";
 import { KeyValueEditor } from "../common/KeyValueEditor";
+import { AssertionEditor } from "../common/AssertionEditor";
 import { Tabs } from "../common/Tabs";
 import {
   SendIcon,
@@ -40,12 +41,19 @@ export const RequestBuilder: React.FC = () => {
     isSSEConnected,
   } = useAppStore();
 
-  const { sendRequest, startSSE, stopSSE } = useMockRequest();
+  const { sendRequest, cancelRequest, startSSE, stopSSE } = useRequest();
   
   const [activeTab, setActiveTab] = useState("params");
   const [showMethodDropdown, setShowMethodDropdown] = useState(false);
   const [sseMode, setSseMode] = useState(false);
 
+  // 重试状态码输入框的文本，失焦时解析写回请求；切换请求或状态码变化时同步
+  const retryStatusCodes = currentRequest.retry?.statusCodes;
+  const [statusCodesText, setStatusCodesText] = useState("");
+  useEffect(() => {
+    setStatusCodesText(retryStatusCodes?.join(", ") ?? "");
+  }, [currentRequest.id, retryStatusCodes]);
+
   const handleMethodChange = (method: HttpMethod) => {
     setCurrentRequest({ method });
     setShowMethodDropdown(false);
@@ -58,6 +66,8 @@ export const RequestBuilder: React.FC = () => {
       } else {
         startSSE();
       }
+    } else if (isLoading) {
+      cancelRequest();
     } else {
       sendRequest();
     }
@@ -67,6 +77,7 @@ export const RequestBuilder: React.FC = () => {
     { id: "params", label: "Params", badge: currentRequest.queryParams.filter(p => p.key).length },
     { id: "headers", label: "Headers", badge: currentRequest.headers.filter(h => h.key).length },
     { id: "body", label: "Body" },
+    { id: "tests", label: "Tests", badge: currentRequest.assertions?.filter(a => a.enabled).length },
     { id: "settings", label: "Settings" },
   ];
 
@@ -144,7 +155,7 @@ export const RequestBuilder: React.FC = () => {
           {/* 发送按钮 */}
           <button
             onClick={handleSend}
-            disabled={isLoading && !sseMode}
+            disabled={isLoading && sseMode}
             className={`
               flex items-center gap-2 px-6 py-2.5 rounded-lg font-semibold text-sm
               transition-all
@@ -158,7 +169,7 @@ export const RequestBuilder: React.FC = () => {
             {isLoading && !sseMode ? (
               <>
                 <LoadingIcon size={16} />
-                <span>发送中...</span>
+                <span>取消</span>
               </>
             ) : sseMode ? (
               isSSEConnected ? (
@@ -290,6 +301,21 @@ export const RequestBuilder: React.FC = () => {
           </div>
         )}
 
+        {activeTab === "tests" && (
+          <div className="animate-fade-in">
+            <h3 className="text-sm font-medium text-text-secondary mb-1">
+              断言
+            </h3>
+            <p className="text-xs text-text-muted mb-3">
+              随请求保存到集合，由 api-debugger-cli 运行集合时执行
+            </p>
+            <AssertionEditor
+              items={currentRequest.assertions ?? []}
+              onChange={(assertions) => setCurrentRequest({ assertions })}
+            />
+          </div>
+        )}
+
         {activeTab === "settings" && (
           <div className="animate-fade-in space-y-6">
             {/* 超时设置 */}
@@ -309,6 +335,73 @@ export const RequestBuilder: React.FC = () => {
               />
             </div>
 
+            {/* 协议版本 */}
+            <div className="space-y-2">
+              <label className="text-sm font-medium text-text-secondary">
+                HTTP 协议版本
+              </label>
+              <select
+                value={currentRequest.httpVersion ?? "auto"}
+                onChange={(e) =>
+                  setCurrentRequest({ httpVersion: e.target.value as HttpVersion })
+                }
+                className="w-64 px-4 py-2 bg-bg-secondary border border-border-primary rounded-lg
+                           text-text-primary text-sm
+                           focus:border-accent-blue focus:ring-2 focus:ring-accent-blue/20"
+              >
+                <option value="auto">自动协商</option>
+                <option value="http1">HTTP/1.1</option>
+                <option value="http2">HTTP/2（ALPN）</option>
+                <option value="http2PriorKnowledge">HTTP/2（先验知识）</option>
+                <option value="http3">HTTP/3</option>
+              </select>
+            </div>
+
+            {/* 重试策略 */}
+            <div className="space-y-2">
+              <label className="text-sm font-medium text-text-secondary">
+                最大尝试次数（1 表示不重试）
+              </label>
+              <input
+                type="number"
+                min={1}
+                value={currentRequest.retry?.maxAttempts ?? 1}
+                onChange={(e) => {
+                  const maxAttempts = parseInt(e.target.value) || 1;
+                  setCurrentRequest({
+                    retry: maxAttempts > 1 ? { ...currentRequest.retry, maxAttempts } : undefined,
+                  });
+                }}
+                className="w-48 px-4 py-2 bg-bg-secondary border border-border-primary rounded-lg
+                           text-text-primary text-sm
+                           focus:border-accent-blue focus:ring-2 focus:ring-accent-blue/20"
+              />
+              {currentRequest.retry && (
+                <input
+                  type="text"
+                  placeholder="重试状态码，如 429, 502, 503, 504"
+                  value={statusCodesText}
+                  onChange={(e) => setStatusCodesText(e.target.value)}
+                  onBlur={() => {
+                    const codes = statusCodesText
+                      .split(",")
+                      .map((c) => parseInt(c.trim()))
+                      .filter((c) => !isNaN(c));
+                    setStatusCodesText(codes.join(", "));
+                    setCurrentRequest({
+                      retry: {
+                        ...currentRequest.retry!,
+                        statusCodes: codes.length > 0 ? codes : undefined,
+                      },
+                    });
+                  }}
+                  className="block w-64 px-4 py-2 bg-bg-secondary border border-border-primary rounded-lg
+                             text-text-primary text-sm
+                             focus:border-accent-blue focus:ring-2 focus:ring-accent-blue/20"
+                />
+              )}
+            </div>
+
             {/* 其他设置 */}
             <div className="space-y-3">
               <label className="flex items-center gap-3 cursor-pointer">
@@ -173,6 +173,25 @@ export const ResponseViewer: React.FC = () => {
             <div className="text-sm text-text-secondary">
               {formatBytes(currentResponse.bodySize)}
             </div>
+
+            {/* 协议版本 */}
+            {currentResponse.httpVersion && (
+              <div className="text-sm text-text-secondary">
+                {currentResponse.httpVersion}
+              </div>
+            )}
+
+            {/* 重试次数 */}
+            {currentResponse.attempts && currentResponse.attempts.length > 1 && (
+              <div
+                className="text-sm text-accent-orange"
+                title={currentResponse.attempts
+                  .map((a) => `#${a.attempt} ${a.status ?? a.error ?? ""} ${a.duration}ms`)
+                  .join("\n")}
+              >
+                重试 {currentResponse.attempts.length - 1} 次
+              </div>
+            )}
           </div>
 
           {/* 操作按钮 */}
@@ -206,6 +225,15 @@ export const ResponseViewer: React.FC = () => {
       <div className="flex-1 overflow-auto p-4">
         {activeTab === "body" && currentResponse && (
           <div className="animate-fade-in">
+            {/* 大响应体仅返回开头部分，完整内容保存在缓存文件中 */}
+            {currentResponse.bodyTruncated && (
+              <div
+                className="mb-3 px-3 py-2 text-xs text-accent-orange bg-accent-orange/10 rounded-md"
+                title={currentResponse.bodyFile}
+              >
+                响应体共 {formatBytes(currentResponse.bodySize)}，仅显示开头部分
+              </div>
+            )}
             {currentResponse.error ? (
               <div className="p-4 bg-accent-red/10 border border-accent-red/30 rounded-lg">
                 <h4 className="text-sm font-medium text-accent-red mb-2">
@@ -358,8 +386,14 @@ const JsonHighlight: React.FC<{ json: string }> = ({ json }) => {
 };
 
 export const ResponseViewer: React.FC = () => {
-  const { currentRequest, currentResponse, isLoading, sseEvents, isSSEConnected } =
-    useAppStore();
+  const {
+    currentRequest,
+    currentResponse,
+    isLoading,
+    sseEvents,
+    isSSEConnected,
+    transferProgress,
+  } = useAppStore();
   const [activeTab, setActiveTab] = useState("body");
   const [copied, setCopied] = useState(false);
 
@@ -447,7 +481,13 @@ export const ResponseViewer: React.FC = () => {
             <h3 className="text-lg font-medium text-text-primary mb-1">
               发送请求中...
             </h3>
-            <p className="text-sm text-text-muted">请稍候</p>
+            <p className="text-sm text-text-muted">
+              {transferProgress
+                ? `${transferProgress.phase === "upload" ? "上传" : "下载"} ${formatBytes(
+                    transferProgress.loaded
+                  )}${transferProgress.total ? ` / ${formatBytes(transferProgress.total)}` : ""}`
+                : "请稍候"}
+            </p>
           </div>
         </div>
       </div>
@@ -484,6 +524,25 @@ export const ResponseViewer: React.FC = () => {
               </svg>
               <span>{formatBytes(currentResponse.bodySize)}</span>
             </div>
+
+            {/* 协议版本 */}
+            {currentResponse.httpVersion && (
+              <div className="text-sm text-text-secondary">
+                {currentResponse.httpVersion}
+              </div>
+            )}
+
+            {/* 重试次数 */}
+            {currentResponse.attempts && currentResponse.attempts.length > 1 && (
+              <div
+                className="text-sm text-accent-orange"
+                title={currentResponse.attempts
+                  .map((a) => `#${a.attempt} ${a.status ?? a.error ?? ""} ${a.duration}ms`)
+                  .join("\n")}
+              >
+                重试 {currentResponse.attempts.length - 1} 次
+              </div>
+            )}
           </div>
 
           {/* 操作按钮 */}
@@ -517,6 +576,15 @@ export const ResponseViewer: React.FC = () => {
       <div className="flex-1 overflow-auto p-4">
         {activeTab === "body" && currentResponse && (
           <div className="animate-fade-in">
+            {/* 大响应体仅返回开头部分，完整内容保存在缓存文件中 */}
+            {currentResponse.bodyTruncated && (
+              <div
+                className="mb-3 px-3 py-2 text-xs text-accent-orange bg-accent-orange/10 rounded-md"
+                title={currentResponse.bodyFile}
+              >
+                响应体共 {formatBytes(currentResponse.bodySize)}，仅显示开头部分
+              </div>
+            )}
             {currentResponse.error ? (
               <div className="p-4 bg-accent-red/10 border border-accent-red/30 rounded-lg">
                 <h4 className="text-sm font-medium text-accent-red mb-2">请求错误</h4>
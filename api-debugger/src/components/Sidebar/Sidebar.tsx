@@ -16,6 +16,7 @@ import {
   ChevronRightIcon,
   ChevronDownIcon,
   CheckIcon,
+  DownloadIcon,
 } from "../common/Icons";
 
 // 历史记录列表
@@ -112,10 +113,24 @@ const HistoryItemComponent: React.FC<{ item: HistoryItem }> = ({ item }) => {
 
 // 集合列表
 const CollectionList: React.FC = () => {
-  const { collections, addCollection } = useAppStore();
+  const { collections, environments, addCollection } = useAppStore();
   const [newCollectionName, setNewCollectionName] = useState("");
   const [showAddForm, setShowAddForm] = useState(false);
 
+  // 导出为 api-debugger-cli 可直接运行的集合文件，结构与本地存储相同
+  const handleExport = () => {
+    const data = { state: { collections, environments } };
+    const blob = new Blob([JSON.stringify(data, null, 2)], {
+      type: "application/json",
+    });
+    const url = URL.createObjectURL(blob);
+    const a = document.createElement("a");
+    a.href = url;
+    a.download = `api-debugger-collections-${Date.now()}.json`;
+    a.click();
+    URL.revokeObjectURL(url);
+  };
+
   const handleAddCollection = () => {
     if (newCollectionName.trim()) {
       addCollection(newCollectionName.trim());
@@ -126,14 +141,27 @@ const CollectionList: React.FC = () => {
 
   return (
     <div className="space-y-2">
-      <button
-        onClick={() => setShowAddForm(!showAddForm)}
-        className="flex items-center gap-2 w-full px-2 py-1.5 text-sm text-text-secondary 
-                   hover:text-accent-blue hover:bg-bg-hover rounded-lg transition-all"
-      >
-        <PlusIcon size={14} />
-        <span>新建集合</span>
-      </button>
+      <div className="flex gap-1">
+        <button
+          onClick={() => setShowAddForm(!showAddForm)}
+          className="flex items-center gap-2 flex-1 px-2 py-1.5 text-sm text-text-secondary 
+                     hover:text-accent-blue hover:bg-bg-hover rounded-lg transition-all"
+        >
+          <PlusIcon size={14} />
+          <span>新建集合</span>
+        </button>
+        <button
+          onClick={handleExport}
+          disabled={collections.length === 0}
+          className="flex items-center gap-2 px-2 py-1.5 text-sm text-text-secondary 
+                     hover:text-accent-blue hover:bg-bg-hover rounded-lg transition-all
+                     disabled:opacity-50 disabled:pointer-events-none"
+          title="导出集合和环境，供 api-debugger-cli 运行"
+        >
+          <DownloadIcon size={14} />
+          <span>导出</span>
+        </button>
+      </div>
 
       {showAddForm && (
         <div className="flex gap-2 p-2 bg-bg-tertiary rounded-lg animate-fade-in">
@@ -0,0 +1,175 @@
+import React from "react";
+import type { Assertion, QueryEngine } from "../../types";
+import { TrashIcon, PlusIcon } from "./Icons";
+
+interface AssertionEditorProps {
+  items: Assertion[];
+  onChange: (items: Assertion[]) => void;
+}
+
+const SOURCES: { value: Assertion["source"]; label: string }[] = [
+  { value: "status", label: "状态码" },
+  { value: "header", label: "响应头" },
+  { value: "body", label: "响应体" },
+  { value: "jsonBody", label: "JSON 字段" },
+  { value: "responseTime", label: "耗时 (ms)" },
+];
+
+const OPERATORS: { value: Assertion["operator"]; label: string }[] = [
+  { value: "equals", label: "等于" },
+  { value: "notEquals", label: "不等于" },
+  { value: "contains", label: "包含" },
+  { value: "notContains", label: "不包含" },
+  { value: "exists", label: "存在" },
+  { value: "notExists", label: "不存在" },
+  { value: "lessThan", label: "小于" },
+  { value: "greaterThan", label: "大于" },
+];
+
+const ENGINES: { value: QueryEngine; label: string; hint: string }[] = [
+  { value: "jsonPath", label: "JSONPath", hint: "结果带规范化路径" },
+  { value: "jmesPath", label: "JMESPath", hint: "只返回结果值，不带路径" },
+  { value: "jq", label: "jq", hint: "只返回结果值，不带路径" },
+];
+
+const inputClass = `min-w-0 px-3 py-2 text-sm bg-bg-secondary border border-border-primary
+  rounded-md focus:border-accent-blue focus:ring-1 focus:ring-accent-blue/20
+  placeholder:text-text-muted transition-all`;
+
+// 新断言默认检查状态码为 200
+const createAssertion = (): Assertion => ({
+  enabled: true,
+  source: "status",
+  operator: "equals",
+  expected: "200",
+});
+
+// 断言编辑器（断言随请求保存，由命令行运行器执行）
+export const AssertionEditor: React.FC<AssertionEditorProps> = ({
+  items,
+  onChange,
+}) => {
+  const handleItemChange = (index: number, patch: Partial<Assertion>) => {
+    onChange(items.map((item, i) => (i === index ? { ...item, ...patch } : item)));
+  };
+
+  const handleSourceChange = (index: number, source: Assertion["source"]) => {
+    const needsProperty = source === "header" || source === "jsonBody";
+    handleItemChange(index, {
+      source,
+      property: needsProperty ? items[index].property : undefined,
+      engine: source === "jsonBody" ? items[index].engine ?? "jsonPath" : undefined,
+    });
+  };
+
+  return (
+    <div className="space-y-2">
+      {items.map((item, index) => {
+        const needsExpected = item.operator !== "exists" && item.operator !== "notExists";
+        return (
+          <div
+            key={index}
+            className="group flex items-center gap-2 animate-fade-in"
+          >
+            {/* 启用/禁用复选框 */}
+            <label className="flex items-center justify-center w-5 h-5 cursor-pointer">
+              <input
+                type="checkbox"
+                checked={item.enabled}
+                onChange={(e) => handleItemChange(index, { enabled: e.target.checked })}
+                className="w-4 h-4 rounded border-border-primary bg-bg-secondary
+                           checked:bg-accent-blue checked:border-accent-blue
+                           focus:ring-accent-blue focus:ring-offset-0
+                           cursor-pointer transition-colors"
+              />
+            </label>
+
+            {/* 数据来源 */}
+            <select
+              value={item.source}
+              onChange={(e) => handleSourceChange(index, e.target.value as Assertion["source"])}
+              className={`w-32 ${inputClass} ${!item.enabled ? "opacity-50" : ""}`}
+            >
+              {SOURCES.map((source) => (
+                <option key={source.value} value={source.value}>
+                  {source.label}
+                </option>
+              ))}
+            </select>
+
+            {/* JSON 字段的查询语言 */}
+            {item.source === "jsonBody" && (
+              <select
+                value={item.engine ?? "jsonPath"}
+                title={ENGINES.find((engine) => engine.value === (item.engine ?? "jsonPath"))?.hint}
+                onChange={(e) => handleItemChange(index, { engine: e.target.value as QueryEngine })}
+                className={`w-28 ${inputClass} ${!item.enabled ? "opacity-50" : ""}`}
+              >
+                {ENGINES.map((engine) => (
+                  <option key={engine.value} value={engine.value} title={engine.hint}>
+                    {engine.label}
+                  </option>
+                ))}
+              </select>
+            )}
+
+            {/* 头名称或查询表达式 */}
+            {(item.source === "header" || item.source === "jsonBody") && (
+              <input
+                type="text"
+                value={item.property ?? ""}
+                onChange={(e) => handleItemChange(index, { property: e.target.value })}
+                placeholder={item.source === "header" ? "Header Name" : "$.data.id"}
+                className={`flex-1 font-mono ${inputClass} ${!item.enabled ? "opacity-50" : ""}`}
+              />
+            )}
+
+            {/* 比较方式 */}
+            <select
+              value={item.operator}
+              onChange={(e) => handleItemChange(index, { operator: e.target.value as Assertion["operator"] })}
+              className={`w-28 ${inputClass} ${!item.enabled ? "opacity-50" : ""}`}
+            >
+              {OPERATORS.map((operator) => (
+                <option key={operator.value} value={operator.value}>
+                  {operator.label}
+                </option>
+              ))}
+            </select>
+
+            {/* 期望值 */}
+            {needsExpected && (
+              <input
+                type="text"
+                value={item.expected ?? ""}
+                onChange={(e) => handleItemChange(index, { expected: e.target.value })}
+                placeholder="期望值"
+                className={`flex-1 ${inputClass} ${!item.enabled ? "opacity-50" : ""}`}
+              />
+            )}
+
+            {/* 删除按钮 */}
+            <button
+              onClick={() => onChange(items.filter((_, i) => i !== index))}
+              className="p-2 text-text-muted hover:text-accent-red hover:bg-accent-red/10
+                         rounded-md transition-all opacity-0 group-hover:opacity-100"
+              title="删除"
+            >
+              <TrashIcon size={16} />
+            </button>
+          </div>
+        );
+      })}
+
+      {/* 添加按钮 */}
+      <button
+        onClick={() => onChange([...items, createAssertion()])}
+        className="flex items-center gap-2 px-3 py-2 text-sm text-text-secondary
+                   hover:text-accent-blue hover:bg-bg-hover rounded-md transition-all w-full"
+      >
+        <PlusIcon size={16} />
+        <span>添加断言</span>
+      </button>
+    </div>
+  );
+};
@@ -1,7 +1,13 @@
 import { useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { useAppStore } from "../stores/appStore";
-import type { RequestConfig, ResponseData } from "../types";
+import { listen } from "@tauri-apps/api/event";
+import { useAppStore, generateId } from "../stores/appStore";
+import type {
+  RequestConfig,
+  ResponseData,
+  RetryFailedError,
+  TransferProgress,
+} from "../types";
 import { keyValueToObject, buildFullUrl } from "../utils/helpers";
 
 // 请求 Hook
@@ -15,6 +21,9 @@ export const useRequest = () => {
     addSSEEvent,
     setSSEConnected,
     clearSSEEvents,
+    activeRequestId,
+    setActiveRequestId,
+    setTransferProgress,
   } = useAppStore();
 
   // 发送普通 HTTP 请求
@@ -37,6 +46,16 @@ export const useRequest = () => {
     setResponse(null);
 
     const startTime = Date.now();
+    const requestId = generateId();
+    setActiveRequestId(requestId);
+    setTransferProgress(null);
+
+    // 监听当前请求的传输进度
+    const unlisten = await listen<TransferProgress>("http:progress", (event) => {
+      if (event.payload.requestId === requestId) {
+        setTransferProgress(event.payload);
+      }
+    });
 
     try {
       // 替换环境变量
@@ -74,6 +93,7 @@ export const useRequest = () => {
 
       // 调用 Tauri 命令
       const response = await invoke<ResponseData>("send_http_request", {
+        requestId,
         method: currentRequest.method,
         url: fullUrl,
         headers,
@@ -81,6 +101,8 @@ export const useRequest = () => {
         timeout: currentRequest.timeout,
         followRedirects: currentRequest.followRedirects,
         verifySsl: currentRequest.verifySsl,
+        httpVersion: currentRequest.httpVersion ?? "auto",
+        retry: currentRequest.retry ?? null,
       });
 
       const duration = Date.now() - startTime;
@@ -93,18 +115,28 @@ export const useRequest = () => {
       setResponse(finalResponse);
       addToHistory(currentRequest, finalResponse);
     } catch (error) {
+      // 重试失败的错误带有每次尝试的记录
+      const retryFailed =
+        typeof error === "object" && error !== null && "attempts" in error
+          ? (error as RetryFailedError)
+          : null;
+      const message = retryFailed ? retryFailed.message : String(error);
       const errorResponse: ResponseData = {
         status: 0,
         statusText: "Error",
         headers: {},
-        body: String(error),
+        body: message,
         bodySize: 0,
         duration: Date.now() - startTime,
         timestamp: Date.now(),
-        error: String(error),
+        error: message,
+        attempts: retryFailed?.attempts,
       };
       setResponse(errorResponse);
     } finally {
+      unlisten();
+      setActiveRequestId(null);
+      setTransferProgress(null);
       setLoading(false);
     }
   }, [
@@ -113,8 +145,16 @@ export const useRequest = () => {
     setResponse,
     addToHistory,
     replaceVariables,
+    setActiveRequestId,
+    setTransferProgress,
   ]);
 
+  // 取消进行中的请求
+  const cancelRequest = useCallback(async () => {
+    if (!activeRequestId) return;
+    await invoke<boolean>("cancel_request", { requestId: activeRequestId });
+  }, [activeRequestId]);
+
   // 发送 SSE 请求
   const startSSE = useCallback(async () => {
     if (!currentRequest.url.trim()) return;
@@ -172,6 +212,7 @@ export const useRequest = () => {
 
   return {
     sendRequest,
+    cancelRequest,
     startSSE,
     stopSSE,
   };
@@ -266,6 +307,7 @@ export const useMockRequest = () => {
 
   return {
     sendRequest: sendMockRequest,
+    cancelRequest: async () => {},
     startSSE: async () => {},
     stopSSE: () => {},
   };
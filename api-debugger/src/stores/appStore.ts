@@ -9,6 +9,7 @@ import type {
   Collection,
   Environment,
   KeyValuePair,
+  TransferProgress,
 } from "../types";
 
 // 生成唯一 ID
@@ -37,6 +38,7 @@ const defaultRequest: RequestConfig = {
   timeout: 30000,
   followRedirects: true,
   verifySsl: true,
+  httpVersion: "auto",
   createdAt: Date.now(),
   updatedAt: Date.now(),
 };
@@ -52,6 +54,8 @@ interface AppStore extends AppState {
 
   // 加载状态
   setLoading: (loading: boolean) => void;
+  setActiveRequestId: (id: string | null) => void;
+  setTransferProgress: (progress: TransferProgress | null) => void;
 
   // SSE 操作
   addSSEEvent: (event: SSEEvent) => void;
@@ -92,6 +96,8 @@ export const useAppStore = create<AppStore>()(
       currentRequest: { ...defaultRequest },
       currentResponse: null,
       isLoading: false,
+      activeRequestId: null,
+      transferProgress: null,
       sseEvents: [],
       isSSEConnected: false,
       history: [],
@@ -124,6 +130,8 @@ export const useAppStore = create<AppStore>()(
 
       // 加载状态
       setLoading: (loading) => set({ isLoading: loading }),
+      setActiveRequestId: (id) => set({ activeRequestId: id }),
+      setTransferProgress: (progress) => set({ transferProgress: progress }),
 
       // SSE 操作
       addSSEEvent: (event) =>
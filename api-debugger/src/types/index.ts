@@ -8,6 +8,9 @@ export type HttpMethod =
   | "HEAD"
   | "OPTIONS";
 
+// HTTP 协议版本
+export type HttpVersion = "auto" | "http1" | "http2" | "http2PriorKnowledge" | "http3";
+
 // 请求体类型
 export type BodyType = "none" | "json" | "form-data" | "x-www-form-urlencoded" | "raw" | "binary";
 
@@ -34,6 +37,9 @@ export interface RequestConfig {
   timeout: number;
   followRedirects: boolean;
   verifySsl: boolean;
+  httpVersion?: HttpVersion;
+  // 重试策略
+  retry?: RetryPolicy;
   // 加密配置
   encryption?: {
     enabled: boolean;
@@ -44,10 +50,82 @@ export interface RequestConfig {
   sse?: {
     enabled: boolean;
   };
+  // 断言（命令行运行器执行）
+  assertions?: Assertion[];
+  // 变量提取（命令行运行器执行）
+  extract?: VariableExtraction[];
   createdAt: number;
   updatedAt: number;
 }
 
+// 重试策略（未填写的字段使用后端默认值）
+export interface RetryPolicy {
+  maxAttempts: number;
+  retryOnConnectError?: boolean;
+  retryOnTimeout?: boolean;
+  statusCodes?: number[];
+  baseDelay?: number;
+  maxDelay?: number;
+  jitter?: boolean;
+  respectRetryAfter?: boolean;
+}
+
+// 单次尝试记录
+export interface RetryAttempt {
+  attempt: number;
+  status: number | null;
+  error: string | null;
+  duration: number;
+  delay: number;
+}
+
+// 重试后仍失败时 send_http_request 返回的错误（其余错误为字符串）
+export interface RetryFailedError {
+  message: string;
+  attempts: RetryAttempt[];
+}
+
+// 断言
+export interface Assertion {
+  enabled: boolean;
+  source: "status" | "header" | "body" | "jsonBody" | "responseTime";
+  property?: string;
+  engine?: QueryEngine;
+  operator:
+    | "equals"
+    | "notEquals"
+    | "contains"
+    | "notContains"
+    | "exists"
+    | "notExists"
+    | "lessThan"
+    | "greaterThan";
+  expected?: string;
+}
+
+// 查询语言
+export type QueryEngine = "jsonPath" | "jmesPath" | "jq";
+
+// 变量提取
+export interface VariableExtraction {
+  variable: string;
+  engine?: QueryEngine;
+  expression: string;
+}
+
+// 响应查询结果（query_response 命令）
+export interface QueryMatch {
+  // 规范化路径，仅 JSONPath 提供；JMESPath 与 jq 为 null
+  path: string | null;
+  value: unknown;
+}
+
+export interface QueryResult {
+  matches: QueryMatch[];
+  total: number;
+  truncated: boolean;
+}
+
 // 响应数据
 export interface ResponseData {
   status: number;
@@ -58,6 +136,21 @@ export interface ResponseData {
   duration: number;
   timestamp: number;
   error?: string;
+  httpVersion?: string;
+  // 大响应体的缓存文件（用于 query_response）
+  bodyFile?: string;
+  // body 仅为开头部分的预览，完整内容见 bodyFile
+  bodyTruncated?: boolean;
+  // 每次尝试的记录（含重试）
+  attempts?: RetryAttempt[];
+}
+
+// 传输进度（http:progress 事件）
+export interface TransferProgress {
+  requestId: string;
+  phase: "upload" | "download";
+  loaded: number;
+  total: number | null;
 }
 
 // SSE 事件
@@ -102,6 +195,10 @@ export interface AppState {
   currentResponse: ResponseData | null;
   // 加载状态
   isLoading: boolean;
+  // 进行中请求的 ID（用于取消）
+  activeRequestId: string | null;
+  // 当前请求的传输进度
+  transferProgress: TransferProgress | null;
   // SSE 事件列表
   sseEvents: SSEEvent[];
   // 是否 SSE 连接中
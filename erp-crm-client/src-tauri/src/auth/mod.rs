@@ -0,0 +1,500 @@
+//! 认证模块 - 本地用户登录、会话与密码管理
+//!
+//! 密码以 Argon2id 哈希（PHC 字符串）保存在 `users` 表。登录成功后生成随机会话令牌，
+//! 会话保存在内存中（应用状态），空闲超过 [`SESSION_TTL`] 后失效。
+
+use argon2::password_hash::{PasswordHash, SaltString};
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, OnceLock};
+use thiserror::Error;
+
+use crate::database::{Database, User};
+
+mod permissions;
+
+pub use permissions::*;
+
+/// 会话空闲有效期
+pub const SESSION_TTL: Duration = Duration::hours(8);
+/// 连续失败多少次后锁定
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+/// 锁定时长
+pub const LOCKOUT_DURATION: Duration = Duration::minutes(15);
+/// 密码最小长度
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// 可分配的角色
+pub const ROLES: [&str; 5] = ["admin", "manager", "staff", "warehouse", "viewer"];
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("用户名或密码错误")]
+    InvalidCredentials,
+    #[error("账号已锁定，请 {0} 分钟后再试")]
+    AccountLocked(i64),
+    #[error("账号已停用")]
+    AccountDisabled,
+    #[error("未登录")]
+    NotAuthenticated,
+    #[error("登录已过期，请重新登录")]
+    SessionExpired,
+    #[error("需要管理员权限")]
+    AdminRequired,
+    #[error("没有权限: {module}:{action}")]
+    PermissionDenied { module: &'static str, action: &'static str },
+    #[error("系统已完成初始化")]
+    SetupCompleted,
+    #[error("密码至少需要 {MIN_PASSWORD_LENGTH} 位")]
+    WeakPassword,
+    #[error("无效的输入: {0}")]
+    InvalidInput(String),
+    #[error("用户名已存在")]
+    UsernameTaken,
+    #[error("用户不存在")]
+    UserNotFound,
+    #[error("数据库已加密，无法解锁: {0}")]
+    DatabaseLocked(String),
+    #[error("数据库已加密，当前账号还没有数据库密钥，请管理员先登录解锁数据库，之后（程序重启前）再用此账号登录")]
+    DatabaseKeyMissing,
+    #[error("密码哈希失败: {0}")]
+    HashError(String),
+    #[error("数据库错误: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+}
+
+impl AuthError {
+    /// 返回给前端的错误码，用于区分未登录与无权限
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            AuthError::NotAuthenticated | AuthError::SessionExpired => Some("UNAUTHENTICATED"),
+            AuthError::AdminRequired | AuthError::PermissionDenied { .. } => Some("PERMISSION_DENIED"),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AuthError>;
+
+/// 登录会话
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub token: String,
+    pub user_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 登录结果
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginResult {
+    pub user: User,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 新建或修改用户的资料
+#[derive(Debug, Clone, Default)]
+pub struct UserInput {
+    pub username: String,
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    pub role: String,
+    pub department: String,
+    pub status: String,
+}
+
+/// 计算密码的 Argon2 哈希（PHC 字符串，含随机 salt）
+pub fn hash_password(password: &str) -> Result<String> {
+    let mut salt_bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut salt_bytes);
+    let salt = SaltString::encode_b64(&salt_bytes).map_err(|e| AuthError::HashError(e.to_string()))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AuthError::HashError(e.to_string()))
+}
+
+/// 校验密码，哈希格式错误视为不匹配
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+/// 用户名不存在时也做一次哈希校验，避免通过响应时间判断用户名是否存在
+fn dummy_verify(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let hash = DUMMY_HASH.get_or_init(|| hash_password("dummy-password").unwrap_or_default());
+    verify_password(password, hash);
+}
+
+fn check_password_strength(password: &str) -> Result<()> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AuthError::WeakPassword);
+    }
+    Ok(())
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// 认证管理器，保存当前会话
+pub struct AuthManager {
+    db: Arc<Database>,
+    session: Mutex<Option<Session>>,
+}
+
+impl AuthManager {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            session: Mutex::new(None),
+        }
+    }
+
+    /// 是否需要首次运行设置（尚无任何用户）
+    pub fn needs_setup(&self) -> Result<bool> {
+        // 数据库加密锁定说明已经初始化过
+        if self.db.is_locked() {
+            return Ok(false);
+        }
+        Ok(self.db.count_users()? == 0)
+    }
+
+    /// 首次运行创建管理员并直接登录
+    pub fn setup_admin(&self, input: UserInput, password: &str) -> Result<LoginResult> {
+        check_password_strength(password)?;
+        let user = Self::new_user(UserInput { role: "admin".to_string(), ..input })?;
+        if !self.db.create_first_user(&user, &hash_password(password)?)? {
+            return Err(AuthError::SetupCompleted);
+        }
+        self.db.record_login_success(&user.id)?;
+        log::info!("已创建管理员账号: {}", user.username);
+        self.start_session(&user.id)
+    }
+
+    /// 用户名密码登录
+    pub fn login(&self, username: &str, password: &str) -> Result<LoginResult> {
+        let Some((user, credentials)) = self.db.get_user_credentials(username.trim())? else {
+            dummy_verify(password);
+            return Err(AuthError::InvalidCredentials);
+        };
+
+        if let Some(until) = credentials
+            .locked_until
+            .as_deref()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        {
+            let remaining = until.with_timezone(&Utc) - Utc::now();
+            if remaining > Duration::zero() {
+                return Err(AuthError::AccountLocked(remaining.num_minutes() + 1));
+            }
+        }
+
+        if !verify_password(password, &credentials.password_hash) {
+            if credentials.failed_attempts + 1 >= MAX_FAILED_ATTEMPTS {
+                let until = (Utc::now() + LOCKOUT_DURATION).to_rfc3339();
+                self.db.record_login_failure(&user.id, Some(&until))?;
+                log::warn!("账号 {} 连续登录失败，已锁定", user.username);
+                return Err(AuthError::AccountLocked(LOCKOUT_DURATION.num_minutes()));
+            }
+            self.db.record_login_failure(&user.id, None)?;
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        if user.status != "active" {
+            return Err(AuthError::AccountDisabled);
+        }
+
+        self.db.record_login_success(&user.id)?;
+        self.start_session(&user.id)
+    }
+
+    fn start_session(&self, user_id: &str) -> Result<LoginResult> {
+        let session = Session {
+            token: generate_token(),
+            user_id: user_id.to_string(),
+            expires_at: Utc::now() + SESSION_TTL,
+        };
+        *self.session.lock().unwrap() = Some(session.clone());
+        let user = self.db.get_user(user_id)?.ok_or(AuthError::UserNotFound)?;
+        Ok(LoginResult {
+            user,
+            token: session.token,
+            expires_at: session.expires_at,
+        })
+    }
+
+    pub fn logout(&self) {
+        self.session.lock().unwrap().take();
+    }
+
+    /// 当前会话（每次访问顺延有效期）
+    pub fn session(&self) -> Result<Session> {
+        let mut guard = self.session.lock().unwrap();
+        let session = guard.as_mut().ok_or(AuthError::NotAuthenticated)?;
+        let now = Utc::now();
+        if session.expires_at <= now {
+            guard.take();
+            return Err(AuthError::SessionExpired);
+        }
+        session.expires_at = now + SESSION_TTL;
+        Ok(session.clone())
+    }
+
+    /// 当前登录用户，账号被停用或删除时结束会话
+    pub fn current_user(&self) -> Result<User> {
+        let session = self.session()?;
+        match self.db.get_user(&session.user_id)? {
+            Some(user) if user.status == "active" => Ok(user),
+            _ => {
+                self.logout();
+                Err(AuthError::NotAuthenticated)
+            }
+        }
+    }
+
+    /// 当前用户必须是管理员，拒绝时写入审计日志
+    pub fn require_admin(&self) -> Result<User> {
+        let user = self.current_user()?;
+        if user.role != "admin" {
+            self.audit(Some(&user), "users", "admin", "", &AuthError::AdminRequired.to_string());
+            return Err(AuthError::AdminRequired);
+        }
+        Ok(user)
+    }
+
+    /// 校验当前用户的旧密码与新密码强度，不修改密码；用于先重新加密用户的存储再保存新密码
+    pub fn check_password_change(&self, old_password: &str, new_password: &str) -> Result<User> {
+        let user = self.current_user()?;
+        let current_hash = self.db.get_password_hash(&user.id)?.ok_or(AuthError::UserNotFound)?;
+        if !verify_password(old_password, &current_hash) {
+            return Err(AuthError::InvalidCredentials);
+        }
+        check_password_strength(new_password)?;
+        Ok(user)
+    }
+
+    /// 修改当前用户的密码，返回用户
+    pub fn change_password(&self, old_password: &str, new_password: &str) -> Result<User> {
+        let user = self.check_password_change(old_password, new_password)?;
+        self.db.set_password_hash(&user.id, &hash_password(new_password)?)?;
+        Ok(user)
+    }
+
+    /// 管理员创建用户
+    pub fn create_user(&self, input: UserInput, password: &str) -> Result<User> {
+        self.require_admin()?;
+        check_password_strength(password)?;
+        let user = Self::new_user(input)?;
+        if self.db.get_user_credentials(&user.username)?.is_some() {
+            return Err(AuthError::UsernameTaken);
+        }
+        self.db.create_user(&user, &hash_password(password)?)?;
+        Ok(user)
+    }
+
+    /// 管理员修改用户资料、角色或状态（用户名不可修改，空字段保持原值）
+    pub fn update_user(&self, id: &str, input: UserInput) -> Result<User> {
+        let admin = self.require_admin()?;
+        let existing = self.db.get_user(id)?.ok_or(AuthError::UserNotFound)?;
+        let role = if input.role.is_empty() { existing.role.clone() } else { input.role };
+        let status = if input.status.is_empty() { existing.status.clone() } else { input.status };
+        validate_role_and_status(&role, &status)?;
+        if admin.id == id && (role != "admin" || status != "active") {
+            return Err(AuthError::InvalidInput("不能停用自己或取消自己的管理员角色".to_string()));
+        }
+        // 未填写的字段保持原值
+        let keep = |value: String, old: &str| if value.is_empty() { old.to_string() } else { value };
+        let user = User {
+            name: keep(input.name, &existing.name),
+            email: keep(input.email, &existing.email),
+            phone: keep(input.phone, &existing.phone),
+            department: keep(input.department, &existing.department),
+            role,
+            status,
+            updated_at: Utc::now().to_rfc3339(),
+            ..existing
+        };
+        self.db.update_user(&user)?;
+        Ok(user)
+    }
+
+    /// 管理员重置用户密码（同时解除锁定）
+    pub fn reset_password(&self, id: &str, new_password: &str) -> Result<()> {
+        self.require_admin()?;
+        check_password_strength(new_password)?;
+        self.db.get_user(id)?.ok_or(AuthError::UserNotFound)?;
+        self.db.set_password_hash(id, &hash_password(new_password)?)?;
+        Ok(())
+    }
+
+    fn new_user(input: UserInput) -> Result<User> {
+        let username = input.username.trim().to_string();
+        if username.is_empty() || username.chars().any(char::is_whitespace) {
+            return Err(AuthError::InvalidInput("用户名不能为空或包含空格".to_string()));
+        }
+        let role = if input.role.is_empty() { "staff".to_string() } else { input.role };
+        let status = if input.status.is_empty() { "active".to_string() } else { input.status };
+        validate_role_and_status(&role, &status)?;
+        let now = Utc::now().to_rfc3339();
+        Ok(User {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: if input.name.is_empty() { username.clone() } else { input.name },
+            username,
+            email: input.email,
+            phone: input.phone,
+            role,
+            department: input.department,
+            status,
+            last_login_at: None,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+    }
+}
+
+fn validate_role_and_status(role: &str, status: &str) -> Result<()> {
+    if !ROLES.contains(&role) {
+        return Err(AuthError::InvalidInput(format!("未知角色: {}", role)));
+    }
+    if status != "active" && status != "inactive" {
+        return Err(AuthError::InvalidInput(format!("未知状态: {}", status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn manager() -> (tempfile::TempDir, AuthManager) {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("test.db")).unwrap());
+        (dir, AuthManager::new(db))
+    }
+
+    fn input(username: &str) -> UserInput {
+        UserInput { username: username.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_setup_login_and_change_password() {
+        let (_dir, auth) = manager();
+        assert!(auth.needs_setup().unwrap());
+        assert!(matches!(auth.current_user(), Err(AuthError::NotAuthenticated)));
+
+        let result = auth.setup_admin(input("admin"), "admin-pass-1").unwrap();
+        assert_eq!(result.user.role, "admin");
+        assert!(!auth.needs_setup().unwrap());
+        assert!(matches!(auth.setup_admin(input("other"), "admin-pass-1"), Err(AuthError::SetupCompleted)));
+
+        auth.logout();
+        assert!(matches!(auth.login("admin", "wrong-pass"), Err(AuthError::InvalidCredentials)));
+        assert!(matches!(auth.login("nobody", "admin-pass-1"), Err(AuthError::InvalidCredentials)));
+        let result = auth.login("ADMIN", "admin-pass-1").unwrap();
+        assert_eq!(auth.session().unwrap().token, result.token);
+        assert_eq!(auth.current_user().unwrap().username, "admin");
+
+        assert!(matches!(auth.check_password_change("wrong-pass", "new-pass-123"), Err(AuthError::InvalidCredentials)));
+        auth.check_password_change("admin-pass-1", "new-pass-123").unwrap();
+        auth.logout();
+        auth.login("admin", "admin-pass-1").unwrap();
+        assert!(matches!(auth.change_password("wrong-pass", "new-pass-123"), Err(AuthError::InvalidCredentials)));
+        assert!(matches!(auth.change_password("admin-pass-1", "short"), Err(AuthError::WeakPassword)));
+        auth.change_password("admin-pass-1", "new-pass-123").unwrap();
+        auth.logout();
+        assert!(auth.login("admin", "admin-pass-1").is_err());
+        auth.login("admin", "new-pass-123").unwrap();
+    }
+
+    #[test]
+    fn test_lockout_and_user_management() {
+        let (_dir, auth) = manager();
+        auth.setup_admin(input("admin"), "admin-pass-1").unwrap();
+        let staff = auth.create_user(input("zhang"), "staff-pass-1").unwrap();
+        assert_eq!(staff.role, "staff");
+        assert!(matches!(auth.create_user(input("Zhang"), "staff-pass-1"), Err(AuthError::UsernameTaken)));
+
+        for _ in 0..MAX_FAILED_ATTEMPTS - 1 {
+            assert!(matches!(auth.login("zhang", "bad"), Err(AuthError::InvalidCredentials)));
+        }
+        assert!(matches!(auth.login("zhang", "bad"), Err(AuthError::AccountLocked(_))));
+        // 锁定期间正确的密码也无法登录
+        assert!(matches!(auth.login("zhang", "staff-pass-1"), Err(AuthError::AccountLocked(_))));
+
+        // 管理员重置密码后解除锁定；非管理员不能管理用户
+        auth.login("admin", "admin-pass-1").unwrap();
+        auth.reset_password(&staff.id, "staff-pass-2").unwrap();
+        auth.login("zhang", "staff-pass-2").unwrap();
+        assert!(matches!(auth.create_user(input("li"), "staff-pass-1"), Err(AuthError::AdminRequired)));
+
+        // 停用账号后当前会话失效
+        auth.login("admin", "admin-pass-1").unwrap();
+        auth.update_user(&staff.id, UserInput { status: "inactive".to_string(), ..Default::default() }).unwrap();
+        assert!(matches!(auth.login("zhang", "staff-pass-2"), Err(AuthError::AccountDisabled)));
+    }
+
+    #[test]
+    fn test_permissions_and_audit() {
+        let (_dir, auth) = manager();
+        auth.setup_admin(input("admin"), "admin-pass-1").unwrap();
+        auth.create_user(UserInput { role: "warehouse".to_string(), ..input("clerk") }, "clerk-pass-1").unwrap();
+        assert!(auth.authorize(Module::Customers, Action::Delete, "c1").is_ok());
+
+        auth.login("clerk", "clerk-pass-1").unwrap();
+        assert!(auth.authorize(Module::Stock, Action::Create, "p1").is_ok());
+        let err = auth.authorize(Module::Customers, Action::Delete, "c1").unwrap_err();
+        assert!(matches!(err, AuthError::PermissionDenied { module: "customers", action: "delete" }));
+        assert_eq!(err.code(), Some("PERMISSION_DENIED"));
+        assert!(matches!(auth.authorize(Module::Reports, Action::View, ""), Err(AuthError::PermissionDenied { .. })));
+        assert!(matches!(auth.audit_log(true, 10), Err(AuthError::AdminRequired)));
+
+        auth.logout();
+        let err = auth.authorize(Module::Products, Action::View, "").unwrap_err();
+        assert_eq!(err.code(), Some("UNAUTHENTICATED"));
+
+        // 管理员查看拒绝记录并放开仓库角色的报表查看权限
+        auth.login("admin", "admin-pass-1").unwrap();
+        let denied = auth.audit_log(true, 10).unwrap();
+        assert_eq!(denied.len(), 4);
+        assert_eq!(denied[3].username.as_deref(), Some("clerk"));
+        assert_eq!((denied[3].module.as_str(), denied[3].action.as_str(), denied[3].target.as_str()), ("customers", "delete", "c1"));
+        assert!(denied[0].user_id.is_none());
+
+        let perms = auth.set_role_permissions("warehouse", "reports", &["view".to_string()]).unwrap();
+        assert!(perms.iter().any(|p| p.module == "reports" && p.customized && p.actions == ["view"]));
+        assert!(auth.set_role_permissions("admin", "reports", &[]).is_err());
+        assert!(auth.has_permission("warehouse", Module::Reports, Action::View).unwrap());
+        auth.reset_role_permissions("warehouse").unwrap();
+        assert!(!auth.has_permission("warehouse", Module::Reports, Action::View).unwrap());
+    }
+
+    #[test]
+    fn test_stock_approve_permission() {
+        let (_dir, auth) = manager();
+        assert_eq!(Action::parse("approve"), Some(Action::Approve));
+        assert_eq!(Action::Approve.as_str(), "approve");
+
+        // 盘点过账需要库存审核权限：仓管可录入但不能过账，经理和管理员可以
+        assert!(auth.has_permission("warehouse", Module::Stock, Action::Update).unwrap());
+        assert!(!auth.has_permission("warehouse", Module::Stock, Action::Approve).unwrap());
+        assert!(!auth.has_permission("staff", Module::Stock, Action::Approve).unwrap());
+        assert!(auth.has_permission("manager", Module::Stock, Action::Approve).unwrap());
+        assert!(auth.has_permission("admin", Module::Stock, Action::Approve).unwrap());
+
+        // 单独授予审核权限不依赖删除权限
+        auth.setup_admin(input("admin"), "admin-pass-1").unwrap();
+        auth.set_role_permissions("warehouse", "stock", &["view".to_string(), "approve".to_string()]).unwrap();
+        assert!(auth.has_permission("warehouse", Module::Stock, Action::Approve).unwrap());
+        assert!(!auth.has_permission("warehouse", Module::Stock, Action::Delete).unwrap());
+    }
+}
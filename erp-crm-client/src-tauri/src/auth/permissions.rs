@@ -0,0 +1,206 @@
+//! 角色权限 - 按模块划分的查看/新增/修改/删除权限与拒绝审计
+//!
+//! 每个角色有内置默认权限，管理员可以按模块覆盖（保存在 `role_permissions` 表）。
+//! 管理员角色始终拥有全部权限。
+
+use serde::Serialize;
+
+use super::{AuthError, AuthManager, Result};
+use crate::database::{AuditEntry, User};
+
+/// 业务模块
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Module {
+    Customers,
+    Products,
+    Orders,
+    Stock,
+    Reports,
+    Settings,
+}
+
+/// 操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    View,
+    Create,
+    Update,
+    Delete,
+    /// 审核过账（盘点差异等）
+    Approve,
+}
+
+impl Module {
+    pub const ALL: [Module; 6] =
+        [Module::Customers, Module::Products, Module::Orders, Module::Stock, Module::Reports, Module::Settings];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Module::Customers => "customers",
+            Module::Products => "products",
+            Module::Orders => "orders",
+            Module::Stock => "stock",
+            Module::Reports => "reports",
+            Module::Settings => "settings",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == s)
+    }
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [Action::View, Action::Create, Action::Update, Action::Delete, Action::Approve];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::View => "view",
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+            Action::Approve => "approve",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == s)
+    }
+}
+
+/// 角色在某个模块上的权限
+#[derive(Debug, Clone, Serialize)]
+pub struct ModulePermission {
+    pub module: String,
+    pub actions: Vec<String>,
+    /// 是否为管理员修改过的配置
+    pub customized: bool,
+}
+
+/// 内置默认权限
+pub fn default_actions(role: &str, module: Module) -> &'static [Action] {
+    use Action::*;
+    use Module::*;
+    const ALL: &[Action] = &[View, Create, Update, Delete, Approve];
+    const VIEW: &[Action] = &[View];
+    const EDIT: &[Action] = &[View, Create, Update];
+    match (role, module) {
+        ("admin", _) => ALL,
+        ("manager", Customers | Products | Orders | Stock) => ALL,
+        ("manager", Reports | Settings) => VIEW,
+        ("staff", Customers | Orders) => EDIT,
+        ("staff", Products | Stock) => VIEW,
+        ("warehouse", Products | Stock) => EDIT,
+        ("warehouse", Orders) => VIEW,
+        ("viewer", Customers | Products | Orders | Stock) => VIEW,
+        _ => &[],
+    }
+}
+
+/// 解析逗号分隔的操作列表，忽略未知项
+fn parse_actions(s: &str) -> Vec<Action> {
+    s.split(',').filter_map(|a| Action::parse(a.trim())).collect()
+}
+
+impl AuthManager {
+    /// 角色在模块上允许的操作（覆盖配置优先）
+    fn allowed_actions(&self, role: &str, module: Module) -> Result<(Vec<Action>, bool)> {
+        if role == "admin" {
+            return Ok((Action::ALL.to_vec(), false));
+        }
+        Ok(match self.db.get_role_permission(role, module.as_str())? {
+            Some(actions) => (parse_actions(&actions), true),
+            None => (default_actions(role, module).to_vec(), false),
+        })
+    }
+
+    pub fn has_permission(&self, role: &str, module: Module, action: Action) -> Result<bool> {
+        Ok(self.allowed_actions(role, module)?.0.contains(&action))
+    }
+
+    /// 角色的完整权限表
+    pub fn permissions_for(&self, role: &str) -> Result<Vec<ModulePermission>> {
+        Module::ALL
+            .into_iter()
+            .map(|module| {
+                let (actions, customized) = self.allowed_actions(role, module)?;
+                Ok(ModulePermission {
+                    module: module.as_str().to_string(),
+                    actions: actions.iter().map(|a| a.as_str().to_string()).collect(),
+                    customized,
+                })
+            })
+            .collect()
+    }
+
+    /// 校验当前用户能否执行操作，返回当前用户；未登录或无权限时写入审计日志
+    pub fn authorize(&self, module: Module, action: Action, target: &str) -> Result<User> {
+        let user = match self.current_user() {
+            Ok(user) => user,
+            Err(e) => {
+                self.audit(None, module.as_str(), action.as_str(), target, &e.to_string());
+                return Err(e);
+            }
+        };
+        if !self.has_permission(&user.role, module, action)? {
+            let err = AuthError::PermissionDenied { module: module.as_str(), action: action.as_str() };
+            log::warn!("用户 {} 无权执行 {}:{} {}", user.username, module.as_str(), action.as_str(), target);
+            self.audit(Some(&user), module.as_str(), action.as_str(), target, &err.to_string());
+            return Err(err);
+        }
+        Ok(user)
+    }
+
+    /// 管理员修改角色在模块上的权限；`actions` 为空表示该模块无任何权限
+    pub fn set_role_permissions(&self, role: &str, module: &str, actions: &[String]) -> Result<Vec<ModulePermission>> {
+        self.require_admin()?;
+        if !super::ROLES.contains(&role) {
+            return Err(AuthError::InvalidInput(format!("未知角色: {}", role)));
+        }
+        if role == "admin" {
+            return Err(AuthError::InvalidInput("管理员角色始终拥有全部权限".to_string()));
+        }
+        let module = Module::parse(module).ok_or_else(|| AuthError::InvalidInput(format!("未知模块: {}", module)))?;
+        let mut parsed = Vec::new();
+        for action in actions {
+            let action = Action::parse(action).ok_or_else(|| AuthError::InvalidInput(format!("未知操作: {}", action)))?;
+            if !parsed.contains(&action) {
+                parsed.push(action);
+            }
+        }
+        let value = parsed.iter().map(|a| a.as_str()).collect::<Vec<_>>().join(",");
+        self.db.set_role_permission(role, module.as_str(), &value)?;
+        self.permissions_for(role)
+    }
+
+    /// 管理员恢复角色的默认权限
+    pub fn reset_role_permissions(&self, role: &str) -> Result<Vec<ModulePermission>> {
+        self.require_admin()?;
+        self.db.reset_role_permissions(role)?;
+        self.permissions_for(role)
+    }
+
+    /// 管理员查看审计日志
+    pub fn audit_log(&self, only_denied: bool, limit: usize) -> Result<Vec<AuditEntry>> {
+        self.require_admin()?;
+        Ok(self.db.list_audit(only_denied.then_some("denied"), limit)?)
+    }
+
+    /// 记录一次拒绝访问，写入失败只记日志，不影响返回给调用方的错误
+    pub(super) fn audit(&self, user: Option<&User>, module: &str, action: &str, target: &str, detail: &str) {
+        let entry = AuditEntry {
+            id: 0,
+            user_id: user.map(|u| u.id.clone()),
+            username: user.map(|u| u.username.clone()),
+            module: module.to_string(),
+            action: action.to_string(),
+            target: target.to_string(),
+            result: "denied".to_string(),
+            detail: detail.to_string(),
+            created_at: String::new(),
+        };
+        if let Err(e) = self.db.insert_audit(&entry) {
+            log::error!("写入审计日志失败: {}", e);
+        }
+    }
+}
@@ -5,11 +5,155 @@
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tauri::{AppHandle, Emitter, State};
-use crate::database::{Database, Customer, Product, Order};
-use chrono::Utc;
-use std::sync::Arc;
+use crate::auth::{Action, AuthError, AuthManager, Module, UserInput};
+use crate::database::{Database, DatabaseKey, EncryptionError, Customer, User, generate_database_key, Product, Order, OrderItem, OrderStatus, ItemShipment, OversellPolicy, PaymentInput, PaymentStatus, PaymentType, StockBatch, StockCountInput, StockMovement, StockRecord, TransferItemInput, Warehouse, ConflictResolution, CostMethod, ExportFilter, ImportEntity, ImportOptions, ReportExport, ReportGranularity, SalesReportQuery, suggest_columns, API_BASE_URL_KEY, SYNC_INTERVAL_KEY};
+use crate::spreadsheet::{read_sheet, write_sheet, Sheet, SheetFormat};
+use crate::scanner::{gtin_variants, parse_barcode, simulate_scan, ParsedBarcode, ScanResult, ScannerConfig, ScannerManager, WedgeKeyEvent};
+use crate::storage::{is_reserved_key, StorageError, UserVaults};
+use crate::sync::{ConflictStrategy, SyncEvent, SyncManager, DEFAULT_API_BASE_URL, DEFAULT_SYNC_INTERVAL_MINUTES, VERSIONED_ENTITIES};
+use chrono::{Datelike, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::mpsc;
 
 pub struct DbState(pub Arc<Database>);
+pub struct AuthState(pub Arc<AuthManager>);
+/// 当前登录用户的加密存储
+pub struct StorageState(pub Arc<UserVaults>);
+pub struct ScannerState(pub ScannerManager);
+
+/// 同步管理器及自动同步任务的停止信号
+pub struct SyncState {
+    manager: RwLock<Arc<SyncManager>>,
+    auto_sync: Mutex<Option<mpsc::Sender<()>>>,
+}
+
+impl SyncState {
+    pub fn new(manager: SyncManager) -> Self {
+        Self { manager: RwLock::new(Arc::new(manager)), auto_sync: Mutex::new(None) }
+    }
+
+    fn manager(&self) -> Arc<SyncManager> {
+        Arc::clone(&self.manager.read().unwrap())
+    }
+
+    /// 自动同步任务是否在运行
+    fn auto_sync_running(&self) -> bool {
+        self.auto_sync.lock().unwrap().as_ref().is_some_and(|tx| !tx.is_closed())
+    }
+
+    /// 停止自动同步任务（丢弃发送端即可让任务退出）
+    fn stop_auto_sync(&self) -> bool {
+        self.auto_sync.lock().unwrap().take().is_some_and(|tx| !tx.is_closed())
+    }
+}
+
+/// 按保存的服务器地址与同步间隔创建同步管理器，同步事件转发为 `sync:progress` / `sync:complete` / `sync:error`
+pub fn build_sync_manager(app: &AppHandle, db: Arc<Database>) -> SyncManager {
+    let api_base_url = db.get_sync_state(API_BASE_URL_KEY).ok().flatten()
+        .unwrap_or_else(|| DEFAULT_API_BASE_URL.to_string());
+    let interval = db.get_sync_state(SYNC_INTERVAL_KEY).ok().flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_SYNC_INTERVAL_MINUTES);
+    let app = app.clone();
+    SyncManager::new(db, api_base_url, interval).with_event_handler(move |event| {
+        match event {
+            SyncEvent::Progress(progress) => app.emit("sync:progress", progress).ok(),
+            SyncEvent::Complete(report) => app.emit("sync:complete", report).ok(),
+            SyncEvent::Error(error) => app.emit("sync:error", json!({ "error": error })).ok(),
+        };
+    })
+}
+
+/// 创建扫码枪管理器，每次扫码查找对应产品后以 `scanner:scan` 事件通知前端
+pub fn build_scanner_manager(app: &AppHandle, db: Arc<Database>) -> ScannerManager {
+    let app = app.clone();
+    let manager = ScannerManager::new();
+    manager.set_callback(move |result| {
+        if let Err(e) = app.emit("scanner:scan", scan_payload(&db, result)) {
+            log::error!("发送扫码事件失败: {}", e);
+        }
+    });
+    manager
+}
+
+/// 扫码结果附带解析出的 GS1 字段和对应的产品
+fn scan_payload(db: &Database, result: ScanResult) -> serde_json::Value {
+    let parsed = parse_barcode(&result.barcode);
+    let product = resolve_product(db, &parsed).ok().flatten();
+    json!({ "barcode": result.barcode, "timestamp": result.timestamp, "parsed": parsed, "product": product })
+}
+
+/// 按条码查找产品：有 GTIN 时依次匹配 14/13/12/8 位写法，否则（或未找到时）按条码内容精确匹配
+fn resolve_product(db: &Database, parsed: &ParsedBarcode) -> rusqlite::Result<Option<Product>> {
+    let gtins = parsed.gtin.as_deref().map(gtin_variants).unwrap_or_default();
+    for barcode in gtins.iter().map(String::as_str).chain([parsed.code.as_str(), parsed.raw.as_str()]) {
+        if barcode.is_empty() {
+            continue;
+        }
+        if let Some(product) = db.get_product_by_barcode(barcode)? {
+            return Ok(Some(product));
+        }
+    }
+    Ok(None)
+}
+
+/// 出入库的批次信息：可直接传 `batchNo` / `productionDate` / `expiryDate`，也可传扫到的 `barcode` 从 GS1 字段中提取（显式传入的优先）
+fn stock_batch(batch: Option<&serde_json::Value>) -> Result<StockBatch, String> {
+    let Some(batch) = batch else {
+        return Ok(StockBatch::default());
+    };
+    let parsed = batch["barcode"].as_str().map(parse_barcode);
+    let batch_no = batch["batchNo"].as_str().map(|s| s.trim().to_string())
+        .or_else(|| parsed.as_ref().and_then(|p| p.batch.clone()))
+        .unwrap_or_default();
+    let production_date = batch["productionDate"].as_str().map(|s| s.trim().to_string())
+        .or_else(|| parsed.as_ref().and_then(|p| p.production.clone()))
+        .unwrap_or_default();
+    let expiry_date = batch["expiryDate"].as_str().map(|s| s.trim().to_string())
+        .or_else(|| parsed.as_ref().and_then(|p| p.expiry.clone()))
+        .unwrap_or_default();
+    for (label, date) in [("生产日期", &production_date), ("有效期", &expiry_date)] {
+        if !date.is_empty() && chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return Err(format!("{}格式应为 YYYY-MM-DD: {}", label, date));
+        }
+    }
+    if !production_date.is_empty() && !expiry_date.is_empty() && expiry_date < production_date {
+        return Err(format!("有效期 {} 早于生产日期 {}", expiry_date, production_date));
+    }
+    Ok(StockBatch { batch_no, production_date, expiry_date })
+}
+
+/// 出入库流水按批次汇总，出库按先到期先出拆分时有多行
+fn batch_parts(records: &[StockRecord]) -> Vec<serde_json::Value> {
+    records.iter().map(|r| json!({ "batchNo": r.batch_no, "expiryDate": r.expiry_date, "quantity": r.quantity })).collect()
+}
+
+/// 订单明细：`items` 中每项传 `productId`、`quantity`、`unitPrice`，可选 `discount`（金额）和 `remark`，产品名称等从产品表带出
+fn order_items(db: &Database, order_id: &str, items: &serde_json::Value) -> Result<Vec<OrderItem>, String> {
+    let Some(items) = items.as_array() else {
+        return Ok(Vec::new());
+    };
+    items.iter().map(|item| {
+        let product_id = item["productId"].as_str().unwrap_or("");
+        let quantity = item["quantity"].as_i64().unwrap_or(0) as i32;
+        if quantity <= 0 {
+            return Err(format!("明细数量必须大于 0: {}", product_id));
+        }
+        let product = db.get_product(product_id).map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("产品不存在: {}", product_id))?;
+        let unit_price = item["unitPrice"].as_f64().unwrap_or(product.sell_price);
+        let discount = item["discount"].as_f64().unwrap_or(0.0);
+        Ok(OrderItem {
+            id: uuid::Uuid::new_v4().to_string(), order_id: order_id.to_string(),
+            product_id: product.id, product_name: product.name, product_code: product.code, unit: product.unit,
+            quantity, unit_price, discount,
+            amount: quantity as f64 * unit_price - discount,
+            remark: item["remark"].as_str().unwrap_or("").to_string(),
+            shipped_quantity: 0,
+        })
+    }).collect()
+}
 
 #[derive(Debug, Serialize)]
 pub struct CommandError(String);
@@ -23,30 +167,274 @@ pub struct ApiResponse<T> {
     pub data: Option<T>,
     pub error: Option<String>,
     pub message: Option<String>,
+    /// 错误码：UNAUTHENTICATED（未登录）/ PERMISSION_DENIED（无权限）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 impl<T> ApiResponse<T> {
-    pub fn success(data: T) -> Self { Self { success: true, data: Some(data), error: None, message: None } }
-    pub fn error(error: &str) -> Self { Self { success: false, data: None, error: Some(error.to_string()), message: None } }
+    pub fn success(data: T) -> Self { Self { success: true, data: Some(data), error: None, message: None, code: None } }
+    pub fn error(error: &str) -> Self { Self { success: false, data: None, error: Some(error.to_string()), message: None, code: None } }
+    pub fn auth_error(error: &AuthError) -> Self { Self { code: error.code().map(str::to_string), ..Self::error(&error.to_string()) } }
+}
+
+/// 校验当前用户的模块权限，拒绝时返回带错误码的响应（已写入审计日志）
+fn authorize<T>(auth: &AuthState, module: Module, action: Action, target: &str) -> Result<User, ApiResponse<T>> {
+    auth.0.authorize(module, action, target).map_err(|e| ApiResponse::auth_error(&e))
+}
+
+/// 只要求已登录
+fn require_login<T>(auth: &AuthState) -> Result<User, ApiResponse<T>> {
+    auth.0.current_user().map_err(|e| ApiResponse::auth_error(&e))
+}
+
+/// 同步冲突所属的权限模块
+fn conflict_module(entity_type: &str) -> Module {
+    match entity_type {
+        "customer" => Module::Customers,
+        "product" => Module::Products,
+        _ => Module::Orders,
+    }
+}
+
+fn user_input(data: &serde_json::Value) -> UserInput {
+    UserInput {
+        username: data["username"].as_str().unwrap_or("").to_string(),
+        name: data["name"].as_str().unwrap_or("").to_string(),
+        email: data["email"].as_str().unwrap_or("").to_string(),
+        phone: data["phone"].as_str().unwrap_or("").to_string(),
+        role: data["role"].as_str().unwrap_or("").to_string(),
+        department: data["department"].as_str().unwrap_or("").to_string(),
+        status: data["status"].as_str().unwrap_or("").to_string(),
+    }
+}
+
+/// 登录成功后解锁用户的加密存储；失败不影响登录，只是加密存储保持锁定
+fn unlock_storage(vaults: &UserVaults, user: &User, password: &str) {
+    if let Err(e) = vaults.unlock(&user.id, &user.username, password) {
+        log::error!("解锁加密存储失败: {}", e);
+    }
+}
+
+/// 数据库加密锁定时，用登录密码解锁用户的加密存储，取出数据库密钥解锁数据库
+fn unlock_database(db: &Database, vaults: &UserVaults, username: &str, password: &str) -> Result<(), AuthError> {
+    // 数据库锁定时无法查询用户表，没有加密存储的账号只能等管理员解锁数据库后登录一次获得密钥
+    let user_id = vaults.user_id_for(username).ok_or(AuthError::DatabaseKeyMissing)?;
+    match vaults.unlock(&user_id, username.trim(), password) {
+        Ok(_) => {}
+        Err(StorageError::WrongPassword) => return Err(AuthError::InvalidCredentials),
+        Err(e) => return Err(AuthError::DatabaseLocked(e.to_string())),
+    }
+    let keys = vaults.database_keys(&user_id).map_err(|e| AuthError::DatabaseLocked(e.to_string()))?;
+    for key in keys {
+        let Ok(key) = DatabaseKey::try_from(key.as_slice()) else { continue };
+        match db.unlock(&key) {
+            Ok(_) => return Ok(()),
+            Err(EncryptionError::WrongKey) => continue,
+            Err(e) => {
+                vaults.lock();
+                return Err(AuthError::DatabaseLocked(e.to_string()));
+            }
+        }
+    }
+    vaults.lock();
+    Err(AuthError::DatabaseKeyMissing)
+}
+
+/// 数据库已加密时，把密钥同步到用户已解锁的加密存储（加密或换密钥后其他用户首次登录时获得新密钥）
+fn share_database_key(db: &Database, vaults: &UserVaults, user_id: &str) {
+    if let Some(key) = db.encryption_key() {
+        if vaults.is_unlocked_for(user_id) {
+            if let Err(e) = vaults.set_database_key(user_id, &key, false) {
+                log::error!("保存数据库密钥失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 加密或换密钥后的提示：其他在用账号的加密存储只能在本人登录时写入新密钥
+fn key_pending_message(db: &Database, admin_id: &str) -> Option<String> {
+    let pending: Vec<String> = db
+        .list_users()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|user| user.id != admin_id && user.status == "active")
+        .map(|user| user.username)
+        .collect();
+    (!pending.is_empty()).then(|| format!(
+        "以下账号需在程序重启前登录一次以获得数据库密钥，否则重启后需管理员先登录解锁数据库: {}",
+        pending.join("、")
+    ))
+}
+
+/// 存储命令不能读写内部使用的数据库密钥
+fn check_entry_key(key: &str) -> crate::storage::Result<()> {
+    if is_reserved_key(key) {
+        return Err(StorageError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+/// 已登录且加密存储已解锁时执行操作
+fn with_storage<T>(auth: &AuthState, storage: &StorageState, f: impl FnOnce(&UserVaults, &str) -> crate::storage::Result<T>) -> ApiResponse<T> {
+    let user = match require_login(auth) {
+        Ok(user) => user,
+        Err(denied) => {
+            storage.0.lock();
+            return denied;
+        }
+    };
+    match f(&storage.0, &user.id) {
+        Ok(value) => ApiResponse::success(value),
+        Err(StorageError::Locked) => ApiResponse::error("加密存储未解锁，请重新登录"),
+        Err(e) => ApiResponse::error(&format!("加密存储操作失败: {}", e)),
+    }
+}
+
+/// 是否需要首次运行设置管理员
+#[tauri::command]
+pub async fn auth_needs_setup(auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    match auth.0.needs_setup() {
+        Ok(needs_setup) => Ok(ApiResponse::success(json!({ "needsSetup": needs_setup }))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+/// 首次运行创建管理员账号并登录
+#[tauri::command]
+pub async fn auth_setup(auth: State<'_, AuthState>, storage: State<'_, StorageState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let password = data["password"].as_str().unwrap_or("");
+    match auth.0.setup_admin(user_input(&data), password) {
+        Ok(result) => {
+            unlock_storage(&storage.0, &result.user, password);
+            Ok(ApiResponse::success(json!(result)))
+        }
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+#[tauri::command]
+pub async fn auth_login(auth: State<'_, AuthState>, db: State<'_, DbState>, storage: State<'_, StorageState>, username: String, password: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    // Argon2 校验与密钥派生较慢，放到阻塞线程池中执行
+    let auth = Arc::clone(&auth.0);
+    let db = Arc::clone(&db.0);
+    let vaults = Arc::clone(&storage.0);
+    let result = tokio::task::spawn_blocking(move || {
+        vaults.lock();
+        if db.is_locked() {
+            unlock_database(&db, &vaults, &username, &password)?;
+        }
+        let result = auth.login(&username, &password).inspect_err(|_| vaults.lock())?;
+        if !vaults.is_unlocked_for(&result.user.id) {
+            unlock_storage(&vaults, &result.user, &password);
+        }
+        share_database_key(&db, &vaults, &result.user.id);
+        Ok::<_, AuthError>(result)
+    })
+        .await
+        .map_err(|e| CommandError(e.to_string()))?;
+    match result {
+        Ok(result) => Ok(ApiResponse::success(json!(result))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+#[tauri::command]
+pub async fn auth_logout(auth: State<'_, AuthState>, storage: State<'_, StorageState>) -> CmdResult<ApiResponse<()>> {
+    auth.0.logout();
+    storage.0.lock();
+    Ok(ApiResponse::success(()))
+}
+
+#[tauri::command]
+pub async fn auth_get_current_user(auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    match auth.0.current_user() {
+        Ok(user) => Ok(ApiResponse::success(json!(user))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+#[tauri::command]
+pub async fn auth_change_password(auth: State<'_, AuthState>, storage: State<'_, StorageState>, old_password: String, new_password: String) -> CmdResult<ApiResponse<()>> {
+    // 先用新密码重新加密存储（其中有数据库密钥），成功后再保存新密码；任一步失败都保持旧密码
+    let user = match auth.0.check_password_change(&old_password, &new_password) {
+        Ok(user) => user,
+        Err(e) => return Ok(ApiResponse::auth_error(&e)),
+    };
+    if let Err(e) = storage.0.change_password(&user.id, &old_password, &new_password) {
+        log::error!("加密存储重新加密失败: {}", e);
+        return Ok(ApiResponse::error(&format!("加密存储重新加密失败，密码未修改: {}", e)));
+    }
+    match auth.0.change_password(&old_password, &new_password) {
+        Ok(_) => Ok(ApiResponse::success(())),
+        Err(e) => {
+            if let Err(revert) = storage.0.change_password(&user.id, &new_password, &old_password) {
+                log::error!("恢复加密存储密码失败: {}", revert);
+            }
+            Ok(ApiResponse::auth_error(&e))
+        }
+    }
 }
 
 #[tauri::command]
-pub async fn auth_login(username: String, password: String) -> ApiResponse<serde_json::Value> {
-    if username == "admin" && password == "admin123" {
-        ApiResponse::success(json!({"id": "user-1", "username": "admin", "name": "管理员", "email": "admin@example.com", "role": "admin"}))
-    } else { ApiResponse::error("用户名或密码错误") }
+pub async fn user_list(auth: State<'_, AuthState>, db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(e) = auth.0.require_admin() {
+        return Ok(ApiResponse::auth_error(&e));
+    }
+    match db.0.list_users() {
+        Ok(users) => Ok(ApiResponse::success(json!({ "items": users, "total": users.len() }))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取用户列表失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn user_create(auth: State<'_, AuthState>, db: State<'_, DbState>, storage: State<'_, StorageState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let password = data["password"].as_str().unwrap_or("");
+    match auth.0.create_user(user_input(&data), password) {
+        Ok(user) => {
+            // 数据库已加密时为新用户准备带数据库密钥的加密存储，否则无法登录
+            if let Some(key) = db.0.encryption_key() {
+                if let Err(e) = storage.0.provision(&user.id, &user.username, password, Some(&key)) {
+                    return Ok(ApiResponse::error(&format!("用户已创建，但写入数据库密钥失败: {}", e)));
+                }
+            }
+            Ok(ApiResponse::success(json!(user)))
+        }
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
 }
 
 #[tauri::command]
-pub async fn auth_logout() -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn user_update(auth: State<'_, AuthState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    match auth.0.update_user(&id, user_input(&data)) {
+        Ok(user) => Ok(ApiResponse::success(json!(user))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
 
 #[tauri::command]
-pub async fn auth_get_current_user() -> ApiResponse<serde_json::Value> {
-    ApiResponse::success(json!({"id": "user-1", "username": "admin", "name": "管理员", "email": "admin@example.com", "role": "admin"}))
+pub async fn user_reset_password(auth: State<'_, AuthState>, db: State<'_, DbState>, storage: State<'_, StorageState>, id: String, password: String) -> CmdResult<ApiResponse<()>> {
+    match auth.0.reset_password(&id, &password) {
+        // 旧密码派生的密钥已无法获得，用户的加密存储随之清空；数据库已加密时重新写入数据库密钥
+        Ok(_) => {
+            let reset = match (db.0.encryption_key(), db.0.get_user(&id)) {
+                (Some(key), Ok(Some(user))) => storage.0.provision(&id, &user.username, &password, Some(&key)),
+                _ => storage.0.reset(&id),
+            };
+            match reset {
+                Ok(_) => Ok(ApiResponse::success(())),
+                Err(e) => Ok(ApiResponse::error(&format!("重建加密存储失败: {}", e)))
+            }
+        }
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
 }
 
 #[tauri::command]
-pub async fn customer_list(db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn customer_list(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Customers, Action::View, "") {
+        return Ok(denied);
+    }
     match db.0.list_customers() {
         Ok(customers) => Ok(ApiResponse::success(json!({ "items": customers, "total": customers.len() }))),
         Err(e) => Ok(ApiResponse::error(&format!("获取客户列表失败: {}", e)))
@@ -54,7 +442,10 @@ pub async fn customer_list(db: State<'_, DbState>) -> CmdResult<ApiResponse<serd
 }
 
 #[tauri::command]
-pub async fn customer_get(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn customer_get(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Customers, Action::View, &id) {
+        return Ok(denied);
+    }
     match db.0.get_customer(&id) {
         Ok(Some(c)) => Ok(ApiResponse::success(json!(c))),
         Ok(None) => Ok(ApiResponse::error("客户不存在")),
@@ -63,7 +454,10 @@ pub async fn customer_get(db: State<'_, DbState>, id: String) -> CmdResult<ApiRe
 }
 
 #[tauri::command]
-pub async fn customer_create(db: State<'_, DbState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn customer_create(db: State<'_, DbState>, auth: State<'_, AuthState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Customers, Action::Create, "") {
+        return Ok(denied);
+    }
     let now = Utc::now().to_rfc3339();
     let customer = Customer {
         id: uuid::Uuid::new_v4().to_string(),
@@ -76,10 +470,11 @@ pub async fn customer_create(db: State<'_, DbState>, data: serde_json::Value) ->
         status: data["status"].as_str().unwrap_or("active").to_string(),
         contact_person: data["contactPerson"].as_str().unwrap_or("").to_string(),
         credit_limit: data["creditLimit"].as_f64().unwrap_or(0.0),
-        balance: data["balance"].as_f64().unwrap_or(0.0),
+        // 应收余额只由收付款记录维护，新客户从 0 开始
+        balance: 0.0,
         remark: data["remark"].as_str().unwrap_or("").to_string(),
         source: data["source"].as_str().unwrap_or("").to_string(),
-        created_at: now.clone(), updated_at: now,
+        created_at: now.clone(), updated_at: now, version: 0,
     };
     match db.0.create_customer(&customer) {
         Ok(_) => Ok(ApiResponse::success(json!(customer))),
@@ -88,7 +483,10 @@ pub async fn customer_create(db: State<'_, DbState>, data: serde_json::Value) ->
 }
 
 #[tauri::command]
-pub async fn customer_update(db: State<'_, DbState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn customer_update(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Customers, Action::Update, &id) {
+        return Ok(denied);
+    }
     let now = Utc::now().to_rfc3339();
     let customer = Customer {
         id: id.clone(),
@@ -101,19 +499,25 @@ pub async fn customer_update(db: State<'_, DbState>, id: String, data: serde_jso
         status: data["status"].as_str().unwrap_or("active").to_string(),
         contact_person: data["contactPerson"].as_str().unwrap_or("").to_string(),
         credit_limit: data["creditLimit"].as_f64().unwrap_or(0.0),
-        balance: data["balance"].as_f64().unwrap_or(0.0),
+        // 应收余额由收付款记录维护，更新时不读取，返回值取保存后的记录
+        balance: 0.0,
         remark: data["remark"].as_str().unwrap_or("").to_string(),
         source: data["source"].as_str().unwrap_or("").to_string(),
         created_at: data["createdAt"].as_str().unwrap_or(&now).to_string(), updated_at: now,
+        version: data["version"].as_i64().unwrap_or(0),
     };
-    match db.0.update_customer(&customer) {
-        Ok(_) => Ok(ApiResponse::success(json!(customer))),
+    match db.0.update_customer(&customer).and_then(|_| db.0.get_customer(&id)) {
+        Ok(Some(saved)) => Ok(ApiResponse::success(json!(saved))),
+        Ok(None) => Ok(ApiResponse::error("客户不存在")),
         Err(e) => Ok(ApiResponse::error(&format!("更新客户失败: {}", e)))
     }
 }
 
 #[tauri::command]
-pub async fn customer_delete(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<()>> {
+pub async fn customer_delete(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = authorize(&auth, Module::Customers, Action::Delete, &id) {
+        return Ok(denied);
+    }
     match db.0.delete_customer(&id) {
         Ok(_) => Ok(ApiResponse::success(())),
         Err(e) => Ok(ApiResponse::error(&format!("删除客户失败: {}", e)))
@@ -121,7 +525,10 @@ pub async fn customer_delete(db: State<'_, DbState>, id: String) -> CmdResult<Ap
 }
 
 #[tauri::command]
-pub async fn product_list(db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn product_list(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::View, "") {
+        return Ok(denied);
+    }
     match db.0.list_products() {
         Ok(products) => Ok(ApiResponse::success(json!({ "items": products, "total": products.len() }))),
         Err(e) => Ok(ApiResponse::error(&format!("获取产品列表失败: {}", e)))
@@ -129,7 +536,10 @@ pub async fn product_list(db: State<'_, DbState>) -> CmdResult<ApiResponse<serde
 }
 
 #[tauri::command]
-pub async fn product_get(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn product_get(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::View, &id) {
+        return Ok(denied);
+    }
     match db.0.get_product(&id) {
         Ok(Some(p)) => Ok(ApiResponse::success(json!(p))),
         Ok(None) => Ok(ApiResponse::error("产品不存在")),
@@ -138,8 +548,11 @@ pub async fn product_get(db: State<'_, DbState>, id: String) -> CmdResult<ApiRes
 }
 
 #[tauri::command]
-pub async fn product_get_by_barcode(db: State<'_, DbState>, barcode: String) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.get_product_by_barcode(&barcode) {
+pub async fn product_get_by_barcode(db: State<'_, DbState>, auth: State<'_, AuthState>, barcode: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::View, &barcode) {
+        return Ok(denied);
+    }
+    match resolve_product(&db.0, &parse_barcode(&barcode)) {
         Ok(Some(p)) => Ok(ApiResponse::success(json!(p))),
         Ok(None) => Ok(ApiResponse::error("产品不存在")),
         Err(e) => Ok(ApiResponse::error(&format!("获取产品失败: {}", e)))
@@ -147,7 +560,10 @@ pub async fn product_get_by_barcode(db: State<'_, DbState>, barcode: String) ->
 }
 
 #[tauri::command]
-pub async fn product_create(db: State<'_, DbState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn product_create(db: State<'_, DbState>, auth: State<'_, AuthState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::Create, "") {
+        return Ok(denied);
+    }
     let now = Utc::now().to_rfc3339();
     let id = uuid::Uuid::new_v4().to_string();
     let product = Product {
@@ -168,7 +584,7 @@ pub async fn product_create(db: State<'_, DbState>, data: serde_json::Value) ->
         location: data["location"].as_str().unwrap_or("").to_string(),
         status: data["status"].as_str().unwrap_or("active").to_string(),
         description: data["description"].as_str().unwrap_or("").to_string(),
-        created_at: now.clone(), updated_at: now,
+        created_at: now.clone(), updated_at: now, version: 0,
     };
     match db.0.create_product(&product) {
         Ok(_) => Ok(ApiResponse::success(json!(product))),
@@ -177,7 +593,10 @@ pub async fn product_create(db: State<'_, DbState>, data: serde_json::Value) ->
 }
 
 #[tauri::command]
-pub async fn product_update(db: State<'_, DbState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+pub async fn product_update(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::Update, &id) {
+        return Ok(denied);
+    }
     let now = Utc::now().to_rfc3339();
     let product = Product {
         id: id.clone(),
@@ -192,262 +611,1406 @@ pub async fn product_update(db: State<'_, DbState>, id: String, data: serde_json
         sell_price: data["sellPrice"].as_f64().unwrap_or(0.0),
         min_stock: data["minStock"].as_i64().unwrap_or(10) as i32,
         max_stock: data["maxStock"].as_i64().unwrap_or(1000) as i32,
-        current_stock: data["currentStock"].as_i64().unwrap_or(0) as i32,
+        // 库存由出入库流水维护，更新时不读取，返回值取保存后的记录
+        current_stock: 0,
         warehouse_id: data["warehouseId"].as_str().unwrap_or("").to_string(),
         location: data["location"].as_str().unwrap_or("").to_string(),
         status: data["status"].as_str().unwrap_or("active").to_string(),
         description: data["description"].as_str().unwrap_or("").to_string(),
         created_at: data["createdAt"].as_str().unwrap_or(&now).to_string(), updated_at: now,
+        version: data["version"].as_i64().unwrap_or(0),
     };
-    match db.0.update_product(&product) {
-        Ok(_) => Ok(ApiResponse::success(json!(product))),
+    match db.0.update_product(&product).and_then(|_| db.0.get_product(&id)) {
+        Ok(Some(saved)) => Ok(ApiResponse::success(json!(saved))),
+        Ok(None) => Ok(ApiResponse::error("产品不存在")),
         Err(e) => Ok(ApiResponse::error(&format!("更新产品失败: {}", e)))
     }
 }
 
 #[tauri::command]
-pub async fn product_delete(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<()>> {
+pub async fn product_delete(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::Delete, &id) {
+        return Ok(denied);
+    }
     match db.0.delete_product(&id) {
         Ok(_) => Ok(ApiResponse::success(())),
         Err(e) => Ok(ApiResponse::error(&format!("删除产品失败: {}", e)))
     }
 }
 
+/// 低于安全库存的产品；指定仓库时按该仓库的结存判断，结果带 `warehouse_stock`
 #[tauri::command]
-pub async fn product_low_stock(db: State<'_, DbState>) -> CmdResult<ApiResponse<Vec<serde_json::Value>>> {
-    match db.0.list_low_stock_products() {
-        Ok(products) => Ok(ApiResponse::success(products.into_iter().map(|p| json!(p)).collect())),
+pub async fn product_low_stock(db: State<'_, DbState>, auth: State<'_, AuthState>, warehouse_id: Option<String>) -> CmdResult<ApiResponse<Vec<serde_json::Value>>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::View, "") {
+        return Ok(denied);
+    }
+    let result = match warehouse_id.as_deref() {
+        Some(warehouse_id) => db.0.list_low_stock_in_warehouse(warehouse_id).map(|rows| rows.into_iter().map(|r| json!(r)).collect()),
+        None => db.0.list_low_stock_products().map(|products| products.into_iter().map(|p| json!(p)).collect()),
+    };
+    match result {
+        Ok(products) => Ok(ApiResponse::success(products)),
         Err(e) => Ok(ApiResponse::error(&format!("获取低库存产品失败: {}", e)))
     }
 }
 
+/// 手工入库，未指定仓库时记到产品的所属仓库和库位
 #[tauri::command]
-pub async fn stock_in(db: State<'_, DbState>, product_id: String, quantity: i32, unit_price: f64, remark: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.update_stock(&product_id, quantity, "in", unit_price, remark.as_deref().unwrap_or("")) {
-        Ok(_) => Ok(ApiResponse::success(json!({ "productId": product_id, "type": "in", "quantity": quantity }))),
+#[allow(clippy::too_many_arguments)]
+pub async fn stock_in(db: State<'_, DbState>, auth: State<'_, AuthState>, product_id: String, quantity: i32, unit_price: f64, remark: Option<String>, batch: Option<serde_json::Value>, warehouse_id: Option<String>, location: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Create, &product_id) {
+        return Ok(denied);
+    }
+    let batch = match stock_batch(batch.as_ref()) {
+        Ok(batch) => batch,
+        Err(e) => return Ok(ApiResponse::error(&format!("入库失败: {}", e))),
+    };
+    if quantity <= 0 {
+        return Ok(ApiResponse::error("入库失败: 数量必须大于 0"));
+    }
+    let movement = StockMovement {
+        product_id: &product_id, quantity_change: quantity, record_type: "in", unit_price, remark: remark.as_deref().unwrap_or(""),
+        batch: &batch, order_id: "", warehouse_id: warehouse_id.as_deref().unwrap_or(""), location: location.as_deref().unwrap_or(""),
+    };
+    match db.0.update_stock(&movement) {
+        Ok(records) => Ok(ApiResponse::success(json!({
+            "productId": product_id, "type": "in", "quantity": quantity, "batchNo": batch.batch_no,
+            "productionDate": batch.production_date, "expiryDate": batch.expiry_date,
+            "warehouseId": records.first().map(|r| &r.warehouse_id), "location": records.first().map(|r| &r.location), "batches": batch_parts(&records),
+        }))),
         Err(e) => Ok(ApiResponse::error(&format!("入库失败: {}", e)))
     }
 }
 
+/// 手工出库，未指定仓库时记到产品的所属仓库和库位
 #[tauri::command]
-pub async fn stock_out(db: State<'_, DbState>, product_id: String, quantity: i32, unit_price: f64, remark: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.update_stock(&product_id, -quantity, "out", unit_price, remark.as_deref().unwrap_or("")) {
-        Ok(_) => Ok(ApiResponse::success(json!({ "productId": product_id, "type": "out", "quantity": quantity }))),
+#[allow(clippy::too_many_arguments)]
+pub async fn stock_out(db: State<'_, DbState>, auth: State<'_, AuthState>, product_id: String, quantity: i32, unit_price: f64, remark: Option<String>, batch: Option<serde_json::Value>, warehouse_id: Option<String>, location: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Create, &product_id) {
+        return Ok(denied);
+    }
+    let batch = match stock_batch(batch.as_ref()) {
+        Ok(batch) => batch,
+        Err(e) => return Ok(ApiResponse::error(&format!("出库失败: {}", e))),
+    };
+    if quantity <= 0 {
+        return Ok(ApiResponse::error("出库失败: 数量必须大于 0"));
+    }
+    let movement = StockMovement {
+        product_id: &product_id, quantity_change: -quantity, record_type: "out", unit_price, remark: remark.as_deref().unwrap_or(""),
+        batch: &batch, order_id: "", warehouse_id: warehouse_id.as_deref().unwrap_or(""), location: location.as_deref().unwrap_or(""),
+    };
+    match db.0.update_stock(&movement) {
+        Ok(records) => Ok(ApiResponse::success(json!({
+            "productId": product_id, "type": "out", "quantity": quantity, "batchNo": batch.batch_no,
+            "productionDate": batch.production_date, "expiryDate": batch.expiry_date,
+            "warehouseId": records.first().map(|r| &r.warehouse_id), "location": records.first().map(|r| &r.location), "batches": batch_parts(&records),
+        }))),
         Err(e) => Ok(ApiResponse::error(&format!("出库失败: {}", e)))
     }
 }
 
+/// 盘点调整：把仓库库位的结存调整为实际数量，未指定仓库时调整产品的所属仓库和库位
 #[tauri::command]
-pub async fn stock_adjust(db: State<'_, DbState>, product_id: String, actual_quantity: i32, remark: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.get_product(&product_id) {
-        Ok(Some(p)) => {
-            let diff = actual_quantity - p.current_stock;
-            match db.0.update_stock(&product_id, diff, "adjust", 0.0, remark.as_deref().unwrap_or("")) {
-                Ok(_) => Ok(ApiResponse::success(json!({ "productId": product_id, "type": "adjust", "actualQuantity": actual_quantity }))),
-                Err(e) => Ok(ApiResponse::error(&format!("调整库存失败: {}", e)))
-            }
-        }
-        Ok(None) => Ok(ApiResponse::error("产品不存在")),
-        Err(e) => Ok(ApiResponse::error(&format!("获取产品失败: {}", e)))
+pub async fn stock_adjust(db: State<'_, DbState>, auth: State<'_, AuthState>, product_id: String, actual_quantity: i32, remark: Option<String>, warehouse_id: Option<String>, location: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Update, &product_id) {
+        return Ok(denied);
+    }
+    match db.0.adjust_stock(&product_id, warehouse_id.as_deref().unwrap_or(""), location.as_deref().unwrap_or(""), actual_quantity, remark.as_deref().unwrap_or("")) {
+        Ok(records) => Ok(ApiResponse::success(json!({
+            "productId": product_id, "type": "adjust", "actualQuantity": actual_quantity,
+            "warehouseId": records.first().map(|r| &r.warehouse_id), "location": records.first().map(|r| &r.location), "batches": batch_parts(&records),
+        }))),
+        Err(e) => Ok(ApiResponse::error(&format!("调整库存失败: {}", e)))
     }
 }
 
+/// 产品的当前库存、调拨在途、订单预留与可用库存
 #[tauri::command]
-pub async fn order_list(db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.list_orders() {
-        Ok(orders) => Ok(ApiResponse::success(json!({ "items": orders, "total": orders.len() }))),
-        Err(e) => Ok(ApiResponse::error(&format!("获取订单列表失败: {}", e)))
+pub async fn stock_availability(db: State<'_, DbState>, auth: State<'_, AuthState>, product_id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, &product_id) {
+        return Ok(denied);
+    }
+    match db.0.get_stock_availability(&product_id) {
+        Ok(availability) => Ok(ApiResponse::success(json!({
+            "productId": availability.product_id,
+            "currentStock": availability.current_stock,
+            "inTransit": availability.in_transit,
+            "reserved": availability.reserved,
+            "available": availability.available,
+        }))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取可用库存失败: {}", e)))
     }
 }
 
 #[tauri::command]
-pub async fn order_get(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.get_order(&id) {
-        Ok(Some(order)) => {
-            let items = db.0.get_order_items(&id).unwrap_or_default();
-            Ok(ApiResponse::success(json!({ "order": order, "items": items })))
-        }
-        Ok(None) => Ok(ApiResponse::error("订单不存在")),
-        Err(e) => Ok(ApiResponse::error(&format!("获取订单失败: {}", e)))
+pub async fn stock_get_oversell_policy(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<OversellPolicy>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match db.0.get_oversell_policy() {
+        Ok(policy) => Ok(ApiResponse::success(policy)),
+        Err(e) => Ok(ApiResponse::error(&format!("获取超卖策略失败: {}", e)))
     }
 }
 
+/// 设置超卖策略：`reject` 可用库存不足时拒绝确认订单和出库，`allow` 允许库存为负
 #[tauri::command]
-pub async fn order_create(db: State<'_, DbState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
-    let now = Utc::now().to_rfc3339();
-    let id = uuid::Uuid::new_v4().to_string();
-    let order_type = data["type"].as_str().unwrap_or("sale");
-    let code = db.0.generate_order_code(order_type);
-    let order = Order {
-        id: id.clone(), code: code.clone(), order_type: order_type.to_string(),
-        customer_id: data["customerId"].as_str().unwrap_or("").to_string(),
-        customer_name: data["customerName"].as_str().unwrap_or("").to_string(),
-        status: "draft".to_string(),
-        total_quantity: data["totalQuantity"].as_i64().unwrap_or(0) as i32,
-        total_amount: data["totalAmount"].as_f64().unwrap_or(0.0),
-        discount_amount: data["discountAmount"].as_f64().unwrap_or(0.0),
-        payable_amount: data["payableAmount"].as_f64().unwrap_or(0.0),
-        paid_amount: data["paidAmount"].as_f64().unwrap_or(0.0),
-        delivery_address: data["deliveryAddress"].as_str().unwrap_or("").to_string(),
-        delivery_date: data["deliveryDate"].as_str().unwrap_or("").to_string(),
-        remark: data["remark"].as_str().unwrap_or("").to_string(),
-        operator_id: "user-1".to_string(), operator_name: "管理员".to_string(),
-        created_at: now.clone(), updated_at: now,
-    };
-    match db.0.create_order(&order) {
-        Ok(_) => Ok(ApiResponse::success(json!(order))),
-        Err(e) => Ok(ApiResponse::error(&format!("创建订单失败: {}", e)))
+pub async fn stock_set_oversell_policy(db: State<'_, DbState>, auth: State<'_, AuthState>, policy: OversellPolicy) -> CmdResult<ApiResponse<OversellPolicy>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Update, "") {
+        return Ok(denied);
+    }
+    match db.0.set_oversell_policy(policy) {
+        Ok(_) => Ok(ApiResponse::success(policy)),
+        Err(e) => Ok(ApiResponse::error(&format!("保存超卖策略失败: {}", e)))
     }
 }
 
 #[tauri::command]
-pub async fn order_update(db: State<'_, DbState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.get_order(&id) {
-        Ok(Some(mut order)) => {
-            order.customer_id = data["customerId"].as_str().unwrap_or(&order.customer_id).to_string();
-            order.customer_name = data["customerName"].as_str().unwrap_or(&order.customer_name).to_string();
-            order.total_quantity = data["totalQuantity"].as_i64().unwrap_or(order.total_quantity as i64) as i32;
-            order.total_amount = data["totalAmount"].as_f64().unwrap_or(order.total_amount);
-            order.discount_amount = data["discountAmount"].as_f64().unwrap_or(order.discount_amount);
-            order.payable_amount = data["payableAmount"].as_f64().unwrap_or(order.payable_amount);
-            order.remark = data["remark"].as_str().unwrap_or(&order.remark).to_string();
-            order.updated_at = Utc::now().to_rfc3339();
-            match db.0.update_order(&order) {
-                Ok(_) => Ok(ApiResponse::success(json!(order))),
-                Err(e) => Ok(ApiResponse::error(&format!("更新订单失败: {}", e)))
-            }
-        }
-        Ok(None) => Ok(ApiResponse::error("订单不存在")),
-        Err(e) => Ok(ApiResponse::error(&format!("获取订单失败: {}", e)))
+pub async fn stock_get_cost_method(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<CostMethod>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match db.0.get_cost_method() {
+        Ok(method) => Ok(ApiResponse::success(method)),
+        Err(e) => Ok(ApiResponse::error(&format!("获取成本计价方法失败: {}", e)))
     }
 }
 
+/// 设置成本计价方法，库存金额与销售成本按新方法重新计算
 #[tauri::command]
-pub async fn order_delete(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<()>> {
-    match db.0.delete_order(&id) {
-        Ok(_) => Ok(ApiResponse::success(())),
-        Err(e) => Ok(ApiResponse::error(&format!("删除订单失败: {}", e)))
+pub async fn stock_set_cost_method(db: State<'_, DbState>, auth: State<'_, AuthState>, method: CostMethod) -> CmdResult<ApiResponse<CostMethod>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Update, "") {
+        return Ok(denied);
+    }
+    match db.0.set_cost_method(method) {
+        Ok(_) => Ok(ApiResponse::success(method)),
+        Err(e) => Ok(ApiResponse::error(&format!("保存成本计价方法失败: {}", e)))
     }
 }
 
-#[tauri::command]
-pub async fn order_confirm(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.update_order_status(&id, "confirmed") {
-        Ok(_) => Ok(ApiResponse::success(json!({ "id": id, "status": "confirmed" }))),
-        Err(e) => Ok(ApiResponse::error(&format!("确认订单失败: {}", e)))
+fn warehouse_from(id: String, data: &serde_json::Value) -> Warehouse {
+    let now = Utc::now().to_rfc3339();
+    Warehouse {
+        code: data["code"].as_str().map(|s| s.trim().to_string()).unwrap_or_else(|| format!("WH{}", &id[..8]).to_uppercase()),
+        id,
+        name: data["name"].as_str().unwrap_or("").to_string(),
+        address: data["address"].as_str().unwrap_or("").to_string(),
+        manager: data["manager"].as_str().unwrap_or("").to_string(),
+        phone: data["phone"].as_str().unwrap_or("").to_string(),
+        status: data["status"].as_str().unwrap_or("active").to_string(),
+        created_at: now.clone(), updated_at: now,
     }
 }
 
 #[tauri::command]
-pub async fn order_cancel(db: State<'_, DbState>, id: String, reason: String) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.update_order_status(&id, "cancelled") {
-        Ok(_) => Ok(ApiResponse::success(json!({ "id": id, "status": "cancelled", "cancelReason": reason }))),
-        Err(e) => Ok(ApiResponse::error(&format!("取消订单失败: {}", e)))
+pub async fn warehouse_list(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<Vec<Warehouse>>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match db.0.list_warehouses() {
+        Ok(warehouses) => Ok(ApiResponse::success(warehouses)),
+        Err(e) => Ok(ApiResponse::error(&format!("获取仓库列表失败: {}", e)))
     }
 }
 
 #[tauri::command]
-pub async fn order_complete(db: State<'_, DbState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
-    match db.0.update_order_status(&id, "completed") {
-        Ok(_) => Ok(ApiResponse::success(json!({ "id": id, "status": "completed" }))),
-        Err(e) => Ok(ApiResponse::error(&format!("完成订单失败: {}", e)))
+pub async fn warehouse_create(db: State<'_, DbState>, auth: State<'_, AuthState>, data: serde_json::Value) -> CmdResult<ApiResponse<Warehouse>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Create, "") {
+        return Ok(denied);
+    }
+    let warehouse = warehouse_from(uuid::Uuid::new_v4().to_string(), &data);
+    if warehouse.name.trim().is_empty() {
+        return Ok(ApiResponse::error("创建仓库失败: 仓库名称不能为空"));
+    }
+    match db.0.create_warehouse(&warehouse) {
+        Ok(_) => Ok(ApiResponse::success(warehouse)),
+        Err(e) => Ok(ApiResponse::error(&format!("创建仓库失败: {}", e)))
     }
 }
 
 #[tauri::command]
-pub async fn sync_status() -> ApiResponse<serde_json::Value> {
-    ApiResponse::success(json!({ "isSyncing": false, "lastSyncAt": null, "pendingChanges": 0, "syncErrors": [] }))
+pub async fn warehouse_update(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<Warehouse>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Update, &id) {
+        return Ok(denied);
+    }
+    let warehouse = warehouse_from(id, &data);
+    match db.0.update_warehouse(&warehouse) {
+        Ok(_) => Ok(ApiResponse::success(warehouse)),
+        Err(e) => Ok(ApiResponse::error(&format!("更新仓库失败: {}", e)))
+    }
 }
 
 #[tauri::command]
-pub async fn sync_start(app: AppHandle) -> ApiResponse<()> {
-    app.emit("sync:progress", json!({"progress": 0, "message": "开始同步..."})).ok();
-    tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        app.emit("sync:complete", ()).ok();
-    });
-    ApiResponse::success(())
+pub async fn warehouse_delete(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Delete, &id) {
+        return Ok(denied);
+    }
+    match db.0.delete_warehouse(&id) {
+        Ok(_) => Ok(ApiResponse::success(())),
+        Err(e) => Ok(ApiResponse::error(&format!("删除仓库失败: {}", e)))
+    }
 }
 
+/// 按产品和（或）仓库查询各库位的结存
 #[tauri::command]
-pub async fn sync_stop() -> ApiResponse<()> { ApiResponse::success(()) }
-
-#[tauri::command]
-pub async fn sync_force_full() -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn stock_levels(db: State<'_, DbState>, auth: State<'_, AuthState>, product_id: Option<String>, warehouse_id: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, product_id.as_deref().unwrap_or("")) {
+        return Ok(denied);
+    }
+    match db.0.list_stock_levels(product_id.as_deref(), warehouse_id.as_deref()) {
+        Ok(levels) => Ok(ApiResponse::success(json!(levels))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取仓库库存失败: {}", e)))
+    }
+}
 
+/// 有结存的批次，先到期的在前
 #[tauri::command]
-pub async fn scanner_list_ports() -> ApiResponse<Vec<String>> {
-    match serialport::available_ports() {
-        Ok(ports) => ApiResponse::success(ports.into_iter().map(|p| p.port_name).collect()),
-        Err(e) => ApiResponse::error(&format!("获取串口列表失败: {}", e)),
+pub async fn stock_batches(db: State<'_, DbState>, auth: State<'_, AuthState>, product_id: Option<String>, warehouse_id: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, product_id.as_deref().unwrap_or("")) {
+        return Ok(denied);
+    }
+    match db.0.list_stock_batches(product_id.as_deref(), warehouse_id.as_deref()) {
+        Ok(batches) => Ok(ApiResponse::success(json!(batches))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取批次库存失败: {}", e)))
     }
 }
 
+/// 批次追溯：批次的出入库流水、随哪些订单发出或收入，以及当前结存
 #[tauri::command]
-pub async fn scanner_connect(config: serde_json::Value) -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn stock_trace_batch(db: State<'_, DbState>, auth: State<'_, AuthState>, batch_no: String, product_id: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, &batch_no) {
+        return Ok(denied);
+    }
+    let batch_no = batch_no.trim();
+    if batch_no.is_empty() {
+        return Ok(ApiResponse::error("批次追溯失败: 批号不能为空"));
+    }
+    match db.0.trace_batch(batch_no, product_id.as_deref()) {
+        Ok(trace) => Ok(ApiResponse::success(json!(trace))),
+        Err(e) => Ok(ApiResponse::error(&format!("批次追溯失败: {}", e)))
+    }
+}
 
+/// 创建调拨单并从调出仓库出库；`items` 每行为 `{ productId, quantity, fromLocation?, toLocation? }`
 #[tauri::command]
-pub async fn scanner_disconnect() -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn transfer_create(db: State<'_, DbState>, auth: State<'_, AuthState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Stock, Action::Create, "") {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    let items: Vec<TransferItemInput> = data["items"]
+        .as_array()
+        .map(|items| {
+            items.iter().map(|item| TransferItemInput {
+                product_id: item["productId"].as_str().unwrap_or("").to_string(),
+                quantity: item["quantity"].as_i64().unwrap_or(0) as i32,
+                from_location: item["fromLocation"].as_str().unwrap_or("").to_string(),
+                to_location: item["toLocation"].as_str().unwrap_or("").to_string(),
+            }).collect()
+        })
+        .unwrap_or_default();
+    match db.0.create_transfer(
+        data["fromWarehouseId"].as_str().unwrap_or(""),
+        data["toWarehouseId"].as_str().unwrap_or(""),
+        &items,
+        data["remark"].as_str().unwrap_or(""),
+        &operator,
+    ) {
+        Ok(transfer) => Ok(ApiResponse::success(json!(transfer))),
+        Err(e) => Ok(ApiResponse::error(&format!("创建调拨单失败: {}", e)))
+    }
+}
 
+/// 调入仓库确认收货
 #[tauri::command]
-pub async fn scanner_status() -> ApiResponse<serde_json::Value> {
-    ApiResponse::success(json!({ "connected": false, "port": null }))
+pub async fn transfer_receive(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Update, &id) {
+        return Ok(denied);
+    }
+    match db.0.receive_transfer(&id) {
+        Ok(transfer) => Ok(ApiResponse::success(json!(transfer))),
+        Err(e) => Ok(ApiResponse::error(&format!("调拨收货失败: {}", e)))
+    }
 }
 
+/// 取消在途调拨，货物退回调出仓库
 #[tauri::command]
-pub async fn scanner_handle_scan(db: State<'_, DbState>, barcode: String) -> CmdResult<ApiResponse<serde_json::Value>> {
-    let product = db.0.get_product_by_barcode(&barcode).ok().flatten();
-    Ok(ApiResponse::success(json!({ "barcode": barcode, "timestamp": Utc::now().to_rfc3339(), "product": product })))
+pub async fn transfer_cancel(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, reason: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Update, &id) {
+        return Ok(denied);
+    }
+    match db.0.cancel_transfer(&id, reason.as_deref().unwrap_or("")) {
+        Ok(transfer) => Ok(ApiResponse::success(json!(transfer))),
+        Err(e) => Ok(ApiResponse::error(&format!("取消调拨失败: {}", e)))
+    }
 }
 
 #[tauri::command]
-pub async fn storage_set_secure(key: String, value: String) -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn transfer_get(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, &id) {
+        return Ok(denied);
+    }
+    match db.0.get_transfer(&id) {
+        Ok(Some(transfer)) => Ok(ApiResponse::success(json!(transfer))),
+        Ok(None) => Ok(ApiResponse::error("调拨单不存在")),
+        Err(e) => Ok(ApiResponse::error(&format!("获取调拨单失败: {}", e)))
+    }
+}
 
+/// 调拨单列表，`status` 可选 `in_transit` / `received` / `cancelled`
 #[tauri::command]
-pub async fn storage_get_secure(key: String) -> ApiResponse<Option<String>> { ApiResponse::success(None) }
+pub async fn transfer_list(db: State<'_, DbState>, auth: State<'_, AuthState>, status: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, "") {
+        return Ok(denied);
+    }
+    match db.0.list_transfers(status.as_deref()) {
+        Ok(transfers) => Ok(ApiResponse::success(json!(transfers))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取调拨单列表失败: {}", e)))
+    }
+}
 
+/// 开始盘点：冻结仓库各库位的账面数量，`category` 为空时盘点整个仓库
 #[tauri::command]
-pub async fn storage_delete_secure(key: String) -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn stock_check_create(db: State<'_, DbState>, auth: State<'_, AuthState>, warehouse_id: String, category: Option<String>, remark: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Stock, Action::Create, &warehouse_id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    let category = category.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    match db.0.create_stock_check(&warehouse_id, category, remark.as_deref().unwrap_or(""), &operator) {
+        Ok(check) => Ok(ApiResponse::success(json!(check))),
+        Err(e) => Ok(ApiResponse::error(&format!("创建盘点单失败: {}", e)))
+    }
+}
 
+/// 批量录入实盘数量；`items` 每行为 `{ itemId, actualQuantity, remark? }`
 #[tauri::command]
-pub async fn storage_clear_secure() -> ApiResponse<()> { ApiResponse::success(()) }
+pub async fn stock_check_submit(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, items: Vec<serde_json::Value>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Create, &id) {
+        return Ok(denied);
+    }
+    let mut counts = Vec::with_capacity(items.len());
+    for item in &items {
+        let Some(actual_quantity) = item["actualQuantity"].as_i64() else {
+            return Ok(ApiResponse::error("实盘数量不能为空"));
+        };
+        counts.push(StockCountInput {
+            item_id: item["itemId"].as_str().unwrap_or("").to_string(),
+            actual_quantity: actual_quantity as i32,
+            remark: item["remark"].as_str().unwrap_or("").to_string(),
+        });
+    }
+    match db.0.record_stock_counts(&id, &counts) {
+        Ok(check) => Ok(ApiResponse::success(json!(check))),
+        Err(e) => Ok(ApiResponse::error(&format!("录入盘点结果失败: {}", e)))
+    }
+}
 
+/// 扫码计数：按条码找到产品，实盘数量累加 `quantity`（默认 1）
 #[tauri::command]
-pub async fn report_dashboard_stats(db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
-    let total_customers = db.0.count_customers().unwrap_or(0);
-    let total_products = db.0.count_products().unwrap_or(0);
-    let low_stock = db.0.count_low_stock_products().unwrap_or(0);
-    let today_orders = db.0.count_today_orders().unwrap_or(0);
-    let today_revenue = db.0.get_today_revenue().unwrap_or(0.0);
-    Ok(ApiResponse::success(json!({
-        "totalCustomers": total_customers, "activeCustomers": total_customers,
-        "totalProducts": total_products, "lowStockProducts": low_stock,
-        "todayOrders": today_orders, "todayRevenue": today_revenue,
-        "monthlyRevenue": today_revenue * 30.0, "monthlyGrowth": 12.5
-    })))
+pub async fn stock_check_scan(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, barcode: String, quantity: Option<i32>, location: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Create, &id) {
+        return Ok(denied);
+    }
+    let product = match resolve_product(&db.0, &parse_barcode(&barcode)) {
+        Ok(Some(product)) => product,
+        Ok(None) => return Ok(ApiResponse::error(&format!("未找到条码对应的产品: {}", barcode))),
+        Err(e) => return Ok(ApiResponse::error(&format!("查找产品失败: {}", e))),
+    };
+    match db.0.scan_stock_count(&id, &product.id, location.as_deref().unwrap_or(""), quantity.unwrap_or(1)) {
+        Ok(item) => Ok(ApiResponse::success(json!(item))),
+        Err(e) => Ok(ApiResponse::error(&format!("扫码盘点失败: {}", e)))
+    }
 }
 
 #[tauri::command]
-pub async fn report_sales(start_date: String, end_date: String) -> ApiResponse<serde_json::Value> {
-    ApiResponse::success(json!({ "period": format!("{} - {}", start_date, end_date), "totalOrders": 0, "totalAmount": 0 }))
+pub async fn stock_check_get(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, &id) {
+        return Ok(denied);
+    }
+    match db.0.get_stock_check(&id) {
+        Ok(Some(check)) => Ok(ApiResponse::success(json!(check))),
+        Ok(None) => Ok(ApiResponse::error("盘点单不存在")),
+        Err(e) => Ok(ApiResponse::error(&format!("获取盘点单失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn stock_check_list(db: State<'_, DbState>, auth: State<'_, AuthState>, status: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, "") {
+        return Ok(denied);
+    }
+    match db.0.list_stock_checks(status.as_deref()) {
+        Ok(checks) => Ok(ApiResponse::success(json!(checks))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取盘点单列表失败: {}", e)))
+    }
+}
+
+/// 盘点差异：盘盈盘亏明细、未录入明细与金额汇总
+#[tauri::command]
+pub async fn stock_check_report(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, &id) {
+        return Ok(denied);
+    }
+    match db.0.stock_check_report(&id) {
+        Ok(report) => Ok(ApiResponse::success(json!(report))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取盘点差异失败: {}", e)))
+    }
+}
+
+/// 审核过账：整单差异一次写入调整流水。会批量改写库存，需要库存模块的审核权限（默认为经理和管理员）
+#[tauri::command]
+pub async fn stock_check_post(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let approver = match authorize(&auth, Module::Stock, Action::Approve, &id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    match db.0.post_stock_check(&id, &approver) {
+        Ok((check, records)) => Ok(ApiResponse::success(json!({ "check": check, "records": records }))),
+        Err(e) => Ok(ApiResponse::error(&format!("盘点过账失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn stock_check_cancel(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, reason: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::Update, &id) {
+        return Ok(denied);
+    }
+    match db.0.cancel_stock_check(&id, reason.as_deref().unwrap_or("")) {
+        Ok(check) => Ok(ApiResponse::success(json!(check))),
+        Err(e) => Ok(ApiResponse::error(&format!("取消盘点失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_list(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::View, "") {
+        return Ok(denied);
+    }
+    match db.0.list_orders() {
+        Ok(orders) => Ok(ApiResponse::success(json!({ "items": orders, "total": orders.len() }))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取订单列表失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_get(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::View, &id) {
+        return Ok(denied);
+    }
+    match db.0.get_order(&id) {
+        Ok(Some(order)) => {
+            let items = db.0.get_order_items(&id).unwrap_or_default();
+            let next_states = OrderStatus::parse(&order.status).map_or(&[][..], |status| status.next_states());
+            let payment_status = PaymentStatus::of(&order);
+            Ok(ApiResponse::success(json!({ "order": order, "items": items, "nextStates": next_states, "paymentStatus": payment_status })))
+        }
+        Ok(None) => Ok(ApiResponse::error("订单不存在")),
+        Err(e) => Ok(ApiResponse::error(&format!("获取订单失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_create(db: State<'_, DbState>, auth: State<'_, AuthState>, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Create, "") {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    let now = Utc::now().to_rfc3339();
+    let id = uuid::Uuid::new_v4().to_string();
+    let order_type = data["type"].as_str().unwrap_or("sale");
+    let code = db.0.generate_order_code(order_type);
+    let order = Order {
+        id: id.clone(), code: code.clone(), order_type: order_type.to_string(),
+        customer_id: data["customerId"].as_str().unwrap_or("").to_string(),
+        customer_name: data["customerName"].as_str().unwrap_or("").to_string(),
+        status: "draft".to_string(),
+        total_quantity: data["totalQuantity"].as_i64().unwrap_or(0) as i32,
+        total_amount: data["totalAmount"].as_f64().unwrap_or(0.0),
+        discount_amount: data["discountAmount"].as_f64().unwrap_or(0.0),
+        payable_amount: data["payableAmount"].as_f64().unwrap_or(0.0),
+        // 已付金额只由收付款记录维护
+        paid_amount: 0.0,
+        delivery_address: data["deliveryAddress"].as_str().unwrap_or("").to_string(),
+        delivery_date: data["deliveryDate"].as_str().unwrap_or("").to_string(),
+        warehouse_id: data["warehouseId"].as_str().unwrap_or("").to_string(),
+        remark: data["remark"].as_str().unwrap_or("").to_string(),
+        operator_id: operator.id, operator_name: operator.name,
+        created_at: now.clone(), updated_at: now, version: 0,
+    };
+    let items = match order_items(&db.0, &id, &data["items"]) {
+        Ok(items) => items,
+        Err(e) => return Ok(ApiResponse::error(&format!("创建订单失败: {}", e))),
+    };
+    match db.0.create_order_with_items(&order, &items) {
+        Ok(order) => {
+            let mut data = json!(order);
+            data["items"] = json!(items);
+            Ok(ApiResponse::success(data))
+        }
+        Err(e) => Ok(ApiResponse::error(&format!("创建订单失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_update(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::Update, &id) {
+        return Ok(denied);
+    }
+    match db.0.get_order(&id) {
+        Ok(Some(mut order)) => {
+            order.customer_id = data["customerId"].as_str().unwrap_or(&order.customer_id).to_string();
+            order.customer_name = data["customerName"].as_str().unwrap_or(&order.customer_name).to_string();
+            order.total_quantity = data["totalQuantity"].as_i64().unwrap_or(order.total_quantity as i64) as i32;
+            order.total_amount = data["totalAmount"].as_f64().unwrap_or(order.total_amount);
+            order.discount_amount = data["discountAmount"].as_f64().unwrap_or(order.discount_amount);
+            order.payable_amount = data["payableAmount"].as_f64().unwrap_or(order.payable_amount);
+            order.warehouse_id = data["warehouseId"].as_str().unwrap_or(&order.warehouse_id).to_string();
+            order.remark = data["remark"].as_str().unwrap_or(&order.remark).to_string();
+            order.updated_at = Utc::now().to_rfc3339();
+            match db.0.update_order(&order) {
+                Ok(saved) => Ok(ApiResponse::success(json!(saved))),
+                Err(e) => Ok(ApiResponse::error(&format!("更新订单失败: {}", e)))
+            }
+        }
+        Ok(None) => Ok(ApiResponse::error("订单不存在")),
+        Err(e) => Ok(ApiResponse::error(&format!("获取订单失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_delete(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::Delete, &id) {
+        return Ok(denied);
+    }
+    match db.0.delete_order(&id) {
+        Ok(_) => Ok(ApiResponse::success(())),
+        Err(e) => Ok(ApiResponse::error(&format!("删除订单失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_confirm(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Update, &id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    match db.0.confirm_order(&id, &operator) {
+        Ok(order) => Ok(ApiResponse::success(json!(order))),
+        Err(e) => Ok(ApiResponse::error(&format!("确认订单失败: {}", e)))
+    }
+}
+
+/// 销售订单发货：`items` 为 `[{ itemId, quantity }]`，不传时发出全部未发货数量
+#[tauri::command]
+pub async fn order_ship(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, items: Option<Vec<serde_json::Value>>, remark: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Update, &id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    let shipments: Vec<ItemShipment> = items.unwrap_or_default().iter().map(|item| ItemShipment {
+        item_id: item["itemId"].as_str().unwrap_or("").to_string(),
+        quantity: item["quantity"].as_i64().unwrap_or(0) as i32,
+    }).collect();
+    match db.0.ship_order(&id, &shipments, remark.as_deref().unwrap_or("").trim(), &operator) {
+        Ok(order) => Ok(ApiResponse::success(json!(order))),
+        Err(e) => Ok(ApiResponse::error(&format!("发货失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_cancel(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, reason: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Update, &id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    match db.0.cancel_order(&id, reason.trim(), &operator) {
+        Ok(order) => {
+            let mut data = json!(order);
+            data["cancelReason"] = json!(reason);
+            Ok(ApiResponse::success(data))
+        }
+        Err(e) => Ok(ApiResponse::error(&format!("取消订单失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_complete(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Update, &id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    match db.0.complete_order(&id, &operator) {
+        Ok(order) => Ok(ApiResponse::success(json!(order))),
+        Err(e) => Ok(ApiResponse::error(&format!("完成订单失败: {}", e)))
+    }
+}
+
+/// 销售订单整单退货，已发货数量退回入库
+#[tauri::command]
+pub async fn order_return(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, reason: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Update, &id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    match db.0.return_order(&id, reason.trim(), &operator) {
+        Ok(order) => Ok(ApiResponse::success(json!(order))),
+        Err(e) => Ok(ApiResponse::error(&format!("退货失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn order_status_history(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::View, &id) {
+        return Ok(denied);
+    }
+    match db.0.list_order_status_history(&id) {
+        Ok(history) => Ok(ApiResponse::success(json!(history))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取订单状态历史失败: {}", e)))
+    }
+}
+
+/// 登记订单收款或退款：`data` 含 `type`（payment / refund）、`amount`、`method`、`reference`、`remark`、`paidAt`
+#[tauri::command]
+pub async fn payment_record(db: State<'_, DbState>, auth: State<'_, AuthState>, order_id: String, data: serde_json::Value) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let operator = match authorize(&auth, Module::Orders, Action::Update, &order_id) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    let payment_type = match data["type"].as_str().unwrap_or("payment") {
+        "payment" => PaymentType::Payment,
+        "refund" => PaymentType::Refund,
+        other => return Ok(ApiResponse::error(&format!("登记收款失败: 不支持的类型 {}", other))),
+    };
+    let paid_at = data["paidAt"].as_str().unwrap_or("").trim().to_string();
+    if !paid_at.is_empty() && chrono::NaiveDate::parse_from_str(&paid_at, "%Y-%m-%d").is_err() {
+        return Ok(ApiResponse::error(&format!("登记收款失败: 收款日期格式应为 YYYY-MM-DD: {}", paid_at)));
+    }
+    let input = PaymentInput {
+        order_id,
+        payment_type,
+        amount: data["amount"].as_f64().unwrap_or(0.0),
+        method: data["method"].as_str().unwrap_or("cash").to_string(),
+        reference: data["reference"].as_str().unwrap_or("").trim().to_string(),
+        remark: data["remark"].as_str().unwrap_or("").to_string(),
+        paid_at,
+    };
+    match db.0.record_payment(&input, &operator) {
+        Ok((payment, order)) => Ok(ApiResponse::success(json!({
+            "payment": payment,
+            "paymentStatus": PaymentStatus::of(&order),
+            "order": order,
+        }))),
+        Err(e) => Ok(ApiResponse::error(&format!("登记收款失败: {}", e)))
+    }
 }
 
 #[tauri::command]
-pub async fn report_inventory(db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
-    let total = db.0.count_products().unwrap_or(0);
+pub async fn payment_list(db: State<'_, DbState>, auth: State<'_, AuthState>, order_id: Option<String>, customer_id: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::View, order_id.as_deref().unwrap_or("")) {
+        return Ok(denied);
+    }
+    match db.0.list_payments(order_id.as_deref(), customer_id.as_deref()) {
+        Ok(payments) => Ok(ApiResponse::success(json!(payments))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取收款记录失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn sync_status(auth: State<'_, AuthState>, sync: State<'_, SyncState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match sync.manager().status() {
+        Ok(status) => {
+            let mut data = json!(status);
+            data["autoSync"] = json!(sync.auto_sync_running());
+            Ok(ApiResponse::success(data))
+        }
+        Err(e) => Ok(ApiResponse::error(&format!("获取同步状态失败: {}", e)))
+    }
+}
+
+/// 立即同步，并在后台按间隔自动同步；传入服务器地址或间隔时先保存并重建同步管理器
+#[tauri::command]
+pub async fn sync_start(app: AppHandle, db: State<'_, DbState>, auth: State<'_, AuthState>, sync: State<'_, SyncState>, api_base_url: Option<String>, interval_minutes: Option<u64>) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    let current = sync.manager();
+    let url_changed = api_base_url.as_deref().is_some_and(|url| url != current.api_base_url());
+    let interval_changed = interval_minutes.is_some_and(|m| m != current.interval_minutes());
+    if url_changed || interval_changed {
+        if current.is_syncing() {
+            return Ok(ApiResponse::error("正在同步，请稍后再修改同步设置"));
+        }
+        if let Some(url) = &api_base_url {
+            if let Err(e) = db.0.set_sync_state(API_BASE_URL_KEY, url) {
+                return Ok(ApiResponse::error(&format!("保存同步设置失败: {}", e)));
+            }
+        }
+        if let Some(minutes) = interval_minutes {
+            if let Err(e) = db.0.set_sync_state(SYNC_INTERVAL_KEY, &minutes.max(1).to_string()) {
+                return Ok(ApiResponse::error(&format!("保存同步设置失败: {}", e)));
+            }
+        }
+        sync.stop_auto_sync();
+        *sync.manager.write().unwrap() = Arc::new(build_sync_manager(&app, Arc::clone(&db.0)));
+    }
+
+    let manager = sync.manager();
+    if manager.is_syncing() {
+        return Ok(ApiResponse::error("同步已在进行中"));
+    }
+
+    if sync.auto_sync_running() {
+        // 自动同步已在运行：额外执行一次，结果通过事件通知
+        tokio::spawn(async move {
+            if let Err(e) = manager.sync().await {
+                log::warn!("手动同步失败: {}", e);
+            }
+        });
+    } else {
+        // 自动同步任务启动时会立即同步一次
+        let (tx, rx) = mpsc::channel(1);
+        *sync.auto_sync.lock().unwrap() = Some(tx);
+        tokio::spawn(async move { manager.start_auto_sync(rx).await });
+    }
+    Ok(ApiResponse::success(()))
+}
+
+/// 停止自动同步，并中断正在进行的同步
+#[tauri::command]
+pub async fn sync_stop(auth: State<'_, AuthState>, sync: State<'_, SyncState>) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    if sync.stop_auto_sync() {
+        Ok(ApiResponse::success(()))
+    } else {
+        Ok(ApiResponse::error("自动同步未在运行"))
+    }
+}
+
+/// 在后台执行全量同步，结果通过事件通知
+#[tauri::command]
+pub async fn sync_force_full(auth: State<'_, AuthState>, sync: State<'_, SyncState>) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Update, "") {
+        return Ok(denied);
+    }
+    let manager = sync.manager();
+    if manager.is_syncing() {
+        return Ok(ApiResponse::error("同步已在进行中"));
+    }
+    tokio::spawn(async move {
+        if let Err(e) = manager.force_full_sync().await {
+            log::warn!("全量同步失败: {}", e);
+        }
+    });
+    Ok(ApiResponse::success(()))
+}
+
+#[tauri::command]
+pub async fn sync_list_conflicts(db: State<'_, DbState>, auth: State<'_, AuthState>, include_resolved: Option<bool>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let user = match require_login(&auth) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    // 冲突中带有完整的本地与服务器数据，只返回有对应模块查看权限的冲突
+    match db.0.list_conflicts(include_resolved.unwrap_or(false)) {
+        Ok(conflicts) => {
+            let visible: Vec<_> = conflicts
+                .into_iter()
+                .filter(|c| auth.0.has_permission(&user.role, conflict_module(&c.entity_type), Action::View).unwrap_or(false))
+                .collect();
+            Ok(ApiResponse::success(json!(visible)))
+        }
+        Err(e) => Ok(ApiResponse::error(&format!("获取同步冲突失败: {}", e)))
+    }
+}
+
+/// `resolution` 为 local / remote / merge，merge 时 `values` 为各冲突字段最终采用的值
+#[tauri::command]
+pub async fn sync_resolve_conflict(db: State<'_, DbState>, auth: State<'_, AuthState>, id: String, resolution: String, values: Option<serde_json::Value>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let module = match db.0.get_conflict(&id) {
+        Ok(Some(conflict)) => conflict_module(&conflict.entity_type),
+        Ok(None) => return Ok(ApiResponse::error("冲突不存在或已处理")),
+        Err(e) => return Ok(ApiResponse::error(&format!("处理同步冲突失败: {}", e))),
+    };
+    if let Err(denied) = authorize(&auth, module, Action::Update, &id) {
+        return Ok(denied);
+    }
+    let Some(resolution) = ConflictResolution::parse(&resolution, values) else {
+        return Ok(ApiResponse::error("无效的冲突处理方式"));
+    };
+    match db.0.resolve_conflict(&id, &resolution) {
+        Ok(saved) => Ok(ApiResponse::success(json!({ "id": id, "data": saved }))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(ApiResponse::error("冲突不存在或已处理")),
+        Err(e) => Ok(ApiResponse::error(&format!("处理同步冲突失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn sync_get_conflict_strategies(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::View, "") {
+        return Ok(denied);
+    }
+    match db.0.conflict_strategies() {
+        Ok(strategies) => Ok(ApiResponse::success(json!(strategies.into_iter().collect::<std::collections::HashMap<_, _>>()))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取冲突策略失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn sync_set_conflict_strategy(db: State<'_, DbState>, auth: State<'_, AuthState>, entity_type: String, strategy: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Update, &entity_type) {
+        return Ok(denied);
+    }
+    if !VERSIONED_ENTITIES.contains(&entity_type.as_str()) {
+        return Ok(ApiResponse::error(&format!("不支持的实体类型: {}", entity_type)));
+    }
+    let Some(strategy) = ConflictStrategy::parse(&strategy) else {
+        return Ok(ApiResponse::error(&format!("无效的冲突策略: {}", strategy)));
+    };
+    match db.0.set_conflict_strategy(&entity_type, strategy) {
+        Ok(_) => Ok(ApiResponse::success(json!({ "entityType": entity_type, "strategy": strategy }))),
+        Err(e) => Ok(ApiResponse::error(&format!("设置冲突策略失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn scanner_list_ports(auth: State<'_, AuthState>) -> CmdResult<ApiResponse<Vec<String>>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match serialport::available_ports() {
+        Ok(ports) => Ok(ApiResponse::success(ports.into_iter().map(|p| p.port_name).collect())),
+        Err(e) => Ok(ApiResponse::error(&format!("获取串口列表失败: {}", e))),
+    }
+}
+
+#[tauri::command]
+pub async fn scanner_connect(auth: State<'_, AuthState>, scanner: State<'_, ScannerState>, config: serde_json::Value) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    let config: ScannerConfig = match serde_json::from_value(config) {
+        Ok(config) => config,
+        Err(e) => return Ok(ApiResponse::error(&format!("扫码枪配置无效: {}", e))),
+    };
+    match scanner.0.connect(config) {
+        Ok(_) => Ok(ApiResponse::success(())),
+        Err(e) => Ok(ApiResponse::error(&format!("连接扫码枪失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn scanner_disconnect(auth: State<'_, AuthState>, scanner: State<'_, ScannerState>) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match scanner.0.disconnect() {
+        Ok(_) => Ok(ApiResponse::success(())),
+        Err(e) => Ok(ApiResponse::error(&format!("断开扫码枪失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn scanner_status(auth: State<'_, AuthState>, scanner: State<'_, ScannerState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    Ok(ApiResponse::success(json!(scanner.0.get_status())))
+}
+
+/// 键盘模式下应用窗口转发的按键（前端在回车时把缓存的按键一起发送，保证顺序）
+#[tauri::command]
+pub async fn scanner_wedge_keys(auth: State<'_, AuthState>, scanner: State<'_, ScannerState>, keys: Vec<WedgeKeyEvent>) -> CmdResult<ApiResponse<usize>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    match scanner.0.feed_keys(&keys) {
+        Ok(count) => Ok(ApiResponse::success(count)),
+        Err(e) => Ok(ApiResponse::error(&format!("处理扫码按键失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn scanner_handle_scan(db: State<'_, DbState>, auth: State<'_, AuthState>, barcode: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::View, &barcode) {
+        return Ok(denied);
+    }
+    Ok(ApiResponse::success(scan_payload(&db.0, simulate_scan(barcode.trim()))))
+}
+
+#[tauri::command]
+pub async fn storage_set_secure(auth: State<'_, AuthState>, storage: State<'_, StorageState>, key: String, value: String) -> CmdResult<ApiResponse<()>> {
+    Ok(with_storage(&auth, &storage, |vaults, user_id| {
+        check_entry_key(&key)?;
+        vaults.with(user_id, |s| s.set(&key, &value))
+    }))
+}
+
+#[tauri::command]
+pub async fn storage_get_secure(auth: State<'_, AuthState>, storage: State<'_, StorageState>, key: String) -> CmdResult<ApiResponse<Option<String>>> {
+    Ok(with_storage(&auth, &storage, |vaults, user_id| {
+        check_entry_key(&key)?;
+        vaults.with(user_id, |s| s.get(&key))
+    }))
+}
+
+#[tauri::command]
+pub async fn storage_delete_secure(auth: State<'_, AuthState>, storage: State<'_, StorageState>, key: String) -> CmdResult<ApiResponse<()>> {
+    Ok(with_storage(&auth, &storage, |vaults, user_id| {
+        check_entry_key(&key)?;
+        vaults.with(user_id, |s| s.delete(&key))
+    }))
+}
+
+#[tauri::command]
+pub async fn storage_clear_secure(auth: State<'_, AuthState>, storage: State<'_, StorageState>) -> CmdResult<ApiResponse<()>> {
+    Ok(with_storage(&auth, &storage, |vaults, user_id| vaults.clear_user_entries(user_id)))
+}
+
+#[tauri::command]
+pub async fn db_encryption_status(auth: State<'_, AuthState>, db: State<'_, DbState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    Ok(ApiResponse::success(json!({ "supported": db.0.encryption_supported(), "encrypted": db.0.is_encrypted() })))
+}
+
+/// 管理员将未加密的数据库就地加密，密钥保存在当前用户的加密存储中；
+/// 其他用户的存储需要本人密码才能写入，返回信息中列出还需登录一次的账号
+#[tauri::command]
+pub async fn db_encrypt(auth: State<'_, AuthState>, db: State<'_, DbState>, storage: State<'_, StorageState>) -> CmdResult<ApiResponse<()>> {
+    let admin = match auth.0.require_admin() {
+        Ok(user) => user,
+        Err(e) => return Ok(ApiResponse::auth_error(&e)),
+    };
+    if db.0.is_encrypted() {
+        return Ok(ApiResponse::error("数据库已加密"));
+    }
+    let key = generate_database_key();
+    // 先保存密钥再加密，避免加密成功后密钥丢失
+    if let Err(e) = storage.0.set_database_key(&admin.id, &key, false) {
+        return Ok(ApiResponse::error(&format!("保存数据库密钥失败: {}", e)));
+    }
+    let database = Arc::clone(&db.0);
+    let result = tokio::task::spawn_blocking(move || database.encrypt_in_place(&key))
+        .await
+        .map_err(|e| CommandError(e.to_string()))?;
+    match result {
+        Ok(_) => Ok(ApiResponse { message: key_pending_message(&db.0, &admin.id), ..ApiResponse::success(()) }),
+        Err(e) => Ok(ApiResponse::error(&format!("加密数据库失败: {}", e)))
+    }
+}
+
+/// 管理员更换数据库密钥；其他用户下次登录（数据库已解锁时）获得新密钥
+#[tauri::command]
+pub async fn db_rotate_key(auth: State<'_, AuthState>, db: State<'_, DbState>, storage: State<'_, StorageState>) -> CmdResult<ApiResponse<()>> {
+    let admin = match auth.0.require_admin() {
+        Ok(user) => user,
+        Err(e) => return Ok(ApiResponse::auth_error(&e)),
+    };
+    let Some(old_key) = db.0.encryption_key() else {
+        return Ok(ApiResponse::error("数据库未加密"));
+    };
+    let new_key = generate_database_key();
+    // 保留旧密钥直到换密钥完成，中途退出时下次登录仍可用旧密钥解锁
+    if let Err(e) = storage.0.set_database_key(&admin.id, &new_key, true) {
+        return Ok(ApiResponse::error(&format!("保存数据库密钥失败: {}", e)));
+    }
+    let vaults = Arc::clone(&storage.0);
+    let database = Arc::clone(&db.0);
+    let admin_id = admin.id.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let result = database.rekey(&new_key);
+        let saved = match &result {
+            Ok(_) => vaults.drop_previous_database_key(&admin_id),
+            Err(_) => vaults
+                .set_database_key(&admin_id, &old_key, false)
+                .and_then(|_| vaults.drop_previous_database_key(&admin_id)),
+        };
+        if let Err(e) = saved {
+            log::error!("更新数据库密钥记录失败: {}", e);
+        }
+        result
+    })
+        .await
+        .map_err(|e| CommandError(e.to_string()))?;
+    match result {
+        Ok(_) => Ok(ApiResponse { message: key_pending_message(&db.0, &admin.id), ..ApiResponse::success(()) }),
+        Err(e) => Ok(ApiResponse::error(&format!("更换数据库密钥失败: {}", e)))
+    }
+}
+
+#[tauri::command]
+pub async fn report_dashboard_stats(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Reports, Action::View, "") {
+        return Ok(denied);
+    }
+    match db.0.dashboard_stats(Utc::now()) {
+        Ok(stats) => Ok(ApiResponse::success(json!({
+            "totalCustomers": stats.total_customers, "activeCustomers": stats.active_customers,
+            "totalProducts": stats.total_products, "lowStockProducts": stats.low_stock_products,
+            "todayOrders": stats.today_orders, "todayRevenue": stats.today_revenue,
+            "monthlyOrders": stats.monthly_orders, "monthlyRevenue": stats.monthly_revenue,
+            "previousMonthlyRevenue": stats.previous_monthly_revenue, "monthlyGrowth": stats.monthly_growth,
+        }))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取统计数据失败: {}", e)))
+    }
+}
+
+/// 销售报表：日期含首尾，`granularity` 为 day / week / month（默认 day），`top` 为排行条数（默认 10）
+#[tauri::command]
+pub async fn report_sales(db: State<'_, DbState>, auth: State<'_, AuthState>, start_date: String, end_date: String, granularity: Option<String>, top: Option<usize>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Reports, Action::View, "") {
+        return Ok(denied);
+    }
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| format!("日期格式应为 YYYY-MM-DD: {}", d));
+    let (start, end) = match (parse(&start_date), parse(&end_date)) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(e), _) | (_, Err(e)) => return Ok(ApiResponse::error(&e)),
+    };
+    if start > end {
+        return Ok(ApiResponse::error("开始日期不能晚于结束日期"));
+    }
+    let granularity = match granularity.as_deref().map(ReportGranularity::parse) {
+        None => ReportGranularity::Day,
+        Some(Some(granularity)) => granularity,
+        Some(None) => return Ok(ApiResponse::error("汇总粒度应为 day、week 或 month")),
+    };
+    let query = SalesReportQuery { start, end, granularity, top: top.unwrap_or(10) };
+    match db.0.sales_report(&query) {
+        Ok(report) => Ok(ApiResponse::success(json!(report))),
+        Err(e) => Ok(ApiResponse::error(&format!("生成销售报表失败: {}", e)))
+    }
+}
+
+/// 库存报表：按当前成本计价方法计算的库存金额，含分类汇总与产品明细
+#[tauri::command]
+pub async fn report_inventory(db: State<'_, DbState>, auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Stock, Action::View, "") {
+        return Ok(denied);
+    }
     let low = db.0.count_low_stock_products().unwrap_or(0);
-    Ok(ApiResponse::success(json!({ "totalProducts": total, "totalValue": 0, "lowStockProducts": low })))
+    match db.0.inventory_valuation() {
+        Ok(valuation) => Ok(ApiResponse::success(json!({
+            "method": valuation.method,
+            "totalProducts": valuation.items.len(),
+            "totalQuantity": valuation.total_quantity,
+            "totalValue": valuation.total_value,
+            "lowStockProducts": low,
+            "outOfStockProducts": valuation.items.iter().filter(|item| item.quantity <= 0).count(),
+            "categoryBreakdown": valuation.categories.iter().map(|c| json!({
+                "category": c.category, "productCount": c.product_count, "quantity": c.quantity, "totalValue": c.total_value,
+            })).collect::<Vec<_>>(),
+            "items": valuation.items,
+        }))),
+        Err(e) => Ok(ApiResponse::error(&format!("计算库存金额失败: {}", e)))
+    }
 }
 
+/// 已完成销售订单的销售成本与毛利，日期为下单日期（含首尾），默认本月
 #[tauri::command]
-pub async fn system_info() -> ApiResponse<serde_json::Value> {
-    ApiResponse::success(json!({ "version": "1.0.0", "os": std::env::consts::OS, "arch": std::env::consts::ARCH }))
+pub async fn report_order_costs(db: State<'_, DbState>, auth: State<'_, AuthState>, start_date: Option<String>, end_date: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Reports, Action::View, "") {
+        return Ok(denied);
+    }
+    let today = Utc::now().date_naive();
+    let parse = |date: Option<&str>, default: chrono::NaiveDate| match date {
+        Some(d) => chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| format!("日期格式应为 YYYY-MM-DD: {}", d)),
+        None => Ok(default),
+    };
+    let (start, end) = match (parse(start_date.as_deref(), today.with_day(1).unwrap_or(today)), parse(end_date.as_deref(), today)) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(e), _) | (_, Err(e)) => return Ok(ApiResponse::error(&e)),
+    };
+    let end_exclusive = end + chrono::Days::new(1);
+    match db.0.order_costs(&start.to_string(), &end_exclusive.to_string()) {
+        Ok(orders) => {
+            let revenue: f64 = orders.iter().map(|o| o.revenue).sum();
+            let cost: f64 = orders.iter().map(|o| o.cost).sum();
+            let gross_margin = if revenue.abs() > f64::EPSILON { (revenue - cost) / revenue * 100.0 } else { 0.0 };
+            Ok(ApiResponse::success(json!({
+                "startDate": start.to_string(), "endDate": end.to_string(),
+                "revenue": revenue, "cost": cost, "grossProfit": revenue - cost, "grossMargin": gross_margin,
+                "orders": orders,
+            })))
+        }
+        Err(e) => Ok(ApiResponse::error(&format!("计算销售成本失败: {}", e)))
+    }
+}
+
+/// 应收账龄（30 天内 / 31-60 / 61-90 / 90 天以上），`as_of` 默认为当天
+#[tauri::command]
+pub async fn report_ar_aging(db: State<'_, DbState>, auth: State<'_, AuthState>, as_of: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Reports, Action::View, "") {
+        return Ok(denied);
+    }
+    let as_of = match as_of.as_deref().map(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d")) {
+        Some(Ok(date)) => date,
+        Some(Err(_)) => return Ok(ApiResponse::error("日期格式应为 YYYY-MM-DD")),
+        None => Utc::now().date_naive(),
+    };
+    match db.0.ar_aging_report(as_of) {
+        Ok(report) => Ok(ApiResponse::success(json!(report))),
+        Err(e) => Ok(ApiResponse::error(&format!("生成账龄报表失败: {}", e)))
+    }
+}
+
+/// 临期批次预警：`days` 天内到期（含已过期）且仍有结存的批次，默认 30 天
+#[tauri::command]
+pub async fn report_expiring_batches(db: State<'_, DbState>, auth: State<'_, AuthState>, days: Option<i64>, warehouse_id: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Reports, Action::View, "") {
+        return Ok(denied);
+    }
+    let days = days.unwrap_or(30);
+    if days < 0 {
+        return Ok(ApiResponse::error("天数不能为负数"));
+    }
+    match db.0.list_expiring_batches(days, warehouse_id.as_deref()) {
+        Ok(batches) => Ok(ApiResponse::success(json!({ "days": days, "items": batches }))),
+        Err(e) => Ok(ApiResponse::error(&format!("获取临期批次失败: {}", e)))
+    }
+}
+
+/// 导出格式：未指定时按文件扩展名
+fn export_format(path: &str, format: Option<&str>) -> Result<SheetFormat, String> {
+    let format = format.unwrap_or_else(|| std::path::Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or(""));
+    SheetFormat::parse(format).ok_or_else(|| format!("不支持的导出格式: {}，请使用 csv 或 xlsx", format))
+}
+
+fn export_filter(filter: Option<&serde_json::Value>) -> Result<ExportFilter, String> {
+    let Some(filter) = filter else {
+        return Ok(ExportFilter::default());
+    };
+    let text = |key: &str| filter[key].as_str().unwrap_or("").to_string();
+    let date = |key: &str| match filter[key].as_str().filter(|d| !d.is_empty()) {
+        Some(d) => chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map(Some).map_err(|_| format!("日期格式应为 YYYY-MM-DD: {}", d)),
+        None => Ok(None),
+    };
+    Ok(ExportFilter {
+        keyword: text("keyword"),
+        category: text("category"),
+        status: text("status"),
+        order_type: text("orderType"),
+        customer_id: text("customerId"),
+        start_date: date("startDate")?,
+        end_date: date("endDate")?,
+    })
+}
+
+fn write_export(sheet: rusqlite::Result<Sheet>, path: &str, format: SheetFormat) -> ApiResponse<serde_json::Value> {
+    let sheet = match sheet {
+        Ok(sheet) => sheet,
+        Err(e) => return ApiResponse::error(&format!("导出失败: {}", e)),
+    };
+    match write_sheet(std::path::Path::new(path), format, &sheet) {
+        Ok(()) => ApiResponse::success(json!({ "path": path, "rows": sheet.rows.len() })),
+        Err(e) => ApiResponse::error(&format!("导出失败: {}", e)),
+    }
+}
+
+fn run_import(db: &Database, entity: ImportEntity, path: &str, mapping: Option<HashMap<String, String>>, dry_run: Option<bool>) -> ApiResponse<serde_json::Value> {
+    let sheet = match read_sheet(std::path::Path::new(path)) {
+        Ok(sheet) => sheet,
+        Err(e) => return ApiResponse::error(&format!("读取导入文件失败: {}", e)),
+    };
+    let options = ImportOptions { mapping: mapping.unwrap_or_default(), dry_run: dry_run.unwrap_or(false) };
+    match db.import_sheet(entity, &sheet, &options) {
+        Ok(report) => ApiResponse::success(json!(report)),
+        Err(e) => ApiResponse::error(&format!("导入失败: {}", e)),
+    }
+}
+
+/// 读取导入文件的表头，返回可导入的字段和按表头自动识别的列对应关系
+#[tauri::command]
+pub async fn import_columns(auth: State<'_, AuthState>, entity: String, path: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let Some(entity) = ImportEntity::parse(&entity) else {
+        return Ok(ApiResponse::error("导入对象应为 customer 或 product"));
+    };
+    let module = if entity == ImportEntity::Customer { Module::Customers } else { Module::Products };
+    if let Err(denied) = authorize(&auth, module, Action::View, "") {
+        return Ok(denied);
+    }
+    match read_sheet(std::path::Path::new(&path)) {
+        Ok(sheet) => Ok(ApiResponse::success(json!({
+            "headers": sheet.headers,
+            "rows": sheet.rows.len(),
+            "fields": entity.fields(),
+            "mapping": suggest_columns(entity, &sheet.headers),
+        }))),
+        Err(e) => Ok(ApiResponse::error(&format!("读取导入文件失败: {}", e)))
+    }
+}
+
+/// 从 CSV/XLSX 导入客户，按电话或名称匹配已有客户；`dry_run` 时只校验并返回预览
+#[tauri::command]
+pub async fn import_customers(db: State<'_, DbState>, auth: State<'_, AuthState>, path: String, mapping: Option<HashMap<String, String>>, dry_run: Option<bool>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    for action in [Action::Create, Action::Update] {
+        if let Err(denied) = authorize(&auth, Module::Customers, action, "") {
+            return Ok(denied);
+        }
+    }
+    Ok(run_import(&db.0, ImportEntity::Customer, &path, mapping, dry_run))
+}
+
+/// 从 CSV/XLSX 导入产品，按编码或条码匹配已有产品；`dry_run` 时只校验并返回预览
+#[tauri::command]
+pub async fn import_products(db: State<'_, DbState>, auth: State<'_, AuthState>, path: String, mapping: Option<HashMap<String, String>>, dry_run: Option<bool>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    for action in [Action::Create, Action::Update] {
+        if let Err(denied) = authorize(&auth, Module::Products, action, "") {
+            return Ok(denied);
+        }
+    }
+    Ok(run_import(&db.0, ImportEntity::Product, &path, mapping, dry_run))
+}
+
+#[tauri::command]
+pub async fn customer_export(db: State<'_, DbState>, auth: State<'_, AuthState>, path: String, format: Option<String>, filter: Option<serde_json::Value>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Customers, Action::View, "") {
+        return Ok(denied);
+    }
+    let (format, filter) = match (export_format(&path, format.as_deref()), export_filter(filter.as_ref())) {
+        (Ok(format), Ok(filter)) => (format, filter),
+        (Err(e), _) | (_, Err(e)) => return Ok(ApiResponse::error(&e)),
+    };
+    Ok(write_export(db.0.export_customers(&filter), &path, format))
+}
+
+#[tauri::command]
+pub async fn product_export(db: State<'_, DbState>, auth: State<'_, AuthState>, path: String, format: Option<String>, filter: Option<serde_json::Value>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Products, Action::View, "") {
+        return Ok(denied);
+    }
+    let (format, filter) = match (export_format(&path, format.as_deref()), export_filter(filter.as_ref())) {
+        (Ok(format), Ok(filter)) => (format, filter),
+        (Err(e), _) | (_, Err(e)) => return Ok(ApiResponse::error(&e)),
+    };
+    Ok(write_export(db.0.export_products(&filter), &path, format))
+}
+
+#[tauri::command]
+pub async fn order_export(db: State<'_, DbState>, auth: State<'_, AuthState>, path: String, format: Option<String>, filter: Option<serde_json::Value>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = authorize(&auth, Module::Orders, Action::View, "") {
+        return Ok(denied);
+    }
+    let (format, filter) = match (export_format(&path, format.as_deref()), export_filter(filter.as_ref())) {
+        (Ok(format), Ok(filter)) => (format, filter),
+        (Err(e), _) | (_, Err(e)) => return Ok(ApiResponse::error(&e)),
+    };
+    Ok(write_export(db.0.export_orders(&filter), &path, format))
+}
+
+/// 导出报表，日期含首尾，默认本月；库存报表与日期无关
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn report_export(db: State<'_, DbState>, auth: State<'_, AuthState>, report_type: String, path: String, format: Option<String>, start_date: Option<String>, end_date: Option<String>, granularity: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let Some(kind) = ReportExport::parse(&report_type) else {
+        return Ok(ApiResponse::error(&format!("不支持导出的报表: {}", report_type)));
+    };
+    // 库存报表与 report_inventory 一样按库存权限
+    let module = if kind == ReportExport::Inventory { Module::Stock } else { Module::Reports };
+    if let Err(denied) = authorize(&auth, module, Action::View, "") {
+        return Ok(denied);
+    }
+    let format = match export_format(&path, format.as_deref()) {
+        Ok(format) => format,
+        Err(e) => return Ok(ApiResponse::error(&e)),
+    };
+    let today = Utc::now().date_naive();
+    let parse = |date: Option<&str>, default: chrono::NaiveDate| match date {
+        Some(d) => chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| format!("日期格式应为 YYYY-MM-DD: {}", d)),
+        None => Ok(default),
+    };
+    let (start, end) = match (parse(start_date.as_deref(), today.with_day(1).unwrap_or(today)), parse(end_date.as_deref(), today)) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(e), _) | (_, Err(e)) => return Ok(ApiResponse::error(&e)),
+    };
+    if start > end {
+        return Ok(ApiResponse::error("开始日期不能晚于结束日期"));
+    }
+    let granularity = match granularity.as_deref().map(ReportGranularity::parse) {
+        None => ReportGranularity::Day,
+        Some(Some(granularity)) => granularity,
+        Some(None) => return Ok(ApiResponse::error("汇总粒度应为 day、week 或 month")),
+    };
+    let query = SalesReportQuery { start, end, granularity, top: 10 };
+    Ok(write_export(db.0.export_report(kind, &query), &path, format))
 }
 
 #[tauri::command]
-pub async fn system_check_update() -> ApiResponse<serde_json::Value> {
-    ApiResponse::success(json!({ "hasUpdate": false, "version": null, "releaseNotes": null }))
+pub async fn system_info(auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    Ok(ApiResponse::success(json!({ "version": "1.0.0", "os": std::env::consts::OS, "arch": std::env::consts::ARCH })))
 }
 
 #[tauri::command]
-pub async fn system_clear_cache() -> ApiResponse<()> { ApiResponse::success(()) }
\ No newline at end of file
+pub async fn system_check_update(auth: State<'_, AuthState>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    if let Err(denied) = require_login(&auth) {
+        return Ok(denied);
+    }
+    Ok(ApiResponse::success(json!({ "hasUpdate": false, "version": null, "releaseNotes": null })))
+}
+
+#[tauri::command]
+pub async fn system_clear_cache(auth: State<'_, AuthState>) -> CmdResult<ApiResponse<()>> {
+    if let Err(denied) = authorize(&auth, Module::Settings, Action::Update, "") {
+        return Ok(denied);
+    }
+    Ok(ApiResponse::success(()))
+}
+
+/// 角色权限表，不传角色时返回当前用户的权限
+#[tauri::command]
+pub async fn rbac_get_permissions(auth: State<'_, AuthState>, role: Option<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    let user = match require_login(&auth) {
+        Ok(user) => user,
+        Err(denied) => return Ok(denied),
+    };
+    let role = match role {
+        Some(role) if role != user.role => {
+            if let Err(denied) = authorize(&auth, Module::Settings, Action::View, &role) {
+                return Ok(denied);
+            }
+            role
+        }
+        _ => user.role,
+    };
+    match auth.0.permissions_for(&role) {
+        Ok(permissions) => Ok(ApiResponse::success(json!({ "role": role, "permissions": permissions }))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+/// 管理员设置角色在模块上的权限（`actions` 为 view / create / update / delete 的子集）
+#[tauri::command]
+pub async fn rbac_set_permissions(auth: State<'_, AuthState>, role: String, module: String, actions: Vec<String>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    match auth.0.set_role_permissions(&role, &module, &actions) {
+        Ok(permissions) => Ok(ApiResponse::success(json!({ "role": role, "permissions": permissions }))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+/// 管理员恢复角色的默认权限
+#[tauri::command]
+pub async fn rbac_reset_permissions(auth: State<'_, AuthState>, role: String) -> CmdResult<ApiResponse<serde_json::Value>> {
+    match auth.0.reset_role_permissions(&role) {
+        Ok(permissions) => Ok(ApiResponse::success(json!({ "role": role, "permissions": permissions }))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
+
+/// 管理员查看审计日志
+#[tauri::command]
+pub async fn audit_list(auth: State<'_, AuthState>, only_denied: Option<bool>, limit: Option<usize>) -> CmdResult<ApiResponse<serde_json::Value>> {
+    match auth.0.audit_log(only_denied.unwrap_or(false), limit.unwrap_or(200).min(1000)) {
+        Ok(entries) => Ok(ApiResponse::success(json!({ "items": entries, "total": entries.len() }))),
+        Err(e) => Ok(ApiResponse::auth_error(&e))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,114 @@
+//! 访问控制 - 角色权限配置与审计日志（不参与数据同步）
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+
+use super::Database;
+
+/// 审计日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// 未登录时为空
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub module: String,
+    pub action: String,
+    /// 操作对象（如客户 ID），没有时为空字符串
+    pub target: String,
+    /// allowed / denied
+    pub result: String,
+    pub detail: String,
+    pub created_at: String,
+}
+
+pub(super) fn create_access_tables(conn: &Connection) -> SqliteResult<()> {
+    // 角色权限覆盖配置，没有记录的角色与模块使用内置默认权限
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS role_permissions (
+            role TEXT NOT NULL,
+            module TEXT NOT NULL,
+            actions TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (role, module)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id TEXT,
+            username TEXT,
+            module TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT DEFAULT '',
+            result TEXT NOT NULL,
+            detail TEXT DEFAULT '',
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_audit_log_created ON audit_log(created_at)", [])?;
+    Ok(())
+}
+
+impl Database {
+    /// 读取角色在模块上的权限覆盖配置（逗号分隔的操作列表）
+    pub fn get_role_permission(&self, role: &str, module: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT actions FROM role_permissions WHERE role = ?1 AND module = ?2",
+            [role, module],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    pub fn set_role_permission(&self, role: &str, module: &str, actions: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO role_permissions (role, module, actions, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![role, module, actions, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// 删除覆盖配置，恢复默认权限
+    pub fn reset_role_permissions(&self, role: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM role_permissions WHERE role = ?1", [role])?;
+        Ok(())
+    }
+
+    pub fn insert_audit(&self, entry: &AuditEntry) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO audit_log (user_id, username, module, action, target, result, detail, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.user_id, entry.username, entry.module, entry.action, entry.target,
+                entry.result, entry.detail, Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 最近的审计日志，`result` 为空时返回全部
+    pub fn list_audit(&self, result: Option<&str>, limit: usize) -> SqliteResult<Vec<AuditEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, user_id, username, module, action, target, result, detail, created_at
+             FROM audit_log WHERE ?1 IS NULL OR result = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![result, limit as i64], |row| {
+            Ok(AuditEntry {
+                id: row.get(0)?, user_id: row.get(1)?, username: row.get(2)?, module: row.get(3)?,
+                action: row.get(4)?, target: row.get(5)?, result: row.get(6)?, detail: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
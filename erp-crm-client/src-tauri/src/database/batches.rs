@@ -0,0 +1,371 @@
+//! 批次与有效期 - 批次结存、先到期先出（FEFO）分配、临期预警与批次追溯
+//!
+//! `stock_batches` 按仓库、库位记录每个批次的结存，与 `stock_levels` 一样由库存流水累加。
+//! 库位结存减去各批次结存即为未分批的库存。出库未指定批次时按有效期从早到晚分配，
+//! 没有有效期的批次排在后面，未分批的库存最后出；每个批次单独记一条流水，便于按批次追溯订单。
+
+use chrono::{NaiveDate, Utc};
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::Serialize;
+
+use super::inventory::{oversell_policy, OversellPolicy, StockError, StockResult};
+use super::warehouses::level_quantity;
+use super::{Database, StockBatch, StockRecord};
+
+/// 批次在某个仓库、库位的结存
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchStock {
+    pub product_id: String,
+    pub product_code: String,
+    pub product_name: String,
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    pub location: String,
+    pub batch_no: String,
+    pub production_date: String,
+    pub expiry_date: String,
+    pub quantity: i32,
+    /// 距有效期的天数，已过期为负数，没有有效期时为 `None`
+    pub days_to_expiry: Option<i64>,
+}
+
+/// 与某个批次有关的订单
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchOrder {
+    pub order_id: String,
+    pub order_code: String,
+    pub order_type: String,
+    pub status: String,
+    pub customer_id: String,
+    pub customer_name: String,
+    pub product_id: String,
+    /// 该批次随订单出库的数量
+    pub shipped: i32,
+    /// 该批次随订单入库（采购收货、退货）的数量
+    pub received: i32,
+    pub last_movement_at: String,
+}
+
+/// 批次追溯：全部出入库流水及涉及的订单
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchTrace {
+    pub batch_no: String,
+    pub records: Vec<StockRecord>,
+    pub orders: Vec<BatchOrder>,
+    /// 各仓库库位的当前结存
+    pub stock: Vec<BatchStock>,
+}
+
+pub(super) fn create_batch_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_batches (
+            product_id TEXT NOT NULL,
+            warehouse_id TEXT NOT NULL,
+            location TEXT NOT NULL DEFAULT '',
+            batch_no TEXT NOT NULL,
+            production_date TEXT DEFAULT '',
+            expiry_date TEXT DEFAULT '',
+            quantity INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (product_id, warehouse_id, location, batch_no)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_batches_expiry ON stock_batches(expiry_date)", [])?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_batches_batch ON stock_batches(batch_no)", [])?;
+    super::ensure_column(conn, "stock_records", "production_date", "TEXT DEFAULT ''")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_records_batch ON stock_records(batch_no)", [])?;
+    super::ensure_column(conn, "stock_transfer_items", "batch_no", "TEXT DEFAULT ''")?;
+    super::ensure_column(conn, "stock_transfer_items", "production_date", "TEXT DEFAULT ''")?;
+    super::ensure_column(conn, "stock_transfer_items", "expiry_date", "TEXT DEFAULT ''")?;
+
+    // 已有带批号的流水汇总为批次结存；多仓库之前的流水记到产品的所属仓库和库位
+    conn.execute(
+        "INSERT OR IGNORE INTO stock_batches (product_id, warehouse_id, location, batch_no, production_date, expiry_date, quantity, updated_at)
+         SELECT r.product_id,
+                CASE WHEN r.warehouse_id != '' THEN r.warehouse_id WHEN p.warehouse_id != '' THEN p.warehouse_id ELSE ?1 END,
+                CASE WHEN r.warehouse_id != '' THEN r.location ELSE p.location END,
+                r.batch_no, '', MAX(r.expiry_date),
+                SUM(CASE r.record_type
+                        WHEN 'out' THEN -r.quantity
+                        WHEN 'transfer_out' THEN -r.quantity
+                        WHEN 'adjust' THEN r.after_quantity - r.before_quantity
+                        ELSE r.quantity END),
+                ?2
+         FROM stock_records r JOIN products p ON p.id = r.product_id
+         WHERE r.batch_no != ''
+         GROUP BY 1, 2, 3, 4",
+        params![super::DEFAULT_WAREHOUSE_ID, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// 更新批次结存；生产日期、有效期以首次登记的为准，之前为空时补上
+pub(super) fn adjust_batch(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str, batch: &StockBatch, delta: i32) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO stock_batches (product_id, warehouse_id, location, batch_no, production_date, expiry_date, quantity, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(product_id, warehouse_id, location, batch_no) DO UPDATE SET
+             quantity = quantity + excluded.quantity,
+             production_date = CASE WHEN production_date = '' THEN excluded.production_date ELSE production_date END,
+             expiry_date = CASE WHEN expiry_date = '' THEN excluded.expiry_date ELSE expiry_date END,
+             updated_at = excluded.updated_at",
+        params![
+            product_id, warehouse_id, location, batch.batch_no, batch.production_date, batch.expiry_date,
+            delta, Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+/// 库位上有结存的批次，按先到期先出排序
+fn batches_at(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str) -> SqliteResult<Vec<(StockBatch, i32)>> {
+    let mut stmt = conn.prepare(
+        "SELECT batch_no, production_date, expiry_date, quantity FROM stock_batches
+         WHERE product_id = ?1 AND warehouse_id = ?2 AND location = ?3 AND quantity > 0
+         ORDER BY expiry_date = '', expiry_date, production_date, batch_no",
+    )?;
+    let rows = stmt.query_map([product_id, warehouse_id, location], |row| {
+        Ok((StockBatch { batch_no: row.get(0)?, production_date: row.get(1)?, expiry_date: row.get(2)? }, row.get(3)?))
+    })?;
+    rows.collect()
+}
+
+/// 按超卖策略检查指定批次的结存是否足够出库
+pub(super) fn check_batch(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str, batch_no: &str, requested: i32) -> StockResult<()> {
+    if requested <= 0 || oversell_policy(conn)? == OversellPolicy::Allow {
+        return Ok(());
+    }
+    let available: i32 = conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM stock_batches WHERE product_id = ?1 AND warehouse_id = ?2 AND location = ?3 AND batch_no = ?4",
+        [product_id, warehouse_id, location, batch_no],
+        |row| row.get(0),
+    )?;
+    if requested > available {
+        let product = conn.query_row("SELECT name FROM products WHERE id = ?1", [product_id], |row| row.get(0))?;
+        return Err(StockError::InsufficientBatchStock { product, batch_no: batch_no.to_string(), requested, available: available.max(0) });
+    }
+    Ok(())
+}
+
+fn today() -> String {
+    Utc::now().date_naive().format("%Y-%m-%d").to_string()
+}
+
+/// 按先到期先出把出库数量分配到各批次，不足部分从未分批的库存出（批次为空）
+///
+/// `skip_expired` 时不分配已过期的批次（销售出库）；此时未过期库存不足且超卖策略为拒绝，返回 `ExpiredStock`。
+pub(super) fn allocate_fefo(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str, requested: i32, skip_expired: bool) -> StockResult<Vec<(StockBatch, i32)>> {
+    let batches = batches_at(conn, product_id, warehouse_id, location)?;
+    let batched: i32 = batches.iter().map(|(_, quantity)| quantity).sum();
+    let today = today();
+    let mut parts = Vec::new();
+    let mut remaining = requested;
+    let mut expired = 0;
+    for (batch, quantity) in batches {
+        if remaining == 0 {
+            break;
+        }
+        if skip_expired && !batch.expiry_date.is_empty() && batch.expiry_date < today {
+            expired += quantity;
+            continue;
+        }
+        let take = quantity.min(remaining);
+        parts.push((batch, take));
+        remaining -= take;
+    }
+    if remaining > 0 {
+        let unbatched = level_quantity(conn, product_id, warehouse_id, location)? - batched;
+        if expired > 0 && remaining > unbatched.max(0) && oversell_policy(conn)? == OversellPolicy::Reject {
+            let product = conn.query_row("SELECT name FROM products WHERE id = ?1", [product_id], |row| row.get(0))?;
+            return Err(StockError::ExpiredStock { product, requested, expired });
+        }
+        parts.push((StockBatch::default(), remaining));
+    }
+    Ok(parts)
+}
+
+fn days_between(from: &str, to: &str) -> Option<i64> {
+    let from = NaiveDate::parse_from_str(from, "%Y-%m-%d").ok()?;
+    let to = NaiveDate::parse_from_str(to, "%Y-%m-%d").ok()?;
+    Some((to - from).num_days())
+}
+
+fn query_batch_stock(conn: &Connection, filter: &str, values: &[&dyn rusqlite::ToSql]) -> SqliteResult<Vec<BatchStock>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT b.product_id, COALESCE(p.code, ''), COALESCE(p.name, ''), b.warehouse_id, COALESCE(w.name, b.warehouse_id), b.location,
+                b.batch_no, b.production_date, b.expiry_date, b.quantity
+         FROM stock_batches b
+         LEFT JOIN products p ON p.id = b.product_id
+         LEFT JOIN warehouses w ON w.id = b.warehouse_id
+         WHERE b.quantity > 0 AND {}
+         ORDER BY b.expiry_date = '', b.expiry_date, p.code, b.batch_no",
+        filter
+    ))?;
+    let today = today();
+    let rows = stmt.query_map(values, |row| {
+        let expiry_date: String = row.get(8)?;
+        Ok(BatchStock {
+            product_id: row.get(0)?, product_code: row.get(1)?, product_name: row.get(2)?,
+            warehouse_id: row.get(3)?, warehouse_name: row.get(4)?, location: row.get(5)?,
+            batch_no: row.get(6)?, production_date: row.get(7)?,
+            days_to_expiry: days_between(&today, &expiry_date),
+            expiry_date, quantity: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+impl Database {
+    /// 有结存的批次，可按产品和（或）仓库筛选，先到期的在前
+    pub fn list_stock_batches(&self, product_id: Option<&str>, warehouse_id: Option<&str>) -> SqliteResult<Vec<BatchStock>> {
+        let conn = self.conn.lock().unwrap();
+        query_batch_stock(
+            &conn,
+            "(?1 IS NULL OR b.product_id = ?1) AND (?2 IS NULL OR b.warehouse_id = ?2)",
+            &[&product_id, &warehouse_id],
+        )
+    }
+
+    /// `within_days` 天内到期（含已过期）且仍有结存的批次
+    pub fn list_expiring_batches(&self, within_days: i64, warehouse_id: Option<&str>) -> SqliteResult<Vec<BatchStock>> {
+        let conn = self.conn.lock().unwrap();
+        let until = (Utc::now().date_naive() + chrono::Duration::days(within_days)).format("%Y-%m-%d").to_string();
+        query_batch_stock(
+            &conn,
+            "b.expiry_date != '' AND b.expiry_date <= ?1 AND (?2 IS NULL OR b.warehouse_id = ?2)",
+            &[&until, &warehouse_id],
+        )
+    }
+
+    /// 批次追溯：批次的全部流水、随哪些订单出入库，以及当前结存
+    pub fn trace_batch(&self, batch_no: &str, product_id: Option<&str>) -> SqliteResult<BatchTrace> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, product_id, record_type, quantity, before_quantity, after_quantity, unit_price, total_amount, remark, created_at,
+                    batch_no, expiry_date, order_id, warehouse_id, location, production_date
+             FROM stock_records WHERE batch_no = ?1 AND (?2 IS NULL OR product_id = ?2) ORDER BY created_at, rowid",
+        )?;
+        let records = stmt
+            .query_map(params![batch_no, product_id], |row| {
+                Ok(StockRecord {
+                    id: row.get(0)?, product_id: row.get(1)?, record_type: row.get(2)?, quantity: row.get(3)?,
+                    before_quantity: row.get(4)?, after_quantity: row.get(5)?, unit_price: row.get(6)?,
+                    total_amount: row.get(7)?, remark: row.get(8)?, created_at: row.get(9)?, batch_no: row.get(10)?,
+                    expiry_date: row.get(11)?, order_id: row.get(12)?, warehouse_id: row.get(13)?, location: row.get(14)?,
+                    production_date: row.get(15)?,
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT o.id, o.code, o.order_type, o.status, o.customer_id, o.customer_name, r.product_id,
+                    SUM(CASE WHEN r.record_type = 'out' THEN r.quantity ELSE 0 END),
+                    SUM(CASE WHEN r.record_type IN ('in', 'return') THEN r.quantity ELSE 0 END),
+                    MAX(r.created_at)
+             FROM stock_records r JOIN orders o ON o.id = r.order_id
+             WHERE r.batch_no = ?1 AND (?2 IS NULL OR r.product_id = ?2)
+             GROUP BY o.id, r.product_id
+             ORDER BY MAX(r.created_at)",
+        )?;
+        let orders = stmt
+            .query_map(params![batch_no, product_id], |row| {
+                Ok(BatchOrder {
+                    order_id: row.get(0)?, order_code: row.get(1)?, order_type: row.get(2)?, status: row.get(3)?,
+                    customer_id: row.get(4)?, customer_name: row.get(5)?, product_id: row.get(6)?,
+                    shipped: row.get(7)?, received: row.get(8)?, last_movement_at: row.get(9)?,
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        let stock = query_batch_stock(&conn, "b.batch_no = ?1 AND (?2 IS NULL OR b.product_id = ?2)", &[&batch_no, &product_id])?;
+        Ok(BatchTrace { batch_no: batch_no.to_string(), records, orders, stock })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{database_with, operator, product};
+    use crate::database::{Order, OrderItem, Product, StockMovement};
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        database_with(&[Product { name: "鲜奶".into(), unit: "瓶".into(), cost_price: 3.0, sell_price: 5.0, ..product("p1", "P001", 0) }])
+    }
+
+    fn date(days: i64) -> String {
+        (Utc::now().date_naive() + chrono::Duration::days(days)).format("%Y-%m-%d").to_string()
+    }
+
+    fn receive(db: &Database, batch_no: &str, expiry_date: String, quantity: i32) {
+        let batch = StockBatch { batch_no: batch_no.into(), production_date: date(-30), expiry_date };
+        db.update_stock(&StockMovement {
+            product_id: "p1", quantity_change: quantity, record_type: "in", unit_price: 3.0, remark: "", batch: &batch,
+            order_id: "", warehouse_id: "", location: "",
+        })
+        .unwrap();
+    }
+
+    fn out(db: &Database, quantity: i32) -> StockResult<Vec<StockRecord>> {
+        db.update_stock(&StockMovement {
+            product_id: "p1", quantity_change: -quantity, record_type: "out", unit_price: 5.0, remark: "",
+            batch: &StockBatch::default(), order_id: "", warehouse_id: "", location: "",
+        })
+    }
+
+    fn batch_quantities(db: &Database) -> Vec<(String, i32)> {
+        db.list_stock_batches(Some("p1"), None).unwrap().into_iter().map(|b| (b.batch_no, b.quantity)).collect()
+    }
+
+    #[test]
+    fn test_fefo_allocation() {
+        let (_dir, db) = setup();
+        receive(&db, "LATE", date(60), 5);
+        receive(&db, "SOON", date(10), 5);
+        receive(&db, "GONE", date(-1), 3);
+
+        // 先出 10 天后到期的批次，已过期的批次不用于销售出库
+        let records = out(&db, 7).unwrap();
+        let parts: Vec<_> = records.iter().map(|r| (r.batch_no.as_str(), r.quantity)).collect();
+        assert_eq!(parts, [("SOON", 5), ("LATE", 2)]);
+        assert_eq!(batch_quantities(&db), [("GONE".to_string(), 3), ("LATE".to_string(), 3)]);
+
+        // 剩下的未过期库存只有 3 件
+        assert!(matches!(out(&db, 4), Err(StockError::ExpiredStock { expired: 3, .. })));
+
+        let expiring = db.list_expiring_batches(30, None).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!((expiring[0].batch_no.as_str(), expiring[0].days_to_expiry), ("GONE", Some(-1)));
+        assert_eq!(db.list_expiring_batches(90, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_trace_batch_to_orders() {
+        let (_dir, db) = setup();
+        receive(&db, "L01", date(30), 10);
+        let operator = operator("clerk", "店员", "staff");
+        let now = Utc::now().to_rfc3339();
+        let id = uuid::Uuid::new_v4().to_string();
+        let order = Order {
+            id: id.clone(), code: format!("SO-{}", &id[..8]), order_type: "sale".into(), customer_id: "c1".into(),
+            customer_name: "便利店".into(), status: "draft".into(), total_quantity: 0, total_amount: 0.0, discount_amount: 0.0,
+            payable_amount: 0.0, paid_amount: 0.0, delivery_address: String::new(), delivery_date: String::new(), warehouse_id: String::new(),
+            remark: String::new(), operator_id: String::new(), operator_name: String::new(), created_at: now.clone(),
+            updated_at: now, version: 0,
+        };
+        let item = OrderItem {
+            id: uuid::Uuid::new_v4().to_string(), order_id: id.clone(), product_id: "p1".into(), product_name: "鲜奶".into(),
+            product_code: "P001".into(), unit: "瓶".into(), quantity: 4, unit_price: 5.0, discount: 0.0, amount: 20.0,
+            remark: String::new(), shipped_quantity: 0,
+        };
+        db.create_order_with_items(&order, &[item]).unwrap();
+        db.confirm_order(&id, &operator).unwrap();
+        db.complete_order(&id, &operator).unwrap();
+
+        let trace = db.trace_batch("L01", None).unwrap();
+        assert_eq!(trace.records.len(), 2);
+        assert_eq!(trace.orders.len(), 1);
+        assert_eq!((trace.orders[0].customer_name.as_str(), trace.orders[0].shipped), ("便利店", 4));
+        assert_eq!(trace.stock[0].quantity, 6);
+    }
+}
@@ -0,0 +1,465 @@
+//! 同步冲突 - 服务器变更与本地未上传修改的检测与处理
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use chrono::Utc;
+
+use super::outbox::{delete_row, record_change, set_state, table_for, write_row, ApplyError};
+use super::Database;
+use crate::sync::{local_is_newer, merge_fields, ConflictStrategy, FieldConflict, MergeOutcome, SyncPacket, VERSIONED_ENTITIES};
+
+/// 冲突策略在 sync_state 中的键前缀
+const STRATEGY_KEY_PREFIX: &str = "conflict_strategy.";
+
+/// 整行冲突（一方删除、另一方修改）使用的字段名
+pub const DELETED_FIELD: &str = "_deleted";
+
+/// 同步冲突记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub id: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    /// 双方共同的基准数据（首次同步前产生的冲突没有基准）
+    pub base_data: Option<Value>,
+    /// 冲突发生时的本地数据，`None` 表示本地已删除
+    pub local_data: Option<Value>,
+    /// 服务器数据，`None` 表示服务器已删除
+    pub remote_data: Option<Value>,
+    pub fields: Vec<FieldConflict>,
+    /// open / resolved
+    pub status: String,
+    /// local / remote / merge 或自动处理时使用的策略
+    pub resolution: Option<String>,
+    pub created_at: String,
+    pub resolved_at: Option<String>,
+}
+
+/// 手动处理冲突的方式
+#[derive(Debug, Clone)]
+pub enum ConflictResolution {
+    /// 保留本地数据并重新上传
+    Local,
+    /// 采用服务器数据，丢弃本地修改
+    Remote,
+    /// 以服务器数据为底，使用指定的字段值
+    Merge(Value),
+}
+
+impl ConflictResolution {
+    pub fn parse(kind: &str, values: Option<Value>) -> Option<Self> {
+        match kind {
+            "local" => Some(Self::Local),
+            "remote" => Some(Self::Remote),
+            "merge" => values.filter(Value::is_object).map(Self::Merge),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Remote => "remote",
+            Self::Merge(_) => "merge",
+        }
+    }
+}
+
+/// 单条服务器变更的应用结果
+pub(super) enum ApplyOutcome {
+    Applied,
+    /// 已记录为待处理冲突
+    Conflict,
+    /// 无法识别的实体类型
+    Unknown,
+}
+
+fn row_to_conflict(row: &rusqlite::Row) -> SqliteResult<SyncConflict> {
+    let parse = |text: Option<String>| text.and_then(|t| serde_json::from_str(&t).ok());
+    let fields: String = row.get(6)?;
+    Ok(SyncConflict {
+        id: row.get(0)?,
+        entity_type: row.get(1)?,
+        entity_id: row.get(2)?,
+        base_data: parse(row.get(3)?),
+        local_data: parse(row.get(4)?),
+        remote_data: parse(row.get(5)?),
+        fields: serde_json::from_str(&fields).unwrap_or_default(),
+        status: row.get(7)?,
+        resolution: row.get(8)?,
+        created_at: row.get(9)?,
+        resolved_at: row.get(10)?,
+    })
+}
+
+const CONFLICT_COLUMNS: &str =
+    "id, entity_type, entity_id, base_data, local_data, remote_data, fields, status, resolution, created_at, resolved_at";
+
+fn to_json_text<T: Serialize + ?Sized>(value: &T) -> SqliteResult<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+impl From<ApplyError> for rusqlite::Error {
+    fn from(e: ApplyError) -> Self {
+        match e {
+            ApplyError::Data(e) => rusqlite::Error::ToSqlConversionFailure(Box::new(e)),
+            ApplyError::Sqlite(e) => e,
+        }
+    }
+}
+
+impl Database {
+    /// 冲突列表，默认只返回未处理的冲突
+    pub fn list_conflicts(&self, include_resolved: bool) -> SqliteResult<Vec<SyncConflict>> {
+        let conn = self.conn.lock().unwrap();
+        let sql = if include_resolved {
+            format!("SELECT {} FROM sync_conflicts ORDER BY created_at DESC", CONFLICT_COLUMNS)
+        } else {
+            format!("SELECT {} FROM sync_conflicts WHERE status = 'open' ORDER BY created_at DESC", CONFLICT_COLUMNS)
+        };
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], row_to_conflict)?;
+        rows.collect()
+    }
+
+    pub fn get_conflict(&self, id: &str) -> SqliteResult<Option<SyncConflict>> {
+        let conn = self.conn.lock().unwrap();
+        load_conflict(&conn, id)
+    }
+
+    pub fn count_open_conflicts(&self) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM sync_conflicts WHERE status = 'open'", [], |row| row.get(0))
+    }
+
+    /// 手动处理冲突，返回处理后的本地数据（`None` 表示记录已删除）
+    ///
+    /// 保留本地或合并时以服务器版本号为基准重新排队上传；采用服务器数据时丢弃本地未上传的修改。
+    pub fn resolve_conflict(&self, id: &str, resolution: &ConflictResolution) -> SqliteResult<Option<Value>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let conflict = load_conflict(&tx, id)?
+            .filter(|c| c.status == "open")
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let entity_type = conflict.entity_type.as_str();
+        let entity_id = conflict.entity_id.as_str();
+        let table = table_for(entity_type).ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let remote_version = conflict.remote_data.as_ref().map(|r| r["version"].clone());
+
+        match resolution {
+            ConflictResolution::Remote => {
+                apply_remote(&tx, table, entity_type, entity_id, conflict.remote_data.clone())?;
+                drop_pending(&tx, entity_type, entity_id)?;
+            }
+            ConflictResolution::Local => match load_local(&tx, entity_type, entity_id)? {
+                Some(mut local) => {
+                    if let Some(version) = remote_version {
+                        local["version"] = version;
+                    }
+                    write_row(&tx, table, local)?;
+                    requeue(&tx, entity_type, entity_id)?;
+                }
+                None => requeue(&tx, entity_type, entity_id)?,
+            },
+            ConflictResolution::Merge(values) => {
+                let mut merged = match (&conflict.remote_data, load_local(&tx, entity_type, entity_id)?) {
+                    (Some(remote), _) => remote.clone(),
+                    (None, Some(local)) => local,
+                    (None, None) => return Err(rusqlite::Error::QueryReturnedNoRows),
+                };
+                if let (Some(target), Some(values)) = (merged.as_object_mut(), values.as_object()) {
+                    for (field, value) in values {
+                        if !matches!(field.as_str(), "id" | "version" | "created_at" | DELETED_FIELD) {
+                            target.insert(field.clone(), value.clone());
+                        }
+                    }
+                }
+                merged["updated_at"] = Value::from(Utc::now().to_rfc3339());
+                write_row(&tx, table, merged)?;
+                requeue(&tx, entity_type, entity_id)?;
+            }
+        }
+
+        mark_resolved(&tx, id, resolution.as_str())?;
+        let saved = load_local(&tx, entity_type, entity_id)?;
+        tx.commit()?;
+        Ok(saved)
+    }
+
+    /// 实体当前使用的冲突策略
+    pub fn conflict_strategy(&self, entity_type: &str) -> SqliteResult<ConflictStrategy> {
+        let conn = self.conn.lock().unwrap();
+        strategy_for(&conn, entity_type)
+    }
+
+    /// 所有参与冲突检测的实体及其策略
+    pub fn conflict_strategies(&self) -> SqliteResult<Vec<(String, ConflictStrategy)>> {
+        let conn = self.conn.lock().unwrap();
+        VERSIONED_ENTITIES
+            .iter()
+            .map(|entity| Ok((entity.to_string(), strategy_for(&conn, entity)?)))
+            .collect()
+    }
+
+    pub fn set_conflict_strategy(&self, entity_type: &str, strategy: ConflictStrategy) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        set_state(&conn, &format!("{}{}", STRATEGY_KEY_PREFIX, entity_type), strategy.as_str())
+    }
+}
+
+fn strategy_for(conn: &Connection, entity_type: &str) -> SqliteResult<ConflictStrategy> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT value FROM sync_state WHERE key = ?1",
+            [format!("{}{}", STRATEGY_KEY_PREFIX, entity_type)],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(stored
+        .as_deref()
+        .and_then(ConflictStrategy::parse)
+        .unwrap_or_else(|| ConflictStrategy::default_for(entity_type)))
+}
+
+fn load_conflict(conn: &Connection, id: &str) -> SqliteResult<Option<SyncConflict>> {
+    conn.query_row(
+        &format!("SELECT {} FROM sync_conflicts WHERE id = ?1", CONFLICT_COLUMNS),
+        [id],
+        row_to_conflict,
+    )
+    .optional()
+}
+
+fn open_conflict(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<Option<SyncConflict>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM sync_conflicts WHERE entity_type = ?1 AND entity_id = ?2 AND status = 'open'",
+            CONFLICT_COLUMNS
+        ),
+        [entity_type, entity_id],
+        row_to_conflict,
+    )
+    .optional()
+}
+
+fn mark_resolved(conn: &Connection, id: &str, resolution: &str) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE sync_conflicts SET status = 'resolved', resolution = ?2, resolved_at = ?3 WHERE id = ?1",
+        params![id, resolution, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+pub(super) fn save_shadow_raw(conn: &Connection, entity_type: &str, entity_id: &str, data: &str) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_shadow (entity_type, entity_id, data) VALUES (?1, ?2, ?3)",
+        params![entity_type, entity_id, data],
+    )?;
+    Ok(())
+}
+
+pub(super) fn delete_shadow(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<()> {
+    conn.execute(
+        "DELETE FROM sync_shadow WHERE entity_type = ?1 AND entity_id = ?2",
+        [entity_type, entity_id],
+    )?;
+    Ok(())
+}
+
+fn load_shadow(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<Option<Value>> {
+    let data: Option<String> = conn
+        .query_row(
+            "SELECT data FROM sync_shadow WHERE entity_type = ?1 AND entity_id = ?2",
+            [entity_type, entity_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(data.and_then(|d| serde_json::from_str(&d).ok()))
+}
+
+/// 读取本地数据（JSON 形式），`None` 表示本地不存在
+fn load_local(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<Option<Value>> {
+    let value = match entity_type {
+        "customer" => Database::load_customer(conn, entity_id)?.map(|c| json!(c)),
+        "product" => Database::load_product(conn, entity_id)?.map(|p| json!(p)),
+        "order" => Database::load_order(conn, entity_id)?.map(|o| json!(o)),
+        _ => None,
+    };
+    Ok(value)
+}
+
+/// 最近一次本地未上传修改的时间，没有则返回 `None`
+fn latest_pending_at(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<Option<String>> {
+    conn.query_row(
+        "SELECT MAX(created_at) FROM sync_queue WHERE entity_type = ?1 AND entity_id = ?2",
+        [entity_type, entity_id],
+        |row| row.get(0),
+    )
+}
+
+fn drop_pending(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<()> {
+    conn.execute(
+        "DELETE FROM sync_queue WHERE entity_type = ?1 AND entity_id = ?2",
+        [entity_type, entity_id],
+    )?;
+    Ok(())
+}
+
+/// 用当前本地状态替换该实体所有未上传的变更：存在则上传整行，不存在则上传删除
+fn requeue(conn: &Connection, entity_type: &str, entity_id: &str) -> SqliteResult<()> {
+    drop_pending(conn, entity_type, entity_id)?;
+    match load_local(conn, entity_type, entity_id)? {
+        Some(local) => record_change(conn, entity_type, entity_id, "update", &local),
+        None => record_change(conn, entity_type, entity_id, "delete", &json!({ "id": entity_id })),
+    }
+}
+
+/// 直接采用服务器数据，并记为新的比较基准
+fn apply_remote(
+    conn: &Connection,
+    table: &str,
+    entity_type: &str,
+    entity_id: &str,
+    remote: Option<Value>,
+) -> Result<(), ApplyError> {
+    match remote {
+        Some(data) => {
+            save_shadow_raw(conn, entity_type, entity_id, &data.to_string())?;
+            write_row(conn, table, data)?;
+        }
+        None => {
+            delete_shadow(conn, entity_type, entity_id)?;
+            delete_row(conn, table, entity_id)?;
+        }
+    }
+    Ok(())
+}
+
+/// 应用带版本号实体的服务器变更
+///
+/// 本地没有未上传的修改时直接覆盖；否则以共同基准做字段级比较，无冲突则合并后重新上传，
+/// 有冲突则按实体配置的策略处理。
+pub(super) fn apply_versioned(conn: &Connection, change: &SyncPacket, table: &str) -> Result<ApplyOutcome, ApplyError> {
+    let entity_type = change.entity_type.as_str();
+    let entity_id = change.data["id"].as_str().unwrap_or(&change.entity_id).to_string();
+    let entity_id = entity_id.as_str();
+    let remote = (change.operation != "delete").then(|| change.data.clone());
+
+    let existing = open_conflict(conn, entity_type, entity_id)?;
+    let has_pending = latest_pending_at(conn, entity_type, entity_id)?.is_some();
+    if existing.is_none() && !has_pending {
+        apply_remote(conn, table, entity_type, entity_id, remote)?;
+        return Ok(ApplyOutcome::Applied);
+    }
+
+    let local = load_local(conn, entity_type, entity_id)?;
+    // 未处理的冲突沿用原来的基准，避免后续下载把尚未确认的本地修改误判为可合并
+    let base = match &existing {
+        Some(c) => c.base_data.clone(),
+        None => load_shadow(conn, entity_type, entity_id)?,
+    };
+
+    let outcome = match (&local, &remote) {
+        (Some(l), Some(r)) => merge_fields(base.as_ref(), l, r),
+        (None, None) => {
+            // 双方都已删除
+            drop_pending(conn, entity_type, entity_id)?;
+            delete_shadow(conn, entity_type, entity_id)?;
+            if let Some(c) = &existing {
+                mark_resolved(conn, &c.id, "remote")?;
+            }
+            return Ok(ApplyOutcome::Applied);
+        }
+        _ => MergeOutcome {
+            merged: Value::Null,
+            conflicts: vec![FieldConflict {
+                field: DELETED_FIELD.to_string(),
+                base: Value::Bool(false),
+                local: Value::Bool(local.is_none()),
+                remote: Value::Bool(remote.is_none()),
+            }],
+        },
+    };
+
+    if outcome.conflicts.is_empty() {
+        // 双方修改了不同字段：合并结果带服务器版本号，重新上传
+        if let Some(r) = &remote {
+            save_shadow_raw(conn, entity_type, entity_id, &r.to_string())?;
+        }
+        write_row(conn, table, outcome.merged)?;
+        requeue(conn, entity_type, entity_id)?;
+        if let Some(c) = &existing {
+            mark_resolved(conn, &c.id, "merge")?;
+        }
+        return Ok(ApplyOutcome::Applied);
+    }
+
+    let strategy = match strategy_for(conn, entity_type)? {
+        ConflictStrategy::LastWriterWins => {
+            let local_stamp = match &local {
+                Some(l) => l.clone(),
+                None => json!({ "updated_at": latest_pending_at(conn, entity_type, entity_id)? }),
+            };
+            let remote_stamp = remote.clone().unwrap_or_else(|| json!({ "updated_at": change.timestamp }));
+            if local_is_newer(&local_stamp, &remote_stamp) {
+                ConflictStrategy::ClientWins
+            } else {
+                ConflictStrategy::ServerWins
+            }
+        }
+        strategy => strategy,
+    };
+
+    match strategy {
+        ConflictStrategy::ServerWins => {
+            apply_remote(conn, table, entity_type, entity_id, remote)?;
+            drop_pending(conn, entity_type, entity_id)?;
+        }
+        ConflictStrategy::ClientWins => {
+            match &remote {
+                Some(r) => {
+                    save_shadow_raw(conn, entity_type, entity_id, &r.to_string())?;
+                    if local.is_some() {
+                        write_row(conn, table, outcome.prefer_local())?;
+                    }
+                }
+                None => delete_shadow(conn, entity_type, entity_id)?,
+            }
+            requeue(conn, entity_type, entity_id)?;
+        }
+        _ => {
+            // 手动处理：保留本地数据，暂停该实体的上传直到用户处理
+            let fields = to_json_text(&outcome.conflicts)?;
+            let local_data = local.as_ref().map(Value::to_string);
+            let remote_data = remote.as_ref().map(Value::to_string);
+            match &existing {
+                Some(c) => {
+                    // 已有未处理的冲突：刷新双方数据，不计为新冲突
+                    conn.execute(
+                        "UPDATE sync_conflicts SET local_data = ?2, remote_data = ?3, fields = ?4 WHERE id = ?1",
+                        params![c.id, local_data, remote_data, fields],
+                    )?;
+                    return Ok(ApplyOutcome::Applied);
+                }
+                None => {
+                    conn.execute(
+                        "INSERT INTO sync_conflicts (id, entity_type, entity_id, base_data, local_data, remote_data, fields, status, created_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'open', ?8)",
+                        params![
+                            uuid::Uuid::new_v4().to_string(), entity_type, entity_id,
+                            base.as_ref().map(Value::to_string), local_data, remote_data, fields,
+                            Utc::now().to_rfc3339()
+                        ],
+                    )?;
+                }
+            }
+            return Ok(ApplyOutcome::Conflict);
+        }
+    }
+
+    if let Some(c) = &existing {
+        mark_resolved(conn, &c.id, strategy.as_str())?;
+    }
+    Ok(ApplyOutcome::Applied)
+}
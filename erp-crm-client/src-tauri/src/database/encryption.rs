@@ -0,0 +1,187 @@
+//! 数据库加密 - SQLCipher 整库加密
+//!
+//! 数据库密钥是随机生成的 32 字节原始密钥，不直接由密码派生：每个用户的加密存储
+//! （由登录密码派生的密钥保护）中保存一份，登录时取出后解锁数据库。
+//! 需要以 `sqlcipher` 特性构建，否则只能使用未加密的数据库。
+
+use rand::{rngs::OsRng, RngCore};
+use rusqlite::Connection;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use thiserror::Error;
+
+use super::Database;
+
+/// 未加密 SQLite 文件的文件头
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// 数据库原始密钥
+pub type DatabaseKey = [u8; 32];
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("当前版本未启用数据库加密支持")]
+    NotSupported,
+    #[error("数据库已加密")]
+    AlreadyEncrypted,
+    #[error("数据库未加密")]
+    NotEncrypted,
+    #[error("数据库已加密，尚未解锁")]
+    Locked,
+    #[error("数据库密钥错误")]
+    WrongKey,
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("IO 错误: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Migration(#[from] super::MigrationError),
+}
+
+pub type EncryptionResult<T> = std::result::Result<T, EncryptionError>;
+
+/// 生成新的随机数据库密钥
+pub fn generate_database_key() -> DatabaseKey {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// 文件存在且不是明文 SQLite 文件头，视为已加密
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(_) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// SQLCipher 的原始密钥写法 `x'<64 位十六进制>'`
+fn key_literal(key: &DatabaseKey) -> String {
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("x'{}'", hex)
+}
+
+/// 确认链接的是 SQLCipher，普通 SQLite 会静默忽略 `PRAGMA key`
+fn ensure_sqlcipher(conn: &Connection) -> EncryptionResult<()> {
+    let version: Option<String> = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .ok();
+    match version {
+        Some(v) if !v.is_empty() => Ok(()),
+        _ => Err(EncryptionError::NotSupported),
+    }
+}
+
+/// 用密钥打开加密数据库并校验密钥
+fn open_with_key(path: &Path, key: &DatabaseKey) -> EncryptionResult<Connection> {
+    let conn = Connection::open(path)?;
+    ensure_sqlcipher(&conn)?;
+    conn.pragma_update(None, "key", key_literal(key))?;
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map_err(|_| EncryptionError::WrongKey)?;
+    Ok(conn)
+}
+
+impl Database {
+    /// 数据库是否已加密（已解锁或仍锁定）
+    pub fn is_encrypted(&self) -> bool {
+        self.is_locked() || self.key.lock().unwrap().is_some()
+    }
+
+    /// 数据库已加密且尚未解锁
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    /// 是否支持加密（以 `sqlcipher` 特性构建）
+    pub fn encryption_supported(&self) -> bool {
+        ensure_sqlcipher(&self.conn.lock().unwrap()).is_ok()
+    }
+
+    /// 当前数据库密钥，未加密时为 `None`
+    pub fn encryption_key(&self) -> Option<DatabaseKey> {
+        *self.key.lock().unwrap()
+    }
+
+    /// 用密钥解锁加密数据库，并把表结构升级到当前版本
+    pub fn unlock(&self, key: &DatabaseKey) -> EncryptionResult<()> {
+        if !self.is_locked() {
+            return match self.encryption_key() {
+                Some(current) if current == *key => Ok(()),
+                Some(_) => Err(EncryptionError::WrongKey),
+                None => Err(EncryptionError::NotEncrypted),
+            };
+        }
+        let conn = open_with_key(&self.path, key)?;
+        *self.conn.lock().unwrap() = conn;
+        *self.key.lock().unwrap() = Some(*key);
+        self.locked.store(false, Ordering::SeqCst);
+        self.migrate()?;
+        log::info!("数据库已解锁");
+        Ok(())
+    }
+
+    /// 将未加密的数据库就地加密：导出到临时加密文件后替换原文件
+    pub fn encrypt_in_place(&self, key: &DatabaseKey) -> EncryptionResult<()> {
+        if self.is_locked() {
+            return Err(EncryptionError::Locked);
+        }
+        if self.encryption_key().is_some() {
+            return Err(EncryptionError::AlreadyEncrypted);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        ensure_sqlcipher(&conn)?;
+
+        let mut tmp_name = self.path.as_os_str().to_owned();
+        tmp_name.push(".encrypting");
+        let tmp = std::path::PathBuf::from(tmp_name);
+        if tmp.exists() {
+            fs::remove_file(&tmp)?;
+        }
+
+        let export = || -> EncryptionResult<()> {
+            let user_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            conn.execute(
+                "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+                [tmp.to_string_lossy().as_ref(), key_literal(key).as_str()],
+            )?;
+            let exported = conn
+                .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+                .and_then(|_| conn.execute_batch(&format!("PRAGMA encrypted.user_version = {}", user_version)));
+            conn.execute("DETACH DATABASE encrypted", [])?;
+            exported?;
+            Ok(())
+        };
+        if let Err(e) = export() {
+            fs::remove_file(&tmp).ok();
+            return Err(e);
+        }
+
+        // 关闭原连接后替换文件，再用密钥重新打开
+        drop(std::mem::replace(&mut *conn, Connection::open_in_memory()?));
+        fs::rename(&tmp, &self.path)?;
+        *conn = open_with_key(&self.path, key)?;
+        *self.key.lock().unwrap() = Some(*key);
+        log::info!("数据库已加密");
+        Ok(())
+    }
+
+    /// 更换数据库密钥（SQLCipher 会重新加密全部页面）
+    pub fn rekey(&self, new_key: &DatabaseKey) -> EncryptionResult<()> {
+        if self.is_locked() {
+            return Err(EncryptionError::Locked);
+        }
+        if self.encryption_key().is_none() {
+            return Err(EncryptionError::NotEncrypted);
+        }
+        let conn = self.conn.lock().unwrap();
+        conn.pragma_update(None, "rekey", key_literal(new_key))?;
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
+        *self.key.lock().unwrap() = Some(*new_key);
+        log::info!("数据库密钥已更换");
+        Ok(())
+    }
+}
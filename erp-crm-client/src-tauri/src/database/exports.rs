@@ -0,0 +1,266 @@
+//! 导出 - 把筛选后的列表和报表整理成表格
+//!
+//! 状态、分类等写成界面上的名称；产品、客户的列名与导入时识别的表头一致，导出的文件可以改完再导入。
+
+use super::{Database, SalesReportQuery};
+use super::imports::{choice_label, CUSTOMER_CATEGORIES, CUSTOMER_STATUSES, PRODUCT_STATUSES};
+use crate::spreadsheet::{Cell, Sheet};
+use chrono::NaiveDate;
+use rusqlite::Result as SqliteResult;
+use serde::{Deserialize, Serialize};
+
+const ORDER_TYPES: &[(&str, &str)] = &[("sale", "销售订单"), ("purchase", "采购订单"), ("return", "退货订单")];
+const ORDER_STATUSES: &[(&str, &str)] = &[
+    ("draft", "草稿"),
+    ("pending", "待确认"),
+    ("confirmed", "已确认"),
+    ("partially_shipped", "部分发货"),
+    ("shipped", "已发货"),
+    ("completed", "已完成"),
+    ("cancelled", "已取消"),
+    ("returned", "已退货"),
+];
+
+/// 列表筛选条件，空字符串表示不筛选
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    /// 模糊匹配名称、编码、电话等
+    pub keyword: String,
+    pub category: String,
+    pub status: String,
+    pub order_type: String,
+    pub customer_id: String,
+    /// 订单下单日期范围（含首尾）
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
+
+impl ExportFilter {
+    fn matches_keyword(&self, values: &[&str]) -> bool {
+        let keyword = self.keyword.trim().to_lowercase();
+        keyword.is_empty() || values.iter().any(|v| v.to_lowercase().contains(&keyword))
+    }
+
+    fn matches(filter: &str, value: &str) -> bool {
+        filter.is_empty() || filter == value
+    }
+
+    /// 比较 RFC 3339 时间的日期部分
+    fn matches_date(&self, created_at: &str) -> bool {
+        let date = created_at.get(..10).unwrap_or(created_at);
+        self.start_date.is_none_or(|start| date >= start.to_string().as_str())
+            && self.end_date.is_none_or(|end| date <= end.to_string().as_str())
+    }
+}
+
+/// 可导出的报表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportExport {
+    /// 销售趋势（按查询的汇总粒度）
+    SalesTrend,
+    SalesProducts,
+    SalesCustomers,
+    SalesCategories,
+    SalesOperators,
+    /// 库存金额明细，与日期无关
+    Inventory,
+    /// 已完成销售订单的成本与毛利
+    OrderCosts,
+}
+
+impl ReportExport {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sales_trend" => Some(ReportExport::SalesTrend),
+            "sales_products" => Some(ReportExport::SalesProducts),
+            "sales_customers" => Some(ReportExport::SalesCustomers),
+            "sales_categories" => Some(ReportExport::SalesCategories),
+            "sales_operators" => Some(ReportExport::SalesOperators),
+            "inventory" => Some(ReportExport::Inventory),
+            "order_costs" => Some(ReportExport::OrderCosts),
+            _ => None,
+        }
+    }
+}
+
+/// 金额保留两位小数，避免导出 12.300000000000001
+fn money(value: f64) -> Cell {
+    Cell::Number((value * 100.0).round() / 100.0)
+}
+
+impl Database {
+    pub fn export_customers(&self, filter: &ExportFilter) -> SqliteResult<Sheet> {
+        let mut sheet = Sheet::new("客户", &["名称", "公司", "联系人", "电话", "邮箱", "地址", "分类", "状态", "信用额度", "应收余额", "来源", "备注", "创建时间"]);
+        for c in self.list_customers()? {
+            if !filter.matches_keyword(&[&c.name, &c.company, &c.contact_person, &c.phone, &c.email])
+                || !ExportFilter::matches(&filter.category, &c.category)
+                || !ExportFilter::matches(&filter.status, &c.status)
+            {
+                continue;
+            }
+            sheet.push(vec![
+                c.name.into(), c.company.into(), c.contact_person.into(), c.phone.into(), c.email.into(), c.address.into(),
+                choice_label(CUSTOMER_CATEGORIES, &c.category).into(), choice_label(CUSTOMER_STATUSES, &c.status).into(),
+                money(c.credit_limit), money(c.balance), c.source.into(), c.remark.into(), c.created_at.into(),
+            ]);
+        }
+        Ok(sheet)
+    }
+
+    pub fn export_products(&self, filter: &ExportFilter) -> SqliteResult<Sheet> {
+        let mut sheet = Sheet::new(
+            "产品",
+            &["编码", "条码", "名称", "分类", "单位", "规格", "品牌", "成本价", "售价", "最低库存", "最高库存", "库存", "库位", "状态", "描述"],
+        );
+        for p in self.list_products()? {
+            if !filter.matches_keyword(&[&p.code, &p.barcode, &p.name, &p.specification, &p.brand])
+                || !ExportFilter::matches(&filter.category, &p.category)
+                || !ExportFilter::matches(&filter.status, &p.status)
+            {
+                continue;
+            }
+            sheet.push(vec![
+                p.code.into(), p.barcode.into(), p.name.into(), p.category.into(), p.unit.into(), p.specification.into(), p.brand.into(),
+                p.cost_price.into(), p.sell_price.into(), p.min_stock.into(), p.max_stock.into(), p.current_stock.into(),
+                p.location.into(), choice_label(PRODUCT_STATUSES, &p.status).into(), p.description.into(),
+            ]);
+        }
+        Ok(sheet)
+    }
+
+    pub fn export_orders(&self, filter: &ExportFilter) -> SqliteResult<Sheet> {
+        let mut sheet = Sheet::new(
+            "订单",
+            &["订单号", "类型", "客户", "状态", "数量", "金额", "折扣", "应收", "已收", "未收", "交货日期", "交货地址", "业务员", "备注", "下单时间"],
+        );
+        for o in self.list_orders()? {
+            if !filter.matches_keyword(&[&o.code, &o.customer_name])
+                || !ExportFilter::matches(&filter.order_type, &o.order_type)
+                || !ExportFilter::matches(&filter.status, &o.status)
+                || !ExportFilter::matches(&filter.customer_id, &o.customer_id)
+                || !filter.matches_date(&o.created_at)
+            {
+                continue;
+            }
+            sheet.push(vec![
+                o.code.into(), choice_label(ORDER_TYPES, &o.order_type).into(), o.customer_name.into(),
+                choice_label(ORDER_STATUSES, &o.status).into(), o.total_quantity.into(), money(o.total_amount),
+                money(o.discount_amount), money(o.payable_amount), money(o.paid_amount), money(o.payable_amount - o.paid_amount),
+                o.delivery_date.into(), o.delivery_address.into(), o.operator_name.into(), o.remark.into(), o.created_at.into(),
+            ]);
+        }
+        Ok(sheet)
+    }
+
+    /// 导出报表；销售报表的排行导出全部条目，不受 `query.top` 限制
+    pub fn export_report(&self, kind: ReportExport, query: &SalesReportQuery) -> SqliteResult<Sheet> {
+        let sales = || self.sales_report(&SalesReportQuery { top: usize::MAX, ..*query });
+        let sheet = match kind {
+            ReportExport::SalesTrend => {
+                let mut sheet = Sheet::new("销售趋势", &["期间", "订单数", "数量", "销售额", "毛利"]);
+                for b in sales()?.trend {
+                    sheet.push(vec![b.period.into(), b.order_count.into(), b.quantity.into(), money(b.revenue), money(b.gross_profit)]);
+                }
+                sheet
+            }
+            ReportExport::SalesProducts => {
+                let mut sheet = Sheet::new("产品销售", &["编码", "名称", "分类", "订单数", "数量", "销售额", "成本", "毛利", "毛利率(%)"]);
+                for p in sales()?.top_products {
+                    sheet.push(vec![
+                        p.product_code.into(), p.product_name.into(), p.category.into(), p.order_count.into(), p.quantity.into(),
+                        money(p.revenue), money(p.cost), money(p.gross_profit), money(p.gross_margin),
+                    ]);
+                }
+                sheet
+            }
+            ReportExport::SalesCustomers => {
+                let mut sheet = Sheet::new("客户销售", &["客户", "订单数", "销售额", "毛利"]);
+                for c in sales()?.top_customers {
+                    sheet.push(vec![c.customer_name.into(), c.order_count.into(), money(c.revenue), money(c.gross_profit)]);
+                }
+                sheet
+            }
+            ReportExport::SalesCategories => {
+                let mut sheet = Sheet::new("分类销售", &["分类", "产品数", "数量", "销售额", "成本", "毛利", "毛利率(%)"]);
+                for c in sales()?.categories {
+                    sheet.push(vec![
+                        c.category.into(), c.product_count.into(), c.quantity.into(),
+                        money(c.revenue), money(c.cost), money(c.gross_profit), money(c.gross_margin),
+                    ]);
+                }
+                sheet
+            }
+            ReportExport::SalesOperators => {
+                let mut sheet = Sheet::new("业务员销售", &["业务员", "订单数", "销售额", "毛利"]);
+                for o in sales()?.operators {
+                    sheet.push(vec![o.operator_name.into(), o.order_count.into(), money(o.revenue), money(o.gross_profit)]);
+                }
+                sheet
+            }
+            ReportExport::Inventory => {
+                let mut sheet = Sheet::new("库存金额", &["编码", "名称", "分类", "数量", "单位成本", "库存金额"]);
+                for item in self.inventory_valuation()?.items {
+                    sheet.push(vec![
+                        item.product_code.into(), item.product_name.into(), item.category.into(),
+                        item.quantity.into(), item.unit_cost.into(), money(item.total_value),
+                    ]);
+                }
+                sheet
+            }
+            ReportExport::OrderCosts => {
+                let end_exclusive = query.end + chrono::Days::new(1);
+                let mut sheet = Sheet::new("销售成本", &["订单号", "客户", "下单时间", "收入", "成本", "毛利", "毛利率(%)"]);
+                for o in self.order_costs(&query.start.to_string(), &end_exclusive.to_string())? {
+                    sheet.push(vec![
+                        o.order_code.into(), o.customer_name.into(), o.created_at.into(),
+                        money(o.revenue), money(o.cost), money(o.gross_profit), money(o.gross_margin),
+                    ]);
+                }
+                sheet
+            }
+        };
+        Ok(sheet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{database_with, product};
+    use crate::database::{ImportEntity, ImportOptions, Product, ReportGranularity};
+    use chrono::Utc;
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        let products: Vec<_> = [("p1", "P001", "五金", "active"), ("p2", "P002", "耗材", "discontinued")]
+            .into_iter()
+            .map(|(id, code, category, status)| Product {
+                barcode: format!("69{}", code), name: format!("产品{}", code), category: category.into(), cost_price: 2.5,
+                sell_price: 4.0, min_stock: 1, max_stock: 100, status: status.into(), ..product(id, code, 8)
+            })
+            .collect();
+        database_with(&products)
+    }
+
+    #[test]
+    fn test_export_products_filters_and_reimports() {
+        let (_dir, db) = setup();
+        let filter = ExportFilter { category: "耗材".into(), ..Default::default() };
+        let sheet = db.export_products(&filter).unwrap();
+        assert_eq!(sheet.rows.len(), 1);
+        assert_eq!(sheet.rows[0][0].to_string(), "P002");
+        assert_eq!(sheet.rows[0][13].to_string(), "已停产");
+
+        // 导出的文件原样导入：全部按编码更新，不新建
+        let all = db.export_products(&ExportFilter::default()).unwrap();
+        let report = db.import_sheet(ImportEntity::Product, &all, &ImportOptions::default()).unwrap();
+        assert_eq!((report.created, report.updated, report.skipped), (0, 2, 0));
+        assert_eq!(db.get_product("p2").unwrap().unwrap().status, "discontinued");
+
+        let today = Utc::now().date_naive();
+        let query = SalesReportQuery { start: today, end: today, granularity: ReportGranularity::Day, top: 10 };
+        let inventory = db.export_report(ReportExport::Inventory, &query).unwrap();
+        assert_eq!(inventory.rows.len(), 2);
+        assert_eq!(db.export_report(ReportExport::SalesTrend, &query).unwrap().rows.len(), 1);
+    }
+}
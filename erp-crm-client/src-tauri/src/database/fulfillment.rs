@@ -0,0 +1,580 @@
+//! 订单履约 - 订单状态变化与库存变动在同一个事务中完成
+//!
+//! 销售订单确认时预留库存、发货时按发货数量出库并消耗预留、取消时释放预留（部分发货后取消时订单金额减为已发货部分）、
+//! 退货时按发货流水退回原批次和原仓库；
+//! 采购订单完成（收货）时入库，退货单完成时退回入库。出入库流水都关联订单，记在订单的仓库（未指定时为产品的所属仓库），
+//! 预留同样按仓库记录。
+//! 状态流转规则见 `order_status`。
+
+use rusqlite::{params, Connection, Result as SqliteResult};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use super::inventory::{self, StockError, StockMovement};
+use super::warehouses::resolve_place;
+use super::payments;
+use super::order_status::{change_status, check_transition, current_status, OrderStatus};
+use super::{record_change, Database, Order, OrderItem, StockBatch, User};
+
+#[derive(Error, Debug)]
+pub enum OrderError {
+    #[error("订单不存在: {0}")]
+    NotFound(String),
+    #[error("订单状态不能从 {from} 变为 {to}")]
+    InvalidTransition { from: OrderStatus, to: OrderStatus },
+    #[error("无法识别的订单状态: {0}")]
+    UnknownStatus(String),
+    #[error("只有销售订单可以{0}")]
+    NotSaleOrder(&'static str),
+    #[error("只有草稿订单可以修改或删除，当前状态: {0}")]
+    NotDraft(OrderStatus),
+    #[error("订单明细不存在: {0}")]
+    ItemNotFound(String),
+    #[error("{product} 发货数量 {requested} 超过未发货数量 {remaining}")]
+    OverShipment { product: String, requested: i32, remaining: i32 },
+    #[error("没有需要发货的明细")]
+    NothingToShip,
+    #[error("{customer} 超出信用额度：额度 {limit:.2}，确认后应收 {exposure:.2}")]
+    CreditLimitExceeded { customer: String, limit: f64, exposure: f64 },
+    #[error(transparent)]
+    Stock(#[from] StockError),
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type OrderResult<T> = std::result::Result<T, OrderError>;
+
+/// 一行明细的发货数量
+#[derive(Debug, Clone)]
+pub struct ItemShipment {
+    pub item_id: String,
+    pub quantity: i32,
+}
+
+impl Database {
+    /// 在一个事务中创建订单及明细，数量与金额按明细汇总
+    pub fn create_order_with_items(&self, order: &Order, items: &[OrderItem]) -> SqliteResult<Order> {
+        let mut order = order.clone();
+        if !items.is_empty() {
+            order.total_quantity = items.iter().map(|i| i.quantity).sum();
+            order.total_amount = items.iter().map(|i| i.amount).sum();
+            order.payable_amount = order.total_amount - order.discount_amount;
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::insert_order(&tx, &order)?;
+        for item in items {
+            let item = OrderItem { order_id: order.id.clone(), ..item.clone() };
+            Self::insert_order_item(&tx, &item)?;
+        }
+        tx.commit()?;
+        Ok(order)
+    }
+
+    /// 按目标状态执行对应的流转；部分发货需要发货数量，请使用 `ship_order`
+    pub fn update_order_status(&self, id: &str, to: OrderStatus, reason: &str, operator: &User) -> OrderResult<Order> {
+        match to {
+            OrderStatus::Confirmed => self.confirm_order(id, operator),
+            OrderStatus::Shipped => self.ship_order(id, &[], reason, operator),
+            OrderStatus::Completed => self.complete_order(id, operator),
+            OrderStatus::Cancelled => self.cancel_order(id, reason, operator),
+            OrderStatus::Returned => self.return_order(id, reason, operator),
+            OrderStatus::Draft | OrderStatus::PartiallyShipped => {
+                let order = self.get_order(id)?.ok_or_else(|| OrderError::NotFound(id.to_string()))?;
+                Err(OrderError::InvalidTransition { from: current_status(&order)?, to })
+            }
+        }
+    }
+
+    /// 确认订单；销售订单检查客户信用额度并在发货仓库按明细预留库存，可用库存不足时按超卖策略拒绝
+    pub fn confirm_order(&self, id: &str, operator: &User) -> OrderResult<Order> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let order = load(&tx, id)?;
+        check_transition(&order, OrderStatus::Confirmed)?;
+        if order.order_type == "sale" {
+            check_credit_limit(&tx, &order)?;
+            let items = Self::load_order_items(&tx, id)?;
+            for (product_id, quantity) in quantities_by_product(&items) {
+                let (warehouse_id, _) = resolve_place(&tx, product_id, &order.warehouse_id, "")?;
+                inventory::check_available(&tx, product_id, quantity, id)?;
+                inventory::check_warehouse_available(&tx, product_id, &warehouse_id, quantity, id)?;
+            }
+            for item in &items {
+                let (warehouse_id, _) = resolve_place(&tx, &item.product_id, &order.warehouse_id, "")?;
+                inventory::reserve(&tx, id, &item.id, &item.product_id, &warehouse_id, item.quantity)?;
+            }
+        }
+        let order = change_status(&tx, &order, OrderStatus::Confirmed, operator, "")?;
+        tx.commit()?;
+        Ok(order)
+    }
+
+    /// 销售订单发货并出库；`shipments` 为空时发出全部未发货数量，全部发完为已发货，否则为部分发货
+    pub fn ship_order(&self, id: &str, shipments: &[ItemShipment], remark: &str, operator: &User) -> OrderResult<Order> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let order = load(&tx, id)?;
+        if order.order_type != "sale" {
+            return Err(OrderError::NotSaleOrder("发货"));
+        }
+        // 先按“部分发货”校验，已确认和部分发货状态都可以发货
+        check_transition(&order, OrderStatus::PartiallyShipped)?;
+        let all_shipped = ship_items(&tx, &order, shipments)?;
+        let to = if all_shipped { OrderStatus::Shipped } else { OrderStatus::PartiallyShipped };
+        let order = change_status(&tx, &order, to, operator, remark)?;
+        tx.commit()?;
+        Ok(order)
+    }
+
+    /// 完成订单：销售订单从已确认直接完成时出库全部明细；采购收货入库，退货单退回入库
+    pub fn complete_order(&self, id: &str, operator: &User) -> OrderResult<Order> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let order = load(&tx, id)?;
+        let from = check_transition(&order, OrderStatus::Completed)?;
+        match order.order_type.as_str() {
+            "sale" if from == OrderStatus::Confirmed => {
+                ship_items(&tx, &order, &[])?;
+            }
+            "purchase" => stock_in_items(&tx, &order, "in", &format!("采购入库 {}", order.code))?,
+            "return" => stock_in_items(&tx, &order, "return", &format!("退货入库 {}", order.code))?,
+            _ => {}
+        }
+        inventory::close_reservations(&tx, id, "consumed")?;
+        let order = change_status(&tx, &order, OrderStatus::Completed, operator, "")?;
+        tx.commit()?;
+        Ok(order)
+    }
+
+    /// 取消订单并释放预留，取消原因记入状态历史
+    ///
+    /// 部分发货的订单只取消未发货部分：订单数量与金额减为已发货部分，客户应收扣除减少的金额。
+    pub fn cancel_order(&self, id: &str, reason: &str, operator: &User) -> OrderResult<Order> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut order = load(&tx, id)?;
+        let from = check_transition(&order, OrderStatus::Cancelled)?;
+        inventory::close_reservations(&tx, id, "released")?;
+        if from == OrderStatus::PartiallyShipped {
+            order = reduce_to_shipped(&tx, &order)?;
+        }
+        let order = change_status(&tx, &order, OrderStatus::Cancelled, operator, reason)?;
+        tx.commit()?;
+        Ok(order)
+    }
+
+    /// 销售订单整单退货，已发货数量退回入库，部分发货时同时释放未发货部分的预留
+    pub fn return_order(&self, id: &str, reason: &str, operator: &User) -> OrderResult<Order> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let order = load(&tx, id)?;
+        if order.order_type != "sale" {
+            return Err(OrderError::NotSaleOrder("退货"));
+        }
+        check_transition(&order, OrderStatus::Returned)?;
+        inventory::close_reservations(&tx, id, "released")?;
+        let remark = format!("销售退货 {}", order.code);
+        // 按发货出库流水原样退回：同一批次、同一仓库和库位
+        let mut returned: BTreeMap<String, i32> = BTreeMap::new();
+        for shipped in shipped_records(&tx, id)? {
+            inventory::apply_movement(&tx, &StockMovement {
+                product_id: &shipped.product_id,
+                quantity_change: shipped.quantity,
+                record_type: "return",
+                unit_price: shipped.unit_price,
+                remark: &remark,
+                batch: &shipped.batch,
+                order_id: id,
+                warehouse_id: &shipped.warehouse_id,
+                location: &shipped.location,
+            })?;
+            *returned.entry(shipped.product_id).or_default() += shipped.quantity;
+        }
+        // 没有出库流水的发货数量（如旧数据）退回订单仓库
+        let batch = StockBatch::default();
+        for item in Self::load_order_items(&tx, id)? {
+            let recorded = returned.entry(item.product_id.clone()).or_default();
+            let covered = (*recorded).min(item.shipped_quantity);
+            *recorded -= covered;
+            let missing = item.shipped_quantity - covered;
+            if missing > 0 {
+                inventory::apply_movement(&tx, &StockMovement {
+                    product_id: &item.product_id,
+                    quantity_change: missing,
+                    record_type: "return",
+                    unit_price: item.unit_price,
+                    remark: &remark,
+                    batch: &batch,
+                    order_id: id,
+                    warehouse_id: &order.warehouse_id,
+                    location: "",
+                })?;
+            }
+        }
+        let order = change_status(&tx, &order, OrderStatus::Returned, operator, reason)?;
+        tx.commit()?;
+        Ok(order)
+    }
+}
+
+/// 订单在一个批次、仓库和库位上的发货出库件数
+struct ShippedLine {
+    product_id: String,
+    quantity: i32,
+    unit_price: f64,
+    batch: StockBatch,
+    warehouse_id: String,
+    location: String,
+}
+
+/// 订单的发货出库流水，按产品、批次、仓库和库位合并
+fn shipped_records(conn: &Connection, order_id: &str) -> SqliteResult<Vec<ShippedLine>> {
+    let mut stmt = conn.prepare(
+        "SELECT product_id, SUM(quantity), MAX(unit_price), batch_no, production_date, expiry_date, warehouse_id, location
+         FROM stock_records WHERE order_id = ?1 AND record_type = 'out'
+         GROUP BY product_id, batch_no, production_date, expiry_date, warehouse_id, location
+         ORDER BY MIN(created_at)",
+    )?;
+    let lines = stmt.query_map(params![order_id], |row| {
+        Ok(ShippedLine {
+            product_id: row.get(0)?,
+            quantity: row.get(1)?,
+            unit_price: row.get(2)?,
+            batch: StockBatch { batch_no: row.get(3)?, production_date: row.get(4)?, expiry_date: row.get(5)? },
+            warehouse_id: row.get(6)?,
+            location: row.get(7)?,
+        })
+    })?;
+    lines.collect()
+}
+
+fn load(conn: &Connection, id: &str) -> OrderResult<Order> {
+    Database::load_order(conn, id)?.ok_or_else(|| OrderError::NotFound(id.to_string()))
+}
+
+/// 信用额度为 0 表示不限制；确认后的应收余额不能超过额度
+fn check_credit_limit(conn: &Connection, order: &Order) -> OrderResult<()> {
+    let Some((customer, limit, balance)) = payments::credit_position(conn, &order.customer_id)? else {
+        return Ok(());
+    };
+    let exposure = payments::round2(balance + payments::outstanding(order));
+    if limit > 0.0 && exposure > limit {
+        return Err(OrderError::CreditLimitExceeded { customer, limit, exposure });
+    }
+    Ok(())
+}
+
+/// 把部分发货订单的数量与金额减为已发货部分（折扣按比例保留），客户应收扣除减少的应付金额
+fn reduce_to_shipped(conn: &Connection, order: &Order) -> OrderResult<Order> {
+    let items = Database::load_order_items(conn, &order.id)?;
+    let shipped_quantity: i32 = items.iter().map(|item| item.shipped_quantity).sum();
+    let shipped_amount: f64 = items
+        .iter()
+        .filter(|item| item.quantity > 0)
+        .map(|item| item.amount * item.shipped_quantity as f64 / item.quantity as f64)
+        .sum();
+    let ratio = if order.total_amount > 0.0 { shipped_amount / order.total_amount } else { 0.0 };
+    let total_amount = payments::round2(shipped_amount);
+    let payable_amount = payments::round2(order.payable_amount * ratio);
+    conn.execute(
+        "UPDATE orders SET total_quantity = ?1, total_amount = ?2, discount_amount = ?3, payable_amount = ?4 WHERE id = ?5",
+        params![shipped_quantity, total_amount, payments::round2(total_amount - payable_amount), payable_amount, order.id],
+    )?;
+    if order.order_type == "sale" {
+        payments::adjust_customer_balance(conn, &order.customer_id, payable_amount - order.payable_amount)?;
+    }
+    load(conn, &order.id)
+}
+
+/// 按发货数量出库、消耗预留并更新明细的已发货数量，返回是否已全部发货
+fn ship_items(conn: &Connection, order: &Order, shipments: &[ItemShipment]) -> OrderResult<bool> {
+    let mut items = Database::load_order_items(conn, &order.id)?;
+    let requested: Vec<(usize, i32)> = if shipments.is_empty() {
+        items.iter().enumerate()
+            .map(|(index, item)| (index, item.quantity - item.shipped_quantity))
+            .filter(|(_, quantity)| *quantity > 0)
+            .collect()
+    } else {
+        shipments.iter()
+            .filter(|s| s.quantity > 0)
+            .map(|s| {
+                items.iter().position(|item| item.id == s.item_id)
+                    .map(|index| (index, s.quantity))
+                    .ok_or_else(|| OrderError::ItemNotFound(s.item_id.clone()))
+            })
+            .collect::<OrderResult<_>>()?
+    };
+    if requested.is_empty() {
+        return Err(OrderError::NothingToShip);
+    }
+
+    let remark = format!("销售出库 {}", order.code);
+    let batch = StockBatch::default();
+    for (index, quantity) in requested {
+        let item = &mut items[index];
+        let remaining = item.quantity - item.shipped_quantity;
+        if quantity > remaining {
+            return Err(OrderError::OverShipment { product: item.product_name.clone(), requested: quantity, remaining });
+        }
+        inventory::apply_movement(conn, &StockMovement {
+            product_id: &item.product_id,
+            quantity_change: -quantity,
+            record_type: "out",
+            unit_price: item.unit_price,
+            remark: &remark,
+            batch: &batch,
+            order_id: &order.id,
+            warehouse_id: &order.warehouse_id,
+            location: "",
+        })?;
+        inventory::consume_reservation(conn, &item.id, quantity)?;
+        item.shipped_quantity += quantity;
+        conn.execute("UPDATE order_items SET shipped_quantity = ?1 WHERE id = ?2", params![item.shipped_quantity, item.id])?;
+        record_change(conn, "order_item", &item.id, "update", &*item)?;
+    }
+    Ok(items.iter().all(|item| item.shipped_quantity >= item.quantity))
+}
+
+/// 采购收货、退货单按明细入库
+fn stock_in_items(conn: &Connection, order: &Order, record_type: &str, remark: &str) -> OrderResult<()> {
+    let batch = StockBatch::default();
+    for item in Database::load_order_items(conn, &order.id)? {
+        inventory::apply_movement(conn, &StockMovement {
+            product_id: &item.product_id,
+            quantity_change: item.quantity,
+            record_type,
+            unit_price: item.unit_price,
+            remark,
+            batch: &batch,
+            order_id: &order.id,
+            warehouse_id: &order.warehouse_id,
+            location: "",
+        })?;
+    }
+    Ok(())
+}
+
+/// 同一产品可能分多行下单，按产品汇总后再检查库存
+fn quantities_by_product(items: &[OrderItem]) -> BTreeMap<&str, i32> {
+    let mut totals = BTreeMap::new();
+    for item in items {
+        *totals.entry(item.product_id.as_str()).or_insert(0) += item.quantity;
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{self, database_with, product, warehouse};
+    use crate::database::{OversellPolicy, DEFAULT_WAREHOUSE_ID};
+    use chrono::Utc;
+
+    fn operator() -> User {
+        test_support::operator("clerk", "店员", "staff")
+    }
+
+    fn setup(stock: i32) -> (tempfile::TempDir, Database) {
+        database_with(&[product("p1", "P001", stock)])
+    }
+
+    fn order(db: &Database, order_type: &str, quantity: i32) -> Order {
+        let now = Utc::now().to_rfc3339();
+        let id = uuid::Uuid::new_v4().to_string();
+        let order = Order {
+            id: id.clone(), code: format!("{}-{}", db.generate_order_code(order_type), &id[..8]), order_type: order_type.into(),
+            customer_id: String::new(), customer_name: String::new(), status: "draft".into(), total_quantity: 0,
+            total_amount: 0.0, discount_amount: 0.0, payable_amount: 0.0, paid_amount: 0.0,
+            delivery_address: String::new(), delivery_date: String::new(), warehouse_id: String::new(), remark: String::new(),
+            operator_id: String::new(), operator_name: String::new(), created_at: now.clone(), updated_at: now, version: 0,
+        };
+        let item = OrderItem {
+            id: uuid::Uuid::new_v4().to_string(), order_id: id, product_id: "p1".into(), product_name: "螺丝".into(),
+            product_code: "P001".into(), unit: "个".into(), quantity, unit_price: 2.0, discount: 0.0,
+            amount: quantity as f64 * 2.0, remark: String::new(), shipped_quantity: 0,
+        };
+        db.create_order_with_items(&order, &[item]).unwrap()
+    }
+
+    fn stock(db: &Database) -> (i32, i32) {
+        let a = db.get_stock_availability("p1").unwrap();
+        (a.current_stock, a.available)
+    }
+
+    #[test]
+    fn test_sale_order_reserves_and_deducts() {
+        let (_dir, db) = setup(10);
+        let first = order(&db, "sale", 6);
+        assert_eq!(first.total_quantity, 6);
+        assert_eq!(first.payable_amount, 12.0);
+
+        db.confirm_order(&first.id, &operator()).unwrap();
+        assert_eq!(stock(&db), (10, 4));
+
+        // 其他订单不能占用已预留的库存，手工出库同样受限
+        let second = order(&db, "sale", 5);
+        let err = db.confirm_order(&second.id, &operator()).unwrap_err();
+        assert!(matches!(err, OrderError::Stock(StockError::InsufficientStock { available: 4, .. })), "{}", err);
+        assert_eq!(db.get_order(&second.id).unwrap().unwrap().status, "draft");
+        assert!(db
+            .update_stock(&StockMovement {
+                product_id: "p1", quantity_change: -5, record_type: "out", unit_price: 0.0, remark: "",
+                batch: &StockBatch::default(), order_id: "", warehouse_id: "", location: "",
+            })
+            .is_err());
+
+        let done = db.complete_order(&first.id, &operator()).unwrap();
+        assert_eq!(done.status, "completed");
+        assert_eq!(stock(&db), (4, 4));
+        let conn = db.conn.lock().unwrap();
+        let (record_type, quantity, order_id): (String, i32, String) = conn
+            .query_row("SELECT record_type, quantity, order_id FROM stock_records", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap();
+        assert_eq!((record_type.as_str(), quantity, order_id.as_str()), ("out", 6, first.id.as_str()));
+        drop(conn);
+
+        assert!(matches!(
+            db.complete_order(&first.id, &operator()),
+            Err(OrderError::InvalidTransition { from: OrderStatus::Completed, to: OrderStatus::Completed })
+        ));
+        assert!(matches!(db.cancel_order(&first.id, "", &operator()), Err(OrderError::InvalidTransition { .. })));
+    }
+
+    #[test]
+    fn test_cancel_releases_and_purchase_adds() {
+        let (_dir, db) = setup(3);
+        let sale = order(&db, "sale", 3);
+        db.confirm_order(&sale.id, &operator()).unwrap();
+        assert_eq!(stock(&db), (3, 0));
+        let cancelled = db.cancel_order(&sale.id, "客户取消", &operator()).unwrap();
+        assert_eq!(cancelled.status, "cancelled");
+        assert_eq!(stock(&db), (3, 3));
+        let history = db.list_order_status_history(&sale.id).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[1].from_status.as_str(), history[1].to_status.as_str()), ("confirmed", "cancelled"));
+        assert_eq!((history[1].operator_name.as_str(), history[1].reason.as_str()), ("店员", "客户取消"));
+        assert!(matches!(db.confirm_order(&sale.id, &operator()), Err(OrderError::InvalidTransition { .. })));
+
+        let purchase = order(&db, "purchase", 7);
+        db.confirm_order(&purchase.id, &operator()).unwrap();
+        assert_eq!(stock(&db), (3, 3));
+        db.complete_order(&purchase.id, &operator()).unwrap();
+        assert_eq!(stock(&db), (10, 10));
+    }
+
+    #[test]
+    fn test_oversell_policy() {
+        let (_dir, db) = setup(2);
+        assert_eq!(db.get_oversell_policy().unwrap(), OversellPolicy::Reject);
+        let sale = order(&db, "sale", 5);
+        assert!(db.confirm_order(&sale.id, &operator()).is_err());
+
+        db.set_oversell_policy(OversellPolicy::Allow).unwrap();
+        db.confirm_order(&sale.id, &operator()).unwrap();
+        assert_eq!(stock(&db), (2, -3));
+
+        // 切回拒绝后出库会使库存为负，整单回滚
+        db.set_oversell_policy(OversellPolicy::Reject).unwrap();
+        assert!(db.complete_order(&sale.id, &operator()).is_err());
+        assert_eq!(stock(&db), (2, -3));
+        assert_eq!(db.get_order(&sale.id).unwrap().unwrap().status, "confirmed");
+
+        db.set_oversell_policy(OversellPolicy::Allow).unwrap();
+        db.complete_order(&sale.id, &operator()).unwrap();
+        assert_eq!(stock(&db), (-3, -3));
+    }
+
+    #[test]
+    fn test_partial_shipment_and_return() {
+        let (_dir, db) = setup(10);
+        let sale = order(&db, "sale", 6);
+        let item_id = db.get_order_items(&sale.id).unwrap()[0].id.clone();
+        assert!(matches!(db.ship_order(&sale.id, &[], "", &operator()), Err(OrderError::InvalidTransition { .. })));
+        db.confirm_order(&sale.id, &operator()).unwrap();
+
+        let shipment = |quantity| [ItemShipment { item_id: item_id.clone(), quantity }];
+        let partial = db.ship_order(&sale.id, &shipment(4), "第一批", &operator()).unwrap();
+        assert_eq!(partial.status, "partially_shipped");
+        assert_eq!(stock(&db), (6, 4));
+        let err = db.ship_order(&sale.id, &shipment(3), "", &operator()).unwrap_err();
+        assert!(matches!(err, OrderError::OverShipment { requested: 3, remaining: 2, .. }), "{}", err);
+
+        let shipped = db.ship_order(&sale.id, &[], "", &operator()).unwrap();
+        assert_eq!(shipped.status, "shipped");
+        assert_eq!(stock(&db), (4, 4));
+        assert_eq!(db.get_order_items(&sale.id).unwrap()[0].shipped_quantity, 6);
+
+        let returned = db.update_order_status(&sale.id, OrderStatus::Returned, "质量问题", &operator()).unwrap();
+        assert_eq!(returned.status, "returned");
+        assert_eq!(stock(&db), (10, 10));
+        let steps: Vec<String> = db.list_order_status_history(&sale.id).unwrap().into_iter().map(|h| h.to_status).collect();
+        assert_eq!(steps, ["confirmed", "partially_shipped", "shipped", "returned"]);
+
+        let purchase = order(&db, "purchase", 1);
+        db.confirm_order(&purchase.id, &operator()).unwrap();
+        assert!(matches!(db.ship_order(&purchase.id, &[], "", &operator()), Err(OrderError::NotSaleOrder(_))));
+    }
+
+    #[test]
+    fn test_order_warehouse() {
+        let (_dir, db) = setup(10);
+        db.create_warehouse(&warehouse("north", "北仓")).unwrap();
+        db.update_stock(&StockMovement {
+            product_id: "p1", quantity_change: 3, record_type: "in", unit_price: 1.0, remark: "", batch: &StockBatch::default(),
+            order_id: "", warehouse_id: "north", location: "",
+        })
+        .unwrap();
+        let in_north = |order_type, quantity| db.update_order(&Order { warehouse_id: "north".into(), ..order(&db, order_type, quantity) }).unwrap();
+
+        // 合计库存 13 件，但北仓只有 3 件，预留按仓库检查
+        let too_many = in_north("sale", 4);
+        let err = db.confirm_order(&too_many.id, &operator()).unwrap_err();
+        assert!(matches!(err, OrderError::Stock(StockError::InsufficientWarehouseStock { available: 3, .. })), "{}", err);
+        let north = in_north("sale", 3);
+        db.confirm_order(&north.id, &operator()).unwrap();
+        let err = db.confirm_order(&in_north("sale", 1).id, &operator()).unwrap_err();
+        assert!(matches!(err, OrderError::Stock(StockError::InsufficientWarehouseStock { available: 0, .. })), "{}", err);
+        // 默认仓库的库存不受北仓预留影响
+        let home = order(&db, "sale", 5);
+        db.confirm_order(&home.id, &operator()).unwrap();
+
+        // 发货从订单的仓库出库，采购收货入到订单的仓库
+        db.ship_order(&north.id, &[], "", &operator()).unwrap();
+        let purchase = in_north("purchase", 2);
+        db.confirm_order(&purchase.id, &operator()).unwrap();
+        db.complete_order(&purchase.id, &operator()).unwrap();
+        let levels = db.list_stock_levels(Some("p1"), None).unwrap();
+        let quantity = |warehouse_id: &str| levels.iter().find(|l| l.warehouse_id == warehouse_id).unwrap().quantity;
+        assert_eq!((quantity("north"), quantity(DEFAULT_WAREHOUSE_ID)), (2, 10));
+        assert_eq!(stock(&db), (12, 7));
+    }
+
+    #[test]
+    fn test_return_restores_batch_and_warehouse() {
+        let (_dir, db) = setup(10);
+        db.create_warehouse(&warehouse("north", "北仓")).unwrap();
+        let batch = StockBatch { batch_no: "L01".into(), production_date: "2026-01-01".into(), expiry_date: "2099-12-31".into() };
+        db.update_stock(&StockMovement {
+            product_id: "p1", quantity_change: 3, record_type: "in", unit_price: 1.0, remark: "", batch: &batch,
+            order_id: "", warehouse_id: "north", location: "",
+        })
+        .unwrap();
+        let sale = db.update_order(&Order { warehouse_id: "north".into(), ..order(&db, "sale", 3) }).unwrap();
+        db.confirm_order(&sale.id, &operator()).unwrap();
+        db.ship_order(&sale.id, &[], "", &operator()).unwrap();
+        assert!(db.list_stock_batches(Some("p1"), None).unwrap().iter().all(|b| b.batch_no != "L01"));
+
+        // 退货回到发货时的批次和仓库，而不是默认仓库
+        db.return_order(&sale.id, "质量问题", &operator()).unwrap();
+        let batches = db.list_stock_batches(Some("p1"), None).unwrap();
+        let l01 = batches.iter().find(|b| b.batch_no == "L01").unwrap();
+        assert_eq!((l01.warehouse_id.as_str(), l01.location.as_str(), l01.quantity), ("north", "", 3));
+        assert_eq!((l01.production_date.as_str(), l01.expiry_date.as_str()), ("2026-01-01", "2099-12-31"));
+        let levels = db.list_stock_levels(Some("p1"), None).unwrap();
+        let quantity = |warehouse_id: &str| levels.iter().find(|l| l.warehouse_id == warehouse_id).unwrap().quantity;
+        assert_eq!((quantity("north"), quantity(DEFAULT_WAREHOUSE_ID)), (3, 10));
+        let trace = db.trace_batch("L01", None).unwrap();
+        assert_eq!(trace.records.iter().filter(|r| r.record_type == "return").count(), 1);
+    }
+}
@@ -0,0 +1,627 @@
+//! 批量导入 - 从表格文件导入客户和产品
+//!
+//! 列对应关系默认按表头识别（常用的中英文列名），也可以逐个字段指定表头。
+//! 每行单独校验，有错误的行跳过并在报告中列出行号和原因，其余行照常导入。
+//! 产品先按编码、再按条码匹配已有产品；客户先按电话、再按名称匹配。匹配到的更新，否则新建。
+//! 更新时空单元格不覆盖原值；库存数量只作为新建产品的期初库存，已有产品的库存通过出入库或盘点调整。
+//! 试导入在事务中执行全部写入后回滚，返回与正式导入相同的报告。
+
+use super::{Customer, Database, Product};
+use crate::spreadsheet::{Cell, Sheet};
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// 预览最多返回的行数，全部行都会校验和计数
+const PREVIEW_LIMIT: usize = 200;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("缺少用于匹配的列，至少需要 {0} 之一")]
+    MissingColumn(String),
+    #[error("文件中没有列: {0}")]
+    UnknownColumn(String),
+    #[error("不支持导入的字段: {0}")]
+    UnknownField(String),
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type ImportResult<T> = Result<T, ImportError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportEntity {
+    Customer,
+    Product,
+}
+
+impl ImportEntity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "customer" | "customers" => Some(ImportEntity::Customer),
+            "product" | "products" => Some(ImportEntity::Product),
+            _ => None,
+        }
+    }
+
+    pub fn fields(&self) -> &'static [ImportField] {
+        match self {
+            ImportEntity::Customer => CUSTOMER_FIELDS,
+            ImportEntity::Product => PRODUCT_FIELDS,
+        }
+    }
+
+    /// 用于匹配已有记录的字段，文件中至少要有其中一列
+    fn key_fields(&self) -> &'static [&'static str] {
+        match self {
+            ImportEntity::Customer => &["phone", "name"],
+            ImportEntity::Product => &["code", "barcode", "name"],
+        }
+    }
+}
+
+/// 可导入的字段，`key` 与新建客户、产品时的参数名一致
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportField {
+    pub key: &'static str,
+    pub label: &'static str,
+    /// 新建时必填；只更新已有记录的文件可以没有这一列
+    pub required: bool,
+    /// 自动识别时匹配的表头（不区分大小写，忽略空格、下划线和连字符）
+    #[serde(skip)]
+    pub aliases: &'static [&'static str],
+}
+
+const fn field(key: &'static str, label: &'static str, required: bool, aliases: &'static [&'static str]) -> ImportField {
+    ImportField { key, label, required, aliases }
+}
+
+const PRODUCT_FIELDS: &[ImportField] = &[
+    field("code", "编码", false, &["code", "编码", "产品编码", "商品编码", "货号", "sku"]),
+    field("barcode", "条码", false, &["barcode", "条码", "条形码", "商品条码", "ean"]),
+    field("name", "名称", true, &["name", "名称", "产品名称", "商品名称", "品名"]),
+    field("category", "分类", false, &["category", "分类", "类别", "产品分类", "商品分类"]),
+    field("unit", "单位", false, &["unit", "单位"]),
+    field("specification", "规格", false, &["specification", "spec", "规格", "规格型号", "型号"]),
+    field("brand", "品牌", false, &["brand", "品牌"]),
+    field("costPrice", "成本价", false, &["cost_price", "cost", "成本价", "成本", "进价", "采购价"]),
+    field("sellPrice", "售价", false, &["sell_price", "price", "售价", "销售价", "零售价"]),
+    field("minStock", "最低库存", false, &["min_stock", "最低库存", "安全库存", "库存下限"]),
+    field("maxStock", "最高库存", false, &["max_stock", "最高库存", "库存上限"]),
+    field("currentStock", "期初库存", false, &["current_stock", "stock", "期初库存", "当前库存", "库存", "库存数量"]),
+    field("location", "库位", false, &["location", "库位", "货位"]),
+    field("status", "状态", false, &["status", "状态"]),
+    field("description", "描述", false, &["description", "描述", "说明", "备注"]),
+];
+
+const CUSTOMER_FIELDS: &[ImportField] = &[
+    field("name", "名称", true, &["name", "名称", "客户名称", "客户", "姓名"]),
+    field("company", "公司", false, &["company", "公司", "公司名称", "单位名称"]),
+    field("contactPerson", "联系人", false, &["contact_person", "contact", "联系人"]),
+    field("phone", "电话", false, &["phone", "mobile", "tel", "电话", "手机", "手机号", "联系电话"]),
+    field("email", "邮箱", false, &["email", "邮箱", "电子邮件"]),
+    field("address", "地址", false, &["address", "地址", "收货地址"]),
+    field("category", "分类", false, &["category", "分类", "客户分类", "客户类型"]),
+    field("status", "状态", false, &["status", "状态"]),
+    field("creditLimit", "信用额度", false, &["credit_limit", "信用额度"]),
+    field("source", "来源", false, &["source", "来源", "客户来源"]),
+    field("remark", "备注", false, &["remark", "备注", "说明"]),
+];
+
+/// 取值范围：代码与界面上的名称，导入时两者都接受
+pub const PRODUCT_STATUSES: &[(&str, &str)] = &[("active", "正常"), ("discontinued", "已停产"), ("out_of_stock", "缺货")];
+pub const CUSTOMER_CATEGORIES: &[(&str, &str)] = &[("vip", "VIP客户"), ("regular", "普通客户"), ("potential", "潜在客户"), ("inactive", "休眠客户")];
+pub const CUSTOMER_STATUSES: &[(&str, &str)] = &[("active", "活跃"), ("inactive", "停用"), ("blocked", "黑名单")];
+
+/// 代码对应的名称，未知代码原样返回
+pub fn choice_label<'a>(choices: &[(&'a str, &'a str)], code: &'a str) -> &'a str {
+    choices.iter().find(|(c, _)| *c == code).map_or(code, |(_, label)| label)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// 字段 → 表头；未指定的字段按表头自动识别，指定为空字符串表示不导入该字段
+    pub mapping: HashMap<String, String>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Create,
+    Update,
+}
+
+/// 一个单元格的错误，`row` 为文件中的行号
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportRowError {
+    pub row: usize,
+    /// 字段名称，整行的错误为空
+    pub field: String,
+    pub value: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreviewRow {
+    pub row: usize,
+    pub action: ImportAction,
+    /// 导入后的客户或产品
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub entity: ImportEntity,
+    pub dry_run: bool,
+    /// 实际使用的列对应关系（字段 → 表头）
+    pub columns: BTreeMap<String, String>,
+    /// 非空数据行数
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    /// 有错误而跳过的行数
+    pub skipped: usize,
+    pub errors: Vec<ImportRowError>,
+    /// 前 200 行的导入结果
+    pub preview: Vec<ImportPreviewRow>,
+}
+
+fn normalize_header(header: &str) -> String {
+    header.chars().filter(|c| !c.is_whitespace() && *c != '_' && *c != '-').flat_map(char::to_lowercase).collect()
+}
+
+/// 按表头自动识别的列对应关系（字段 → 表头），用于导入前让用户确认
+pub fn suggest_columns(entity: ImportEntity, headers: &[String]) -> BTreeMap<String, String> {
+    let normalized: Vec<String> = headers.iter().map(|h| normalize_header(h)).collect();
+    let mut columns = BTreeMap::new();
+    for field in entity.fields() {
+        let found = field.aliases.iter().find_map(|alias| {
+            let alias = normalize_header(alias);
+            normalized.iter().position(|h| *h == alias)
+        });
+        if let Some(index) = found {
+            columns.insert(field.key.to_string(), headers[index].clone());
+        }
+    }
+    columns
+}
+
+/// 字段 → 列序号
+fn resolve_columns(entity: ImportEntity, headers: &[String], mapping: &HashMap<String, String>) -> ImportResult<HashMap<&'static str, usize>> {
+    let fields = entity.fields();
+    if let Some(unknown) = mapping.keys().find(|key| !fields.iter().any(|f| f.key == key.as_str())) {
+        return Err(ImportError::UnknownField(unknown.clone()));
+    }
+    let mut columns = suggest_columns(entity, headers);
+    for (key, header) in mapping {
+        if header.trim().is_empty() {
+            columns.remove(key);
+        } else {
+            columns.insert(key.clone(), header.trim().to_string());
+        }
+    }
+    let mut resolved = HashMap::new();
+    for field in fields {
+        if let Some(header) = columns.get(field.key) {
+            let index = headers.iter().position(|h| h == header).ok_or_else(|| ImportError::UnknownColumn(header.clone()))?;
+            resolved.insert(field.key, index);
+        }
+    }
+    let keys = entity.key_fields();
+    if !keys.iter().any(|key| resolved.contains_key(key)) {
+        let labels: Vec<&str> = fields.iter().filter(|f| keys.contains(&f.key)).map(|f| f.label).collect();
+        return Err(ImportError::MissingColumn(labels.join("、")));
+    }
+    Ok(resolved)
+}
+
+/// 读取一行中各字段的值，错误累积到 `errors`
+struct RowReader<'a> {
+    line: usize,
+    cells: &'a [Cell],
+    columns: &'a HashMap<&'static str, usize>,
+    fields: &'static [ImportField],
+    errors: Vec<ImportRowError>,
+}
+
+impl RowReader<'_> {
+    fn cell(&self, key: &str) -> Option<&Cell> {
+        self.columns.get(key).and_then(|&index| self.cells.get(index)).filter(|cell| !cell.is_empty())
+    }
+
+    /// 去掉首尾空白的文本，未对应或为空时为 `None`
+    fn text(&self, key: &str) -> Option<String> {
+        self.cell(key).map(|cell| cell.to_string().trim().to_string())
+    }
+
+    fn error(&mut self, key: &str, value: &str, message: &str) {
+        let label = self.fields.iter().find(|f| f.key == key).map_or("", |f| f.label);
+        self.errors.push(ImportRowError { row: self.line, field: label.to_string(), value: value.to_string(), message: message.to_string() });
+    }
+
+    /// 非负数，允许千分位和货币符号
+    fn amount(&mut self, key: &str) -> Option<f64> {
+        let value = match self.cell(key)? {
+            Cell::Number(value) => *value,
+            cell => {
+                let text = cell.to_string();
+                let cleaned: String = text.chars().filter(|c| !matches!(c, ',' | '¥' | '￥' | ' ')).collect();
+                match cleaned.parse::<f64>() {
+                    Ok(value) if value.is_finite() => value,
+                    _ => {
+                        self.error(key, &text, "不是有效的数字");
+                        return None;
+                    }
+                }
+            }
+        };
+        if value < 0.0 {
+            self.error(key, &value.to_string(), "不能为负数");
+            return None;
+        }
+        Some(value)
+    }
+
+    fn quantity(&mut self, key: &str) -> Option<i32> {
+        let value = self.amount(key)?;
+        if value.fract() != 0.0 || value > i32::MAX as f64 {
+            self.error(key, &value.to_string(), "应为整数");
+            return None;
+        }
+        Some(value as i32)
+    }
+
+    fn choice(&mut self, key: &str, choices: &[(&str, &str)]) -> Option<String> {
+        let text = self.text(key)?;
+        match choices.iter().find(|(code, label)| code.eq_ignore_ascii_case(&text) || *label == text) {
+            Some((code, _)) => Some(code.to_string()),
+            None => {
+                let allowed: Vec<&str> = choices.iter().map(|(_, label)| *label).collect();
+                self.error(key, &text, &format!("应为 {} 之一", allowed.join("、")));
+                None
+            }
+        }
+    }
+}
+
+/// 同一文件中重复的编码、条码、电话等，记录首次出现的行号
+#[derive(Default)]
+struct SeenKeys(HashMap<(&'static str, String), usize>);
+
+impl SeenKeys {
+    fn check(&mut self, reader: &mut RowReader, key: &'static str, value: &Option<String>) {
+        if let Some(value) = value {
+            if let Some(first) = self.0.get(&(key, value.clone())) {
+                reader.error(key, value, &format!("与第 {} 行重复", first));
+            } else {
+                self.0.insert((key, value.clone()), reader.line);
+            }
+        }
+    }
+}
+
+fn find_id(conn: &Connection, sql: &str, params: impl rusqlite::Params) -> rusqlite::Result<Option<String>> {
+    conn.prepare_cached(sql)?.query_row(params, |row| row.get(0)).optional()
+}
+
+impl Database {
+    /// 导入表格中的客户或产品，见模块说明
+    pub fn import_sheet(&self, entity: ImportEntity, sheet: &Sheet, options: &ImportOptions) -> ImportResult<ImportReport> {
+        let columns = resolve_columns(entity, &sheet.headers, &options.mapping)?;
+        let mut report = ImportReport {
+            entity,
+            dry_run: options.dry_run,
+            columns: entity
+                .fields()
+                .iter()
+                .filter_map(|f| columns.get(f.key).map(|&index| (f.key.to_string(), sheet.headers[index].clone())))
+                .collect(),
+            total: 0,
+            created: 0,
+            updated: 0,
+            skipped: 0,
+            errors: Vec::new(),
+            preview: Vec::new(),
+        };
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        let mut seen = SeenKeys::default();
+        for (index, cells) in sheet.rows.iter().enumerate() {
+            if cells.iter().all(Cell::is_empty) {
+                continue;
+            }
+            report.total += 1;
+            let mut reader = RowReader { line: sheet.line_of(index), cells, columns: &columns, fields: entity.fields(), errors: Vec::new() };
+            let imported = match entity {
+                ImportEntity::Product => import_product(&tx, &mut reader, &mut seen, &now)?.map(|(action, p)| (action, serde_json::json!(p))),
+                ImportEntity::Customer => import_customer(&tx, &mut reader, &mut seen, &now)?.map(|(action, c)| (action, serde_json::json!(c))),
+            };
+            match imported {
+                Some((action, data)) => {
+                    match action {
+                        ImportAction::Create => report.created += 1,
+                        ImportAction::Update => report.updated += 1,
+                    }
+                    if report.preview.len() < PREVIEW_LIMIT {
+                        report.preview.push(ImportPreviewRow { row: reader.line, action, data });
+                    }
+                }
+                None => {
+                    report.skipped += 1;
+                    report.errors.append(&mut reader.errors);
+                }
+            }
+        }
+        if options.dry_run {
+            tx.rollback()?;
+        } else {
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+}
+
+/// 校验并写入一行产品，有错误时不写入并返回 `None`
+fn import_product(conn: &Connection, reader: &mut RowReader, seen: &mut SeenKeys, now: &str) -> ImportResult<Option<(ImportAction, Product)>> {
+    let code = reader.text("code");
+    let barcode = reader.text("barcode");
+    seen.check(reader, "code", &code);
+    seen.check(reader, "barcode", &barcode);
+
+    let by_code = match &code {
+        Some(code) => find_id(conn, "SELECT id FROM products WHERE code = ?1", [code])?,
+        None => None,
+    };
+    let by_barcode = match &barcode {
+        Some(barcode) => find_id(conn, "SELECT id FROM products WHERE barcode = ?1", [barcode])?,
+        None => None,
+    };
+    if let (Some(a), Some(b), Some(barcode)) = (&by_code, &by_barcode, &barcode) {
+        if a != b {
+            reader.error("barcode", barcode, "条码已被其他产品使用");
+        }
+    }
+    let existing = match by_code.or(by_barcode) {
+        Some(id) => Database::load_product(conn, &id)?,
+        None => None,
+    };
+
+    let name = reader.text("name");
+    if name.is_none() && existing.is_none() {
+        reader.error("name", "", "名称不能为空");
+    }
+    let cost_price = reader.amount("costPrice");
+    let sell_price = reader.amount("sellPrice");
+    let min_stock = reader.quantity("minStock");
+    let max_stock = reader.quantity("maxStock");
+    let current_stock = reader.quantity("currentStock");
+    let status = reader.choice("status", PRODUCT_STATUSES);
+
+    let action = if existing.is_some() { ImportAction::Update } else { ImportAction::Create };
+    let mut product = existing.unwrap_or_else(|| {
+        let id = uuid::Uuid::new_v4().to_string();
+        Product {
+            code: format!("P{}", &id[..8]),
+            id,
+            barcode: String::new(),
+            name: String::new(),
+            category: String::new(),
+            unit: "个".to_string(),
+            specification: String::new(),
+            brand: String::new(),
+            cost_price: 0.0,
+            sell_price: 0.0,
+            min_stock: 10,
+            max_stock: 1000,
+            current_stock: current_stock.unwrap_or(0),
+            warehouse_id: String::new(),
+            location: String::new(),
+            status: "active".to_string(),
+            description: String::new(),
+            created_at: now.to_string(),
+            updated_at: now.to_string(),
+            version: 0,
+        }
+    });
+    let texts = [
+        ("code", &mut product.code),
+        ("barcode", &mut product.barcode),
+        ("name", &mut product.name),
+        ("category", &mut product.category),
+        ("unit", &mut product.unit),
+        ("specification", &mut product.specification),
+        ("brand", &mut product.brand),
+        ("location", &mut product.location),
+        ("description", &mut product.description),
+    ];
+    for (key, target) in texts {
+        if let Some(value) = reader.text(key) {
+            *target = value;
+        }
+    }
+    product.cost_price = cost_price.unwrap_or(product.cost_price);
+    product.sell_price = sell_price.unwrap_or(product.sell_price);
+    product.min_stock = min_stock.unwrap_or(product.min_stock);
+    product.max_stock = max_stock.unwrap_or(product.max_stock);
+    product.status = status.unwrap_or(product.status);
+    product.updated_at = now.to_string();
+    if product.min_stock > product.max_stock {
+        reader.error("minStock", &product.min_stock.to_string(), &format!("不能大于最高库存 {}", product.max_stock));
+    }
+
+    if !reader.errors.is_empty() {
+        return Ok(None);
+    }
+    match action {
+        ImportAction::Create => Database::insert_product(conn, &product)?,
+        ImportAction::Update => Database::write_product(conn, &product)?,
+    }
+    Ok(Some((action, product)))
+}
+
+/// 校验并写入一行客户，有错误时不写入并返回 `None`
+fn import_customer(conn: &Connection, reader: &mut RowReader, seen: &mut SeenKeys, now: &str) -> ImportResult<Option<(ImportAction, Customer)>> {
+    let name = reader.text("name");
+    let phone = reader.text("phone");
+    if phone.is_some() {
+        seen.check(reader, "phone", &phone);
+    } else {
+        seen.check(reader, "name", &name);
+    }
+
+    let mut existing_id = match &phone {
+        Some(phone) => find_id(conn, "SELECT id FROM customers WHERE phone = ?1 LIMIT 1", [phone])?,
+        None => None,
+    };
+    // 按名称匹配时，双方都有电话且不同视为同名的另一个客户
+    if existing_id.is_none() {
+        if let Some(name) = &name {
+            existing_id = find_id(
+                conn,
+                "SELECT id FROM customers WHERE name = ?1 AND (?2 = '' OR phone = '') ORDER BY created_at LIMIT 1",
+                [name, phone.as_deref().unwrap_or("")],
+            )?;
+        }
+    }
+    let existing = match existing_id {
+        Some(id) => Database::load_customer(conn, &id)?,
+        None => None,
+    };
+
+    if name.is_none() && existing.is_none() {
+        reader.error("name", "", "名称不能为空");
+    }
+    if let Some(email) = reader.text("email") {
+        if !email.contains('@') {
+            reader.error("email", &email, "邮箱格式不正确");
+        }
+    }
+    let credit_limit = reader.amount("creditLimit");
+    let category = reader.choice("category", CUSTOMER_CATEGORIES);
+    let status = reader.choice("status", CUSTOMER_STATUSES);
+
+    let action = if existing.is_some() { ImportAction::Update } else { ImportAction::Create };
+    let mut customer = existing.unwrap_or_else(|| Customer {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: String::new(),
+        company: String::new(),
+        email: String::new(),
+        phone: String::new(),
+        address: String::new(),
+        category: "regular".to_string(),
+        status: "active".to_string(),
+        contact_person: String::new(),
+        credit_limit: 0.0,
+        balance: 0.0,
+        remark: String::new(),
+        source: String::new(),
+        created_at: now.to_string(),
+        updated_at: now.to_string(),
+        version: 0,
+    });
+    let texts = [
+        ("name", &mut customer.name),
+        ("company", &mut customer.company),
+        ("contactPerson", &mut customer.contact_person),
+        ("phone", &mut customer.phone),
+        ("email", &mut customer.email),
+        ("address", &mut customer.address),
+        ("source", &mut customer.source),
+        ("remark", &mut customer.remark),
+    ];
+    for (key, target) in texts {
+        if let Some(value) = reader.text(key) {
+            *target = value;
+        }
+    }
+    customer.credit_limit = credit_limit.unwrap_or(customer.credit_limit);
+    customer.category = category.unwrap_or(customer.category);
+    customer.status = status.unwrap_or(customer.status);
+    customer.updated_at = now.to_string();
+
+    if !reader.errors.is_empty() {
+        return Ok(None);
+    }
+    match action {
+        ImportAction::Create => Database::insert_customer(conn, &customer)?,
+        ImportAction::Update => Database::write_customer(conn, &customer)?,
+    }
+    Ok(Some((action, customer)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::database as setup;
+
+    fn sheet(headers: &[&str], rows: Vec<Vec<Cell>>) -> Sheet {
+        let mut sheet = Sheet::new("导入", headers);
+        sheet.rows = rows;
+        sheet
+    }
+
+    #[test]
+    fn test_product_import_dry_run_then_upsert() {
+        let (_dir, db) = setup();
+        let headers = ["商品编码", "条形码", "品名", "进价", "零售价", "期初库存", "状态"];
+        let rows = vec![
+            vec!["S-001".into(), Cell::Number(6901234567892.0), "螺丝".into(), "1,200.5".into(), 2000.into(), 50.into(), "正常".into()],
+            vec!["S-002".into(), "6901234567809".into(), "垫片".into(), "abc".into(), 3.into(), Cell::Empty, Cell::Empty],
+            vec![Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty],
+            vec!["S-001".into(), Cell::Empty, "螺丝（重复）".into(), Cell::Empty, Cell::Empty, Cell::Empty, "停售".into()],
+        ];
+        let file = sheet(&headers, rows);
+
+        let dry = db.import_sheet(ImportEntity::Product, &file, &ImportOptions { dry_run: true, ..Default::default() }).unwrap();
+        assert_eq!((dry.total, dry.created, dry.updated, dry.skipped), (3, 1, 0, 2));
+        assert_eq!(dry.columns["costPrice"], "进价");
+        // 第 3 行进价不是数字；第 5 行编码与第 2 行重复、状态不在取值范围内
+        let errors: Vec<(usize, &str)> = dry.errors.iter().map(|e| (e.row, e.field.as_str())).collect();
+        assert_eq!(errors, vec![(3, "成本价"), (5, "编码"), (5, "状态")]);
+        assert_eq!(db.count_products().unwrap(), 0);
+
+        db.import_sheet(ImportEntity::Product, &file, &ImportOptions::default()).unwrap();
+        let imported = db.get_product_by_barcode("6901234567892").unwrap().unwrap();
+        assert_eq!((imported.code.as_str(), imported.cost_price, imported.current_stock), ("S-001", 1200.5, 50));
+
+        // 按条码匹配更新，空单元格和库存数量不覆盖原值
+        let update = sheet(&["条码", "售价", "库存", "规格"], vec![vec!["6901234567892".into(), 2100.into(), 999.into(), Cell::Empty]]);
+        let report = db.import_sheet(ImportEntity::Product, &update, &ImportOptions::default()).unwrap();
+        assert_eq!((report.created, report.updated), (0, 1));
+        let updated = db.get_product(&imported.id).unwrap().unwrap();
+        assert_eq!((updated.name.as_str(), updated.sell_price, updated.current_stock), ("螺丝", 2100.0, 50));
+    }
+
+    #[test]
+    fn test_customer_import_with_mapping() {
+        let (_dir, db) = setup();
+        let file = sheet(
+            &["客户", "手机", "Mail", "等级"],
+            vec![
+                vec!["张三".into(), "13800000000".into(), "zs@example.com".into(), "VIP客户".into()],
+                vec!["李四".into(), Cell::Empty, "lisi".into(), "regular".into()],
+            ],
+        );
+        let missing = ImportOptions { mapping: HashMap::from([("email".to_string(), "邮件".to_string())]), ..Default::default() };
+        assert!(matches!(db.import_sheet(ImportEntity::Customer, &file, &missing), Err(ImportError::UnknownColumn(_))));
+
+        let mapping = HashMap::from([("email".to_string(), "Mail".to_string()), ("category".to_string(), "等级".to_string())]);
+        let options = ImportOptions { mapping, dry_run: false };
+        let report = db.import_sheet(ImportEntity::Customer, &file, &options).unwrap();
+        assert_eq!((report.created, report.skipped), (1, 1));
+        assert_eq!(report.errors[0].field, "邮箱");
+        let customers = db.list_customers().unwrap();
+        assert_eq!((customers[0].category.as_str(), customers[0].email.as_str()), ("vip", "zs@example.com"));
+
+        // 再次导入按电话匹配，只更新不新建
+        let report = db.import_sheet(ImportEntity::Customer, &file, &options).unwrap();
+        assert_eq!((report.created, report.updated), (0, 1));
+        assert_eq!(db.count_customers().unwrap(), 1);
+    }
+}
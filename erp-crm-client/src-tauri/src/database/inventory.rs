@@ -0,0 +1,360 @@
+//! 库存规则 - 库存变动、订单预留与超卖策略
+//!
+//! 可用库存 = 当前库存 - 调拨在途 - 有效预留；预留记在订单的发货仓库，仓库的可用库存 = 仓库结存 - 该仓库的有效预留。
+//! 预留只保存在本机（不参与数据同步），
+//! 实际的出入库通过库存流水记录并同步。每次变动同时更新所在仓库、库位的结存（见 `warehouses`）和批次结存（见 `batches`）。
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use thiserror::Error;
+
+use super::batches::{adjust_batch, allocate_fefo, check_batch};
+use super::transfers::in_transit_quantity;
+use super::warehouses::{adjust_level, check_level, check_warehouse, level_quantity, resolve_place};
+use super::{record_change, set_state, Database, StockBatch, StockRecord};
+
+/// 超卖策略的设置项（保存在 sync_state 表）
+pub const OVERSELL_POLICY_KEY: &str = "oversell_policy";
+
+#[derive(Error, Debug)]
+pub enum StockError {
+    #[error("产品不存在: {0}")]
+    ProductNotFound(String),
+    #[error("{product} 可用库存不足：需要 {requested}，可用 {available}")]
+    InsufficientStock { product: String, requested: i32, available: i32 },
+    #[error("仓库不存在: {0}")]
+    WarehouseNotFound(String),
+    #[error("仓库 {0} 已停用")]
+    WarehouseInactive(String),
+    #[error("{product} 在 {warehouse}{location} 的库存不足：需要 {requested}，结存 {available}")]
+    InsufficientWarehouseStock { product: String, warehouse: String, location: String, requested: i32, available: i32 },
+    #[error("{product} 批次 {batch_no} 库存不足：需要 {requested}，结存 {available}")]
+    InsufficientBatchStock { product: String, batch_no: String, requested: i32, available: i32 },
+    #[error("{product} 未过期的库存不足：需要 {requested}，另有 {expired} 已过期，请先处理过期批次")]
+    ExpiredStock { product: String, requested: i32, expired: i32 },
+    #[error("{record_type} 的数量无效: {quantity}")]
+    InvalidQuantity { record_type: String, quantity: i32 },
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type StockResult<T> = std::result::Result<T, StockError>;
+
+/// 超卖策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversellPolicy {
+    /// 可用库存不足时拒绝预留和出库（默认）
+    Reject,
+    /// 允许预留超过可用库存、出库后库存为负
+    Allow,
+}
+
+impl OversellPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OversellPolicy::Reject => "reject",
+            OversellPolicy::Allow => "allow",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "reject" => Some(OversellPolicy::Reject),
+            "allow" => Some(OversellPolicy::Allow),
+            _ => None,
+        }
+    }
+}
+
+/// 产品的库存占用情况
+#[derive(Debug, Clone, Serialize)]
+pub struct StockAvailability {
+    pub product_id: String,
+    pub current_stock: i32,
+    /// 调拨在途，计入当前库存但不能预留
+    pub in_transit: i32,
+    pub reserved: i32,
+    pub available: i32,
+}
+
+/// 一次库存变动
+pub struct StockMovement<'a> {
+    pub product_id: &'a str,
+    /// 正数入库，负数出库
+    pub quantity_change: i32,
+    pub record_type: &'a str,
+    pub unit_price: f64,
+    pub remark: &'a str,
+    pub batch: &'a StockBatch,
+    /// 关联订单，手工出入库为空字符串
+    pub order_id: &'a str,
+    /// 仓库与库位，为空时用产品的所属仓库和库位
+    pub warehouse_id: &'a str,
+    pub location: &'a str,
+}
+
+pub(super) fn create_inventory_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_reservations (
+            id TEXT PRIMARY KEY,
+            order_id TEXT NOT NULL,
+            order_item_id TEXT NOT NULL,
+            product_id TEXT NOT NULL,
+            quantity INTEGER NOT NULL,
+            status TEXT NOT NULL DEFAULT 'active',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_reservations_product ON stock_reservations(product_id, status)", [])?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_reservations_order ON stock_reservations(order_id)", [])?;
+    super::ensure_column(conn, "stock_records", "order_id", "TEXT DEFAULT ''")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_records_order ON stock_records(order_id)", [])?;
+    Ok(())
+}
+
+/// 订单的发货仓库；已有的预留记到产品的所属仓库
+pub(super) fn add_order_warehouse(conn: &Connection) -> SqliteResult<()> {
+    super::ensure_column(conn, "orders", "warehouse_id", "TEXT DEFAULT ''")?;
+    super::ensure_column(conn, "stock_reservations", "warehouse_id", "TEXT DEFAULT ''")?;
+    conn.execute(
+        "UPDATE stock_reservations
+         SET warehouse_id = COALESCE((SELECT NULLIF(p.warehouse_id, '') FROM products p WHERE p.id = stock_reservations.product_id), ?1)
+         WHERE warehouse_id = ''",
+        [super::warehouses::DEFAULT_WAREHOUSE_ID],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_reservations_warehouse ON stock_reservations(product_id, warehouse_id, status)", [])?;
+    Ok(())
+}
+
+pub(super) fn oversell_policy(conn: &Connection) -> SqliteResult<OversellPolicy> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM sync_state WHERE key = ?1", [OVERSELL_POLICY_KEY], |row| row.get(0))
+        .optional()?;
+    Ok(value.as_deref().and_then(OversellPolicy::parse).unwrap_or(OversellPolicy::Reject))
+}
+
+/// 产品名称与当前库存
+fn product_stock(conn: &Connection, product_id: &str) -> StockResult<(String, i32)> {
+    conn.query_row("SELECT name, current_stock FROM products WHERE id = ?1", [product_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()?
+        .ok_or_else(|| StockError::ProductNotFound(product_id.to_string()))
+}
+
+/// 产品的有效预留数量，`exclude_order` 的预留不计入（订单出库时自己的预留可以使用）
+pub(super) fn reserved_quantity(conn: &Connection, product_id: &str, exclude_order: &str) -> SqliteResult<i32> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM stock_reservations WHERE product_id = ?1 AND status = 'active' AND order_id != ?2",
+        [product_id, exclude_order],
+        |row| row.get(0),
+    )
+}
+
+/// 产品在某个仓库的有效预留数量，`exclude_order` 的预留不计入
+fn reserved_in_warehouse(conn: &Connection, product_id: &str, warehouse_id: &str, exclude_order: &str) -> SqliteResult<i32> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM stock_reservations
+         WHERE product_id = ?1 AND warehouse_id = ?2 AND status = 'active' AND order_id != ?3",
+        [product_id, warehouse_id, exclude_order],
+        |row| row.get(0),
+    )
+}
+
+/// 按超卖策略检查仓库的可用库存（各库位结存合计减去该仓库的有效预留）
+pub(super) fn check_warehouse_available(conn: &Connection, product_id: &str, warehouse_id: &str, requested: i32, exclude_order: &str) -> StockResult<()> {
+    if requested <= 0 || oversell_policy(conn)? == OversellPolicy::Allow {
+        return Ok(());
+    }
+    let warehouse = check_warehouse(conn, warehouse_id)?;
+    let on_hand: i32 = conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM stock_levels WHERE product_id = ?1 AND warehouse_id = ?2",
+        [product_id, warehouse_id],
+        |row| row.get(0),
+    )?;
+    let available = on_hand - reserved_in_warehouse(conn, product_id, warehouse_id, exclude_order)?;
+    if requested > available {
+        let (product, _) = product_stock(conn, product_id)?;
+        return Err(StockError::InsufficientWarehouseStock {
+            product, warehouse: warehouse.name, location: String::new(), requested, available: available.max(0),
+        });
+    }
+    Ok(())
+}
+
+/// 按超卖策略检查可用库存
+pub(super) fn check_available(conn: &Connection, product_id: &str, requested: i32, exclude_order: &str) -> StockResult<()> {
+    if requested <= 0 || oversell_policy(conn)? == OversellPolicy::Allow {
+        return Ok(());
+    }
+    let (name, current) = product_stock(conn, product_id)?;
+    let available = current - in_transit_quantity(conn, product_id)? - reserved_quantity(conn, product_id, exclude_order)?;
+    if requested > available {
+        return Err(StockError::InsufficientStock { product: name, requested, available: available.max(0) });
+    }
+    Ok(())
+}
+
+/// 在事务中执行库存变动：更新产品库存、仓库结存和批次结存并写入库存流水
+///
+/// 出库未指定批次时按先到期先出拆分到各批次，每个批次一条流水，返回全部流水。
+/// 调拨（`transfer_out` / `transfer_in`）只改变仓库结存，不改变产品的合计库存。
+pub(super) fn apply_movement(conn: &Connection, movement: &StockMovement) -> StockResult<Vec<StockRecord>> {
+    // 数量为 0 或方向与流水类型不符（如负数入库）时拒绝，避免写出与类型相反的流水
+    let valid = match movement.record_type {
+        "in" | "return" | "transfer_in" => movement.quantity_change > 0,
+        "out" | "transfer_out" => movement.quantity_change < 0,
+        _ => movement.quantity_change != 0,
+    };
+    if !valid {
+        return Err(StockError::InvalidQuantity { record_type: movement.record_type.to_string(), quantity: movement.quantity_change });
+    }
+    let is_transfer = matches!(movement.record_type, "transfer_out" | "transfer_in");
+    let (warehouse_id, location) = resolve_place(conn, movement.product_id, movement.warehouse_id, movement.location)?;
+    let outbound = movement.quantity_change < 0;
+    if outbound && movement.record_type != "adjust" {
+        // 出库不能占用其他订单已预留的库存（合计与所在仓库都检查），调拨出库不减少合计库存所以不受预留限制
+        if !is_transfer {
+            check_available(conn, movement.product_id, -movement.quantity_change, movement.order_id)?;
+            check_warehouse_available(conn, movement.product_id, &warehouse_id, -movement.quantity_change, movement.order_id)?;
+        }
+        check_level(conn, movement.product_id, &warehouse_id, &location, -movement.quantity_change)?;
+        if !movement.batch.batch_no.is_empty() {
+            check_batch(conn, movement.product_id, &warehouse_id, &location, &movement.batch.batch_no, -movement.quantity_change)?;
+        }
+    }
+    let parts = if outbound && movement.batch.batch_no.is_empty() {
+        // 销售出库不分配已过期的批次，报损等操作需要指定批次
+        let skip_expired = movement.record_type == "out";
+        allocate_fefo(conn, movement.product_id, &warehouse_id, &location, -movement.quantity_change, skip_expired)?
+    } else {
+        vec![(movement.batch.clone(), movement.quantity_change.abs())]
+    };
+    let sign = movement.quantity_change.signum();
+    parts
+        .iter()
+        .map(|(batch, quantity)| write_movement(conn, movement, &warehouse_id, &location, batch, sign * quantity, is_transfer))
+        .collect()
+}
+
+/// 写入一条（单个批次的）库存变动
+fn write_movement(conn: &Connection, movement: &StockMovement, warehouse_id: &str, location: &str, batch: &StockBatch, quantity_change: i32, is_transfer: bool) -> StockResult<StockRecord> {
+    let (_, current) = product_stock(conn, movement.product_id)?;
+    let new_stock = if is_transfer { current } else { current + quantity_change };
+    let now = Utc::now().to_rfc3339();
+
+    if !is_transfer {
+        conn.execute("UPDATE products SET current_stock = ?1, updated_at = ?2 WHERE id = ?3", params![new_stock, now, movement.product_id])?;
+    }
+    adjust_level(conn, movement.product_id, warehouse_id, location, quantity_change)?;
+    if !batch.batch_no.is_empty() {
+        adjust_batch(conn, movement.product_id, warehouse_id, location, batch, quantity_change)?;
+    }
+
+    let quantity = quantity_change.abs();
+    let record = StockRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        product_id: movement.product_id.to_string(),
+        record_type: movement.record_type.to_string(),
+        quantity,
+        before_quantity: current,
+        after_quantity: new_stock,
+        unit_price: movement.unit_price,
+        total_amount: (quantity as f64) * movement.unit_price,
+        remark: movement.remark.to_string(),
+        created_at: now,
+        batch_no: batch.batch_no.clone(),
+        expiry_date: batch.expiry_date.clone(),
+        order_id: movement.order_id.to_string(),
+        warehouse_id: warehouse_id.to_string(),
+        location: location.to_string(),
+        production_date: batch.production_date.clone(),
+    };
+    conn.execute(
+        "INSERT INTO stock_records (id, product_id, record_type, quantity, before_quantity, after_quantity, unit_price, total_amount, remark, created_at, batch_no, expiry_date, order_id, warehouse_id, location, production_date)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            record.id, record.product_id, record.record_type, record.quantity, record.before_quantity,
+            record.after_quantity, record.unit_price, record.total_amount, record.remark, record.created_at,
+            record.batch_no, record.expiry_date, record.order_id, record.warehouse_id, record.location,
+            record.production_date
+        ],
+    )?;
+    record_change(conn, "stock_record", &record.id, "create", &record)?;
+    Ok(record)
+}
+
+/// 在订单的发货仓库为明细预留库存
+pub(super) fn reserve(conn: &Connection, order_id: &str, order_item_id: &str, product_id: &str, warehouse_id: &str, quantity: i32) -> SqliteResult<()> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO stock_reservations (id, order_id, order_item_id, product_id, quantity, status, created_at, updated_at, warehouse_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'active', ?6, ?6, ?7)",
+        params![uuid::Uuid::new_v4().to_string(), order_id, order_item_id, product_id, quantity, now, warehouse_id],
+    )?;
+    Ok(())
+}
+
+/// 明细发货后扣减对应预留，扣完即为已消耗
+pub(super) fn consume_reservation(conn: &Connection, order_item_id: &str, quantity: i32) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE stock_reservations
+         SET quantity = MAX(quantity - ?1, 0),
+             status = CASE WHEN quantity <= ?1 THEN 'consumed' ELSE status END,
+             updated_at = ?2
+         WHERE order_item_id = ?3 AND status = 'active'",
+        params![quantity, Utc::now().to_rfc3339(), order_item_id],
+    )?;
+    Ok(())
+}
+
+/// 结束订单的有效预留：`released` 取消释放，`consumed` 已出库
+pub(super) fn close_reservations(conn: &Connection, order_id: &str, status: &str) -> SqliteResult<usize> {
+    conn.execute(
+        "UPDATE stock_reservations SET status = ?1, updated_at = ?2 WHERE order_id = ?3 AND status = 'active'",
+        params![status, Utc::now().to_rfc3339(), order_id],
+    )
+}
+
+impl Database {
+    pub fn get_oversell_policy(&self) -> SqliteResult<OversellPolicy> {
+        oversell_policy(&self.conn.lock().unwrap())
+    }
+
+    pub fn set_oversell_policy(&self, policy: OversellPolicy) -> SqliteResult<()> {
+        set_state(&self.conn.lock().unwrap(), OVERSELL_POLICY_KEY, policy.as_str())
+    }
+
+    /// 产品的当前库存、调拨在途、有效预留与可用库存
+    pub fn get_stock_availability(&self, product_id: &str) -> StockResult<StockAvailability> {
+        let conn = self.conn.lock().unwrap();
+        let (_, current_stock) = product_stock(&conn, product_id)?;
+        let in_transit = in_transit_quantity(&conn, product_id)?;
+        let reserved = reserved_quantity(&conn, product_id, "")?;
+        Ok(StockAvailability {
+            product_id: product_id.to_string(), current_stock, in_transit, reserved, available: current_stock - in_transit - reserved,
+        })
+    }
+
+    /// 盘点调整：把仓库库位的结存调整为实际数量，仓库为空时调整产品所属仓库；盘亏按先到期先出扣减批次
+    ///
+    /// 结存与实际数量一致时不写流水，返回空列表。
+    pub fn adjust_stock(&self, product_id: &str, warehouse_id: &str, location: &str, actual_quantity: i32, remark: &str) -> StockResult<Vec<StockRecord>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let (warehouse_id, location) = resolve_place(&tx, product_id, warehouse_id, location)?;
+        let diff = actual_quantity - level_quantity(&tx, product_id, &warehouse_id, &location)?;
+        // 结存已等于实际数量时不写空的调整流水
+        if diff == 0 {
+            return Ok(Vec::new());
+        }
+        let records = apply_movement(&tx, &StockMovement {
+            product_id, quantity_change: diff, record_type: "adjust", unit_price: 0.0, remark,
+            batch: &StockBatch::default(), order_id: "", warehouse_id: &warehouse_id, location: &location,
+        })?;
+        tx.commit()?;
+        Ok(records)
+    }
+}
@@ -0,0 +1,240 @@
+//! 表结构迁移 - 以 `PRAGMA user_version` 记录版本，按顺序执行编译进程序的升级迁移
+//!
+//! 每个迁移在单独的事务中执行并同时写入新版本号，失败时整体回滚，数据库停留在上一个版本。
+//! 有待执行的迁移时先把数据库文件备份到同目录的 `backups/` 下；数据库版本比程序新时拒绝打开。
+//! 新增字段或表时在 `MIGRATIONS` 末尾追加一项，不要修改已发布的迁移。
+
+use chrono::Utc;
+use rusqlite::{Connection, Result as SqliteResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::{batches, ensure_column, inventory, order_status, payments, stock_checks, warehouses, Database};
+
+/// 保留的迁移前备份数量
+const BACKUP_KEEP: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("数据库版本 {found} 高于当前程序支持的版本 {supported}，请升级程序后再打开")]
+    NewerSchema { found: u32, supported: u32 },
+    #[error("迁移前备份数据库失败: {0}")]
+    Backup(#[source] std::io::Error),
+    #[error("数据库迁移 {version}（{description}）失败: {source}")]
+    Failed { version: u32, description: &'static str, source: rusqlite::Error },
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type MigrationResult<T> = std::result::Result<T, MigrationError>;
+
+/// 一次升级迁移，`version` 从 1 开始连续编号
+pub(super) struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub up: fn(&Connection) -> SqliteResult<()>,
+}
+
+pub(super) const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "初始表结构", up: Database::create_base_tables },
+    Migration { version: 2, description: "库存流水批号与有效期", up: add_stock_record_batch },
+    Migration { version: 3, description: "订单库存预留", up: inventory::create_inventory_tables },
+    Migration { version: 4, description: "订单状态历史与分批发货", up: order_status::create_status_history_table },
+    Migration { version: 5, description: "收款记录", up: payments::create_payments_table },
+    Migration { version: 6, description: "多仓库库存与调拨", up: warehouses::create_warehouse_tables },
+    Migration { version: 7, description: "批次结存与生产日期", up: batches::create_batch_tables },
+    Migration { version: 8, description: "库存盘点单", up: stock_checks::create_stock_check_tables },
+    Migration { version: 9, description: "订单发货仓库与按仓库预留", up: inventory::add_order_warehouse },
+];
+
+/// 当前程序的表结构版本
+pub const SCHEMA_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+fn add_stock_record_batch(conn: &Connection) -> SqliteResult<()> {
+    ensure_column(conn, "stock_records", "batch_no", "TEXT DEFAULT ''")?;
+    ensure_column(conn, "stock_records", "expiry_date", "TEXT DEFAULT ''")
+}
+
+/// 升级到最新版本，返回升级前的版本号
+pub(super) fn migrate(conn: &mut Connection, db_path: &Path) -> MigrationResult<u32> {
+    run_migrations(conn, db_path, MIGRATIONS)
+}
+
+fn run_migrations(conn: &mut Connection, db_path: &Path, migrations: &[Migration]) -> MigrationResult<u32> {
+    let current: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let supported = migrations.last().map_or(0, |m| m.version);
+    if current > supported {
+        return Err(MigrationError::NewerSchema { found: current, supported });
+    }
+    let pending: Vec<&Migration> = migrations.iter().filter(|m| m.version > current).collect();
+    if pending.is_empty() {
+        return Ok(current);
+    }
+
+    // 新建的空库不需要备份
+    let has_tables: bool = conn.query_row("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table'", [], |row| row.get(0))?;
+    if has_tables {
+        let backup = backup_database(db_path, current).map_err(MigrationError::Backup)?;
+        log::info!("数据库迁移前已备份到 {}", backup.display());
+    }
+
+    for migration in pending {
+        let failed = |source| MigrationError::Failed { version: migration.version, description: migration.description, source };
+        let tx = conn.transaction().map_err(failed)?;
+        (migration.up)(&tx).map_err(failed)?;
+        tx.pragma_update(None, "user_version", migration.version).map_err(failed)?;
+        tx.commit().map_err(failed)?;
+        log::info!("数据库已迁移到版本 {}: {}", migration.version, migration.description);
+    }
+    Ok(current)
+}
+
+/// 复制数据库文件到 `backups/<文件名>.v<版本>-<时间>.bak`（已加密的库备份同样是加密的），只保留最近几份
+fn backup_database(db_path: &Path, version: u32) -> std::io::Result<PathBuf> {
+    let dir = db_path.parent().unwrap_or_else(|| Path::new(".")).join("backups");
+    fs::create_dir_all(&dir)?;
+    let file_name = db_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "database".to_string());
+    let stamp = Utc::now().format("%Y%m%d%H%M%S%6f").to_string();
+    let mut backup = dir.join(format!("{}.v{}-{}.bak", file_name, version, stamp));
+    let mut n = 1;
+    while backup.exists() {
+        backup = dir.join(format!("{}.v{}-{}_{}.bak", file_name, version, stamp, n));
+        n += 1;
+    }
+    fs::copy(db_path, &backup)?;
+
+    // 文件名中的时间戳保证按名称排序即按时间排序
+    let prefix = format!("{}.v", file_name);
+    let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".bak"))
+        })
+        .collect();
+    backups.sort_by_key(|p| p.file_name().and_then(|n| n.to_str()).and_then(|n| n.rsplit('-').next()).map(str::to_string));
+    while backups.len() > BACKUP_KEEP {
+        let oldest = backups.remove(0);
+        if let Err(e) = fs::remove_file(&oldest) {
+            log::warn!("删除旧备份 {} 失败: {}", oldest.display(), e);
+        }
+    }
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> u32 {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+    }
+
+    fn backups(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir.join("backups")).map(|d| d.map(|e| e.unwrap().path()).collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_fresh_and_legacy_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fresh.db");
+        let db = Database::new(path.clone()).unwrap();
+        assert_eq!(user_version(&db.conn.lock().unwrap()), SCHEMA_VERSION);
+        assert!(backups(dir.path()).is_empty());
+        drop(db);
+
+        // 引入版本号之前的旧库：user_version 为 0，缺少后来加的列
+        let legacy = dir.path().join("legacy.db");
+        {
+            let conn = Connection::open(&legacy).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE customers (id TEXT PRIMARY KEY, name TEXT NOT NULL, company TEXT DEFAULT '', email TEXT DEFAULT '',
+                    phone TEXT DEFAULT '', address TEXT DEFAULT '', category TEXT DEFAULT 'regular', status TEXT DEFAULT 'active',
+                    contact_person TEXT DEFAULT '', credit_limit REAL DEFAULT 0, balance REAL DEFAULT 0, remark TEXT DEFAULT '',
+                    created_at TEXT NOT NULL, updated_at TEXT NOT NULL);
+                 CREATE TABLE stock_records (id TEXT PRIMARY KEY, product_id TEXT NOT NULL, record_type TEXT NOT NULL,
+                    quantity INTEGER NOT NULL, before_quantity INTEGER DEFAULT 0, after_quantity INTEGER DEFAULT 0,
+                    unit_price REAL DEFAULT 0, total_amount REAL DEFAULT 0, remark TEXT DEFAULT '', created_at TEXT NOT NULL);
+                 INSERT INTO customers (id, name, created_at, updated_at) VALUES ('c1', '老客户', 'x', 'x');",
+            )
+            .unwrap();
+        }
+        let db = Database::new(legacy.clone()).unwrap();
+        let customer = db.get_customer("c1").unwrap().unwrap();
+        assert_eq!(customer.name, "老客户");
+        assert_eq!(customer.source, "");
+        {
+            let conn = db.conn.lock().unwrap();
+            assert_eq!(user_version(&conn), SCHEMA_VERSION);
+            let batch: i64 = conn
+                .query_row("SELECT COUNT(*) FROM pragma_table_info('stock_records') WHERE name = 'batch_no'", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(batch, 1);
+        }
+        drop(db);
+
+        let saved = backups(dir.path());
+        assert_eq!(saved.len(), 1);
+        let name = saved[0].file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("legacy.db.v0-"), "{}", name);
+        let backup = Connection::open(&saved[0]).unwrap();
+        assert_eq!(user_version(&backup), 0);
+        let count: i64 = backup.query_row("SELECT COUNT(*) FROM customers", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+
+        // 已是最新版本时不再备份
+        Database::new(legacy).unwrap();
+        assert_eq!(backups(dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn test_refuses_newer_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("newer.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(&format!("CREATE TABLE future (id TEXT); PRAGMA user_version = {};", SCHEMA_VERSION + 1)).unwrap();
+        }
+        match Database::new(path) {
+            Err(MigrationError::NewerSchema { found, supported }) => {
+                assert_eq!(found, SCHEMA_VERSION + 1);
+                assert_eq!(supported, SCHEMA_VERSION);
+            }
+            other => panic!("应拒绝打开: {:?}", other.err()),
+        }
+        assert!(backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        fn create_items(conn: &Connection) -> SqliteResult<()> {
+            conn.execute("CREATE TABLE items (id TEXT PRIMARY KEY)", []).map(|_| ())
+        }
+        fn broken(conn: &Connection) -> SqliteResult<()> {
+            conn.execute("ALTER TABLE items ADD COLUMN qty INTEGER", [])?;
+            conn.execute("ALTER TABLE missing ADD COLUMN qty INTEGER", []).map(|_| ())
+        }
+        let migrations = [
+            Migration { version: 1, description: "items", up: create_items },
+            Migration { version: 2, description: "broken", up: broken },
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.db");
+        let mut conn = Connection::open(&path).unwrap();
+        let err = run_migrations(&mut conn, &path, &migrations).unwrap_err();
+        assert!(matches!(err, MigrationError::Failed { version: 2, .. }), "{}", err);
+
+        // 迁移 1 已提交，迁移 2 的部分修改被回滚
+        assert_eq!(user_version(&conn), 1);
+        let qty: i64 = conn
+            .query_row("SELECT COUNT(*) FROM pragma_table_info('items') WHERE name = 'qty'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(qty, 0);
+
+        for _ in 0..BACKUP_KEEP + 2 {
+            backup_database(&path, 1).unwrap();
+        }
+        assert_eq!(backups(dir.path()).len(), BACKUP_KEEP);
+    }
+}
@@ -2,8 +2,49 @@
 
 #![allow(dead_code)]
 
-use rusqlite::{Connection, Result as SqliteResult, params};
+mod access;
+mod batches;
+mod conflict;
+mod encryption;
+mod exports;
+mod fulfillment;
+mod imports;
+mod inventory;
+mod order_status;
+mod migrations;
+mod outbox;
+mod payments;
+mod reports;
+mod stock_checks;
+mod transfers;
+mod users;
+mod valuation;
+mod warehouses;
+#[cfg(test)]
+mod test_support;
+
+pub use access::*;
+pub use batches::*;
+pub use conflict::*;
+pub use encryption::*;
+pub use exports::*;
+pub use fulfillment::*;
+pub use imports::*;
+pub use order_status::{OrderStatus, OrderStatusChange};
+pub use inventory::{OversellPolicy, StockAvailability, StockError, StockMovement, StockResult, OVERSELL_POLICY_KEY};
+pub use migrations::{MigrationError, MigrationResult, SCHEMA_VERSION};
+pub use outbox::*;
+pub use payments::*;
+pub use reports::*;
+pub use stock_checks::*;
+pub use transfers::*;
+pub use users::*;
+pub use valuation::*;
+pub use warehouses::*;
+
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult, params};
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
@@ -11,6 +52,12 @@ use serde::{Deserialize, Serialize};
 /// 数据库管理器
 pub struct Database {
     pub conn: Arc<Mutex<Connection>>,
+    path: PathBuf,
+    /// 数据库密钥，未加密时为 `None`
+    key: Mutex<Option<DatabaseKey>>,
+    /// 已加密但尚未解锁
+    locked: AtomicBool,
+    sales_reports: reports::SalesReportCache,
 }
 
 /// 客户数据结构
@@ -31,6 +78,9 @@ pub struct Customer {
     pub source: String,
     pub created_at: String,
     pub updated_at: String,
+    /// 服务器版本号（本地修改不递增，上传时作为修改基准）
+    #[serde(default)]
+    pub version: i64,
 }
 
 /// 产品数据结构
@@ -55,6 +105,8 @@ pub struct Product {
     pub description: String,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub version: i64,
 }
 
 /// 订单数据结构
@@ -73,11 +125,16 @@ pub struct Order {
     pub paid_amount: f64,
     pub delivery_address: String,
     pub delivery_date: String,
+    /// 发货或收货的仓库，为空时按各产品的所属仓库
+    #[serde(default)]
+    pub warehouse_id: String,
     pub remark: String,
     pub operator_id: String,
     pub operator_name: String,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub version: i64,
 }
 
 /// 订单明细
@@ -94,21 +151,64 @@ pub struct OrderItem {
     pub discount: f64,
     pub amount: f64,
     pub remark: String,
+    /// 已发货数量（销售订单分批发货）
+    #[serde(default)]
+    pub shipped_quantity: i32,
 }
 
-fn ensure_customers_source_column(conn: &Connection) -> SqliteResult<()> {
-    // 检查 customers 是否已有 source 列
-    let mut stmt = conn.prepare("PRAGMA table_info(customers)")?;
+/// 库存变动记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockRecord {
+    pub id: String,
+    pub product_id: String,
+    pub record_type: String,
+    pub quantity: i32,
+    pub before_quantity: i32,
+    pub after_quantity: i32,
+    pub unit_price: f64,
+    pub total_amount: f64,
+    pub remark: String,
+    pub created_at: String,
+    /// 批号、有效期与生产日期（来自 GS1 条码或手工录入），没有时为空字符串
+    #[serde(default)]
+    pub batch_no: String,
+    #[serde(default)]
+    pub expiry_date: String,
+    /// 订单出入库时关联的订单，手工出入库为空字符串
+    #[serde(default)]
+    pub order_id: String,
+    /// 出入库的仓库与库位，旧版本的流水为空字符串
+    #[serde(default)]
+    pub warehouse_id: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub production_date: String,
+}
+
+/// 库存变动的批次信息
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StockBatch {
+    pub batch_no: String,
+    /// 生产日期 `YYYY-MM-DD`
+    #[serde(default)]
+    pub production_date: String,
+    /// `YYYY-MM-DD`
+    pub expiry_date: String,
+}
+
+/// 为旧库补充列（SQLite 不支持 ADD COLUMN IF NOT EXISTS，所以先检查再执行）
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> SqliteResult<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
     let mut rows = stmt.query([])?;
     while let Some(row) = rows.next()? {
         let col_name: String = row.get(1)?; // 第2列是列名
-        if col_name == "source" {
+        if col_name == column {
             return Ok(());
         }
     }
-    // SQLite 不支持 ADD COLUMN IF NOT EXISTS，所以先检查再执行
     conn.execute(
-        "ALTER TABLE customers ADD COLUMN source TEXT NOT NULL DEFAULT ''",
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
         [],
     )?;
     Ok(())
@@ -116,19 +216,31 @@ fn ensure_customers_source_column(conn: &Connection) -> SqliteResult<()> {
 
 impl Database {
     /// 创建新的数据库连接
-    pub fn new(db_path: PathBuf) -> SqliteResult<Self> {
-        let conn = Connection::open(&db_path)?;
+    pub fn new(db_path: PathBuf) -> MigrationResult<Self> {
+        // 已加密的数据库在登录取得密钥前保持锁定，先用内存连接占位
+        let locked = is_encrypted_file(&db_path);
+        let conn = if locked { Connection::open_in_memory()? } else { Connection::open(&db_path)? };
         let db = Self {
             conn: Arc::new(Mutex::new(conn)),
+            path: db_path,
+            key: Mutex::new(None),
+            locked: AtomicBool::new(locked),
+            sales_reports: Default::default(),
         };
-        db.init_tables()?;
+        if !locked {
+            db.migrate()?;
+        }
         Ok(db)
     }
 
-    /// 初始化数据库表
-    fn init_tables(&self) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
-        
+    /// 把表结构升级到当前版本
+    fn migrate(&self) -> MigrationResult<u32> {
+        let mut conn = self.conn.lock().unwrap();
+        migrations::migrate(&mut conn, &self.path)
+    }
+
+    /// 初始表结构（迁移 1）：使用 IF NOT EXISTS 并补列，兼容引入版本号之前创建的旧库
+    fn create_base_tables(conn: &Connection) -> SqliteResult<()> {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS customers (
                 id TEXT PRIMARY KEY,
@@ -145,13 +257,14 @@ impl Database {
                 remark TEXT DEFAULT '',
                 source TEXT DEFAULT '',
                 created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
+                updated_at TEXT NOT NULL,
+                version INTEGER DEFAULT 0
             )",
             [],
         )?;
 
         // 兼容旧库：旧 customers 表可能没有 source 列（IF NOT EXISTS 不会补列）
-        ensure_customers_source_column(&conn)?;
+        ensure_column(conn, "customers", "source", "TEXT NOT NULL DEFAULT ''")?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS products (
@@ -173,7 +286,8 @@ impl Database {
                 status TEXT DEFAULT 'active',
                 description TEXT DEFAULT '',
                 created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
+                updated_at TEXT NOT NULL,
+                version INTEGER DEFAULT 0
             )",
             [],
         )?;
@@ -197,11 +311,17 @@ impl Database {
                 operator_id TEXT DEFAULT '',
                 operator_name TEXT DEFAULT '',
                 created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
+                updated_at TEXT NOT NULL,
+                version INTEGER DEFAULT 0
             )",
             [],
         )?;
 
+        // 兼容旧库：补充服务器版本号列
+        for table in ["customers", "products", "orders"] {
+            ensure_column(conn, table, "version", "INTEGER DEFAULT 0")?;
+        }
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS order_items (
                 id TEXT PRIMARY KEY,
@@ -235,9 +355,68 @@ impl Database {
             [],
         )?;
 
+        // 同步队列：本地每次写入都会在同一事务中追加一条变更记录
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity_type TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                retry_count INTEGER DEFAULT 0,
+                last_error TEXT,
+                next_attempt_at INTEGER DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // 同步状态（游标、上次同步时间、设备 ID 等）
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_state (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        users::create_users_table(conn)?;
+        access::create_access_tables(conn)?;
+
+        // 最近一次服务器确认的数据，作为冲突检测的比较基准
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_shadow (
+                entity_type TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                data TEXT NOT NULL,
+                PRIMARY KEY (entity_type, entity_id)
+            )",
+            [],
+        )?;
+
+        // 同步冲突（等待手动处理或已处理的记录）
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_conflicts (
+                id TEXT PRIMARY KEY,
+                entity_type TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                base_data TEXT,
+                local_data TEXT,
+                remote_data TEXT,
+                fields TEXT NOT NULL,
+                status TEXT DEFAULT 'open',
+                resolution TEXT,
+                created_at TEXT NOT NULL,
+                resolved_at TEXT
+            )",
+            [],
+        )?;
+
         conn.execute("CREATE INDEX IF NOT EXISTS idx_products_barcode ON products(barcode)", [])?;
         conn.execute("CREATE INDEX IF NOT EXISTS idx_products_code ON products(code)", [])?;
         conn.execute("CREATE INDEX IF NOT EXISTS idx_orders_code ON orders(code)", [])?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_sync_queue_entity ON sync_queue(entity_type, entity_id)", [])?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_sync_conflicts_entity ON sync_conflicts(entity_type, entity_id, status)", [])?;
 
         Ok(())
     }
@@ -245,7 +424,22 @@ impl Database {
     // ==================== 客户 CRUD ====================
 
     pub fn create_customer(&self, customer: &Customer) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::insert_customer(&tx, customer)?;
+        tx.commit()
+    }
+
+    /// 修改客户资料；应收余额由订单和收款维护，这里不更新
+    pub fn update_customer(&self, customer: &Customer) -> SqliteResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::write_customer(&tx, customer)?;
+        tx.commit()
+    }
+
+    /// 在当前事务中新建客户并记录变更
+    fn insert_customer(conn: &Connection, customer: &Customer) -> SqliteResult<()> {
         conn.execute(
             "INSERT INTO customers (id, name, company, email, phone, address, category, status, contact_person, credit_limit, balance, remark, source, created_at, updated_at)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
@@ -256,62 +450,63 @@ impl Database {
                 customer.created_at, customer.updated_at
             ],
         )?;
-        Ok(())
+        record_change(conn, "customer", &customer.id, "create", customer)
     }
 
-    pub fn update_customer(&self, customer: &Customer) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+    /// 在当前事务中修改客户资料并记录变更
+    fn write_customer(conn: &Connection, customer: &Customer) -> SqliteResult<()> {
         conn.execute(
-            "UPDATE customers SET name=?2, company=?3, email=?4, phone=?5, address=?6, category=?7, status=?8, contact_person=?9, credit_limit=?10, balance=?11, remark=?12, source=?13, updated_at=?14 WHERE id=?1",
+            "UPDATE customers SET name=?2, company=?3, email=?4, phone=?5, address=?6, category=?7, status=?8, contact_person=?9, credit_limit=?10, remark=?11, source=?12, updated_at=?13 WHERE id=?1",
             params![
                 customer.id, customer.name, customer.company, customer.email, customer.phone,
                 customer.address, customer.category, customer.status, customer.contact_person,
-                customer.credit_limit, customer.balance, customer.remark, customer.source,
-                customer.updated_at
+                customer.credit_limit, customer.remark, customer.source, customer.updated_at
             ],
         )?;
+        if let Some(saved) = Self::load_customer(conn, &customer.id)? {
+            record_change(conn, "customer", &customer.id, "update", &saved)?;
+        }
         Ok(())
     }
 
     pub fn delete_customer(&self, id: &str) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM customers WHERE id = ?1", [id])?;
-        Ok(())
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM customers WHERE id = ?1", [id])?;
+        record_change(&tx, "customer", id, "delete", &serde_json::json!({ "id": id }))?;
+        tx.commit()
     }
 
     pub fn get_customer(&self, id: &str) -> SqliteResult<Option<Customer>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, company, email, phone, address, category, status, contact_person, credit_limit, balance, remark, source, created_at, updated_at FROM customers WHERE id = ?1"
-        )?;
-        
-        let mut rows = stmt.query([id])?;
-        if let Some(row) = rows.next()? {
-            Ok(Some(Customer {
-                id: row.get(0)?, name: row.get(1)?, company: row.get(2)?, email: row.get(3)?,
-                phone: row.get(4)?, address: row.get(5)?, category: row.get(6)?, status: row.get(7)?,
-                contact_person: row.get(8)?, credit_limit: row.get(9)?, balance: row.get(10)?,
-                remark: row.get(11)?, source: row.get(12)?, created_at: row.get(13)?, updated_at: row.get(14)?,
-            }))
-        } else {
-            Ok(None)
-        }
+        Self::load_customer(&conn, id)
+    }
+
+    fn load_customer(conn: &Connection, id: &str) -> SqliteResult<Option<Customer>> {
+        conn.query_row(
+            "SELECT id, name, company, email, phone, address, category, status, contact_person, credit_limit, balance, remark, source, created_at, updated_at, version FROM customers WHERE id = ?1",
+            [id],
+            Self::row_to_customer,
+        )
+        .optional()
+    }
+
+    fn row_to_customer(row: &rusqlite::Row) -> SqliteResult<Customer> {
+        Ok(Customer {
+            id: row.get(0)?, name: row.get(1)?, company: row.get(2)?, email: row.get(3)?,
+            phone: row.get(4)?, address: row.get(5)?, category: row.get(6)?, status: row.get(7)?,
+            contact_person: row.get(8)?, credit_limit: row.get(9)?, balance: row.get(10)?,
+            remark: row.get(11)?, source: row.get(12)?, created_at: row.get(13)?, updated_at: row.get(14)?,
+            version: row.get(15)?,
+        })
     }
 
     pub fn list_customers(&self) -> SqliteResult<Vec<Customer>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, company, email, phone, address, category, status, contact_person, credit_limit, balance, remark, source, created_at, updated_at FROM customers ORDER BY created_at DESC"
+            "SELECT id, name, company, email, phone, address, category, status, contact_person, credit_limit, balance, remark, source, created_at, updated_at, version FROM customers ORDER BY created_at DESC"
         )?;
-        
-        let rows = stmt.query_map([], |row| {
-            Ok(Customer {
-                id: row.get(0)?, name: row.get(1)?, company: row.get(2)?, email: row.get(3)?,
-                phone: row.get(4)?, address: row.get(5)?, category: row.get(6)?, status: row.get(7)?,
-                contact_person: row.get(8)?, credit_limit: row.get(9)?, balance: row.get(10)?,
-                remark: row.get(11)?, source: row.get(12)?, created_at: row.get(13)?, updated_at: row.get(14)?,
-            })
-        })?;
+        let rows = stmt.query_map([], Self::row_to_customer)?;
         rows.collect()
     }
 
@@ -323,7 +518,21 @@ impl Database {
     // ==================== 产品 CRUD ====================
 
     pub fn create_product(&self, product: &Product) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::insert_product(&tx, product)?;
+        tx.commit()
+    }
+
+    pub fn update_product(&self, product: &Product) -> SqliteResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::write_product(&tx, product)?;
+        tx.commit()
+    }
+
+    /// 在当前事务中新建产品，记录期初结存和变更
+    fn insert_product(conn: &Connection, product: &Product) -> SqliteResult<()> {
         conn.execute(
             "INSERT INTO products (id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
@@ -335,48 +544,57 @@ impl Database {
                 product.created_at, product.updated_at
             ],
         )?;
-        Ok(())
+        warehouses::seed_level(conn, product)?;
+        record_change(conn, "product", &product.id, "create", product)
     }
 
-    pub fn update_product(&self, product: &Product) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+    /// 在当前事务中修改产品并记录变更
+    fn write_product(conn: &Connection, product: &Product) -> SqliteResult<()> {
+        // 库存只由出入库流水变更，编辑产品资料不写 current_stock
         conn.execute(
-            "UPDATE products SET code=?2, barcode=?3, name=?4, category=?5, unit=?6, specification=?7, brand=?8, cost_price=?9, sell_price=?10, min_stock=?11, max_stock=?12, current_stock=?13, warehouse_id=?14, location=?15, status=?16, description=?17, updated_at=?18 WHERE id=?1",
+            "UPDATE products SET code=?2, barcode=?3, name=?4, category=?5, unit=?6, specification=?7, brand=?8, cost_price=?9, sell_price=?10, min_stock=?11, max_stock=?12, warehouse_id=?13, location=?14, status=?15, description=?16, updated_at=?17 WHERE id=?1",
             params![
                 product.id, product.code, product.barcode, product.name, product.category,
                 product.unit, product.specification, product.brand, product.cost_price,
-                product.sell_price, product.min_stock, product.max_stock, product.current_stock,
+                product.sell_price, product.min_stock, product.max_stock,
                 product.warehouse_id, product.location, product.status, product.description,
                 product.updated_at
             ],
         )?;
+        if let Some(saved) = Self::load_product(conn, &product.id)? {
+            record_change(conn, "product", &product.id, "update", &saved)?;
+        }
         Ok(())
     }
 
     pub fn delete_product(&self, id: &str) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM products WHERE id = ?1", [id])?;
-        Ok(())
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM products WHERE id = ?1", [id])?;
+        tx.execute("DELETE FROM stock_levels WHERE product_id = ?1", [id])?;
+        tx.execute("DELETE FROM stock_batches WHERE product_id = ?1", [id])?;
+        record_change(&tx, "product", id, "delete", &serde_json::json!({ "id": id }))?;
+        tx.commit()
     }
 
     pub fn get_product(&self, id: &str) -> SqliteResult<Option<Product>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at FROM products WHERE id = ?1"
-        )?;
-        
-        let mut rows = stmt.query([id])?;
-        if let Some(row) = rows.next()? {
-            Ok(Some(Self::row_to_product(row)?))
-        } else {
-            Ok(None)
-        }
+        Self::load_product(&conn, id)
+    }
+
+    fn load_product(conn: &Connection, id: &str) -> SqliteResult<Option<Product>> {
+        conn.query_row(
+            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at, version FROM products WHERE id = ?1",
+            [id],
+            Self::row_to_product,
+        )
+        .optional()
     }
 
     pub fn get_product_by_barcode(&self, barcode: &str) -> SqliteResult<Option<Product>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at FROM products WHERE barcode = ?1"
+            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at, version FROM products WHERE barcode = ?1"
         )?;
         
         let mut rows = stmt.query([barcode])?;
@@ -394,25 +612,25 @@ impl Database {
             cost_price: row.get(8)?, sell_price: row.get(9)?, min_stock: row.get(10)?,
             max_stock: row.get(11)?, current_stock: row.get(12)?, warehouse_id: row.get(13)?,
             location: row.get(14)?, status: row.get(15)?, description: row.get(16)?,
-            created_at: row.get(17)?, updated_at: row.get(18)?,
+            created_at: row.get(17)?, updated_at: row.get(18)?, version: row.get(19)?,
         })
     }
 
     pub fn list_products(&self) -> SqliteResult<Vec<Product>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at FROM products ORDER BY created_at DESC"
+            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at, version FROM products ORDER BY created_at DESC"
         )?;
-        let rows = stmt.query_map([], |row| Self::row_to_product(row))?;
+        let rows = stmt.query_map([], Self::row_to_product)?;
         rows.collect()
     }
 
     pub fn list_low_stock_products(&self) -> SqliteResult<Vec<Product>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at FROM products WHERE current_stock < min_stock"
+            "SELECT id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at, version FROM products WHERE current_stock < min_stock"
         )?;
-        let rows = stmt.query_map([], |row| Self::row_to_product(row))?;
+        let rows = stmt.query_map([], Self::row_to_product)?;
         rows.collect()
     }
 
@@ -426,77 +644,90 @@ impl Database {
         conn.query_row("SELECT COUNT(*) FROM products WHERE current_stock < min_stock", [], |row| row.get(0))
     }
 
-    pub fn update_stock(&self, product_id: &str, quantity_change: i32, record_type: &str, unit_price: f64, remark: &str) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let current: i32 = conn.query_row("SELECT current_stock FROM products WHERE id = ?1", [product_id], |row| row.get(0))?;
-        let new_stock = current + quantity_change;
-        let now = Utc::now().to_rfc3339();
-        
-        conn.execute("UPDATE products SET current_stock = ?1, updated_at = ?2 WHERE id = ?3", params![new_stock, now, product_id])?;
-        
-        let record_id = uuid::Uuid::new_v4().to_string();
-        conn.execute(
-            "INSERT INTO stock_records (id, product_id, record_type, quantity, before_quantity, after_quantity, unit_price, total_amount, remark, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![record_id, product_id, record_type, quantity_change.abs(), current, new_stock, unit_price, (quantity_change.abs() as f64) * unit_price, remark, now]
-        )?;
-        Ok(())
+    /// 变更库存并记录流水
+    ///
+    /// 同步时只上传库存流水（增量），由服务器据此计算库存，避免覆盖其他终端的出入库。
+    /// 出库受超卖策略约束，不能占用订单已预留的库存，也不能超过所在仓库库位的结存。
+    /// 出库未指定批次时按先到期先出拆分，每个批次返回一条流水。
+    pub fn update_stock(&self, movement: &StockMovement) -> StockResult<Vec<StockRecord>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let records = inventory::apply_movement(&tx, movement)?;
+        tx.commit()?;
+        Ok(records)
     }
 
     // ==================== 订单 CRUD ====================
 
     pub fn create_order(&self, order: &Order) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::insert_order(&tx, order)?;
+        tx.commit()
+    }
+
+    fn insert_order(conn: &Connection, order: &Order) -> SqliteResult<()> {
         conn.execute(
-            "INSERT INTO orders (id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            "INSERT INTO orders (id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at, warehouse_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
             params![
                 order.id, order.code, order.order_type, order.customer_id, order.customer_name,
                 order.status, order.total_quantity, order.total_amount, order.discount_amount,
                 order.payable_amount, order.paid_amount, order.delivery_address, order.delivery_date,
-                order.remark, order.operator_id, order.operator_name, order.created_at, order.updated_at
+                order.remark, order.operator_id, order.operator_name, order.created_at, order.updated_at,
+                order.warehouse_id
             ],
         )?;
-        Ok(())
+        record_change(conn, "order", &order.id, "create", order)
     }
 
-    pub fn update_order(&self, order: &Order) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "UPDATE orders SET customer_id=?2, customer_name=?3, status=?4, total_quantity=?5, total_amount=?6, discount_amount=?7, payable_amount=?8, paid_amount=?9, delivery_address=?10, delivery_date=?11, remark=?12, updated_at=?13 WHERE id=?1",
+    /// 修改草稿订单，返回保存后的订单
+    pub fn update_order(&self, order: &Order) -> OrderResult<Order> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let current = Self::load_order(&tx, &order.id)?.ok_or_else(|| OrderError::NotFound(order.id.clone()))?;
+        order_status::require_draft(&current)?;
+        tx.execute(
+            "UPDATE orders SET customer_id=?2, customer_name=?3, total_quantity=?4, total_amount=?5, discount_amount=?6, payable_amount=?7, delivery_address=?8, delivery_date=?9, remark=?10, updated_at=?11, warehouse_id=?12 WHERE id=?1",
             params![
-                order.id, order.customer_id, order.customer_name, order.status, order.total_quantity,
-                order.total_amount, order.discount_amount, order.payable_amount, order.paid_amount,
-                order.delivery_address, order.delivery_date, order.remark, order.updated_at
+                order.id, order.customer_id, order.customer_name, order.total_quantity,
+                order.total_amount, order.discount_amount, order.payable_amount,
+                order.delivery_address, order.delivery_date, order.remark, order.updated_at,
+                order.warehouse_id
             ],
         )?;
+        let saved = Self::load_order(&tx, &order.id)?.ok_or_else(|| OrderError::NotFound(order.id.clone()))?;
+        record_change(&tx, "order", &order.id, "update", &saved)?;
+        tx.commit()?;
+        Ok(saved)
+    }
+
+    /// 删除草稿订单及其明细（服务器端同样级联删除明细）
+    pub fn delete_order(&self, id: &str) -> OrderResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let current = Self::load_order(&tx, id)?.ok_or_else(|| OrderError::NotFound(id.to_string()))?;
+        order_status::require_draft(&current)?;
+        tx.execute("DELETE FROM order_items WHERE order_id = ?1", [id])?;
+        tx.execute("DELETE FROM stock_reservations WHERE order_id = ?1", [id])?;
+        tx.execute("DELETE FROM orders WHERE id = ?1", [id])?;
+        record_change(&tx, "order", id, "delete", &serde_json::json!({ "id": id }))?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn update_order_status(&self, id: &str, status: &str) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let now = Utc::now().to_rfc3339();
-        conn.execute("UPDATE orders SET status = ?1, updated_at = ?2 WHERE id = ?3", params![status, now, id])?;
-        Ok(())
-    }
-
-    pub fn delete_order(&self, id: &str) -> SqliteResult<()> {
+    pub fn get_order(&self, id: &str) -> SqliteResult<Option<Order>> {
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM order_items WHERE order_id = ?1", [id])?;
-        conn.execute("DELETE FROM orders WHERE id = ?1", [id])?;
-        Ok(())
+        Self::load_order(&conn, id)
     }
 
-    pub fn get_order(&self, id: &str) -> SqliteResult<Option<Order>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at FROM orders WHERE id = ?1"
-        )?;
-        let mut rows = stmt.query([id])?;
-        if let Some(row) = rows.next()? {
-            Ok(Some(Self::row_to_order(row)?))
-        } else {
-            Ok(None)
-        }
+    fn load_order(conn: &Connection, id: &str) -> SqliteResult<Option<Order>> {
+        conn.query_row(
+            "SELECT id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at, version, warehouse_id FROM orders WHERE id = ?1",
+            [id],
+            Self::row_to_order,
+        )
+        .optional()
     }
 
     fn row_to_order(row: &rusqlite::Row) -> SqliteResult<Order> {
@@ -506,16 +737,17 @@ impl Database {
             total_amount: row.get(7)?, discount_amount: row.get(8)?, payable_amount: row.get(9)?,
             paid_amount: row.get(10)?, delivery_address: row.get(11)?, delivery_date: row.get(12)?,
             remark: row.get(13)?, operator_id: row.get(14)?, operator_name: row.get(15)?,
-            created_at: row.get(16)?, updated_at: row.get(17)?,
+            created_at: row.get(16)?, updated_at: row.get(17)?, version: row.get(18)?,
+            warehouse_id: row.get(19)?,
         })
     }
 
     pub fn list_orders(&self) -> SqliteResult<Vec<Order>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at FROM orders ORDER BY created_at DESC"
+            "SELECT id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at, version, warehouse_id FROM orders ORDER BY created_at DESC"
         )?;
-        let rows = stmt.query_map([], |row| Self::row_to_order(row))?;
+        let rows = stmt.query_map([], Self::row_to_order)?;
         rows.collect()
     }
 
@@ -524,21 +756,6 @@ impl Database {
         conn.query_row("SELECT COUNT(*) FROM orders", [], |row| row.get(0))
     }
 
-    pub fn count_today_orders(&self) -> SqliteResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        let today = Utc::now().format("%Y-%m-%d").to_string();
-        conn.query_row("SELECT COUNT(*) FROM orders WHERE created_at LIKE ?1", [format!("{}%", today)], |row| row.get(0))
-    }
-
-    pub fn get_today_revenue(&self) -> SqliteResult<f64> {
-        let conn = self.conn.lock().unwrap();
-        let today = Utc::now().format("%Y-%m-%d").to_string();
-        conn.query_row(
-            "SELECT COALESCE(SUM(payable_amount), 0) FROM orders WHERE created_at LIKE ?1 AND order_type = 'sale' AND status != 'cancelled'",
-            [format!("{}%", today)], |row| row.get(0)
-        )
-    }
-
     pub fn generate_order_code(&self, order_type: &str) -> String {
         let prefix = match order_type { "sale" => "SO", "purchase" => "PO", "return" => "RO", _ => "OD" };
         let timestamp = Utc::now().format("%Y%m%d%H%M%S");
@@ -546,22 +763,32 @@ impl Database {
     }
 
     pub fn add_order_item(&self, item: &OrderItem) -> SqliteResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::insert_order_item(&tx, item)?;
+        tx.commit()
+    }
+
+    fn insert_order_item(conn: &Connection, item: &OrderItem) -> SqliteResult<()> {
         conn.execute(
-            "INSERT INTO order_items (id, order_id, product_id, product_name, product_code, unit, quantity, unit_price, discount, amount, remark) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-            params![item.id, item.order_id, item.product_id, item.product_name, item.product_code, item.unit, item.quantity, item.unit_price, item.discount, item.amount, item.remark],
+            "INSERT INTO order_items (id, order_id, product_id, product_name, product_code, unit, quantity, unit_price, discount, amount, remark, shipped_quantity) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![item.id, item.order_id, item.product_id, item.product_name, item.product_code, item.unit, item.quantity, item.unit_price, item.discount, item.amount, item.remark, item.shipped_quantity],
         )?;
-        Ok(())
+        record_change(conn, "order_item", &item.id, "create", item)
     }
 
     pub fn get_order_items(&self, order_id: &str) -> SqliteResult<Vec<OrderItem>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, order_id, product_id, product_name, product_code, unit, quantity, unit_price, discount, amount, remark FROM order_items WHERE order_id = ?1")?;
+        Self::load_order_items(&conn, order_id)
+    }
+
+    fn load_order_items(conn: &Connection, order_id: &str) -> SqliteResult<Vec<OrderItem>> {
+        let mut stmt = conn.prepare("SELECT id, order_id, product_id, product_name, product_code, unit, quantity, unit_price, discount, amount, remark, shipped_quantity FROM order_items WHERE order_id = ?1")?;
         let rows = stmt.query_map([order_id], |row| {
             Ok(OrderItem {
                 id: row.get(0)?, order_id: row.get(1)?, product_id: row.get(2)?, product_name: row.get(3)?,
                 product_code: row.get(4)?, unit: row.get(5)?, quantity: row.get(6)?, unit_price: row.get(7)?,
-                discount: row.get(8)?, amount: row.get(9)?, remark: row.get(10)?,
+                discount: row.get(8)?, amount: row.get(9)?, remark: row.get(10)?, shipped_quantity: row.get(11)?,
             })
         })?;
         rows.collect()
@@ -0,0 +1,201 @@
+//! 订单状态机 - 状态定义、允许的流转与状态变更历史
+//!
+//! 草稿 → 已确认 → 部分发货 → 已发货 → 已完成，草稿、已确认和部分发货可以取消，部分发货、已发货和已完成可以退货。
+//! 部分发货后取消只取消未发货的部分，已发货部分的货款仍计入应收。
+//! 所有状态变更经 `change_status` 校验并写入 `order_status_history`（只保存在本机，与操作日志一样不参与同步）。
+
+use chrono::Utc;
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::fulfillment::{OrderError, OrderResult};
+use super::payments::{adjust_customer_balance, is_receivable_order, outstanding};
+use super::{record_change, Database, Order, User};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Draft,
+    Confirmed,
+    PartiallyShipped,
+    Shipped,
+    Completed,
+    Cancelled,
+    Returned,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 7] = [
+        OrderStatus::Draft,
+        OrderStatus::Confirmed,
+        OrderStatus::PartiallyShipped,
+        OrderStatus::Shipped,
+        OrderStatus::Completed,
+        OrderStatus::Cancelled,
+        OrderStatus::Returned,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Draft => "draft",
+            OrderStatus::Confirmed => "confirmed",
+            OrderStatus::PartiallyShipped => "partially_shipped",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Completed => "completed",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Returned => "returned",
+        }
+    }
+
+    /// 解析保存的状态；旧版本的 `pending`（待确认）按草稿处理
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(OrderStatus::Draft),
+            _ => Self::ALL.into_iter().find(|status| status.as_str() == s),
+        }
+    }
+
+    /// 可以流转到的状态；部分发货可以多次发货，所以允许流转到自身
+    pub fn next_states(&self) -> &'static [OrderStatus] {
+        use OrderStatus::*;
+        match self {
+            Draft => &[Confirmed, Cancelled],
+            Confirmed => &[PartiallyShipped, Shipped, Completed, Cancelled],
+            PartiallyShipped => &[PartiallyShipped, Shipped, Cancelled, Returned],
+            Shipped => &[Completed, Returned],
+            Completed => &[Returned],
+            Cancelled | Returned => &[],
+        }
+    }
+
+    pub fn can_transition_to(&self, to: OrderStatus) -> bool {
+        self.next_states().contains(&to)
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 一次状态变更
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderStatusChange {
+    pub id: String,
+    pub order_id: String,
+    pub from_status: String,
+    pub to_status: String,
+    pub operator_id: String,
+    pub operator_name: String,
+    /// 取消、退货原因或发货说明
+    pub reason: String,
+    pub created_at: String,
+}
+
+pub(super) fn create_status_history_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS order_status_history (
+            id TEXT PRIMARY KEY,
+            order_id TEXT NOT NULL,
+            from_status TEXT NOT NULL,
+            to_status TEXT NOT NULL,
+            operator_id TEXT DEFAULT '',
+            operator_name TEXT DEFAULT '',
+            reason TEXT DEFAULT '',
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_order_status_history_order ON order_status_history(order_id, created_at)", [])?;
+    super::ensure_column(conn, "order_items", "shipped_quantity", "INTEGER DEFAULT 0")
+}
+
+/// 订单的当前状态，无法识别的状态值返回错误而不是当作草稿
+pub(super) fn current_status(order: &Order) -> OrderResult<OrderStatus> {
+    OrderStatus::parse(&order.status).ok_or_else(|| OrderError::UnknownStatus(order.status.clone()))
+}
+
+/// 校验流转是否允许，返回当前状态
+pub(super) fn check_transition(order: &Order, to: OrderStatus) -> OrderResult<OrderStatus> {
+    let from = current_status(order)?;
+    if !from.can_transition_to(to) {
+        return Err(OrderError::InvalidTransition { from, to });
+    }
+    Ok(from)
+}
+
+/// 修改金额或删除只允许在草稿状态：确认后订单已计入客户应收并可能有收款记录
+pub(super) fn require_draft(order: &Order) -> OrderResult<()> {
+    match current_status(order)? {
+        OrderStatus::Draft => Ok(()),
+        status => Err(OrderError::NotDraft(status)),
+    }
+}
+
+/// 在事务中校验并变更状态，同时记录历史
+pub(super) fn change_status(conn: &Connection, order: &Order, to: OrderStatus, operator: &User, reason: &str) -> OrderResult<Order> {
+    let from = check_transition(order, to)?;
+    let now = Utc::now().to_rfc3339();
+    conn.execute("UPDATE orders SET status = ?1, updated_at = ?2 WHERE id = ?3", params![to.as_str(), now, order.id])?;
+    conn.execute(
+        "INSERT INTO order_status_history (id, order_id, from_status, to_status, operator_id, operator_name, reason, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![uuid::Uuid::new_v4().to_string(), order.id, from.as_str(), to.as_str(), operator.id, operator.name, reason, now],
+    )?;
+    let saved = Database::load_order(conn, &order.id)?.ok_or_else(|| OrderError::NotFound(order.id.clone()))?;
+    record_change(conn, "order", &order.id, "update", &saved)?;
+    // 销售订单确认后计入客户应收，取消或退货时扣除剩余未收金额
+    let receivable_to = is_receivable_order(conn, &order.id, to)?;
+    if order.order_type == "sale" && is_receivable_order(conn, &order.id, from)? != receivable_to {
+        let amount = outstanding(order);
+        adjust_customer_balance(conn, &order.customer_id, if receivable_to { amount } else { -amount })?;
+    }
+    Ok(saved)
+}
+
+impl Database {
+    /// 订单的状态变更历史，按时间先后排列
+    pub fn list_order_status_history(&self, order_id: &str) -> SqliteResult<Vec<OrderStatusChange>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, order_id, from_status, to_status, operator_id, operator_name, reason, created_at
+             FROM order_status_history WHERE order_id = ?1 ORDER BY created_at, rowid",
+        )?;
+        let rows = stmt.query_map([order_id], |row| {
+            Ok(OrderStatusChange {
+                id: row.get(0)?, order_id: row.get(1)?, from_status: row.get(2)?, to_status: row.get(3)?,
+                operator_id: row.get(4)?, operator_name: row.get(5)?, reason: row.get(6)?, created_at: row.get(7)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        use OrderStatus::*;
+        assert!(Draft.can_transition_to(Confirmed));
+        assert!(Confirmed.can_transition_to(PartiallyShipped));
+        assert!(PartiallyShipped.can_transition_to(PartiallyShipped));
+        assert!(Shipped.can_transition_to(Returned));
+        assert!(!Draft.can_transition_to(Shipped));
+        assert!(PartiallyShipped.can_transition_to(Cancelled));
+        assert!(PartiallyShipped.can_transition_to(Returned));
+        assert!(!PartiallyShipped.can_transition_to(Completed));
+        assert!(!Cancelled.can_transition_to(Completed));
+        assert!(Returned.next_states().is_empty());
+
+        assert_eq!(OrderStatus::parse("pending"), Some(Draft));
+        assert_eq!(OrderStatus::parse("partially_shipped"), Some(PartiallyShipped));
+        assert_eq!(OrderStatus::parse("lost"), None);
+        for status in OrderStatus::ALL {
+            assert_eq!(OrderStatus::parse(status.as_str()), Some(status));
+        }
+    }
+}
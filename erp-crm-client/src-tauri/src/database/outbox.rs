@@ -0,0 +1,391 @@
+//! 同步队列（outbox）- 记录本地变更，并应用服务器下发的变更
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+
+use super::conflict::{self, ApplyOutcome};
+use super::payments::insert_payment;
+use super::transfers::write_transfer;
+use super::warehouses::{apply_record_to_level, seed_level, seed_missing_levels, upsert_warehouse};
+use super::{Customer, Database, Order, OrderItem, Payment, Product, StockRecord, StockTransfer, Warehouse};
+use crate::sync::{SyncPacket, VERSIONED_ENTITIES};
+
+/// 下载游标
+pub const SYNC_CURSOR_KEY: &str = "cursor";
+/// 上次成功同步时间
+pub const LAST_SYNC_AT_KEY: &str = "last_sync_at";
+/// 本机设备 ID
+pub const DEVICE_ID_KEY: &str = "device_id";
+/// 同步服务器地址
+pub const API_BASE_URL_KEY: &str = "api_base_url";
+/// 自动同步间隔（分钟）
+pub const SYNC_INTERVAL_KEY: &str = "sync_interval_minutes";
+
+/// 同步队列中的一条本地变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    pub id: i64,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub operation: String,
+    pub payload: serde_json::Value,
+    pub created_at: String,
+    pub retry_count: i32,
+    pub last_error: Option<String>,
+}
+
+/// 在当前事务中追加一条变更记录
+pub(crate) fn record_change<T: Serialize + ?Sized>(
+    conn: &Connection,
+    entity_type: &str,
+    entity_id: &str,
+    operation: &str,
+    payload: &T,
+) -> SqliteResult<()> {
+    let payload = serde_json::to_string(payload)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO sync_queue (entity_type, entity_id, operation, payload, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![entity_type, entity_id, operation, payload, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+fn row_to_change(row: &rusqlite::Row) -> SqliteResult<ChangeRecord> {
+    let payload: String = row.get(4)?;
+    Ok(ChangeRecord {
+        id: row.get(0)?,
+        entity_type: row.get(1)?,
+        entity_id: row.get(2)?,
+        operation: row.get(3)?,
+        payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::Null),
+        created_at: row.get(5)?,
+        retry_count: row.get(6)?,
+        last_error: row.get(7)?,
+    })
+}
+
+impl Database {
+    /// 取出到期待上传的变更（按写入顺序）
+    ///
+    /// 同一实体若有更早的变更仍在退避等待，则其后续变更也暂不上传，保证服务器按顺序应用；
+    /// 存在未处理冲突的实体同样暂停上传。
+    pub fn pending_changes(&self, limit: usize) -> SqliteResult<Vec<ChangeRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp_millis();
+        let mut stmt = conn.prepare(
+            "SELECT id, entity_type, entity_id, operation, payload, created_at, retry_count, last_error
+             FROM sync_queue q
+             WHERE next_attempt_at <= ?1
+               AND NOT EXISTS (
+                   SELECT 1 FROM sync_queue b
+                   WHERE b.entity_type = q.entity_type AND b.entity_id = q.entity_id
+                     AND b.id < q.id AND b.next_attempt_at > ?1
+               )
+               AND NOT EXISTS (
+                   SELECT 1 FROM sync_conflicts c
+                   WHERE c.entity_type = q.entity_type AND c.entity_id = q.entity_id AND c.status = 'open'
+               )
+             ORDER BY id LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![now, limit as i64], row_to_change)?;
+        rows.collect()
+    }
+
+    /// 服务器已接收，移出队列，并把已确认的数据记为新的比较基准
+    pub fn ack_changes(&self, ids: &[i64]) -> SqliteResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for id in ids {
+            let acked = tx
+                .query_row(
+                    "SELECT entity_type, entity_id, operation, payload FROM sync_queue WHERE id = ?1",
+                    [id],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?)),
+                )
+                .optional()?;
+            if let Some((entity_type, entity_id, operation, payload)) = acked {
+                if VERSIONED_ENTITIES.contains(&entity_type.as_str()) {
+                    if operation == "delete" {
+                        conflict::delete_shadow(&tx, &entity_type, &entity_id)?;
+                    } else {
+                        conflict::save_shadow_raw(&tx, &entity_type, &entity_id, &payload)?;
+                    }
+                }
+            }
+            tx.execute("DELETE FROM sync_queue WHERE id = ?1", [id])?;
+        }
+        tx.commit()
+    }
+
+    /// 记录上传失败，`next_attempt_at` 为下次允许重试的时间（毫秒时间戳）
+    pub fn fail_change(&self, id: i64, error: &str, next_attempt_at: i64) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE sync_queue SET retry_count = retry_count + 1, last_error = ?2, next_attempt_at = ?3 WHERE id = ?1",
+            params![id, error, next_attempt_at],
+        )?;
+        Ok(())
+    }
+
+    /// 队列中尚未上传的变更数
+    pub fn count_pending_changes(&self) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM sync_queue", [], |row| row.get(0))
+    }
+
+    /// 上传失败过的变更
+    pub fn list_failed_changes(&self) -> SqliteResult<Vec<ChangeRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, entity_type, entity_id, operation, payload, created_at, retry_count, last_error
+             FROM sync_queue WHERE last_error IS NOT NULL ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], row_to_change)?;
+        rows.collect()
+    }
+
+    pub fn get_sync_state(&self, key: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT value FROM sync_state WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+    }
+
+    pub fn set_sync_state(&self, key: &str, value: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        set_state(&conn, key, value)
+    }
+
+    /// 在一个事务中应用服务器变更并保存新的游标
+    ///
+    /// 应用过程不写入同步队列，避免把服务器变更再回传；无法识别的变更记录日志后跳过。
+    /// 命中本地未上传修改的记录按冲突策略处理，自动合并或按本地值胜出时会重新排队上传。
+    pub fn apply_remote_changes(&self, changes: &[SyncPacket], cursor: &str) -> SqliteResult<ApplySummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut summary = ApplySummary::default();
+        for change in changes {
+            match apply_change(&tx, change) {
+                Ok(ApplyOutcome::Applied) => summary.applied += 1,
+                Ok(ApplyOutcome::Conflict) => {
+                    summary.applied += 1;
+                    summary.conflicts += 1;
+                }
+                Ok(ApplyOutcome::Unknown) => log::warn!("跳过无法识别的变更: {} {}", change.entity_type, change.operation),
+                Err(ApplyError::Data(e)) => log::warn!("跳过格式错误的变更 {}: {}", change.entity_type, e),
+                Err(ApplyError::Sqlite(e)) => return Err(e),
+            }
+        }
+        // 预留只在本机保存，订单被服务器删除时一并清除
+        tx.execute("DELETE FROM stock_reservations WHERE order_id NOT IN (SELECT id FROM orders)", [])?;
+        set_state(&tx, SYNC_CURSOR_KEY, cursor)?;
+        tx.commit()?;
+        Ok(summary)
+    }
+
+    /// 全量同步：清空本地业务数据后应用服务器的全部数据，整个过程在一个事务中完成
+    ///
+    /// 调用前同步队列应已清空；队列中残留的变更会一并丢弃。
+    pub fn rebuild_from_remote(&self, changes: &[SyncPacket], cursor: &str) -> SqliteResult<ApplySummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        // 仓库和批次结存由流水累加而来，重新写入全部流水会重复累加，所以先保存、应用完成后恢复
+        tx.execute("CREATE TEMP TABLE stock_levels_saved AS SELECT * FROM stock_levels", [])?;
+        tx.execute("CREATE TEMP TABLE stock_batches_saved AS SELECT * FROM stock_batches", [])?;
+        for table in [
+            "order_items", "orders", "stock_records", "payments", "stock_transfer_items", "stock_transfers",
+            "products", "customers", "stock_levels", "stock_batches", "sync_queue", "sync_shadow",
+        ] {
+            tx.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+        tx.execute("DELETE FROM sync_conflicts WHERE status = 'open'", [])?;
+
+        let mut summary = ApplySummary::default();
+        for change in changes {
+            match apply_change(&tx, change) {
+                Ok(ApplyOutcome::Unknown) => log::warn!("跳过无法识别的变更: {} {}", change.entity_type, change.operation),
+                Ok(_) => summary.applied += 1,
+                Err(ApplyError::Data(e)) => log::warn!("跳过格式错误的变更 {}: {}", change.entity_type, e),
+                Err(ApplyError::Sqlite(e)) => return Err(e),
+            }
+        }
+        tx.execute("DELETE FROM stock_reservations WHERE order_id NOT IN (SELECT id FROM orders)", [])?;
+        tx.execute("DELETE FROM stock_levels", [])?;
+        tx.execute("INSERT INTO stock_levels SELECT * FROM stock_levels_saved WHERE product_id IN (SELECT id FROM products)", [])?;
+        tx.execute("DROP TABLE stock_levels_saved", [])?;
+        tx.execute("DELETE FROM stock_batches", [])?;
+        tx.execute("INSERT INTO stock_batches SELECT * FROM stock_batches_saved WHERE product_id IN (SELECT id FROM products)", [])?;
+        tx.execute("DROP TABLE stock_batches_saved", [])?;
+        seed_missing_levels(&tx)?;
+        set_state(&tx, SYNC_CURSOR_KEY, cursor)?;
+        tx.commit()?;
+        Ok(summary)
+    }
+}
+
+/// 一批服务器变更的应用结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApplySummary {
+    /// 已处理的变更数
+    pub applied: usize,
+    /// 其中转为待处理冲突的数量
+    pub conflicts: usize,
+}
+
+pub(super) fn set_state(conn: &Connection, key: &str, value: &str) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO sync_state (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+pub(super) enum ApplyError {
+    Data(serde_json::Error),
+    Sqlite(rusqlite::Error),
+}
+
+impl From<serde_json::Error> for ApplyError {
+    fn from(e: serde_json::Error) -> Self {
+        ApplyError::Data(e)
+    }
+}
+
+impl From<rusqlite::Error> for ApplyError {
+    fn from(e: rusqlite::Error) -> Self {
+        ApplyError::Sqlite(e)
+    }
+}
+
+/// 实体类型对应的表名
+pub(super) fn table_for(entity_type: &str) -> Option<&'static str> {
+    match entity_type {
+        "customer" => Some("customers"),
+        "product" => Some("products"),
+        "order" => Some("orders"),
+        "order_item" => Some("order_items"),
+        "stock_record" => Some("stock_records"),
+        "payment" => Some("payments"),
+        "warehouse" => Some("warehouses"),
+        "stock_transfer" => Some("stock_transfers"),
+        _ => None,
+    }
+}
+
+/// 应用单条服务器变更
+fn apply_change(conn: &Connection, change: &SyncPacket) -> Result<ApplyOutcome, ApplyError> {
+    let Some(table) = table_for(&change.entity_type) else {
+        return Ok(ApplyOutcome::Unknown);
+    };
+
+    if VERSIONED_ENTITIES.contains(&change.entity_type.as_str()) {
+        return conflict::apply_versioned(conn, change, table);
+    }
+
+    if change.operation == "delete" {
+        let id = change.data["id"].as_str().unwrap_or(&change.entity_id);
+        delete_row(conn, table, id)?;
+    } else {
+        write_row(conn, table, change.data.clone())?;
+    }
+    Ok(ApplyOutcome::Applied)
+}
+
+/// 删除一行，删除订单、调拨单时一并删除明细，删除产品时一并删除仓库和批次结存
+pub(super) fn delete_row(conn: &Connection, table: &str, id: &str) -> SqliteResult<()> {
+    match table {
+        "orders" => {
+            conn.execute("DELETE FROM order_items WHERE order_id = ?1", [id])?;
+        }
+        "stock_transfers" => {
+            conn.execute("DELETE FROM stock_transfer_items WHERE transfer_id = ?1", [id])?;
+        }
+        "products" => {
+            conn.execute("DELETE FROM stock_levels WHERE product_id = ?1", [id])?;
+            conn.execute("DELETE FROM stock_batches WHERE product_id = ?1", [id])?;
+        }
+        _ => {}
+    }
+    conn.execute(&format!("DELETE FROM {} WHERE id = ?1", table), [id])?;
+    Ok(())
+}
+
+/// 按服务器数据写入一行（不记录同步变更）
+pub(super) fn write_row(conn: &Connection, table: &str, data: serde_json::Value) -> Result<(), ApplyError> {
+    match table {
+        "customers" => {
+            let c: Customer = serde_json::from_value(data)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO customers (id, name, company, email, phone, address, category, status, contact_person, credit_limit, balance, remark, source, created_at, updated_at, version)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                params![
+                    c.id, c.name, c.company, c.email, c.phone, c.address, c.category, c.status,
+                    c.contact_person, c.credit_limit, c.balance, c.remark, c.source, c.created_at, c.updated_at, c.version
+                ],
+            )?;
+        }
+        "products" => {
+            let p: Product = serde_json::from_value(data)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO products (id, code, barcode, name, category, unit, specification, brand, cost_price, sell_price, min_stock, max_stock, current_stock, warehouse_id, location, status, description, created_at, updated_at, version)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+                params![
+                    p.id, p.code, p.barcode, p.name, p.category, p.unit, p.specification, p.brand,
+                    p.cost_price, p.sell_price, p.min_stock, p.max_stock, p.current_stock,
+                    p.warehouse_id, p.location, p.status, p.description, p.created_at, p.updated_at, p.version
+                ],
+            )?;
+            seed_level(conn, &p)?;
+        }
+        "orders" => {
+            let o: Order = serde_json::from_value(data)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO orders (id, code, order_type, customer_id, customer_name, status, total_quantity, total_amount, discount_amount, payable_amount, paid_amount, delivery_address, delivery_date, remark, operator_id, operator_name, created_at, updated_at, version, warehouse_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+                params![
+                    o.id, o.code, o.order_type, o.customer_id, o.customer_name, o.status,
+                    o.total_quantity, o.total_amount, o.discount_amount, o.payable_amount,
+                    o.paid_amount, o.delivery_address, o.delivery_date, o.remark,
+                    o.operator_id, o.operator_name, o.created_at, o.updated_at, o.version,
+                    o.warehouse_id
+                ],
+            )?;
+        }
+        "order_items" => {
+            let i: OrderItem = serde_json::from_value(data)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO order_items (id, order_id, product_id, product_name, product_code, unit, quantity, unit_price, discount, amount, remark, shipped_quantity)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![i.id, i.order_id, i.product_id, i.product_name, i.product_code, i.unit, i.quantity, i.unit_price, i.discount, i.amount, i.remark, i.shipped_quantity],
+            )?;
+        }
+        "payments" => {
+            // 收款记录只追加；订单已收金额与客户余额以服务器下发的订单、客户数据为准
+            let p: Payment = serde_json::from_value(data)?;
+            insert_payment(conn, &p)?;
+        }
+        "warehouses" => {
+            let w: Warehouse = serde_json::from_value(data)?;
+            upsert_warehouse(conn, &w)?;
+        }
+        "stock_transfers" => {
+            // 调拨单只记录单据状态，仓库结存由随之同步的调拨出入库流水更新
+            let t: StockTransfer = serde_json::from_value(data)?;
+            write_transfer(conn, &t)?;
+        }
+        _ => {
+            // 产品合计库存以服务器下发的产品数据为准；新流水累加到对应仓库和批次的结存
+            let r: StockRecord = serde_json::from_value(data)?;
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO stock_records (id, product_id, record_type, quantity, before_quantity, after_quantity, unit_price, total_amount, remark, created_at, batch_no, expiry_date, order_id, warehouse_id, location, production_date)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                params![r.id, r.product_id, r.record_type, r.quantity, r.before_quantity, r.after_quantity, r.unit_price, r.total_amount, r.remark, r.created_at, r.batch_no, r.expiry_date, r.order_id, r.warehouse_id, r.location, r.production_date],
+            )?;
+            if inserted > 0 {
+                apply_record_to_level(conn, &r)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,580 @@
+//! 收款与应收 - 订单收款/退款、客户应收余额、信用额度与账龄
+//!
+//! 客户 `balance` 为应收余额：销售订单确认后计入未收金额，收款减少、退款增加，
+//! 订单取消或退货时扣除该订单剩余的未收金额（部分发货后取消只扣除未发货部分）。收款记录与订单已收金额、客户余额在同一事务中更新。
+
+use chrono::{NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use super::order_status::OrderStatus;
+use super::{record_change, Database, Order, User};
+
+/// 金额比较的容差（分以下的误差忽略）
+const EPSILON: f64 = 0.005;
+
+/// 收款方式
+pub const PAYMENT_METHODS: [&str; 6] = ["cash", "bank_transfer", "wechat", "alipay", "card", "other"];
+
+#[derive(Error, Debug)]
+pub enum PaymentError {
+    #[error("订单不存在: {0}")]
+    OrderNotFound(String),
+    #[error("只有销售订单可以收款")]
+    NotSaleOrder,
+    #[error("订单状态为 {0}，不能收款")]
+    NotPayable(String),
+    #[error("金额必须大于 0")]
+    InvalidAmount,
+    #[error("不支持的收款方式: {0}")]
+    InvalidMethod(String),
+    #[error("收款金额 {amount:.2} 超过未收金额 {outstanding:.2}")]
+    Overpayment { amount: f64, outstanding: f64 },
+    #[error("退款金额 {amount:.2} 超过已收金额 {paid:.2}")]
+    RefundExceedsPaid { amount: f64, paid: f64 },
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type PaymentResult<T> = std::result::Result<T, PaymentError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentType {
+    Payment,
+    Refund,
+}
+
+impl PaymentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentType::Payment => "payment",
+            PaymentType::Refund => "refund",
+        }
+    }
+}
+
+/// 订单的收款状态（按已收金额计算，不单独保存）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Unpaid,
+    PartiallyPaid,
+    Paid,
+}
+
+impl PaymentStatus {
+    pub fn of(order: &Order) -> Self {
+        if order.paid_amount <= EPSILON {
+            PaymentStatus::Unpaid
+        } else if order.paid_amount + EPSILON < order.payable_amount {
+            PaymentStatus::PartiallyPaid
+        } else {
+            PaymentStatus::Paid
+        }
+    }
+}
+
+/// 收款或退款记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payment {
+    pub id: String,
+    pub order_id: String,
+    pub customer_id: String,
+    /// payment / refund
+    pub payment_type: String,
+    /// 金额（退款同样为正数）
+    pub amount: f64,
+    pub method: String,
+    /// 银行流水号、交易单号等
+    pub reference: String,
+    pub remark: String,
+    pub operator_id: String,
+    pub operator_name: String,
+    /// `YYYY-MM-DD`
+    pub paid_at: String,
+    pub created_at: String,
+}
+
+/// 登记收款的参数
+#[derive(Debug, Clone)]
+pub struct PaymentInput {
+    pub order_id: String,
+    pub payment_type: PaymentType,
+    pub amount: f64,
+    pub method: String,
+    pub reference: String,
+    pub remark: String,
+    /// 为空时取当天
+    pub paid_at: String,
+}
+
+/// 账龄分段金额
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AgingBuckets {
+    /// 30 天以内
+    pub current: f64,
+    /// 31-60 天
+    pub days_30: f64,
+    /// 61-90 天
+    pub days_60: f64,
+    /// 90 天以上
+    pub days_90_plus: f64,
+    pub total: f64,
+}
+
+impl AgingBuckets {
+    fn add(&mut self, age_days: i64, amount: f64) {
+        match age_days {
+            ..=30 => self.current += amount,
+            31..=60 => self.days_30 += amount,
+            61..=90 => self.days_60 += amount,
+            _ => self.days_90_plus += amount,
+        }
+        self.total += amount;
+    }
+
+    fn rounded(self) -> Self {
+        Self {
+            current: round2(self.current),
+            days_30: round2(self.days_30),
+            days_60: round2(self.days_60),
+            days_90_plus: round2(self.days_90_plus),
+            total: round2(self.total),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgingRow {
+    pub customer_id: String,
+    pub customer_name: String,
+    #[serde(flatten)]
+    pub buckets: AgingBuckets,
+}
+
+/// 应收账龄报表，账龄按订单日期计算
+#[derive(Debug, Clone, Serialize)]
+pub struct AgingReport {
+    pub as_of: String,
+    pub rows: Vec<AgingRow>,
+    pub totals: AgingBuckets,
+}
+
+pub(super) fn create_payments_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payments (
+            id TEXT PRIMARY KEY,
+            order_id TEXT NOT NULL,
+            customer_id TEXT DEFAULT '',
+            payment_type TEXT NOT NULL DEFAULT 'payment',
+            amount REAL NOT NULL,
+            method TEXT DEFAULT '',
+            reference TEXT DEFAULT '',
+            remark TEXT DEFAULT '',
+            operator_id TEXT DEFAULT '',
+            operator_name TEXT DEFAULT '',
+            paid_at TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_payments_order ON payments(order_id)", [])?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_payments_customer ON payments(customer_id, paid_at)", [])?;
+    Ok(())
+}
+
+pub(super) fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// 计入应收的订单状态
+pub(super) fn is_receivable(status: OrderStatus) -> bool {
+    matches!(
+        status,
+        OrderStatus::Confirmed | OrderStatus::PartiallyShipped | OrderStatus::Shipped | OrderStatus::Completed
+    )
+}
+
+/// 订单在指定状态下是否计入应收：部分发货后取消的订单仍需收取已发货部分的货款
+pub(super) fn is_receivable_order(conn: &Connection, order_id: &str, status: OrderStatus) -> SqliteResult<bool> {
+    if is_receivable(status) {
+        return Ok(true);
+    }
+    if status != OrderStatus::Cancelled {
+        return Ok(false);
+    }
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM order_items WHERE order_id = ?1 AND shipped_quantity > 0)",
+        [order_id],
+        |row| row.get(0),
+    )
+}
+
+/// 订单未收金额
+pub(super) fn outstanding(order: &Order) -> f64 {
+    round2(order.payable_amount - order.paid_amount)
+}
+
+/// 调整客户应收余额并记录同步变更
+pub(super) fn adjust_customer_balance(conn: &Connection, customer_id: &str, delta: f64) -> SqliteResult<()> {
+    if customer_id.is_empty() || delta.abs() < EPSILON {
+        return Ok(());
+    }
+    let changed = conn.execute(
+        "UPDATE customers SET balance = ROUND(balance + ?1, 2), updated_at = ?2 WHERE id = ?3",
+        params![delta, Utc::now().to_rfc3339(), customer_id],
+    )?;
+    if changed > 0 {
+        if let Some(customer) = Database::load_customer(conn, customer_id)? {
+            record_change(conn, "customer", customer_id, "update", &customer)?;
+        }
+    }
+    Ok(())
+}
+
+/// 客户的信用额度与当前应收余额，客户不存在时返回 `None`
+pub(super) fn credit_position(conn: &Connection, customer_id: &str) -> SqliteResult<Option<(String, f64, f64)>> {
+    conn.query_row(
+        "SELECT name, credit_limit, balance FROM customers WHERE id = ?1",
+        [customer_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .optional()
+}
+
+fn row_to_payment(row: &rusqlite::Row) -> SqliteResult<Payment> {
+    Ok(Payment {
+        id: row.get(0)?, order_id: row.get(1)?, customer_id: row.get(2)?, payment_type: row.get(3)?,
+        amount: row.get(4)?, method: row.get(5)?, reference: row.get(6)?, remark: row.get(7)?,
+        operator_id: row.get(8)?, operator_name: row.get(9)?, paid_at: row.get(10)?, created_at: row.get(11)?,
+    })
+}
+
+impl Database {
+    /// 登记收款或退款，同时更新订单已收金额与客户应收余额
+    pub fn record_payment(&self, input: &PaymentInput, operator: &User) -> PaymentResult<(Payment, Order)> {
+        let amount = round2(input.amount);
+        if amount < 0.01 {
+            return Err(PaymentError::InvalidAmount);
+        }
+        if !PAYMENT_METHODS.contains(&input.method.as_str()) {
+            return Err(PaymentError::InvalidMethod(input.method.clone()));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let order = Self::load_order(&tx, &input.order_id)?.ok_or_else(|| PaymentError::OrderNotFound(input.order_id.clone()))?;
+        if order.order_type != "sale" {
+            return Err(PaymentError::NotSaleOrder);
+        }
+        let receivable = match OrderStatus::parse(&order.status) {
+            Some(status) => is_receivable_order(&tx, &order.id, status)?,
+            None => false,
+        };
+        let balance_delta = match input.payment_type {
+            PaymentType::Payment => {
+                if !receivable {
+                    return Err(PaymentError::NotPayable(order.status.clone()));
+                }
+                let outstanding = outstanding(&order);
+                if amount > outstanding + EPSILON {
+                    return Err(PaymentError::Overpayment { amount, outstanding });
+                }
+                -amount
+            }
+            PaymentType::Refund => {
+                if amount > order.paid_amount + EPSILON {
+                    return Err(PaymentError::RefundExceedsPaid { amount, paid: order.paid_amount });
+                }
+                // 已取消或退货的订单不再计入应收，退款只减少已收金额
+                if receivable { amount } else { 0.0 }
+            }
+        };
+
+        let now = Utc::now();
+        let paid_amount = round2(match input.payment_type {
+            PaymentType::Payment => order.paid_amount + amount,
+            PaymentType::Refund => order.paid_amount - amount,
+        });
+        tx.execute(
+            "UPDATE orders SET paid_amount = ?1, updated_at = ?2 WHERE id = ?3",
+            params![paid_amount, now.to_rfc3339(), order.id],
+        )?;
+        let order = Self::load_order(&tx, &order.id)?.ok_or_else(|| PaymentError::OrderNotFound(input.order_id.clone()))?;
+        record_change(&tx, "order", &order.id, "update", &order)?;
+
+        let payment = Payment {
+            id: uuid::Uuid::new_v4().to_string(),
+            order_id: order.id.clone(),
+            customer_id: order.customer_id.clone(),
+            payment_type: input.payment_type.as_str().to_string(),
+            amount,
+            method: input.method.clone(),
+            reference: input.reference.clone(),
+            remark: input.remark.clone(),
+            operator_id: operator.id.clone(),
+            operator_name: operator.name.clone(),
+            paid_at: if input.paid_at.is_empty() { now.format("%Y-%m-%d").to_string() } else { input.paid_at.clone() },
+            created_at: now.to_rfc3339(),
+        };
+        insert_payment(&tx, &payment)?;
+        record_change(&tx, "payment", &payment.id, "create", &payment)?;
+        adjust_customer_balance(&tx, &order.customer_id, balance_delta)?;
+        tx.commit()?;
+        Ok((payment, order))
+    }
+
+    /// 收款记录，按订单或客户筛选（都为空时返回全部），新的在前
+    pub fn list_payments(&self, order_id: Option<&str>, customer_id: Option<&str>) -> SqliteResult<Vec<Payment>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, order_id, customer_id, payment_type, amount, method, reference, remark, operator_id, operator_name, paid_at, created_at
+             FROM payments WHERE (?1 IS NULL OR order_id = ?1) AND (?2 IS NULL OR customer_id = ?2)
+             ORDER BY paid_at DESC, created_at DESC",
+        )?;
+        let rows = stmt.query_map(params![order_id, customer_id], row_to_payment)?;
+        rows.collect()
+    }
+
+    /// 应收账龄：按客户汇总计入应收的销售订单未收金额
+    pub fn ar_aging_report(&self, as_of: NaiveDate) -> SqliteResult<AgingReport> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT o.customer_id, COALESCE(NULLIF(c.name, ''), o.customer_name), o.status, o.payable_amount - o.paid_amount, o.created_at, o.id
+             FROM orders o LEFT JOIN customers c ON c.id = o.customer_id
+             WHERE o.order_type = 'sale' AND o.payable_amount - o.paid_amount > ?1",
+        )?;
+        let rows = stmt.query_map([EPSILON], |row| {
+            Ok((
+                row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, f64>(3)?,
+                row.get::<_, String>(4)?, row.get::<_, String>(5)?,
+            ))
+        })?;
+
+        let mut customers: BTreeMap<String, AgingRow> = BTreeMap::new();
+        let mut totals = AgingBuckets::default();
+        for row in rows {
+            let (customer_id, customer_name, status, amount, created_at, order_id) = row?;
+            let receivable = match OrderStatus::parse(&status) {
+                Some(status) => is_receivable_order(&conn, &order_id, status)?,
+                None => false,
+            };
+            if !receivable {
+                continue;
+            }
+            let order_date = created_at.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()).unwrap_or(as_of);
+            let age_days = (as_of - order_date).num_days();
+            let entry = customers.entry(customer_id.clone()).or_insert_with(|| AgingRow {
+                customer_id,
+                customer_name,
+                buckets: AgingBuckets::default(),
+            });
+            entry.buckets.add(age_days, amount);
+            totals.add(age_days, amount);
+        }
+
+        let mut rows: Vec<AgingRow> = customers
+            .into_values()
+            .map(|row| AgingRow { buckets: row.buckets.rounded(), ..row })
+            .collect();
+        rows.sort_by(|a, b| b.buckets.total.total_cmp(&a.buckets.total));
+        Ok(AgingReport { as_of: as_of.format("%Y-%m-%d").to_string(), rows, totals: totals.rounded() })
+    }
+}
+
+pub(super) fn insert_payment(conn: &Connection, payment: &Payment) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO payments (id, order_id, customer_id, payment_type, amount, method, reference, remark, operator_id, operator_name, paid_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            payment.id, payment.order_id, payment.customer_id, payment.payment_type, payment.amount, payment.method,
+            payment.reference, payment.remark, payment.operator_id, payment.operator_name, payment.paid_at, payment.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{self, database_with, product};
+    use crate::database::{Customer, ItemShipment, OrderError, OrderItem};
+
+    fn operator() -> User {
+        test_support::operator("finance", "财务", "manager")
+    }
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        let (dir, db) = database_with(&[product("p1", "P001", 1000)]);
+        let now = Utc::now().to_rfc3339();
+        db.create_customer(&Customer {
+            id: "c1".into(), name: "华东贸易".into(), company: String::new(), email: String::new(), phone: String::new(),
+            address: String::new(), category: "regular".into(), status: "active".into(), contact_person: String::new(),
+            credit_limit: 100.0, balance: 0.0, remark: String::new(), source: String::new(),
+            created_at: now.clone(), updated_at: now, version: 0,
+        })
+        .unwrap();
+        (dir, db)
+    }
+
+    /// 每个 2 元，数量 `quantity` 的销售订单
+    fn sale_order(db: &Database, quantity: i32) -> Order {
+        let now = Utc::now().to_rfc3339();
+        let id = uuid::Uuid::new_v4().to_string();
+        let order = Order {
+            id: id.clone(), code: format!("SO-{}", &id[..8]), order_type: "sale".into(), customer_id: "c1".into(),
+            customer_name: "华东贸易".into(), status: "draft".into(), total_quantity: 0, total_amount: 0.0,
+            discount_amount: 0.0, payable_amount: 0.0, paid_amount: 0.0, delivery_address: String::new(),
+            delivery_date: String::new(), warehouse_id: String::new(), remark: String::new(), operator_id: String::new(), operator_name: String::new(),
+            created_at: now.clone(), updated_at: now, version: 0,
+        };
+        let item = OrderItem {
+            id: uuid::Uuid::new_v4().to_string(), order_id: id, product_id: "p1".into(), product_name: "螺丝".into(),
+            product_code: "P001".into(), unit: "个".into(), quantity, unit_price: 2.0, discount: 0.0,
+            amount: quantity as f64 * 2.0, remark: String::new(), shipped_quantity: 0,
+        };
+        db.create_order_with_items(&order, &[item]).unwrap()
+    }
+
+    fn pay(db: &Database, order: &Order, payment_type: PaymentType, amount: f64) -> PaymentResult<(Payment, Order)> {
+        db.record_payment(
+            &PaymentInput {
+                order_id: order.id.clone(), payment_type, amount, method: "bank_transfer".into(),
+                reference: "TXN-001".into(), remark: String::new(), paid_at: String::new(),
+            },
+            &operator(),
+        )
+    }
+
+    fn balance(db: &Database) -> f64 {
+        db.get_customer("c1").unwrap().unwrap().balance
+    }
+
+    #[test]
+    fn test_payments_and_credit_limit() {
+        let (_dir, db) = setup();
+        let first = sale_order(&db, 30);
+        assert!(matches!(pay(&db, &first, PaymentType::Payment, 10.0), Err(PaymentError::NotPayable(_))));
+        db.confirm_order(&first.id, &operator()).unwrap();
+        assert_eq!(balance(&db), 60.0);
+
+        let second = sale_order(&db, 25);
+        let err = db.confirm_order(&second.id, &operator()).unwrap_err();
+        assert!(matches!(err, OrderError::CreditLimitExceeded { exposure, .. } if exposure == 110.0), "{}", err);
+
+        let (payment, order) = pay(&db, &first, PaymentType::Payment, 20.0).unwrap();
+        assert_eq!((payment.payment_type.as_str(), payment.reference.as_str()), ("payment", "TXN-001"));
+        assert_eq!(PaymentStatus::of(&order), PaymentStatus::PartiallyPaid);
+        assert_eq!(balance(&db), 40.0);
+        assert!(matches!(pay(&db, &first, PaymentType::Payment, 50.0), Err(PaymentError::Overpayment { .. })));
+        let (_, order) = pay(&db, &first, PaymentType::Payment, 40.0).unwrap();
+        assert_eq!(PaymentStatus::of(&order), PaymentStatus::Paid);
+        assert_eq!(balance(&db), 0.0);
+
+        db.confirm_order(&second.id, &operator()).unwrap();
+        assert_eq!(balance(&db), 50.0);
+        assert!(matches!(pay(&db, &first, PaymentType::Refund, 70.0), Err(PaymentError::RefundExceedsPaid { .. })));
+        let (_, order) = pay(&db, &first, PaymentType::Refund, 10.0).unwrap();
+        assert_eq!(order.paid_amount, 50.0);
+        assert_eq!(balance(&db), 60.0);
+
+        // 取消订单扣除其未收金额，之后的退款不再影响应收
+        db.cancel_order(&second.id, "客户取消", &operator()).unwrap();
+        assert_eq!(balance(&db), 10.0);
+        assert_eq!(db.list_payments(Some(&first.id), None).unwrap().len(), 3);
+        assert_eq!(db.list_payments(None, Some("c1")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_only_draft_orders_editable() {
+        let (_dir, db) = setup();
+
+        // 草稿订单不计入应收，可以改金额和删除
+        let draft = sale_order(&db, 10);
+        // 已付金额只能由收款记录修改
+        let saved = db.update_order(&Order { discount_amount: 5.0, payable_amount: 15.0, paid_amount: 15.0, ..draft.clone() }).unwrap();
+        assert_eq!((saved.payable_amount, saved.paid_amount), (15.0, 0.0));
+        assert_eq!(balance(&db), 0.0);
+        db.delete_order(&draft.id).unwrap();
+        assert!(db.get_order(&draft.id).unwrap().is_none());
+
+        // 已确认且有收款的订单：改金额和删除都被拒绝，应收余额与收款记录不变
+        let order = sale_order(&db, 30);
+        db.confirm_order(&order.id, &operator()).unwrap();
+        let (_, order) = pay(&db, &order, PaymentType::Payment, 20.0).unwrap();
+        assert_eq!(balance(&db), 40.0);
+        let err = db.update_order(&Order { discount_amount: 30.0, payable_amount: 30.0, ..order.clone() }).unwrap_err();
+        assert!(matches!(err, OrderError::NotDraft(OrderStatus::Confirmed)), "{}", err);
+        assert!(matches!(db.delete_order(&order.id), Err(OrderError::NotDraft(OrderStatus::Confirmed))));
+        assert_eq!(db.get_order(&order.id).unwrap().unwrap().payable_amount, 60.0);
+        assert_eq!(balance(&db), 40.0);
+        assert_eq!(db.list_payments(Some(&order.id), None).unwrap().len(), 1);
+        assert!(matches!(db.delete_order("missing"), Err(OrderError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_partially_shipped_cancel_and_return() {
+        let (_dir, db) = setup();
+        let ship = |order: &Order, quantity| {
+            let item_id = db.get_order_items(&order.id).unwrap()[0].id.clone();
+            db.ship_order(&order.id, &[ItemShipment { item_id, quantity }], "", &operator()).unwrap()
+        };
+
+        // 发出 10 件后取消：释放剩余预留，订单金额减为已发货的 20 元，应收只扣除未发货部分
+        let order = sale_order(&db, 30);
+        db.confirm_order(&order.id, &operator()).unwrap();
+        pay(&db, &order, PaymentType::Payment, 10.0).unwrap();
+        ship(&order, 10);
+        assert_eq!(balance(&db), 50.0);
+        let cancelled = db.cancel_order(&order.id, "余量取消", &operator()).unwrap();
+        assert_eq!(cancelled.status, "cancelled");
+        assert_eq!((cancelled.total_quantity, cancelled.payable_amount), (10, 20.0));
+        assert_eq!(balance(&db), 10.0);
+        let stock = db.get_stock_availability("p1").unwrap();
+        assert_eq!((stock.current_stock, stock.reserved), (990, 0));
+
+        // 已发货部分仍计入账龄，可以继续收款
+        assert_eq!(db.ar_aging_report(Utc::now().date_naive()).unwrap().totals.total, 10.0);
+        let (_, paid) = pay(&db, &cancelled, PaymentType::Payment, 10.0).unwrap();
+        assert_eq!(PaymentStatus::of(&paid), PaymentStatus::Paid);
+        assert_eq!(balance(&db), 0.0);
+
+        // 部分发货后整单退货：已发货数量退回入库，未发货部分的预留释放，应收全部扣除
+        let order = sale_order(&db, 10);
+        db.confirm_order(&order.id, &operator()).unwrap();
+        ship(&order, 4);
+        let returned = db.return_order(&order.id, "质量问题", &operator()).unwrap();
+        assert_eq!(returned.status, "returned");
+        assert_eq!(balance(&db), 0.0);
+        let stock = db.get_stock_availability("p1").unwrap();
+        assert_eq!((stock.current_stock, stock.reserved), (990, 0));
+    }
+
+    #[test]
+    fn test_ar_aging() {
+        let (_dir, db) = setup();
+        let today = Utc::now().date_naive();
+        db.conn.lock().unwrap().execute("UPDATE customers SET credit_limit = 0 WHERE id = 'c1'", []).unwrap();
+        for (quantity, age) in [(5, 3), (10, 45), (15, 75), (20, 120)] {
+            let order = sale_order(&db, quantity);
+            let created = (today - chrono::Duration::days(age)).format("%Y-%m-%dT08:00:00+00:00").to_string();
+            db.conn.lock().unwrap().execute("UPDATE orders SET created_at = ?1 WHERE id = ?2", params![created, order.id]).unwrap();
+            db.confirm_order(&order.id, &operator()).unwrap();
+            if age == 45 {
+                pay(&db, &order, PaymentType::Payment, 5.0).unwrap();
+            }
+        }
+        // 草稿订单不计入应收
+        sale_order(&db, 50);
+
+        let report = db.ar_aging_report(today).unwrap();
+        assert_eq!(report.rows.len(), 1);
+        let buckets = &report.rows[0].buckets;
+        assert_eq!((buckets.current, buckets.days_30, buckets.days_60, buckets.days_90_plus), (10.0, 15.0, 30.0, 40.0));
+        assert_eq!(report.totals.total, 95.0);
+        assert_eq!(report.totals.total, balance(&db));
+    }
+}
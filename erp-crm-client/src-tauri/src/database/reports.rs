@@ -0,0 +1,547 @@
+//! 经营报表 - 首页统计与销售报表
+//!
+//! 销售额按销售订单的应收金额统计，只计已确认及之后的订单（草稿、已取消、已退货不计）。
+//! 本月环比按相同天数比较：本月 1 日至今 与 上月 1 日至上月同一时刻。
+//! 销售报表按明细汇总，订单整单折扣按明细金额比例分摊；毛利按产品当前成本价估算（按流水结转的成本见 `valuation`）。
+//! 报表结果按查询条件缓存，订单、明细或产品有改动时重新计算。
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use super::Database;
+
+/// 计入销售额的订单条件（订单表别名为 `o`）
+pub(super) const REVENUE_FILTER: &str = "o.order_type = 'sale' AND o.status NOT IN ('draft', 'pending', 'cancelled', 'returned')";
+
+/// 最多缓存的销售报表数量，超出后清空重新缓存
+const CACHE_LIMIT: usize = 32;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DashboardStats {
+    pub total_customers: i64,
+    pub active_customers: i64,
+    pub total_products: i64,
+    pub low_stock_products: i64,
+    pub today_orders: i64,
+    pub today_revenue: f64,
+    pub monthly_orders: i64,
+    pub monthly_revenue: f64,
+    /// 上月同期销售额
+    pub previous_monthly_revenue: f64,
+    /// 环比增长（%），上月同期没有销售时为空
+    pub monthly_growth: Option<f64>,
+}
+
+/// 销售报表的汇总粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportGranularity {
+    Day,
+    /// 自然周，周一开始
+    Week,
+    Month,
+}
+
+impl ReportGranularity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportGranularity::Day => "day",
+            ReportGranularity::Week => "week",
+            ReportGranularity::Month => "month",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "day" => Some(ReportGranularity::Day),
+            "week" => Some(ReportGranularity::Week),
+            "month" => Some(ReportGranularity::Month),
+            _ => None,
+        }
+    }
+
+    /// 日期所在区间的第一天
+    fn bucket_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            ReportGranularity::Day => date,
+            ReportGranularity::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            ReportGranularity::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    fn next(&self, bucket: NaiveDate) -> NaiveDate {
+        match self {
+            ReportGranularity::Day => bucket + Days::new(1),
+            ReportGranularity::Week => bucket + Days::new(7),
+            ReportGranularity::Month => bucket + Months::new(1),
+        }
+    }
+}
+
+/// 销售报表查询条件，日期含首尾（UTC）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SalesReportQuery {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub granularity: ReportGranularity,
+    /// 产品、客户排行的条数
+    pub top: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SalesSummary {
+    pub order_count: i64,
+    pub quantity: i64,
+    pub revenue: f64,
+    pub cost: f64,
+    pub gross_profit: f64,
+    /// 毛利率（%），收入为 0 时为 0
+    pub gross_margin: f64,
+    pub average_order_value: f64,
+}
+
+/// 与上一个等长期间的对比，上期为 0 时增长率为空
+#[derive(Debug, Clone, Serialize)]
+pub struct SalesComparison {
+    pub previous_start: String,
+    pub previous_end: String,
+    pub previous: SalesSummary,
+    pub revenue_growth: Option<f64>,
+    pub order_growth: Option<f64>,
+    pub gross_profit_growth: Option<f64>,
+    pub average_order_value_growth: Option<f64>,
+}
+
+/// 趋势中的一个区间，没有销售的区间同样列出
+#[derive(Debug, Clone, Serialize)]
+pub struct SalesBucket {
+    /// 区间第一天
+    pub period: String,
+    pub order_count: i64,
+    pub quantity: i64,
+    pub revenue: f64,
+    pub gross_profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductSales {
+    pub product_id: String,
+    pub product_code: String,
+    pub product_name: String,
+    pub category: String,
+    pub order_count: i64,
+    pub quantity: i64,
+    pub revenue: f64,
+    pub cost: f64,
+    pub gross_profit: f64,
+    pub gross_margin: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomerSales {
+    pub customer_id: String,
+    pub customer_name: String,
+    pub order_count: i64,
+    pub revenue: f64,
+    pub gross_profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategorySales {
+    /// 产品分类，未分类为空字符串
+    pub category: String,
+    pub product_count: usize,
+    pub quantity: i64,
+    pub revenue: f64,
+    pub cost: f64,
+    pub gross_profit: f64,
+    pub gross_margin: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorSales {
+    pub operator_id: String,
+    pub operator_name: String,
+    pub order_count: i64,
+    pub revenue: f64,
+    pub gross_profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SalesReport {
+    pub start_date: String,
+    pub end_date: String,
+    pub granularity: ReportGranularity,
+    pub summary: SalesSummary,
+    pub comparison: SalesComparison,
+    pub trend: Vec<SalesBucket>,
+    pub top_products: Vec<ProductSales>,
+    pub top_customers: Vec<CustomerSales>,
+    pub categories: Vec<CategorySales>,
+    pub operators: Vec<OperatorSales>,
+    pub generated_at: String,
+}
+
+/// 销售报表缓存，每条记录带计算时的数据指纹
+#[derive(Default)]
+pub(super) struct SalesReportCache(Mutex<HashMap<SalesReportQuery, (String, SalesReport)>>);
+
+/// 一张销售订单的汇总
+struct OrderSales {
+    date: NaiveDate,
+    customer_id: String,
+    customer_name: String,
+    operator_id: String,
+    operator_name: String,
+    revenue: f64,
+    quantity: i64,
+    cost: f64,
+}
+
+/// `[start, end)` 期间的销售订单数与销售额，时间为 RFC 3339 字符串（UTC）
+pub(super) fn sales_between(conn: &Connection, start: &str, end: &str) -> SqliteResult<(i64, f64)> {
+    conn.query_row(
+        &format!("SELECT COUNT(*), COALESCE(SUM(o.payable_amount), 0) FROM orders o WHERE {} AND o.created_at >= ?1 AND o.created_at < ?2", REVENUE_FILTER),
+        params![start, end],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+fn timestamp(date: NaiveDate) -> String {
+    date.format("%Y-%m-%dT00:00:00").to_string()
+}
+
+/// 环比增长百分比，保留一位小数
+pub(super) fn growth(current: f64, previous: f64) -> Option<f64> {
+    if previous.abs() < 0.005 {
+        return None;
+    }
+    Some(((current - previous) / previous * 1000.0).round() / 10.0)
+}
+
+/// 毛利率（%）
+fn margin(revenue: f64, gross_profit: f64) -> f64 {
+    if revenue.abs() > f64::EPSILON { gross_profit / revenue * 100.0 } else { 0.0 }
+}
+
+/// 订单、明细、产品的数据指纹，任何一项改动都会使缓存失效
+fn fingerprint(conn: &Connection) -> SqliteResult<String> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) || ':' || COALESCE(MAX(updated_at), '') FROM orders)
+             || '|' || (SELECT COUNT(*) FROM order_items)
+             || '|' || (SELECT COUNT(*) || ':' || COALESCE(MAX(updated_at), '') FROM products)",
+        [],
+        |row| row.get(0),
+    )
+}
+
+fn load_order_sales(conn: &Connection, start: NaiveDate, end: NaiveDate) -> SqliteResult<Vec<OrderSales>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT o.created_at, o.customer_id, o.customer_name, o.operator_id, o.operator_name, o.payable_amount,
+                COALESCE(SUM(i.quantity), 0), COALESCE(SUM(i.quantity * COALESCE(p.cost_price, 0)), 0)
+         FROM orders o
+         LEFT JOIN order_items i ON i.order_id = o.id
+         LEFT JOIN products p ON p.id = i.product_id
+         WHERE {} AND o.created_at >= ?1 AND o.created_at < ?2
+         GROUP BY o.id",
+        REVENUE_FILTER
+    ))?;
+    let rows = stmt.query_map(params![timestamp(start), timestamp(end + Days::new(1))], |row| {
+        let created_at: String = row.get(0)?;
+        Ok(OrderSales {
+            date: created_at.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()).unwrap_or(start),
+            customer_id: row.get(1)?, customer_name: row.get(2)?, operator_id: row.get(3)?, operator_name: row.get(4)?,
+            revenue: row.get(5)?, quantity: row.get(6)?, cost: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn summarize(orders: &[OrderSales]) -> SalesSummary {
+    let order_count = orders.len() as i64;
+    let revenue: f64 = orders.iter().map(|o| o.revenue).sum();
+    let cost: f64 = orders.iter().map(|o| o.cost).sum();
+    SalesSummary {
+        order_count,
+        quantity: orders.iter().map(|o| o.quantity).sum(),
+        revenue,
+        cost,
+        gross_profit: revenue - cost,
+        gross_margin: margin(revenue, revenue - cost),
+        average_order_value: if order_count > 0 { revenue / order_count as f64 } else { 0.0 },
+    }
+}
+
+/// 按产品汇总明细，整单折扣按明细金额比例分摊，按销售额从高到低
+fn load_product_sales(conn: &Connection, start: NaiveDate, end: NaiveDate) -> SqliteResult<Vec<ProductSales>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.product_id, MAX(i.product_code), MAX(i.product_name), COALESCE(MAX(p.category), ''), COUNT(DISTINCT o.id), SUM(i.quantity),
+                SUM(i.amount * CASE WHEN o.total_amount > 0 THEN o.payable_amount / o.total_amount ELSE 1 END),
+                SUM(i.quantity * COALESCE(p.cost_price, 0))
+         FROM order_items i
+         JOIN orders o ON o.id = i.order_id
+         LEFT JOIN products p ON p.id = i.product_id
+         WHERE {} AND o.created_at >= ?1 AND o.created_at < ?2
+         GROUP BY i.product_id
+         ORDER BY 7 DESC",
+        REVENUE_FILTER
+    ))?;
+    let rows = stmt.query_map(params![timestamp(start), timestamp(end + Days::new(1))], |row| {
+        let revenue: f64 = row.get(6)?;
+        let cost: f64 = row.get(7)?;
+        Ok(ProductSales {
+            product_id: row.get(0)?, product_code: row.get(1)?, product_name: row.get(2)?, category: row.get(3)?,
+            order_count: row.get(4)?, quantity: row.get(5)?, revenue, cost, gross_profit: revenue - cost,
+            gross_margin: margin(revenue, revenue - cost),
+        })
+    })?;
+    rows.collect()
+}
+
+fn build_sales_report(conn: &Connection, query: &SalesReportQuery) -> SqliteResult<SalesReport> {
+    let orders = load_order_sales(conn, query.start, query.end)?;
+    let summary = summarize(&orders);
+
+    // 上一个等长期间
+    let days = (query.end - query.start).num_days().max(0) as u64 + 1;
+    let previous_end = query.start - Days::new(1);
+    let previous_start = query.start - Days::new(days);
+    let previous = summarize(&load_order_sales(conn, previous_start, previous_end)?);
+    let comparison = SalesComparison {
+        previous_start: previous_start.to_string(),
+        previous_end: previous_end.to_string(),
+        revenue_growth: growth(summary.revenue, previous.revenue),
+        order_growth: growth(summary.order_count as f64, previous.order_count as f64),
+        gross_profit_growth: growth(summary.gross_profit, previous.gross_profit),
+        average_order_value_growth: growth(summary.average_order_value, previous.average_order_value),
+        previous,
+    };
+
+    let mut buckets = BTreeMap::new();
+    let mut bucket = query.granularity.bucket_start(query.start);
+    while bucket <= query.end {
+        buckets.insert(bucket, SalesBucket { period: bucket.to_string(), order_count: 0, quantity: 0, revenue: 0.0, gross_profit: 0.0 });
+        bucket = query.granularity.next(bucket);
+    }
+    let mut customers: HashMap<&str, CustomerSales> = HashMap::new();
+    let mut operators: HashMap<&str, OperatorSales> = HashMap::new();
+    for order in &orders {
+        let profit = order.revenue - order.cost;
+        if let Some(bucket) = buckets.get_mut(&query.granularity.bucket_start(order.date)) {
+            bucket.order_count += 1;
+            bucket.quantity += order.quantity;
+            bucket.revenue += order.revenue;
+            bucket.gross_profit += profit;
+        }
+        let customer = customers.entry(&order.customer_id).or_insert_with(|| CustomerSales {
+            customer_id: order.customer_id.clone(), customer_name: order.customer_name.clone(), order_count: 0, revenue: 0.0, gross_profit: 0.0,
+        });
+        customer.order_count += 1;
+        customer.revenue += order.revenue;
+        customer.gross_profit += profit;
+        let operator = operators.entry(&order.operator_id).or_insert_with(|| OperatorSales {
+            operator_id: order.operator_id.clone(), operator_name: order.operator_name.clone(), order_count: 0, revenue: 0.0, gross_profit: 0.0,
+        });
+        operator.order_count += 1;
+        operator.revenue += order.revenue;
+        operator.gross_profit += profit;
+    }
+    let mut top_customers: Vec<_> = customers.into_values().collect();
+    top_customers.sort_by(|a, b| b.revenue.total_cmp(&a.revenue));
+    top_customers.truncate(query.top);
+    let mut operators: Vec<_> = operators.into_values().collect();
+    operators.sort_by(|a, b| b.revenue.total_cmp(&a.revenue));
+
+    let products = load_product_sales(conn, query.start, query.end)?;
+    let mut categories: BTreeMap<&str, CategorySales> = BTreeMap::new();
+    for product in &products {
+        let category = categories.entry(&product.category).or_insert_with(|| CategorySales {
+            category: product.category.clone(), product_count: 0, quantity: 0, revenue: 0.0, cost: 0.0, gross_profit: 0.0, gross_margin: 0.0,
+        });
+        category.product_count += 1;
+        category.quantity += product.quantity;
+        category.revenue += product.revenue;
+        category.cost += product.cost;
+        category.gross_profit += product.gross_profit;
+    }
+    let mut categories: Vec<_> = categories.into_values().map(|c| CategorySales { gross_margin: margin(c.revenue, c.gross_profit), ..c }).collect();
+    categories.sort_by(|a, b| b.revenue.total_cmp(&a.revenue));
+
+    Ok(SalesReport {
+        start_date: query.start.to_string(),
+        end_date: query.end.to_string(),
+        granularity: query.granularity,
+        summary,
+        comparison,
+        trend: buckets.into_values().collect(),
+        top_products: products.into_iter().take(query.top).collect(),
+        top_customers,
+        categories,
+        operators,
+        generated_at: Utc::now().to_rfc3339(),
+    })
+}
+
+impl Database {
+    /// 首页统计，`now` 为统计时刻（UTC）
+    pub fn dashboard_stats(&self, now: DateTime<Utc>) -> SqliteResult<DashboardStats> {
+        let conn = self.conn.lock().unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0));
+        let today = now.date_naive();
+        let month_start = today.with_day(1).unwrap_or(today);
+        let previous_start = month_start - Months::new(1);
+        // 上月同期：从上月 1 日起经过与本月相同的时间，不超过本月 1 日
+        let elapsed = now.naive_utc() - month_start.and_hms_opt(0, 0, 0).unwrap_or_default();
+        let previous_end = (previous_start.and_hms_opt(0, 0, 0).unwrap_or_default() + elapsed)
+            .min(month_start.and_hms_opt(0, 0, 0).unwrap_or_default());
+        let now_str = now.to_rfc3339();
+
+        let (today_orders, today_revenue) = sales_between(&conn, &timestamp(today), &now_str)?;
+        let (monthly_orders, monthly_revenue) = sales_between(&conn, &timestamp(month_start), &now_str)?;
+        let (_, previous_monthly_revenue) = sales_between(
+            &conn,
+            &timestamp(previous_start),
+            &previous_end.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+        )?;
+        Ok(DashboardStats {
+            total_customers: count("SELECT COUNT(*) FROM customers")?,
+            active_customers: count("SELECT COUNT(*) FROM customers WHERE status = 'active'")?,
+            total_products: count("SELECT COUNT(*) FROM products")?,
+            low_stock_products: count("SELECT COUNT(*) FROM products WHERE current_stock < min_stock")?,
+            today_orders,
+            today_revenue,
+            monthly_orders,
+            monthly_revenue,
+            previous_monthly_revenue,
+            monthly_growth: growth(monthly_revenue, previous_monthly_revenue),
+        })
+    }
+
+    /// 销售报表，数据没有变化时直接返回缓存的结果
+    pub fn sales_report(&self, query: &SalesReportQuery) -> SqliteResult<SalesReport> {
+        let conn = self.conn.lock().unwrap();
+        let fingerprint = fingerprint(&conn)?;
+        let mut cache = self.sales_reports.0.lock().unwrap();
+        if let Some((cached, report)) = cache.get(query) {
+            if *cached == fingerprint {
+                return Ok(report.clone());
+            }
+        }
+        let report = build_sales_report(&conn, query)?;
+        if cache.len() >= CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(*query, (fingerprint, report.clone()));
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{self, database as setup};
+    use crate::database::{Order, OrderItem, Product};
+
+    fn order(db: &Database, status: &str, amount: f64, created_at: &str) {
+        let id = uuid::Uuid::new_v4().to_string();
+        db.create_order(&Order {
+            id: id.clone(), code: format!("SO-{}", &id[..8]), order_type: "sale".into(), customer_id: "c1".into(),
+            customer_name: "客户".into(), status: status.into(), total_quantity: 1, total_amount: amount, discount_amount: 0.0,
+            payable_amount: amount, paid_amount: 0.0, delivery_address: String::new(), delivery_date: String::new(), warehouse_id: String::new(),
+            remark: String::new(), operator_id: String::new(), operator_name: String::new(),
+            created_at: created_at.into(), updated_at: created_at.into(), version: 0,
+        })
+        .unwrap();
+    }
+
+    fn product(db: &Database, id: &str, category: &str, cost_price: f64) {
+        let product = test_support::product(id, &id.to_uppercase(), 0);
+        db.create_product(&Product { name: id.into(), category: category.into(), cost_price, sell_price: 0.0, ..product }).unwrap();
+    }
+
+    /// 带明细的销售订单，`items` 为（产品、数量、单价）
+    fn sale(db: &Database, customer: &str, operator: &str, created_at: &str, discount: f64, items: &[(&str, i32, f64)]) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let order = Order {
+            id: id.clone(), code: format!("SO-{}", &id[..8]), order_type: "sale".into(), customer_id: customer.into(),
+            customer_name: customer.to_uppercase(), status: "completed".into(), total_quantity: 0, total_amount: 0.0,
+            discount_amount: discount, payable_amount: 0.0, paid_amount: 0.0, delivery_address: String::new(),
+            delivery_date: String::new(), warehouse_id: String::new(), remark: String::new(), operator_id: operator.into(), operator_name: operator.to_uppercase(),
+            created_at: created_at.into(), updated_at: created_at.into(), version: 0,
+        };
+        let items: Vec<_> = items
+            .iter()
+            .map(|(product_id, quantity, unit_price)| OrderItem {
+                id: uuid::Uuid::new_v4().to_string(), order_id: id.clone(), product_id: product_id.to_string(),
+                product_name: product_id.to_string(), product_code: product_id.to_uppercase(), unit: "个".into(),
+                quantity: *quantity, unit_price: *unit_price, discount: 0.0, amount: *quantity as f64 * unit_price,
+                remark: String::new(), shipped_quantity: 0,
+            })
+            .collect();
+        db.create_order_with_items(&order, &items).unwrap();
+    }
+
+    #[test]
+    fn test_dashboard_monthly_revenue_and_growth() {
+        let (_dir, db) = setup();
+        order(&db, "completed", 300.0, "2026-10-03T09:00:00+00:00");
+        order(&db, "confirmed", 100.0, "2026-10-19T08:00:00+00:00");
+        order(&db, "cancelled", 999.0, "2026-10-10T08:00:00+00:00");
+        order(&db, "draft", 999.0, "2026-10-11T08:00:00+00:00");
+        // 上月同期（9 月 1 日至 9 月 19 日 12 点）只计 200，19 日之后的不计入比较
+        order(&db, "completed", 200.0, "2026-09-05T08:00:00+00:00");
+        order(&db, "completed", 500.0, "2026-09-25T08:00:00+00:00");
+
+        let now = DateTime::parse_from_rfc3339("2026-10-19T12:00:00+00:00").unwrap().with_timezone(&Utc);
+        let stats = db.dashboard_stats(now).unwrap();
+        assert_eq!((stats.today_orders, stats.monthly_orders), (1, 2));
+        assert!((stats.today_revenue - 100.0).abs() < 1e-9);
+        assert!((stats.monthly_revenue - 400.0).abs() < 1e-9);
+        assert!((stats.previous_monthly_revenue - 200.0).abs() < 1e-9);
+        assert_eq!(stats.monthly_growth, Some(100.0));
+        assert_eq!(growth(50.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_sales_report_breakdowns_comparison_and_cache() {
+        let (_dir, db) = setup();
+        product(&db, "p1", "电子", 6.0);
+        product(&db, "p2", "耗材", 2.0);
+        // 整单折扣 4 按明细金额分摊：p1 18，p2 18
+        sale(&db, "c1", "u1", "2026-10-05T09:00:00+00:00", 4.0, &[("p1", 2, 10.0), ("p2", 5, 4.0)]);
+        sale(&db, "c2", "u2", "2026-10-14T09:00:00+00:00", 0.0, &[("p2", 10, 4.0)]);
+        sale(&db, "c1", "u1", "2026-09-20T09:00:00+00:00", 0.0, &[("p1", 1, 10.0)]);
+        order(&db, "draft", 999.0, "2026-10-06T09:00:00+00:00");
+
+        let start = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2026, 10, 31).unwrap();
+        let query = SalesReportQuery { start, end, granularity: ReportGranularity::Week, top: 10 };
+        let report = db.sales_report(&query).unwrap();
+        assert_eq!((report.summary.order_count, report.summary.quantity), (2, 17));
+        assert!((report.summary.revenue - 76.0).abs() < 1e-9);
+        assert!((report.summary.gross_profit - 34.0).abs() < 1e-9);
+        assert_eq!(report.comparison.previous_start, "2026-08-31");
+        assert_eq!(report.comparison.revenue_growth, Some(660.0));
+
+        // 按周汇总从 9 月 28 日（周一）开始，没有销售的周也列出
+        let periods: Vec<_> = report.trend.iter().map(|b| b.period.as_str()).collect();
+        assert_eq!(periods, ["2026-09-28", "2026-10-05", "2026-10-12", "2026-10-19", "2026-10-26"]);
+        assert_eq!(report.trend[1].order_count, 1);
+
+        assert_eq!(report.top_products[0].product_id, "p2");
+        assert!((report.top_products[0].revenue - 58.0).abs() < 1e-9);
+        assert_eq!(report.top_customers[0].customer_id, "c2");
+        assert_eq!(report.categories.iter().map(|c| c.category.as_str()).collect::<Vec<_>>(), ["耗材", "电子"]);
+        let category_revenue: f64 = report.categories.iter().map(|c| c.revenue).sum();
+        assert!((category_revenue - report.summary.revenue).abs() < 1e-9);
+        assert_eq!(report.operators.len(), 2);
+
+        // 数据没有变化时返回缓存，新增订单后重新计算
+        assert_eq!(db.sales_report(&query).unwrap().generated_at, report.generated_at);
+        sale(&db, "c3", "u1", "2026-10-20T09:00:00+00:00", 0.0, &[("p1", 1, 10.0)]);
+        assert_eq!(db.sales_report(&query).unwrap().summary.order_count, 3);
+    }
+}
@@ -0,0 +1,550 @@
+//! 库存盘点 - 盘点单、账面数量冻结与差异过账
+//!
+//! 开始盘点时按仓库（可限定分类）生成盘点明细，冻结当时各库位的账面数量和成本价；
+//! 盘点期间逐行录入或扫码累加实盘数量，盘点中发生的出入库不影响冻结的账面数量。
+//! 审核过账时在一个事务中把每行的差异（实盘 - 账面）作为 `adjust` 流水写入，未录入实盘的行不调整。
+//! 盘点单只保存在本机，过账生成的库存流水照常同步。
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::inventory::{self, StockError, StockMovement};
+use super::warehouses::{check_warehouse, level_quantity, resolve_place};
+use super::{Database, StockBatch, StockRecord, User};
+
+#[derive(Error, Debug)]
+pub enum StockCheckError {
+    #[error("盘点单不存在: {0}")]
+    NotFound(String),
+    #[error("盘点明细不存在: {0}")]
+    ItemNotFound(String),
+    #[error("该仓库没有可盘点的产品")]
+    Empty,
+    #[error("该仓库已有进行中的盘点单 {0}")]
+    AlreadyOpen(String),
+    #[error("实盘数量不能为负数: {0}")]
+    InvalidQuantity(String),
+    #[error("盘点单{status}，不能{action}")]
+    InvalidStatus { status: &'static str, action: &'static str },
+    #[error(transparent)]
+    Stock(#[from] StockError),
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type StockCheckResult<T> = std::result::Result<T, StockCheckError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockCheckStatus {
+    /// 盘点中，可以录入实盘数量
+    Checking,
+    /// 已审核过账
+    Completed,
+    Cancelled,
+}
+
+impl StockCheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StockCheckStatus::Checking => "checking",
+            StockCheckStatus::Completed => "completed",
+            StockCheckStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "checking" => Some(StockCheckStatus::Checking),
+            "completed" => Some(StockCheckStatus::Completed),
+            "cancelled" => Some(StockCheckStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            StockCheckStatus::Checking => "盘点中",
+            StockCheckStatus::Completed => "已过账",
+            StockCheckStatus::Cancelled => "已取消",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StockCheck {
+    pub id: String,
+    pub code: String,
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    /// 限定盘点的产品分类，为空表示整个仓库
+    pub category: String,
+    pub status: String,
+    pub remark: String,
+    pub operator_id: String,
+    pub operator_name: String,
+    /// 审核过账的用户，未过账时为空字符串
+    pub approver_id: String,
+    pub approver_name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// 过账或取消时间
+    pub checked_at: String,
+    /// 列表查询时不加载明细
+    pub items: Vec<StockCheckItem>,
+}
+
+/// 盘点明细，一个产品在一个库位一行
+#[derive(Debug, Clone, Serialize)]
+pub struct StockCheckItem {
+    pub id: String,
+    pub check_id: String,
+    pub product_id: String,
+    pub product_code: String,
+    pub product_name: String,
+    pub barcode: String,
+    pub unit: String,
+    pub location: String,
+    /// 开始盘点时冻结的账面数量
+    pub system_quantity: i32,
+    /// 实盘数量，未录入时为空
+    pub actual_quantity: Option<i32>,
+    /// 实盘 - 账面，未录入时为空
+    pub difference: Option<i32>,
+    /// 开始盘点时的成本价
+    pub unit_cost: f64,
+    /// 差异金额 = 差异 × 成本价，盘盈为正、盘亏为负
+    pub value_impact: f64,
+    pub remark: String,
+    pub counted_at: String,
+}
+
+/// 录入一行实盘数量
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StockCountInput {
+    pub item_id: String,
+    pub actual_quantity: i32,
+    #[serde(default)]
+    pub remark: String,
+}
+
+/// 盘点差异汇总
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StockCheckSummary {
+    pub total_items: usize,
+    pub counted_items: usize,
+    pub variance_items: usize,
+    pub gain_quantity: i32,
+    pub loss_quantity: i32,
+    pub gain_value: f64,
+    pub loss_value: f64,
+    pub net_value: f64,
+}
+
+/// 差异审核视图：有差异的明细与尚未录入的明细
+#[derive(Debug, Clone, Serialize)]
+pub struct StockCheckReport {
+    pub id: String,
+    pub code: String,
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    pub status: String,
+    pub summary: StockCheckSummary,
+    pub variances: Vec<StockCheckItem>,
+    pub uncounted: Vec<StockCheckItem>,
+}
+
+pub(super) fn create_stock_check_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_checks (
+            id TEXT PRIMARY KEY,
+            code TEXT UNIQUE NOT NULL,
+            warehouse_id TEXT NOT NULL,
+            category TEXT DEFAULT '',
+            status TEXT NOT NULL DEFAULT 'checking',
+            remark TEXT DEFAULT '',
+            operator_id TEXT DEFAULT '',
+            operator_name TEXT DEFAULT '',
+            approver_id TEXT DEFAULT '',
+            approver_name TEXT DEFAULT '',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            checked_at TEXT DEFAULT ''
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_check_items (
+            id TEXT PRIMARY KEY,
+            check_id TEXT NOT NULL,
+            product_id TEXT NOT NULL,
+            product_code TEXT DEFAULT '',
+            product_name TEXT DEFAULT '',
+            barcode TEXT DEFAULT '',
+            unit TEXT DEFAULT '',
+            location TEXT DEFAULT '',
+            system_quantity INTEGER NOT NULL,
+            actual_quantity INTEGER,
+            unit_cost REAL DEFAULT 0,
+            remark TEXT DEFAULT '',
+            counted_at TEXT DEFAULT '',
+            UNIQUE(check_id, product_id, location)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_checks_status ON stock_checks(warehouse_id, status)", [])?;
+    Ok(())
+}
+
+const CHECK_COLUMNS: &str = "c.id, c.code, c.warehouse_id, COALESCE(w.name, c.warehouse_id), c.category, c.status, c.remark,
+    c.operator_id, c.operator_name, c.approver_id, c.approver_name, c.created_at, c.updated_at, c.checked_at";
+
+fn row_to_check(row: &rusqlite::Row) -> SqliteResult<StockCheck> {
+    Ok(StockCheck {
+        id: row.get(0)?, code: row.get(1)?, warehouse_id: row.get(2)?, warehouse_name: row.get(3)?, category: row.get(4)?,
+        status: row.get(5)?, remark: row.get(6)?, operator_id: row.get(7)?, operator_name: row.get(8)?,
+        approver_id: row.get(9)?, approver_name: row.get(10)?, created_at: row.get(11)?, updated_at: row.get(12)?,
+        checked_at: row.get(13)?, items: Vec::new(),
+    })
+}
+
+fn load_check(conn: &Connection, id: &str) -> SqliteResult<Option<StockCheck>> {
+    let check = conn
+        .query_row(
+            &format!("SELECT {} FROM stock_checks c LEFT JOIN warehouses w ON w.id = c.warehouse_id WHERE c.id = ?1", CHECK_COLUMNS),
+            [id],
+            row_to_check,
+        )
+        .optional()?;
+    match check {
+        Some(mut c) => {
+            c.items = load_items(conn, &c.id)?;
+            Ok(Some(c))
+        }
+        None => Ok(None),
+    }
+}
+
+const ITEM_COLUMNS: &str = "id, check_id, product_id, product_code, product_name, barcode, unit, location, system_quantity,
+    actual_quantity, unit_cost, remark, counted_at";
+
+fn row_to_item(row: &rusqlite::Row) -> SqliteResult<StockCheckItem> {
+    let system_quantity: i32 = row.get(8)?;
+    let actual_quantity: Option<i32> = row.get(9)?;
+    let unit_cost: f64 = row.get(10)?;
+    let difference = actual_quantity.map(|actual| actual - system_quantity);
+    Ok(StockCheckItem {
+        id: row.get(0)?, check_id: row.get(1)?, product_id: row.get(2)?, product_code: row.get(3)?, product_name: row.get(4)?,
+        barcode: row.get(5)?, unit: row.get(6)?, location: row.get(7)?, system_quantity, actual_quantity, difference,
+        unit_cost, value_impact: difference.unwrap_or(0) as f64 * unit_cost, remark: row.get(11)?, counted_at: row.get(12)?,
+    })
+}
+
+fn load_items(conn: &Connection, check_id: &str) -> SqliteResult<Vec<StockCheckItem>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM stock_check_items WHERE check_id = ?1 ORDER BY location, product_code, rowid",
+        ITEM_COLUMNS
+    ))?;
+    let rows = stmt.query_map([check_id], row_to_item)?;
+    rows.collect()
+}
+
+fn load_checking(conn: &Connection, id: &str, action: &'static str) -> StockCheckResult<StockCheck> {
+    let check = load_check(conn, id)?.ok_or_else(|| StockCheckError::NotFound(id.to_string()))?;
+    match StockCheckStatus::parse(&check.status) {
+        Some(StockCheckStatus::Checking) => Ok(check),
+        Some(status) => Err(StockCheckError::InvalidStatus { status: status.label(), action }),
+        None => Err(StockCheckError::InvalidStatus { status: "状态未知", action }),
+    }
+}
+
+fn touch(conn: &Connection, id: &str) -> SqliteResult<()> {
+    conn.execute("UPDATE stock_checks SET updated_at = ?1 WHERE id = ?2", params![Utc::now().to_rfc3339(), id])?;
+    Ok(())
+}
+
+fn summarize(items: &[StockCheckItem]) -> StockCheckSummary {
+    let mut summary = StockCheckSummary { total_items: items.len(), ..Default::default() };
+    for item in items {
+        let Some(difference) = item.difference else { continue };
+        summary.counted_items += 1;
+        if difference > 0 {
+            summary.variance_items += 1;
+            summary.gain_quantity += difference;
+            summary.gain_value += item.value_impact;
+        } else if difference < 0 {
+            summary.variance_items += 1;
+            summary.loss_quantity -= difference;
+            summary.loss_value -= item.value_impact;
+        }
+    }
+    summary.net_value = summary.gain_value - summary.loss_value;
+    summary
+}
+
+impl Database {
+    /// 开始盘点：冻结仓库（可限定分类）各库位的账面数量，同一仓库同时只能有一张进行中的盘点单
+    pub fn create_stock_check(&self, warehouse_id: &str, category: Option<&str>, remark: &str, operator: &User) -> StockCheckResult<StockCheck> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        check_warehouse(&tx, warehouse_id)?;
+        let open: Option<String> = tx
+            .query_row("SELECT code FROM stock_checks WHERE warehouse_id = ?1 AND status = 'checking'", [warehouse_id], |row| row.get(0))
+            .optional()?;
+        if let Some(code) = open {
+            return Err(StockCheckError::AlreadyOpen(code));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let code = format!("PD{}{}", now.format("%Y%m%d%H%M%S"), &id[..4].to_uppercase());
+        let now = now.to_rfc3339();
+        let category = category.unwrap_or("");
+        tx.execute(
+            "INSERT INTO stock_checks (id, code, warehouse_id, category, status, remark, operator_id, operator_name, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, 'checking', ?5, ?6, ?7, ?8, ?8)",
+            params![id, code, warehouse_id, category, remark, operator.id, operator.name, now],
+        )?;
+        let frozen = tx.execute(
+            "INSERT INTO stock_check_items (id, check_id, product_id, product_code, product_name, barcode, unit, location, system_quantity, unit_cost)
+             SELECT lower(hex(randomblob(16))), ?1, l.product_id, p.code, p.name, p.barcode, p.unit, l.location, l.quantity, p.cost_price
+             FROM stock_levels l JOIN products p ON p.id = l.product_id
+             WHERE l.warehouse_id = ?2 AND (?3 = '' OR p.category = ?3)",
+            params![id, warehouse_id, category],
+        )?;
+        if frozen == 0 {
+            return Err(StockCheckError::Empty);
+        }
+        let check = load_check(&tx, &id)?.ok_or_else(|| StockCheckError::NotFound(id.clone()))?;
+        tx.commit()?;
+        Ok(check)
+    }
+
+    /// 批量录入实盘数量（覆盖已录入的数量）
+    pub fn record_stock_counts(&self, id: &str, counts: &[StockCountInput]) -> StockCheckResult<StockCheck> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        load_checking(&tx, id, "录入")?;
+        let now = Utc::now().to_rfc3339();
+        for count in counts {
+            let name: String = tx
+                .query_row("SELECT product_name FROM stock_check_items WHERE id = ?1 AND check_id = ?2", [&count.item_id, id], |row| row.get(0))
+                .optional()?
+                .ok_or_else(|| StockCheckError::ItemNotFound(count.item_id.clone()))?;
+            if count.actual_quantity < 0 {
+                return Err(StockCheckError::InvalidQuantity(name));
+            }
+            tx.execute(
+                "UPDATE stock_check_items SET actual_quantity = ?1, remark = ?2, counted_at = ?3 WHERE id = ?4",
+                params![count.actual_quantity, count.remark, now, count.item_id],
+            )?;
+        }
+        touch(&tx, id)?;
+        let check = load_check(&tx, id)?.ok_or_else(|| StockCheckError::NotFound(id.to_string()))?;
+        tx.commit()?;
+        Ok(check)
+    }
+
+    /// 扫码计数：实盘数量累加 `quantity`
+    ///
+    /// 库位为空时优先累加到该产品已有的明细，否则用产品在该仓库的默认库位；
+    /// 盘点单中没有的产品（如放错库位）新增一行，账面数量取当前结存。
+    pub fn scan_stock_count(&self, id: &str, product_id: &str, location: &str, quantity: i32) -> StockCheckResult<StockCheckItem> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let check = load_checking(&tx, id, "录入")?;
+        let existing = check
+            .items
+            .iter()
+            .find(|item| item.product_id == product_id && (location.is_empty() || item.location == location));
+        let item_id = match existing {
+            Some(item) => {
+                if item.actual_quantity.unwrap_or(0) + quantity < 0 {
+                    return Err(StockCheckError::InvalidQuantity(item.product_name.clone()));
+                }
+                item.id.clone()
+            }
+            None => {
+                if quantity < 0 {
+                    return Err(StockCheckError::InvalidQuantity(product_id.to_string()));
+                }
+                let (_, location) = resolve_place(&tx, product_id, &check.warehouse_id, location)?;
+                let system_quantity = level_quantity(&tx, product_id, &check.warehouse_id, &location)?;
+                let item_id = uuid::Uuid::new_v4().to_string();
+                tx.execute(
+                    "INSERT INTO stock_check_items (id, check_id, product_id, product_code, product_name, barcode, unit, location, system_quantity, unit_cost)
+                     SELECT ?1, ?2, id, code, name, barcode, unit, ?3, ?4, cost_price FROM products WHERE id = ?5",
+                    params![item_id, id, location, system_quantity, product_id],
+                )?;
+                item_id
+            }
+        };
+        tx.execute(
+            "UPDATE stock_check_items SET actual_quantity = COALESCE(actual_quantity, 0) + ?1, counted_at = ?2 WHERE id = ?3",
+            params![quantity, Utc::now().to_rfc3339(), item_id],
+        )?;
+        touch(&tx, id)?;
+        let item = tx.query_row(&format!("SELECT {} FROM stock_check_items WHERE id = ?1", ITEM_COLUMNS), [&item_id], row_to_item)?;
+        tx.commit()?;
+        Ok(item)
+    }
+
+    /// 差异审核：汇总盘盈盘亏数量与金额
+    pub fn stock_check_report(&self, id: &str) -> StockCheckResult<StockCheckReport> {
+        let check = load_check(&self.conn.lock().unwrap(), id)?.ok_or_else(|| StockCheckError::NotFound(id.to_string()))?;
+        let summary = summarize(&check.items);
+        let (counted, uncounted): (Vec<_>, Vec<_>) = check.items.into_iter().partition(|item| item.actual_quantity.is_some());
+        Ok(StockCheckReport {
+            id: check.id, code: check.code, warehouse_id: check.warehouse_id, warehouse_name: check.warehouse_name,
+            status: check.status, summary, variances: counted.into_iter().filter(|item| item.difference != Some(0)).collect(), uncounted,
+        })
+    }
+
+    /// 审核过账：所有差异在一个事务中写入调整流水，任何一行失败整单不过账
+    pub fn post_stock_check(&self, id: &str, approver: &User) -> StockCheckResult<(StockCheck, Vec<StockRecord>)> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let check = load_checking(&tx, id, "过账")?;
+        let mut records = Vec::new();
+        for item in &check.items {
+            let Some(difference) = item.difference.filter(|d| *d != 0) else { continue };
+            // 盘点期间已删除的产品没有库存可调整
+            let exists = tx.query_row("SELECT 1 FROM products WHERE id = ?1", [&item.product_id], |_| Ok(())).optional()?.is_some();
+            if !exists {
+                continue;
+            }
+            let remark = if item.remark.is_empty() { format!("盘点 {}", check.code) } else { format!("盘点 {}: {}", check.code, item.remark) };
+            records.extend(inventory::apply_movement(&tx, &StockMovement {
+                product_id: &item.product_id, quantity_change: difference, record_type: "adjust", unit_price: item.unit_cost,
+                remark: &remark, batch: &StockBatch::default(), order_id: "", warehouse_id: &check.warehouse_id, location: &item.location,
+            })?);
+        }
+        let now = Utc::now().to_rfc3339();
+        tx.execute(
+            "UPDATE stock_checks SET status = 'completed', approver_id = ?1, approver_name = ?2, updated_at = ?3, checked_at = ?3 WHERE id = ?4",
+            params![approver.id, approver.name, now, id],
+        )?;
+        let saved = load_check(&tx, id)?.ok_or_else(|| StockCheckError::NotFound(id.to_string()))?;
+        tx.commit()?;
+        Ok((saved, records))
+    }
+
+    /// 取消盘点，不调整库存
+    pub fn cancel_stock_check(&self, id: &str, reason: &str) -> StockCheckResult<StockCheck> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let check = load_checking(&tx, id, "取消")?;
+        let remark = match (check.remark.is_empty(), reason.is_empty()) {
+            (_, true) => check.remark.clone(),
+            (true, false) => format!("取消: {}", reason),
+            (false, false) => format!("{}；取消: {}", check.remark, reason),
+        };
+        let now = Utc::now().to_rfc3339();
+        tx.execute(
+            "UPDATE stock_checks SET status = 'cancelled', remark = ?1, updated_at = ?2, checked_at = ?2 WHERE id = ?3",
+            params![remark, now, id],
+        )?;
+        let saved = load_check(&tx, id)?.ok_or_else(|| StockCheckError::NotFound(id.to_string()))?;
+        tx.commit()?;
+        Ok(saved)
+    }
+
+    pub fn get_stock_check(&self, id: &str) -> SqliteResult<Option<StockCheck>> {
+        load_check(&self.conn.lock().unwrap(), id)
+    }
+
+    /// 盘点单列表（不含明细），可按状态筛选，最新的在前
+    pub fn list_stock_checks(&self, status: Option<&str>) -> SqliteResult<Vec<StockCheck>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM stock_checks c LEFT JOIN warehouses w ON w.id = c.warehouse_id
+             WHERE ?1 IS NULL OR c.status = ?1 ORDER BY c.created_at DESC",
+            CHECK_COLUMNS
+        ))?;
+        let rows = stmt.query_map([status], row_to_check)?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{self, database_with};
+    use crate::database::{Product, DEFAULT_WAREHOUSE_ID};
+
+    fn operator() -> User {
+        test_support::operator("keeper", "库管", "manager")
+    }
+
+    fn product(id: &str, code: &str, category: &str, stock: i32, location: &str) -> Product {
+        Product {
+            barcode: format!("69{}", code), name: code.into(), category: category.into(), cost_price: 2.5, sell_price: 4.0,
+            location: location.into(), ..test_support::product(id, code, stock)
+        }
+    }
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        database_with(&[
+            product("p1", "P001", "五金", 10, "A-01"),
+            product("p2", "P002", "五金", 5, "A-02"),
+            product("p3", "P003", "耗材", 8, "B-01"),
+        ])
+    }
+
+    #[test]
+    fn test_stock_check_freezes_book_and_posts_variances() {
+        let (_dir, db) = setup();
+        let check = db.create_stock_check(DEFAULT_WAREHOUSE_ID, Some("五金"), "", &operator()).unwrap();
+        assert_eq!(check.items.len(), 2);
+        assert!(matches!(
+            db.create_stock_check(DEFAULT_WAREHOUSE_ID, None, "", &operator()),
+            Err(StockCheckError::AlreadyOpen(_))
+        ));
+
+        // 盘点期间出库 2 个，冻结的账面数量不变，过账时只调整盘点差异
+        db.update_stock(&StockMovement {
+            product_id: "p1", quantity_change: -2, record_type: "out", unit_price: 0.0, remark: "",
+            batch: &StockBatch::default(), order_id: "", warehouse_id: "", location: "",
+        })
+        .unwrap();
+        let p1 = check.items.iter().find(|item| item.product_id == "p1").unwrap();
+        assert_eq!(p1.system_quantity, 10);
+        db.record_stock_counts(&check.id, &[StockCountInput { item_id: p1.id.clone(), actual_quantity: 9, remark: "破损".into() }]).unwrap();
+        // 扫码两次累加到 p2 的明细
+        db.scan_stock_count(&check.id, "p2", "", 4).unwrap();
+        let item = db.scan_stock_count(&check.id, "p2", "", 3).unwrap();
+        assert_eq!((item.actual_quantity, item.difference), (Some(7), Some(2)));
+
+        let report = db.stock_check_report(&check.id).unwrap();
+        assert_eq!((report.summary.gain_quantity, report.summary.loss_quantity), (2, 1));
+        assert!((report.summary.net_value - 2.5).abs() < 1e-9);
+        assert!(report.uncounted.is_empty());
+
+        let (posted, records) = db.post_stock_check(&check.id, &operator()).unwrap();
+        assert_eq!(posted.status, "completed");
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.record_type == "adjust" && r.remark.starts_with("盘点 PD")));
+        assert_eq!(db.get_product("p1").unwrap().unwrap().current_stock, 7);
+        assert_eq!(db.get_product("p2").unwrap().unwrap().current_stock, 7);
+        assert_eq!(db.get_product("p3").unwrap().unwrap().current_stock, 8);
+        assert!(matches!(db.post_stock_check(&check.id, &operator()), Err(StockCheckError::InvalidStatus { .. })));
+    }
+
+    #[test]
+    fn test_stock_check_scan_adds_missing_item_and_cancel() {
+        let (_dir, db) = setup();
+        let check = db.create_stock_check(DEFAULT_WAREHOUSE_ID, Some("耗材"), "季度盘点", &operator()).unwrap();
+        // 在 B-01 扫到了不在本次盘点范围的 p1，新增一行，账面数量为该库位结存 0
+        let item = db.scan_stock_count(&check.id, "p1", "B-01", 1).unwrap();
+        assert_eq!((item.location.as_str(), item.system_quantity, item.difference), ("B-01", 0, Some(1)));
+        assert_eq!(db.stock_check_report(&check.id).unwrap().uncounted.len(), 1);
+
+        let cancelled = db.cancel_stock_check(&check.id, "重新安排").unwrap();
+        assert_eq!((cancelled.status.as_str(), cancelled.remark.as_str()), ("cancelled", "季度盘点；取消: 重新安排"));
+        assert_eq!(db.list_stock_levels(Some("p1"), None).unwrap().len(), 1);
+        assert_eq!(db.list_stock_checks(Some("cancelled")).unwrap().len(), 1);
+        assert!(db.create_stock_check(DEFAULT_WAREHOUSE_ID, None, "", &operator()).is_ok());
+    }
+}
@@ -0,0 +1,49 @@
+//! 数据库模块测试共用的临时数据库、产品、仓库和操作人
+
+use chrono::Utc;
+
+use super::{Database, Product, User, Warehouse};
+
+/// 临时目录中的新数据库，目录随返回的 `TempDir` 一起删除
+pub fn database() -> (tempfile::TempDir, Database) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::new(dir.path().join("test.db")).unwrap();
+    (dir, db)
+}
+
+/// 建好 `products` 的临时数据库
+pub fn database_with(products: &[Product]) -> (tempfile::TempDir, Database) {
+    let (dir, db) = database();
+    for product in products {
+        db.create_product(product).unwrap();
+    }
+    (dir, db)
+}
+
+/// 名为"螺丝"、进价 1 售价 2 的在售产品，不指定仓库和库位
+pub fn product(id: &str, code: &str, stock: i32) -> Product {
+    let now = Utc::now().to_rfc3339();
+    Product {
+        id: id.into(), code: code.into(), barcode: String::new(), name: "螺丝".into(), category: String::new(),
+        unit: "个".into(), specification: String::new(), brand: String::new(), cost_price: 1.0, sell_price: 2.0,
+        min_stock: 0, max_stock: 0, current_stock: stock, warehouse_id: String::new(), location: String::new(),
+        status: "active".into(), description: String::new(), created_at: now.clone(), updated_at: now, version: 0,
+    }
+}
+
+/// 启用中的仓库，编码为 `id` 的大写
+pub fn warehouse(id: &str, name: &str) -> Warehouse {
+    let now = Utc::now().to_rfc3339();
+    Warehouse {
+        id: id.into(), code: id.to_uppercase(), name: name.into(), address: String::new(), manager: String::new(),
+        phone: String::new(), status: "active".into(), created_at: now.clone(), updated_at: now,
+    }
+}
+
+pub fn operator(username: &str, name: &str, role: &str) -> User {
+    User {
+        id: "u1".into(), username: username.into(), name: name.into(), email: String::new(), phone: String::new(),
+        role: role.into(), department: String::new(), status: "active".into(), last_login_at: None,
+        created_at: String::new(), updated_at: String::new(),
+    }
+}
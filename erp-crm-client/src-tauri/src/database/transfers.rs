@@ -0,0 +1,421 @@
+//! 仓库调拨 - 调拨单与在途库存
+//!
+//! 创建调拨单即从调出仓库出库（`transfer_out`），货物进入在途；调入仓库确认收货后入库（`transfer_in`）。
+//! 在途期间可以取消，货物退回调出仓库。调拨不改变产品的合计库存，但在途数量不能用于订单预留。
+//! 调出时按先到期先出分配批次，明细按批次拆分，收货时原批次入库。
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::inventory::{self, StockError, StockMovement};
+use super::warehouses::check_warehouse;
+use super::{record_change, Database, StockBatch, User};
+
+#[derive(Error, Debug)]
+pub enum TransferError {
+    #[error("调拨单不存在: {0}")]
+    NotFound(String),
+    #[error("调出和调入不能是同一个仓库")]
+    SameWarehouse,
+    #[error("调拨单没有明细")]
+    Empty,
+    #[error("调拨数量必须大于 0: {0}")]
+    InvalidQuantity(String),
+    #[error("调拨单{status}，不能{action}")]
+    InvalidStatus { status: &'static str, action: &'static str },
+    #[error(transparent)]
+    Stock(#[from] StockError),
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type TransferResult<T> = std::result::Result<T, TransferError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    /// 已从调出仓库出库，等待收货
+    InTransit,
+    Received,
+    Cancelled,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferStatus::InTransit => "in_transit",
+            TransferStatus::Received => "received",
+            TransferStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "in_transit" => Some(TransferStatus::InTransit),
+            "received" => Some(TransferStatus::Received),
+            "cancelled" => Some(TransferStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            TransferStatus::InTransit => "在途",
+            TransferStatus::Received => "已收货",
+            TransferStatus::Cancelled => "已取消",
+        }
+    }
+}
+
+/// 调拨单，明细随单据一起同步
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransfer {
+    pub id: String,
+    pub code: String,
+    pub from_warehouse_id: String,
+    pub to_warehouse_id: String,
+    pub status: String,
+    pub remark: String,
+    pub operator_id: String,
+    pub operator_name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// 收货或取消时间，在途时为空字符串
+    #[serde(default)]
+    pub closed_at: String,
+    #[serde(default)]
+    pub items: Vec<StockTransferItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransferItem {
+    pub id: String,
+    pub transfer_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: i32,
+    pub from_location: String,
+    pub to_location: String,
+    /// 调出时按先到期先出分配的批次，一个批次一行；未分批的库存为空字符串
+    #[serde(default)]
+    pub batch_no: String,
+    #[serde(default)]
+    pub production_date: String,
+    #[serde(default)]
+    pub expiry_date: String,
+}
+
+/// 创建调拨单时的一行明细，库位为空时用产品在该仓库的默认库位
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TransferItemInput {
+    pub product_id: String,
+    pub quantity: i32,
+    #[serde(default)]
+    pub from_location: String,
+    #[serde(default)]
+    pub to_location: String,
+}
+
+pub(super) fn create_transfer_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_transfers (
+            id TEXT PRIMARY KEY,
+            code TEXT UNIQUE NOT NULL,
+            from_warehouse_id TEXT NOT NULL,
+            to_warehouse_id TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'in_transit',
+            remark TEXT DEFAULT '',
+            operator_id TEXT DEFAULT '',
+            operator_name TEXT DEFAULT '',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            closed_at TEXT DEFAULT ''
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_transfer_items (
+            id TEXT PRIMARY KEY,
+            transfer_id TEXT NOT NULL,
+            product_id TEXT NOT NULL,
+            product_name TEXT DEFAULT '',
+            quantity INTEGER NOT NULL,
+            from_location TEXT DEFAULT '',
+            to_location TEXT DEFAULT ''
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_transfer_items_transfer ON stock_transfer_items(transfer_id)", [])?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_transfers_status ON stock_transfers(status)", [])?;
+    Ok(())
+}
+
+/// 按服务器数据写入调拨单及明细（不记录同步变更）
+pub(super) fn write_transfer(conn: &Connection, t: &StockTransfer) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO stock_transfers (id, code, from_warehouse_id, to_warehouse_id, status, remark, operator_id, operator_name, created_at, updated_at, closed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            t.id, t.code, t.from_warehouse_id, t.to_warehouse_id, t.status, t.remark,
+            t.operator_id, t.operator_name, t.created_at, t.updated_at, t.closed_at
+        ],
+    )?;
+    conn.execute("DELETE FROM stock_transfer_items WHERE transfer_id = ?1", [&t.id])?;
+    for item in &t.items {
+        conn.execute(
+            "INSERT INTO stock_transfer_items (id, transfer_id, product_id, product_name, quantity, from_location, to_location, batch_no, production_date, expiry_date)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                item.id, t.id, item.product_id, item.product_name, item.quantity, item.from_location, item.to_location,
+                item.batch_no, item.production_date, item.expiry_date
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn load_transfer(conn: &Connection, id: &str) -> SqliteResult<Option<StockTransfer>> {
+    let transfer = conn
+        .query_row(
+            "SELECT id, code, from_warehouse_id, to_warehouse_id, status, remark, operator_id, operator_name, created_at, updated_at, closed_at
+             FROM stock_transfers WHERE id = ?1",
+            [id],
+            row_to_transfer,
+        )
+        .optional()?;
+    match transfer {
+        Some(mut t) => {
+            t.items = load_transfer_items(conn, &t.id)?;
+            Ok(Some(t))
+        }
+        None => Ok(None),
+    }
+}
+
+fn row_to_transfer(row: &rusqlite::Row) -> SqliteResult<StockTransfer> {
+    Ok(StockTransfer {
+        id: row.get(0)?, code: row.get(1)?, from_warehouse_id: row.get(2)?, to_warehouse_id: row.get(3)?,
+        status: row.get(4)?, remark: row.get(5)?, operator_id: row.get(6)?, operator_name: row.get(7)?,
+        created_at: row.get(8)?, updated_at: row.get(9)?, closed_at: row.get(10)?, items: Vec::new(),
+    })
+}
+
+fn load_transfer_items(conn: &Connection, transfer_id: &str) -> SqliteResult<Vec<StockTransferItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, transfer_id, product_id, product_name, quantity, from_location, to_location, batch_no, production_date, expiry_date
+         FROM stock_transfer_items WHERE transfer_id = ?1 ORDER BY rowid",
+    )?;
+    let rows = stmt.query_map([transfer_id], |row| {
+        Ok(StockTransferItem {
+            id: row.get(0)?, transfer_id: row.get(1)?, product_id: row.get(2)?, product_name: row.get(3)?,
+            quantity: row.get(4)?, from_location: row.get(5)?, to_location: row.get(6)?,
+            batch_no: row.get(7)?, production_date: row.get(8)?, expiry_date: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// 产品的在途调拨数量
+pub(super) fn in_transit_quantity(conn: &Connection, product_id: &str) -> SqliteResult<i32> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(i.quantity), 0) FROM stock_transfer_items i
+         JOIN stock_transfers t ON t.id = i.transfer_id
+         WHERE i.product_id = ?1 AND t.status = 'in_transit'",
+        [product_id],
+        |row| row.get(0),
+    )
+}
+
+/// 收货或取消时按明细的批次入库
+fn return_item(conn: &Connection, item: &StockTransferItem, warehouse_id: &str, location: &str, remark: &str) -> TransferResult<()> {
+    let batch = StockBatch { batch_no: item.batch_no.clone(), production_date: item.production_date.clone(), expiry_date: item.expiry_date.clone() };
+    inventory::apply_movement(conn, &StockMovement {
+        product_id: &item.product_id, quantity_change: item.quantity, record_type: "transfer_in", unit_price: 0.0, remark,
+        batch: &batch, order_id: "", warehouse_id, location,
+    })?;
+    Ok(())
+}
+
+fn close_transfer(conn: &Connection, transfer: &StockTransfer, status: TransferStatus) -> TransferResult<StockTransfer> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE stock_transfers SET status = ?1, updated_at = ?2, closed_at = ?2 WHERE id = ?3",
+        params![status.as_str(), now, transfer.id],
+    )?;
+    let saved = load_transfer(conn, &transfer.id)?.ok_or_else(|| TransferError::NotFound(transfer.id.clone()))?;
+    record_change(conn, "stock_transfer", &saved.id, "update", &saved)?;
+    Ok(saved)
+}
+
+fn load_in_transit(conn: &Connection, id: &str, action: &'static str) -> TransferResult<StockTransfer> {
+    let transfer = load_transfer(conn, id)?.ok_or_else(|| TransferError::NotFound(id.to_string()))?;
+    match TransferStatus::parse(&transfer.status) {
+        Some(TransferStatus::InTransit) => Ok(transfer),
+        Some(status) => Err(TransferError::InvalidStatus { status: status.label(), action }),
+        None => Err(TransferError::InvalidStatus { status: "状态未知", action }),
+    }
+}
+
+impl Database {
+    /// 创建调拨单并从调出仓库出库，整单在一个事务中完成
+    pub fn create_transfer(&self, from_warehouse_id: &str, to_warehouse_id: &str, items: &[TransferItemInput], remark: &str, operator: &User) -> TransferResult<StockTransfer> {
+        if from_warehouse_id == to_warehouse_id {
+            return Err(TransferError::SameWarehouse);
+        }
+        if items.is_empty() {
+            return Err(TransferError::Empty);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        check_warehouse(&tx, from_warehouse_id)?;
+        check_warehouse(&tx, to_warehouse_id)?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let code = format!("TR{}{}", now.format("%Y%m%d%H%M%S"), &id[..4].to_uppercase());
+        let movement_remark = format!("调拨出库 {}", code);
+        let mut transfer_items = Vec::with_capacity(items.len());
+        for item in items {
+            let product_name: String = tx
+                .query_row("SELECT name FROM products WHERE id = ?1", [&item.product_id], |row| row.get(0))
+                .optional()?
+                .ok_or_else(|| StockError::ProductNotFound(item.product_id.clone()))?;
+            if item.quantity <= 0 {
+                return Err(TransferError::InvalidQuantity(product_name));
+            }
+            // 未指定批次，按先到期先出拆分，明细一个批次一行
+            let records = inventory::apply_movement(&tx, &StockMovement {
+                product_id: &item.product_id, quantity_change: -item.quantity, record_type: "transfer_out", unit_price: 0.0,
+                remark: &movement_remark, batch: &StockBatch::default(), order_id: "", warehouse_id: from_warehouse_id,
+                location: &item.from_location,
+            })?;
+            transfer_items.extend(records.into_iter().map(|record| StockTransferItem {
+                id: uuid::Uuid::new_v4().to_string(), transfer_id: id.clone(), product_id: item.product_id.clone(),
+                product_name: product_name.clone(), quantity: record.quantity, from_location: record.location,
+                to_location: item.to_location.clone(), batch_no: record.batch_no, production_date: record.production_date,
+                expiry_date: record.expiry_date,
+            }));
+        }
+
+        let now = now.to_rfc3339();
+        let transfer = StockTransfer {
+            id: id.clone(), code, from_warehouse_id: from_warehouse_id.to_string(), to_warehouse_id: to_warehouse_id.to_string(),
+            status: TransferStatus::InTransit.as_str().to_string(), remark: remark.to_string(),
+            operator_id: operator.id.clone(), operator_name: operator.name.clone(),
+            created_at: now.clone(), updated_at: now, closed_at: String::new(), items: transfer_items,
+        };
+        write_transfer(&tx, &transfer)?;
+        record_change(&tx, "stock_transfer", &id, "create", &transfer)?;
+        tx.commit()?;
+        Ok(transfer)
+    }
+
+    /// 调入仓库确认收货
+    pub fn receive_transfer(&self, id: &str) -> TransferResult<StockTransfer> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let transfer = load_in_transit(&tx, id, "收货")?;
+        let remark = format!("调拨入库 {}", transfer.code);
+        for item in &transfer.items {
+            return_item(&tx, item, &transfer.to_warehouse_id, &item.to_location, &remark)?;
+        }
+        let saved = close_transfer(&tx, &transfer, TransferStatus::Received)?;
+        tx.commit()?;
+        Ok(saved)
+    }
+
+    /// 取消在途调拨，货物退回调出仓库的原库位
+    pub fn cancel_transfer(&self, id: &str, reason: &str) -> TransferResult<StockTransfer> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let transfer = load_in_transit(&tx, id, "取消")?;
+        let remark = if reason.is_empty() { format!("取消调拨 {}", transfer.code) } else { format!("取消调拨 {}: {}", transfer.code, reason) };
+        for item in &transfer.items {
+            return_item(&tx, item, &transfer.from_warehouse_id, &item.from_location, &remark)?;
+        }
+        let saved = close_transfer(&tx, &transfer, TransferStatus::Cancelled)?;
+        tx.commit()?;
+        Ok(saved)
+    }
+
+    pub fn get_transfer(&self, id: &str) -> SqliteResult<Option<StockTransfer>> {
+        load_transfer(&self.conn.lock().unwrap(), id)
+    }
+
+    /// 调拨单列表，可按状态筛选，最新的在前
+    pub fn list_transfers(&self, status: Option<&str>) -> SqliteResult<Vec<StockTransfer>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, code, from_warehouse_id, to_warehouse_id, status, remark, operator_id, operator_name, created_at, updated_at, closed_at
+             FROM stock_transfers WHERE ?1 IS NULL OR status = ?1 ORDER BY created_at DESC",
+        )?;
+        let mut transfers = stmt.query_map([status], row_to_transfer)?.collect::<SqliteResult<Vec<_>>>()?;
+        for transfer in &mut transfers {
+            transfer.items = load_transfer_items(&conn, &transfer.id)?;
+        }
+        Ok(transfers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{self, database_with, product, warehouse};
+    use crate::database::{Product, DEFAULT_WAREHOUSE_ID};
+
+    fn operator() -> User {
+        test_support::operator("keeper", "库管", "staff")
+    }
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        let (dir, db) = database_with(&[Product { location: "A-01".into(), ..product("p1", "P001", 10) }]);
+        db.create_warehouse(&warehouse("south", "南仓")).unwrap();
+        (dir, db)
+    }
+
+    fn quantity(db: &Database, warehouse_id: &str) -> i32 {
+        db.list_stock_levels(Some("p1"), Some(warehouse_id)).unwrap().iter().map(|l| l.quantity).sum()
+    }
+
+    #[test]
+    fn test_transfer_in_transit_and_receive() {
+        let (_dir, db) = setup();
+        let items = [TransferItemInput { product_id: "p1".into(), quantity: 4, to_location: "B-02".into(), ..Default::default() }];
+        let transfer = db.create_transfer(DEFAULT_WAREHOUSE_ID, "south", &items, "", &operator()).unwrap();
+        assert_eq!(transfer.status, "in_transit");
+        assert_eq!(transfer.items[0].from_location, "A-01");
+
+        // 在途：调出仓库已扣减，调入仓库未增加，合计库存不变但不能预留
+        assert_eq!((quantity(&db, DEFAULT_WAREHOUSE_ID), quantity(&db, "south")), (6, 0));
+        let availability = db.get_stock_availability("p1").unwrap();
+        assert_eq!((availability.current_stock, availability.in_transit, availability.available), (10, 4, 6));
+
+        let received = db.receive_transfer(&transfer.id).unwrap();
+        assert_eq!(received.status, "received");
+        assert_eq!((quantity(&db, DEFAULT_WAREHOUSE_ID), quantity(&db, "south")), (6, 4));
+        assert_eq!(db.list_stock_levels(Some("p1"), Some("south")).unwrap()[0].location, "B-02");
+        assert_eq!(db.get_stock_availability("p1").unwrap().available, 10);
+        assert!(matches!(db.cancel_transfer(&transfer.id, ""), Err(TransferError::InvalidStatus { .. })));
+    }
+
+    #[test]
+    fn test_transfer_cancel_and_validation() {
+        let (_dir, db) = setup();
+        let too_many = [TransferItemInput { product_id: "p1".into(), quantity: 11, ..Default::default() }];
+        assert!(matches!(
+            db.create_transfer(DEFAULT_WAREHOUSE_ID, "south", &too_many, "", &operator()),
+            Err(TransferError::Stock(StockError::InsufficientWarehouseStock { .. }))
+        ));
+        assert!(matches!(db.create_transfer("south", "south", &too_many, "", &operator()), Err(TransferError::SameWarehouse)));
+        assert_eq!(quantity(&db, DEFAULT_WAREHOUSE_ID), 10);
+
+        let items = [TransferItemInput { product_id: "p1".into(), quantity: 3, ..Default::default() }];
+        let transfer = db.create_transfer(DEFAULT_WAREHOUSE_ID, "south", &items, "", &operator()).unwrap();
+        db.cancel_transfer(&transfer.id, "车辆故障").unwrap();
+        assert_eq!((quantity(&db, DEFAULT_WAREHOUSE_ID), quantity(&db, "south")), (10, 0));
+        assert_eq!(db.get_product("p1").unwrap().unwrap().current_stock, 10);
+        assert_eq!(db.list_transfers(Some("cancelled")).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! 本地用户 - 账号资料与登录凭据（不参与数据同步）
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+
+use super::Database;
+
+/// 用户资料（不含密码哈希）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    /// admin / manager / staff / warehouse / viewer
+    pub role: String,
+    pub department: String,
+    /// active / inactive
+    pub status: String,
+    pub last_login_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 登录凭据与失败计数
+#[derive(Debug, Clone)]
+pub struct UserCredentials {
+    pub password_hash: String,
+    pub failed_attempts: i32,
+    /// 锁定截止时间（RFC 3339），未锁定为 `None`
+    pub locked_until: Option<String>,
+}
+
+pub(super) fn create_users_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            name TEXT NOT NULL,
+            email TEXT DEFAULT '',
+            phone TEXT DEFAULT '',
+            role TEXT DEFAULT 'staff',
+            department TEXT DEFAULT '',
+            status TEXT DEFAULT 'active',
+            failed_attempts INTEGER DEFAULT 0,
+            locked_until TEXT,
+            last_login_at TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+const USER_COLUMNS: &str =
+    "id, username, name, email, phone, role, department, status, last_login_at, created_at, updated_at";
+
+fn row_to_user(row: &rusqlite::Row) -> SqliteResult<User> {
+    Ok(User {
+        id: row.get(0)?, username: row.get(1)?, name: row.get(2)?, email: row.get(3)?,
+        phone: row.get(4)?, role: row.get(5)?, department: row.get(6)?, status: row.get(7)?,
+        last_login_at: row.get(8)?, created_at: row.get(9)?, updated_at: row.get(10)?,
+    })
+}
+
+impl Database {
+    pub fn count_users(&self) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+    }
+
+    pub fn create_user(&self, user: &User, password_hash: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO users (id, username, password_hash, name, email, phone, role, department, status, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                user.id, user.username, password_hash, user.name, user.email, user.phone,
+                user.role, user.department, user.status, user.created_at, user.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 仅在没有任何用户时创建（首次运行设置管理员），返回是否创建
+    pub fn create_first_user(&self, user: &User, password_hash: &str) -> SqliteResult<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let count: i64 = tx.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+        if count > 0 {
+            return Ok(false);
+        }
+        tx.execute(
+            "INSERT INTO users (id, username, password_hash, name, email, phone, role, department, status, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                user.id, user.username, password_hash, user.name, user.email, user.phone,
+                user.role, user.department, user.status, user.created_at, user.updated_at
+            ],
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// 更新资料、角色与状态（不含密码）
+    pub fn update_user(&self, user: &User) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET name = ?2, email = ?3, phone = ?4, role = ?5, department = ?6, status = ?7, updated_at = ?8 WHERE id = ?1",
+            params![user.id, user.name, user.email, user.phone, user.role, user.department, user.status, user.updated_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_user(&self, id: &str) -> SqliteResult<Option<User>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(&format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS), [id], row_to_user)
+            .optional()
+    }
+
+    pub fn list_users(&self) -> SqliteResult<Vec<User>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM users ORDER BY created_at", USER_COLUMNS))?;
+        let rows = stmt.query_map([], row_to_user)?;
+        rows.collect()
+    }
+
+    /// 按用户名（不区分大小写）读取用户及其凭据
+    pub fn get_user_credentials(&self, username: &str) -> SqliteResult<Option<(User, UserCredentials)>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {}, password_hash, failed_attempts, locked_until FROM users WHERE username = ?1", USER_COLUMNS),
+            [username],
+            |row| {
+                Ok((
+                    row_to_user(row)?,
+                    UserCredentials { password_hash: row.get(11)?, failed_attempts: row.get(12)?, locked_until: row.get(13)? },
+                ))
+            },
+        )
+        .optional()
+    }
+
+    pub fn get_password_hash(&self, id: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT password_hash FROM users WHERE id = ?1", [id], |row| row.get(0))
+            .optional()
+    }
+
+    /// 设置新密码并解除锁定
+    pub fn set_password_hash(&self, id: &str, password_hash: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET password_hash = ?2, failed_attempts = 0, locked_until = NULL, updated_at = ?3 WHERE id = ?1",
+            params![id, password_hash, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// 记录一次登录失败；`lock_until` 不为空时锁定账号并清零失败计数
+    pub fn record_login_failure(&self, id: &str, lock_until: Option<&str>) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        match lock_until {
+            Some(until) => conn.execute(
+                "UPDATE users SET failed_attempts = 0, locked_until = ?2 WHERE id = ?1",
+                params![id, until],
+            )?,
+            None => conn.execute(
+                "UPDATE users SET failed_attempts = failed_attempts + 1 WHERE id = ?1",
+                [id],
+            )?,
+        };
+        Ok(())
+    }
+
+    /// 登录成功：清零失败计数并记录登录时间
+    pub fn record_login_success(&self, id: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET failed_attempts = 0, locked_until = NULL, last_login_at = ?2 WHERE id = ?1",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
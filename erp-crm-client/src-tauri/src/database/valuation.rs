@@ -0,0 +1,401 @@
+//! 库存估值 - 移动加权平均 / 先进先出成本与订单销售成本
+//!
+//! 成本不单独保存，每次按产品重放库存流水计算：期初库存（第一条流水的变动前数量，没有流水时为当前库存）按产品成本价入账，
+//! 之后入库按流水单价入账（单价为 0 的入库、退货入库按当时的单位成本），出库按计价方法结转成本并归集到关联订单。
+//! 调拨只在仓库之间移动，不影响产品的成本。重放后的数量与当前库存不一致时（如同步下来的库存）按单位成本补齐差额。
+
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::{set_state, Database};
+
+/// 成本计价方法的设置项（保存在 sync_state 表）
+pub const COST_METHOD_KEY: &str = "cost_method";
+
+/// 成本计价方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostMethod {
+    /// 移动加权平均（默认）：每次入库后重新计算平均单位成本
+    MovingAverage,
+    /// 先进先出：出库依次消耗最早入库的成本层
+    Fifo,
+}
+
+impl CostMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CostMethod::MovingAverage => "moving_average",
+            CostMethod::Fifo => "fifo",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "moving_average" => Some(CostMethod::MovingAverage),
+            "fifo" => Some(CostMethod::Fifo),
+            _ => None,
+        }
+    }
+}
+
+/// 单个产品的库存估值
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductValuation {
+    pub product_id: String,
+    pub product_code: String,
+    pub product_name: String,
+    pub category: String,
+    pub quantity: i32,
+    pub unit_cost: f64,
+    /// 库存金额，负库存按 0 计
+    pub total_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryValuation {
+    pub category: String,
+    pub product_count: usize,
+    pub quantity: i32,
+    pub total_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryValuation {
+    pub method: CostMethod,
+    pub total_quantity: i32,
+    pub total_value: f64,
+    pub categories: Vec<CategoryValuation>,
+    pub items: Vec<ProductValuation>,
+}
+
+/// 已完成销售订单的销售成本与毛利
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderCost {
+    pub order_id: String,
+    pub order_code: String,
+    pub customer_id: String,
+    pub customer_name: String,
+    pub created_at: String,
+    /// 应收金额（扣除折扣）
+    pub revenue: f64,
+    /// 出库成本减去退货入库冲回的成本
+    pub cost: f64,
+    pub gross_profit: f64,
+    /// 毛利率（%），收入为 0 时为 0
+    pub gross_margin: f64,
+}
+
+/// 成本台账：先进先出按入库顺序保留成本层；移动加权平均只保留一个合并后的成本层
+struct CostLedger {
+    method: CostMethod,
+    layers: VecDeque<(i32, f64)>,
+    /// 负库存数量：出库时没有成本层可消耗的部分，后续入库先补足
+    shortage: i32,
+    last_cost: f64,
+}
+
+impl CostLedger {
+    fn new(method: CostMethod, fallback_cost: f64) -> Self {
+        CostLedger { method, layers: VecDeque::new(), shortage: 0, last_cost: fallback_cost }
+    }
+
+    fn quantity(&self) -> i32 {
+        self.layers.iter().map(|(quantity, _)| quantity).sum::<i32>() - self.shortage
+    }
+
+    fn value(&self) -> f64 {
+        self.layers.iter().map(|(quantity, cost)| *quantity as f64 * cost).sum()
+    }
+
+    /// 当前单位成本，没有库存时为最近一次的成本
+    fn unit_cost(&self) -> f64 {
+        let on_hand: i32 = self.layers.iter().map(|(quantity, _)| quantity).sum();
+        if on_hand > 0 { self.value() / on_hand as f64 } else { self.last_cost }
+    }
+
+    fn receive(&mut self, quantity: i32, cost: f64) {
+        let covered = quantity.min(self.shortage);
+        self.shortage -= covered;
+        let quantity = quantity - covered;
+        self.last_cost = cost;
+        if quantity == 0 {
+            return;
+        }
+        match (self.method, self.layers.front_mut()) {
+            (CostMethod::MovingAverage, Some((on_hand, average))) => {
+                let total = *on_hand + quantity;
+                *average = (*on_hand as f64 * *average + quantity as f64 * cost) / total as f64;
+                *on_hand = total;
+            }
+            _ => self.layers.push_back((quantity, cost)),
+        }
+    }
+
+    /// 出库并返回结转的成本
+    fn issue(&mut self, quantity: i32) -> f64 {
+        let mut remaining = quantity;
+        let mut cost = 0.0;
+        while remaining > 0 {
+            let Some((on_hand, unit_cost)) = self.layers.front_mut() else { break };
+            let take = remaining.min(*on_hand);
+            cost += take as f64 * *unit_cost;
+            self.last_cost = *unit_cost;
+            *on_hand -= take;
+            remaining -= take;
+            if *on_hand == 0 {
+                self.layers.pop_front();
+            }
+        }
+        self.shortage += remaining;
+        cost + remaining as f64 * self.last_cost
+    }
+}
+
+/// 重放一个产品的库存流水
+struct ProductCost {
+    quantity: i32,
+    unit_cost: f64,
+    value: f64,
+    /// 按订单归集的销售成本（出库为正，退货入库冲回为负）
+    order_costs: HashMap<String, f64>,
+}
+
+pub(super) fn cost_method(conn: &Connection) -> SqliteResult<CostMethod> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM sync_state WHERE key = ?1", [COST_METHOD_KEY], |row| row.get(0))
+        .optional()?;
+    Ok(value.as_deref().and_then(CostMethod::parse).unwrap_or(CostMethod::MovingAverage))
+}
+
+fn replay_product(conn: &Connection, product_id: &str, cost_price: f64, current_stock: i32, method: CostMethod) -> SqliteResult<ProductCost> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT record_type, quantity, before_quantity, after_quantity, unit_price, order_id
+         FROM stock_records WHERE product_id = ?1 ORDER BY created_at, rowid",
+    )?;
+    let records = stmt
+        .query_map([product_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?, row.get::<_, i32>(2)?, row.get::<_, i32>(3)?, row.get::<_, f64>(4)?, row.get::<_, String>(5)?))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut ledger = CostLedger::new(method, cost_price);
+    let opening = records.first().map_or(current_stock, |(_, _, before, ..)| *before);
+    if opening > 0 {
+        ledger.receive(opening, cost_price);
+    } else {
+        ledger.shortage = -opening;
+    }
+    let mut order_costs: HashMap<String, f64> = HashMap::new();
+    for (record_type, quantity, before, after, unit_price, order_id) in &records {
+        let change = match record_type.as_str() {
+            "transfer_out" | "transfer_in" => continue,
+            "out" => -quantity,
+            "adjust" => after - before,
+            _ => *quantity,
+        };
+        if change < 0 {
+            let cost = ledger.issue(-change);
+            if !order_id.is_empty() {
+                *order_costs.entry(order_id.clone()).or_default() += cost;
+            }
+        } else if change > 0 {
+            // 采购入库、盘盈带单价；退货入库按当时的单位成本冲回
+            let cost = if *unit_price > 0.0 && record_type != "return" { *unit_price } else { ledger.unit_cost() };
+            ledger.receive(change, cost);
+            if !order_id.is_empty() && record_type == "return" {
+                *order_costs.entry(order_id.clone()).or_default() -= change as f64 * cost;
+            }
+        }
+    }
+    let diff = current_stock - ledger.quantity();
+    if diff > 0 {
+        let cost = ledger.unit_cost();
+        ledger.receive(diff, cost);
+    } else if diff < 0 {
+        ledger.issue(-diff);
+    }
+    Ok(ProductCost { quantity: ledger.quantity(), unit_cost: ledger.unit_cost(), value: ledger.value(), order_costs })
+}
+
+/// 一组订单的销售成本，只重放这些订单涉及的产品
+fn order_costs(conn: &Connection, order_ids: &[String], method: CostMethod) -> SqliteResult<HashMap<String, f64>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT DISTINCT r.product_id, p.cost_price, p.current_stock FROM stock_records r
+         JOIN products p ON p.id = r.product_id WHERE r.order_id = ?1",
+    )?;
+    let mut products = BTreeMap::new();
+    for order_id in order_ids {
+        for row in stmt.query_map([order_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, i32>(2)?)))? {
+            let (product_id, cost_price, current_stock) = row?;
+            products.insert(product_id, (cost_price, current_stock));
+        }
+    }
+    let mut costs = HashMap::new();
+    for (product_id, (cost_price, current_stock)) in products {
+        for (order_id, cost) in replay_product(conn, &product_id, cost_price, current_stock, method)?.order_costs {
+            *costs.entry(order_id).or_default() += cost;
+        }
+    }
+    Ok(costs)
+}
+
+pub(super) fn completed_sale_costs(conn: &Connection, start: &str, end: &str) -> SqliteResult<Vec<OrderCost>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, code, customer_id, customer_name, created_at, payable_amount FROM orders
+         WHERE order_type = 'sale' AND status = 'completed' AND created_at >= ?1 AND created_at < ?2
+         ORDER BY created_at",
+    )?;
+    let orders = stmt
+        .query_map([start, end], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get::<_, String>(4)?, row.get::<_, f64>(5)?))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    let ids: Vec<String> = orders.iter().map(|(id, ..)| id.clone()).collect();
+    let costs = order_costs(conn, &ids, cost_method(conn)?)?;
+    Ok(orders
+        .into_iter()
+        .map(|(order_id, order_code, customer_id, customer_name, created_at, revenue)| {
+            let cost = costs.get(&order_id).copied().unwrap_or(0.0);
+            let gross_profit = revenue - cost;
+            OrderCost {
+                order_id, order_code, customer_id, customer_name, created_at, revenue, cost, gross_profit,
+                gross_margin: if revenue.abs() > f64::EPSILON { gross_profit / revenue * 100.0 } else { 0.0 },
+            }
+        })
+        .collect())
+}
+
+impl Database {
+    pub fn get_cost_method(&self) -> SqliteResult<CostMethod> {
+        cost_method(&self.conn.lock().unwrap())
+    }
+
+    pub fn set_cost_method(&self, method: CostMethod) -> SqliteResult<()> {
+        set_state(&self.conn.lock().unwrap(), COST_METHOD_KEY, method.as_str())
+    }
+
+    /// 按当前计价方法计算全部产品的库存金额
+    pub fn inventory_valuation(&self) -> SqliteResult<InventoryValuation> {
+        let conn = self.conn.lock().unwrap();
+        let method = cost_method(&conn)?;
+        let mut stmt = conn.prepare("SELECT id, code, name, category, cost_price, current_stock FROM products ORDER BY code")?;
+        let products = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get::<_, f64>(4)?, row.get::<_, i32>(5)?))
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        let mut items = Vec::with_capacity(products.len());
+        let mut categories: BTreeMap<String, CategoryValuation> = BTreeMap::new();
+        for (product_id, product_code, product_name, category, cost_price, current_stock) in products {
+            let cost = replay_product(&conn, &product_id, cost_price, current_stock, method)?;
+            let entry = categories.entry(category.clone()).or_insert_with(|| CategoryValuation {
+                category: category.clone(), product_count: 0, quantity: 0, total_value: 0.0,
+            });
+            entry.product_count += 1;
+            entry.quantity += cost.quantity;
+            entry.total_value += cost.value;
+            items.push(ProductValuation {
+                product_id, product_code, product_name, category, quantity: cost.quantity, unit_cost: cost.unit_cost, total_value: cost.value,
+            });
+        }
+        Ok(InventoryValuation {
+            method,
+            total_quantity: items.iter().map(|item| item.quantity).sum(),
+            total_value: items.iter().map(|item| item.total_value).sum(),
+            categories: categories.into_values().collect(),
+            items,
+        })
+    }
+
+    /// 已完成销售订单的销售成本与毛利，按下单时间 `[start, end)` 筛选（日期或 RFC 3339 时间）
+    pub fn order_costs(&self, start: &str, end: &str) -> SqliteResult<Vec<OrderCost>> {
+        completed_sale_costs(&self.conn.lock().unwrap(), start, end)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::database::test_support::{database_with, operator, product};
+    use crate::database::{Order, OrderItem, Product, StockBatch, StockMovement};
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        database_with(&[Product { category: "五金".into(), sell_price: 5.0, ..product("p1", "P001", 10) }])
+    }
+
+    fn movement(db: &Database, quantity_change: i32, record_type: &str, unit_price: f64) {
+        db.update_stock(&StockMovement {
+            product_id: "p1", quantity_change, record_type, unit_price, remark: "", batch: &StockBatch::default(),
+            order_id: "", warehouse_id: "", location: "",
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_moving_average_and_fifo_valuation() {
+        let (_dir, db) = setup();
+        // 期初 10 个 @1，入库 10 个 @3，出库 15 个（单价为售价，不影响成本）
+        movement(&db, 10, "in", 3.0);
+        movement(&db, -15, "out", 5.0);
+
+        let average = db.inventory_valuation().unwrap();
+        assert_eq!((average.method, average.total_quantity), (CostMethod::MovingAverage, 5));
+        assert!((average.items[0].unit_cost - 2.0).abs() < 1e-9);
+        assert!((average.total_value - 10.0).abs() < 1e-9);
+        assert_eq!(average.categories[0].category, "五金");
+
+        // 先进先出：剩下的 5 个都是 @3 的那一层
+        db.set_cost_method(CostMethod::Fifo).unwrap();
+        let fifo = db.inventory_valuation().unwrap();
+        assert_eq!(fifo.method, CostMethod::Fifo);
+        assert!((fifo.total_value - 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_completed_order_cost_of_goods_sold() {
+        let (_dir, db) = setup();
+        movement(&db, 10, "in", 3.0);
+        let now = Utc::now().to_rfc3339();
+        let id = uuid::Uuid::new_v4().to_string();
+        let order = Order {
+            id: id.clone(), code: format!("SO-{}", &id[..8]), order_type: "sale".into(), customer_id: String::new(),
+            customer_name: String::new(), status: "draft".into(), total_quantity: 0, total_amount: 0.0, discount_amount: 0.0,
+            payable_amount: 0.0, paid_amount: 0.0, delivery_address: String::new(), delivery_date: String::new(), warehouse_id: String::new(),
+            remark: String::new(), operator_id: String::new(), operator_name: String::new(), created_at: now.clone(),
+            updated_at: now, version: 0,
+        };
+        let item = OrderItem {
+            id: uuid::Uuid::new_v4().to_string(), order_id: id.clone(), product_id: "p1".into(), product_name: "螺丝".into(),
+            product_code: "P001".into(), unit: "个".into(), quantity: 4, unit_price: 5.0, discount: 0.0, amount: 20.0,
+            remark: String::new(), shipped_quantity: 0,
+        };
+        db.create_order_with_items(&order, &[item]).unwrap();
+        let operator = operator("clerk", "店员", "staff");
+        db.confirm_order(&id, &operator).unwrap();
+        db.complete_order(&id, &operator).unwrap();
+
+        // 移动加权平均单位成本为 2，4 个的销售成本为 8
+        let costs = db.order_costs("2000-01-01", "2100-01-01").unwrap();
+        assert_eq!(costs.len(), 1);
+        assert!((costs[0].revenue - 20.0).abs() < 1e-9);
+        assert!((costs[0].cost - 8.0).abs() < 1e-9);
+        assert!((costs[0].gross_margin - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ledger_negative_stock_and_zero_price_receipt() {
+        let mut ledger = CostLedger::new(CostMethod::Fifo, 2.0);
+        ledger.receive(4, 2.0);
+        assert!((ledger.issue(6) - 12.0).abs() < 1e-9);
+        assert_eq!(ledger.quantity(), -2);
+        // 入库先补足负库存，剩余部分形成新的成本层
+        ledger.receive(5, 4.0);
+        assert_eq!(ledger.quantity(), 3);
+        assert!((ledger.value() - 12.0).abs() < 1e-9);
+    }
+}
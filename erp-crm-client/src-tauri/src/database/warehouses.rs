@@ -0,0 +1,452 @@
+//! 多仓库 - 仓库档案与按仓库、库位的库存结存
+//!
+//! 产品的 `current_stock` 是全部仓库（含调拨在途）的合计，`stock_levels` 按仓库和库位记录结存。
+//! 结存由库存流水逐条累加：本机出入库在同一事务中更新，其他终端的流水在同步写入时累加。
+//! 未指定仓库的出入库记到产品的所属仓库（`products.warehouse_id`，为空时为默认仓库）。
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::batches::adjust_batch;
+use super::inventory::{oversell_policy, OversellPolicy, StockError, StockResult};
+use super::{record_change, Database, Product, StockBatch, StockRecord};
+
+/// 默认仓库的 ID，各终端相同，旧数据的库存迁移到这里
+pub const DEFAULT_WAREHOUSE_ID: &str = "default";
+
+#[derive(Error, Debug)]
+pub enum WarehouseError {
+    #[error("仓库不存在: {0}")]
+    NotFound(String),
+    #[error("仓库编码已存在: {0}")]
+    DuplicateCode(String),
+    #[error("默认仓库不能删除")]
+    DefaultWarehouse,
+    #[error("仓库 {0} 还有库存或在途调拨，不能删除")]
+    InUse(String),
+    #[error("数据库错误: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type WarehouseResult<T> = std::result::Result<T, WarehouseError>;
+
+/// 仓库
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warehouse {
+    pub id: String,
+    pub code: String,
+    pub name: String,
+    pub address: String,
+    pub manager: String,
+    pub phone: String,
+    /// `active` / `inactive`，停用的仓库不能再出入库
+    pub status: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 产品在某个仓库、库位的结存
+#[derive(Debug, Clone, Serialize)]
+pub struct StockLevel {
+    pub product_id: String,
+    pub product_code: String,
+    pub product_name: String,
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    /// 库位，未指定时为空字符串
+    pub location: String,
+    pub quantity: i32,
+    pub updated_at: String,
+}
+
+/// 某个仓库中低于安全库存的产品
+#[derive(Debug, Clone, Serialize)]
+pub struct WarehouseLowStock {
+    #[serde(flatten)]
+    pub product: Product,
+    pub warehouse_id: String,
+    /// 该仓库各库位的结存合计
+    pub warehouse_stock: i32,
+}
+
+pub(super) fn create_warehouse_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS warehouses (
+            id TEXT PRIMARY KEY,
+            code TEXT UNIQUE NOT NULL,
+            name TEXT NOT NULL,
+            address TEXT DEFAULT '',
+            manager TEXT DEFAULT '',
+            phone TEXT DEFAULT '',
+            status TEXT DEFAULT 'active',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_levels (
+            product_id TEXT NOT NULL,
+            warehouse_id TEXT NOT NULL,
+            location TEXT NOT NULL DEFAULT '',
+            quantity INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (product_id, warehouse_id, location)
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_levels_warehouse ON stock_levels(warehouse_id)", [])?;
+    super::transfers::create_transfer_tables(conn)?;
+    super::ensure_column(conn, "stock_records", "warehouse_id", "TEXT DEFAULT ''")?;
+    super::ensure_column(conn, "stock_records", "location", "TEXT DEFAULT ''")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_stock_records_warehouse ON stock_records(warehouse_id)", [])?;
+
+    // 默认仓库，以及产品上已经填写的仓库；现有库存全部记到产品的所属仓库和库位
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT OR IGNORE INTO warehouses (id, code, name, status, created_at, updated_at) VALUES (?1, 'DEFAULT', '默认仓库', 'active', ?2, ?2)",
+        params![DEFAULT_WAREHOUSE_ID, now],
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO warehouses (id, code, name, status, created_at, updated_at)
+         SELECT DISTINCT warehouse_id, warehouse_id, warehouse_id, 'active', ?1, ?1 FROM products WHERE warehouse_id != ''",
+        [&now],
+    )?;
+    seed_missing_levels(conn)
+}
+
+fn row_to_warehouse(row: &rusqlite::Row) -> SqliteResult<Warehouse> {
+    Ok(Warehouse {
+        id: row.get(0)?, code: row.get(1)?, name: row.get(2)?, address: row.get(3)?,
+        manager: row.get(4)?, phone: row.get(5)?, status: row.get(6)?,
+        created_at: row.get(7)?, updated_at: row.get(8)?,
+    })
+}
+
+fn load_warehouse(conn: &Connection, id: &str) -> SqliteResult<Option<Warehouse>> {
+    conn.query_row(
+        "SELECT id, code, name, address, manager, phone, status, created_at, updated_at FROM warehouses WHERE id = ?1",
+        [id],
+        row_to_warehouse,
+    )
+    .optional()
+}
+
+/// 按服务器数据写入仓库（不记录同步变更）
+pub(super) fn upsert_warehouse(conn: &Connection, w: &Warehouse) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO warehouses (id, code, name, address, manager, phone, status, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![w.id, w.code, w.name, w.address, w.manager, w.phone, w.status, w.created_at, w.updated_at],
+    )?;
+    Ok(())
+}
+
+/// 检查仓库存在且未停用
+pub(super) fn check_warehouse(conn: &Connection, id: &str) -> StockResult<Warehouse> {
+    match load_warehouse(conn, id)? {
+        Some(w) if w.status == "inactive" => Err(StockError::WarehouseInactive(w.name)),
+        Some(w) => Ok(w),
+        None => Err(StockError::WarehouseNotFound(id.to_string())),
+    }
+}
+
+/// 产品的所属仓库与库位
+fn home_place(conn: &Connection, product_id: &str) -> SqliteResult<Option<(String, String)>> {
+    conn.query_row("SELECT warehouse_id, location FROM products WHERE id = ?1", [product_id], |row| {
+        let warehouse_id: String = row.get(0)?;
+        let warehouse_id = if warehouse_id.is_empty() { DEFAULT_WAREHOUSE_ID.to_string() } else { warehouse_id };
+        Ok((warehouse_id, row.get(1)?))
+    })
+    .optional()
+}
+
+/// 确定出入库的仓库和库位：未指定仓库时用产品的所属仓库；在所属仓库且未指定库位时用产品的库位
+pub(super) fn resolve_place(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str) -> StockResult<(String, String)> {
+    let (home, home_location) = home_place(conn, product_id)?.ok_or_else(|| StockError::ProductNotFound(product_id.to_string()))?;
+    let warehouse_id = if warehouse_id.is_empty() { home.clone() } else { warehouse_id.to_string() };
+    check_warehouse(conn, &warehouse_id)?;
+    let location = if location.is_empty() && warehouse_id == home { home_location } else { location.to_string() };
+    Ok((warehouse_id, location))
+}
+
+pub(super) fn level_quantity(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str) -> SqliteResult<i32> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM stock_levels WHERE product_id = ?1 AND warehouse_id = ?2 AND location = ?3",
+        [product_id, warehouse_id, location],
+        |row| row.get(0),
+    )
+}
+
+/// 按超卖策略检查仓库库位的结存是否足够出库
+pub(super) fn check_level(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str, requested: i32) -> StockResult<()> {
+    if requested <= 0 || oversell_policy(conn)? == OversellPolicy::Allow {
+        return Ok(());
+    }
+    let available = level_quantity(conn, product_id, warehouse_id, location)?;
+    if requested > available {
+        let product = conn.query_row("SELECT name FROM products WHERE id = ?1", [product_id], |row| row.get(0))?;
+        let warehouse = load_warehouse(conn, warehouse_id)?.map_or_else(|| warehouse_id.to_string(), |w| w.name);
+        return Err(StockError::InsufficientWarehouseStock {
+            product, warehouse, location: location.to_string(), requested, available: available.max(0),
+        });
+    }
+    Ok(())
+}
+
+pub(super) fn adjust_level(conn: &Connection, product_id: &str, warehouse_id: &str, location: &str, delta: i32) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO stock_levels (product_id, warehouse_id, location, quantity, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(product_id, warehouse_id, location) DO UPDATE SET quantity = quantity + excluded.quantity, updated_at = excluded.updated_at",
+        params![product_id, warehouse_id, location, delta, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// 库存流水对所在仓库结存的影响（流水数量不带符号）
+fn level_change(record: &StockRecord) -> i32 {
+    match record.record_type.as_str() {
+        "out" | "transfer_out" => -record.quantity,
+        "adjust" => record.after_quantity - record.before_quantity,
+        _ => record.quantity,
+    }
+}
+
+/// 把其他终端的库存流水累加到本机的仓库结存和批次结存；旧版本终端的流水没有仓库，记到产品的所属仓库
+pub(super) fn apply_record_to_level(conn: &Connection, record: &StockRecord) -> SqliteResult<()> {
+    let (warehouse_id, location) = if record.warehouse_id.is_empty() {
+        match home_place(conn, &record.product_id)? {
+            Some(place) => place,
+            None => return Ok(()),
+        }
+    } else {
+        (record.warehouse_id.clone(), record.location.clone())
+    };
+    let change = level_change(record);
+    adjust_level(conn, &record.product_id, &warehouse_id, &location, change)?;
+    if !record.batch_no.is_empty() {
+        let batch = StockBatch {
+            batch_no: record.batch_no.clone(), production_date: record.production_date.clone(), expiry_date: record.expiry_date.clone(),
+        };
+        adjust_batch(conn, &record.product_id, &warehouse_id, &location, &batch, change)?;
+    }
+    Ok(())
+}
+
+/// 没有任何结存的产品，把当前库存记到所属仓库和库位（新建产品、服务器下发的新产品）
+pub(super) fn seed_level(conn: &Connection, product: &Product) -> SqliteResult<()> {
+    let has_levels: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM stock_levels WHERE product_id = ?1)", [&product.id], |row| row.get(0))?;
+    if has_levels || product.current_stock == 0 {
+        return Ok(());
+    }
+    let warehouse_id = if product.warehouse_id.is_empty() { DEFAULT_WAREHOUSE_ID } else { &product.warehouse_id };
+    adjust_level(conn, &product.id, warehouse_id, &product.location, product.current_stock)
+}
+
+pub(super) fn seed_missing_levels(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO stock_levels (product_id, warehouse_id, location, quantity, updated_at)
+         SELECT id, CASE WHEN warehouse_id = '' THEN ?1 ELSE warehouse_id END, location, current_stock, ?2
+         FROM products WHERE current_stock != 0 AND id NOT IN (SELECT product_id FROM stock_levels)",
+        params![DEFAULT_WAREHOUSE_ID, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+impl Database {
+    pub fn list_warehouses(&self) -> SqliteResult<Vec<Warehouse>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, code, name, address, manager, phone, status, created_at, updated_at FROM warehouses ORDER BY id != ?1, code",
+        )?;
+        let rows = stmt.query_map([DEFAULT_WAREHOUSE_ID], row_to_warehouse)?;
+        rows.collect()
+    }
+
+    pub fn get_warehouse(&self, id: &str) -> SqliteResult<Option<Warehouse>> {
+        load_warehouse(&self.conn.lock().unwrap(), id)
+    }
+
+    pub fn create_warehouse(&self, warehouse: &Warehouse) -> WarehouseResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::check_warehouse_code(&tx, &warehouse.code, &warehouse.id)?;
+        upsert_warehouse(&tx, warehouse)?;
+        record_change(&tx, "warehouse", &warehouse.id, "create", warehouse)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn update_warehouse(&self, warehouse: &Warehouse) -> WarehouseResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let existing = load_warehouse(&tx, &warehouse.id)?.ok_or_else(|| WarehouseError::NotFound(warehouse.id.clone()))?;
+        Self::check_warehouse_code(&tx, &warehouse.code, &warehouse.id)?;
+        let saved = Warehouse { created_at: existing.created_at, ..warehouse.clone() };
+        upsert_warehouse(&tx, &saved)?;
+        record_change(&tx, "warehouse", &saved.id, "update", &saved)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删除仓库；默认仓库、仍有结存或在途调拨的仓库不能删除
+    pub fn delete_warehouse(&self, id: &str) -> WarehouseResult<()> {
+        if id == DEFAULT_WAREHOUSE_ID {
+            return Err(WarehouseError::DefaultWarehouse);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let warehouse = load_warehouse(&tx, id)?.ok_or_else(|| WarehouseError::NotFound(id.to_string()))?;
+        let in_use: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM stock_levels WHERE warehouse_id = ?1 AND quantity != 0)
+                 OR EXISTS(SELECT 1 FROM stock_transfers WHERE status = 'in_transit' AND (from_warehouse_id = ?1 OR to_warehouse_id = ?1))",
+            [id],
+            |row| row.get(0),
+        )?;
+        if in_use {
+            return Err(WarehouseError::InUse(warehouse.name));
+        }
+        tx.execute("DELETE FROM stock_levels WHERE warehouse_id = ?1", [id])?;
+        tx.execute("DELETE FROM warehouses WHERE id = ?1", [id])?;
+        record_change(&tx, "warehouse", id, "delete", &serde_json::json!({ "id": id }))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn check_warehouse_code(conn: &Connection, code: &str, id: &str) -> WarehouseResult<()> {
+        let taken: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM warehouses WHERE code = ?1 AND id != ?2)", [code, id], |row| row.get(0))?;
+        if taken {
+            return Err(WarehouseError::DuplicateCode(code.to_string()));
+        }
+        Ok(())
+    }
+
+    /// 按产品和（或）仓库查询结存，不含数量为 0 的库位
+    pub fn list_stock_levels(&self, product_id: Option<&str>, warehouse_id: Option<&str>) -> SqliteResult<Vec<StockLevel>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT l.product_id, COALESCE(p.code, ''), COALESCE(p.name, ''), l.warehouse_id, COALESCE(w.name, l.warehouse_id), l.location, l.quantity, l.updated_at
+             FROM stock_levels l
+             LEFT JOIN products p ON p.id = l.product_id
+             LEFT JOIN warehouses w ON w.id = l.warehouse_id
+             WHERE l.quantity != 0 AND (?1 IS NULL OR l.product_id = ?1) AND (?2 IS NULL OR l.warehouse_id = ?2)
+             ORDER BY l.warehouse_id, p.code, l.location",
+        )?;
+        let rows = stmt.query_map(params![product_id, warehouse_id], |row| {
+            Ok(StockLevel {
+                product_id: row.get(0)?, product_code: row.get(1)?, product_name: row.get(2)?,
+                warehouse_id: row.get(3)?, warehouse_name: row.get(4)?, location: row.get(5)?,
+                quantity: row.get(6)?, updated_at: row.get(7)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// 仓库中结存低于安全库存的产品；只看在该仓库有过结存或所属仓库为该仓库的产品
+    pub fn list_low_stock_in_warehouse(&self, warehouse_id: &str) -> SqliteResult<Vec<WarehouseLowStock>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT p.id, p.code, p.barcode, p.name, p.category, p.unit, p.specification, p.brand, p.cost_price, p.sell_price, p.min_stock, p.max_stock, p.current_stock, p.warehouse_id, p.location, p.status, p.description, p.created_at, p.updated_at, p.version,
+                    COALESCE((SELECT SUM(quantity) FROM stock_levels WHERE product_id = p.id AND warehouse_id = ?1), 0) AS warehouse_stock
+             FROM products p
+             WHERE (EXISTS(SELECT 1 FROM stock_levels WHERE product_id = p.id AND warehouse_id = ?1)
+                    OR (CASE WHEN p.warehouse_id = '' THEN ?2 ELSE p.warehouse_id END) = ?1)
+               AND warehouse_stock < p.min_stock
+             ORDER BY p.code",
+        )?;
+        let rows = stmt.query_map([warehouse_id, DEFAULT_WAREHOUSE_ID], |row| {
+            Ok(WarehouseLowStock { product: Self::row_to_product(row)?, warehouse_id: warehouse_id.to_string(), warehouse_stock: row.get(20)? })
+        })?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_support::{self, database_with};
+    use crate::database::StockMovement;
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        database_with(&[Product { min_stock: 5, ..test_support::product("p1", "P001", 10) }])
+    }
+
+    fn warehouse(id: &str) -> Warehouse {
+        test_support::warehouse(id, &format!("{}仓", id))
+    }
+
+    fn movement<'a>(warehouse_id: &'a str, quantity_change: i32, record_type: &'a str, batch: &'a StockBatch) -> StockMovement<'a> {
+        StockMovement {
+            product_id: "p1", quantity_change, record_type, unit_price: 0.0, remark: "", batch,
+            order_id: "", warehouse_id, location: "",
+        }
+    }
+
+    #[test]
+    fn test_update_product_keeps_stock() {
+        let (_dir, db) = setup();
+        let batch = StockBatch::default();
+        db.update_stock(&movement(DEFAULT_WAREHOUSE_ID, 5, "in", &batch)).unwrap();
+
+        // 编辑资料时传入的库存数量被忽略，库存与仓库结存保持一致
+        let mut product = db.get_product("p1").unwrap().unwrap();
+        product.name = "六角螺丝".into();
+        product.current_stock = 0;
+        db.update_product(&product).unwrap();
+        let saved = db.get_product("p1").unwrap().unwrap();
+        assert_eq!((saved.name.as_str(), saved.current_stock), ("六角螺丝", 15));
+        assert_eq!(db.list_stock_levels(Some("p1"), None).unwrap()[0].quantity, 15);
+    }
+
+    #[test]
+    fn test_stock_per_warehouse() {
+        let (_dir, db) = setup();
+        db.create_warehouse(&warehouse("north")).unwrap();
+        assert!(matches!(db.create_warehouse(&Warehouse { id: "x".into(), ..warehouse("north") }), Err(WarehouseError::DuplicateCode(_))));
+
+        // 新产品的库存记在默认仓库
+        let levels = db.list_stock_levels(Some("p1"), None).unwrap();
+        assert_eq!(levels.len(), 1);
+        assert_eq!((levels[0].warehouse_id.as_str(), levels[0].quantity), (DEFAULT_WAREHOUSE_ID, 10));
+
+        let batch = StockBatch::default();
+        let record = db.update_stock(&movement("north", 4, "in", &batch)).unwrap().remove(0);
+        assert_eq!((record.warehouse_id.as_str(), record.after_quantity), ("north", 14));
+        // 北仓只有 4 件，总库存够也不能出 5 件
+        assert!(matches!(db.update_stock(&movement("north", -5, "out", &batch)), Err(StockError::InsufficientWarehouseStock { available: 4, .. })));
+        db.update_stock(&movement("north", -3, "out", &batch)).unwrap();
+        assert!(matches!(db.update_stock(&movement("nowhere", 1, "in", &batch)), Err(StockError::WarehouseNotFound(_))));
+
+        let north = db.list_stock_levels(None, Some("north")).unwrap();
+        assert_eq!(north[0].quantity, 1);
+        assert_eq!(db.get_product("p1").unwrap().unwrap().current_stock, 11);
+
+        // 安全库存 5：北仓 1 件预警，默认仓库 10 件不预警
+        let low = db.list_low_stock_in_warehouse("north").unwrap();
+        assert_eq!((low.len(), low[0].warehouse_stock), (1, 1));
+        assert!(db.list_low_stock_in_warehouse(DEFAULT_WAREHOUSE_ID).unwrap().is_empty());
+
+        assert!(matches!(db.delete_warehouse("north"), Err(WarehouseError::InUse(_))));
+        assert!(matches!(db.delete_warehouse(DEFAULT_WAREHOUSE_ID), Err(WarehouseError::DefaultWarehouse)));
+    }
+
+    #[test]
+    fn test_invalid_movement_quantity() {
+        let (_dir, db) = setup();
+        let batch = StockBatch::default();
+
+        // 负数入库、正数出库和数量为 0 的流水都被拒绝，库存不变
+        for (quantity, record_type) in [(-3, "in"), (3, "out"), (0, "in")] {
+            let result = db.update_stock(&movement(DEFAULT_WAREHOUSE_ID, quantity, record_type, &batch));
+            assert!(matches!(result, Err(StockError::InvalidQuantity { .. })), "{} {}", record_type, quantity);
+        }
+        assert_eq!(db.get_product("p1").unwrap().unwrap().current_stock, 10);
+
+        // 实际数量与结存一致时不写调整流水
+        assert!(db.adjust_stock("p1", "", "", 10, "").unwrap().is_empty());
+        let count: i32 = db.conn.lock().unwrap().query_row("SELECT COUNT(*) FROM stock_records WHERE record_type = 'adjust'", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -1,13 +1,17 @@
 //! ERP/CRM 客户端 - Tauri 后端
 
+pub mod auth;
 pub mod commands;
 pub mod database;
 pub mod scanner;
+pub mod spreadsheet;
 pub mod storage;
 pub mod sync;
 
+use auth::AuthManager;
 use commands::*;
 use database::Database;
+use storage::UserVaults;
 use tauri::Manager;
 use std::sync::Arc;
 
@@ -16,24 +20,40 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
-            auth_login, auth_logout, auth_get_current_user,
+            auth_needs_setup, auth_setup, auth_login, auth_logout, auth_get_current_user, auth_change_password,
+            user_list, user_create, user_update, user_reset_password,
             customer_list, customer_get, customer_create, customer_update, customer_delete,
             product_list, product_get, product_get_by_barcode, product_create, product_update, product_delete, product_low_stock,
-            stock_in, stock_out, stock_adjust,
-            order_list, order_get, order_create, order_update, order_delete, order_confirm, order_cancel, order_complete,
+            stock_in, stock_out, stock_adjust, stock_availability, stock_get_oversell_policy, stock_set_oversell_policy,
+            stock_get_cost_method, stock_set_cost_method,
+            stock_levels, stock_batches, stock_trace_batch, warehouse_list, warehouse_create, warehouse_update, warehouse_delete,
+            transfer_create, transfer_receive, transfer_cancel, transfer_get, transfer_list,
+            stock_check_create, stock_check_submit, stock_check_scan, stock_check_get, stock_check_list, stock_check_report,
+            stock_check_post, stock_check_cancel,
+            order_list, order_get, order_create, order_update, order_delete, order_confirm, order_cancel, order_complete, order_ship, order_return, order_status_history,
+            payment_record, payment_list,
             sync_status, sync_start, sync_stop, sync_force_full,
-            scanner_list_ports, scanner_connect, scanner_disconnect, scanner_status, scanner_handle_scan,
+            sync_list_conflicts, sync_resolve_conflict, sync_get_conflict_strategies, sync_set_conflict_strategy,
+            scanner_list_ports, scanner_connect, scanner_disconnect, scanner_status, scanner_wedge_keys, scanner_handle_scan,
             storage_set_secure, storage_get_secure, storage_delete_secure, storage_clear_secure,
-            report_dashboard_stats, report_sales, report_inventory,
+            db_encryption_status, db_encrypt, db_rotate_key,
+            report_dashboard_stats, report_sales, report_inventory, report_ar_aging, report_expiring_batches, report_order_costs, report_export,
+            import_columns, import_customers, import_products, customer_export, product_export, order_export,
             system_info, system_check_update, system_clear_cache,
+            rbac_get_permissions, rbac_set_permissions, rbac_reset_permissions, audit_list,
         ])
         .setup(|app| {
             let data_dir = app.path().app_data_dir().expect("获取数据目录失败");
             std::fs::create_dir_all(&data_dir).ok();
 
             let db_path = data_dir.join("erp_crm.db");
-            let db = Database::new(db_path).expect("数据库初始化失败");
-            app.manage(DbState(Arc::new(db)));
+            let db = Arc::new(Database::new(db_path).expect("数据库初始化失败"));
+            let sync_manager = build_sync_manager(app.handle(), Arc::clone(&db));
+            app.manage(ScannerState(build_scanner_manager(app.handle(), Arc::clone(&db))));
+            app.manage(AuthState(Arc::new(AuthManager::new(Arc::clone(&db)))));
+            app.manage(StorageState(Arc::new(UserVaults::new(data_dir.join("secure")))));
+            app.manage(DbState(db));
+            app.manage(SyncState::new(sync_manager));
 
             Ok(())
         })
@@ -0,0 +1,468 @@
+//! 条码解析 - 码制识别、校验位验证与 GS1 应用标识符解析
+//!
+//! 支持 EAN-13 / EAN-8 / UPC-A / ITF-14 校验位验证，识别 Code128，
+//! 并解析 GS1-128 / GS1 DataMatrix 中的 GTIN、批号、有效期、序列号和数量。
+//! GS1 数据可以是扫码枪原样输出（以 GS 字符 `\x1D` 分隔可变长字段），
+//! 也可以是标签上的人工可读格式 `(01)...(17)...(10)...`。
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// GS1 字段分隔符（FNC1 由扫码枪转为 ASCII 29）
+const GS: char = '\u{1d}';
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BarcodeError {
+    #[error("校验位错误")]
+    InvalidCheckDigit,
+    #[error("不支持的 GS1 应用标识符: {0}")]
+    UnknownAi(String),
+    #[error("GS1 字段 ({ai}) 的值无效: {value}")]
+    InvalidValue { ai: String, value: String },
+    #[error("GS1 数据不完整")]
+    Truncated,
+}
+
+/// 码制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symbology {
+    Ean13,
+    Ean8,
+    UpcA,
+    Itf14,
+    Code128,
+    /// GS1-128 / GS1 DataMatrix 等携带应用标识符的条码
+    Gs1,
+    Unknown,
+}
+
+/// 解析后的条码
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsedBarcode {
+    /// 扫码枪输出的原始内容（已去掉首尾空白）
+    pub raw: String,
+    pub symbology: Symbology,
+    /// 去掉码制标识符（如 `]C1`）后的内容，非 GS1 条码按此查找产品
+    pub code: String,
+    /// 校验位正确且 GS1 字段完整
+    pub valid: bool,
+    pub error: Option<String>,
+    /// 14 位 GTIN（EAN/UPC 左侧补零）
+    pub gtin: Option<String>,
+    pub batch: Option<String>,
+    /// 生产日期 `YYYY-MM-DD`
+    pub production: Option<String>,
+    /// 有效期 `YYYY-MM-DD`
+    pub expiry: Option<String>,
+    pub serial: Option<String>,
+    pub quantity: Option<u32>,
+}
+
+/// GS1 应用标识符解析结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Gs1Data {
+    pub gtin: Option<String>,
+    pub batch: Option<String>,
+    pub production: Option<String>,
+    pub expiry: Option<String>,
+    pub serial: Option<String>,
+    pub quantity: Option<u32>,
+}
+
+/// 应用标识符的长度：定长字段之后不需要分隔符
+#[derive(Debug, Clone, Copy)]
+enum AiLength {
+    Fixed(usize),
+    Variable(usize),
+}
+
+/// 按前缀查找应用标识符，返回 (标识符位数, 数据长度)；只收录常见的标识符
+fn ai_spec(data: &str) -> Option<(usize, AiLength)> {
+    use AiLength::*;
+    let prefix = |n: usize| data.get(..n).filter(|p| p.bytes().all(|b| b.is_ascii_digit()));
+    let two = prefix(2)?;
+    let spec = match two {
+        "00" => (2, Fixed(18)),
+        "01" | "02" => (2, Fixed(14)),
+        "10" | "21" | "22" => (2, Variable(20)),
+        "11" | "12" | "13" | "15" | "16" | "17" => (2, Fixed(6)),
+        "20" => (2, Fixed(2)),
+        "30" | "37" => (2, Variable(8)),
+        // 31xx-36xx 计量值（重量、长度等），第 4 位是小数位数
+        "31" | "32" | "33" | "34" | "35" | "36" => (prefix(4)?.len(), Fixed(6)),
+        "24" | "25" => match prefix(3)? {
+            "240" | "241" | "250" | "251" | "253" | "254" => (3, Variable(30)),
+            _ => return None,
+        },
+        "40" => match prefix(3)? {
+            "400" | "401" | "403" => (3, Variable(30)),
+            "402" => (3, Fixed(17)),
+            _ => return None,
+        },
+        "41" => match prefix(3)? {
+            "410" | "411" | "412" | "413" | "414" | "415" | "416" | "417" => (3, Fixed(13)),
+            _ => return None,
+        },
+        "42" => match prefix(3)? {
+            "420" => (3, Variable(20)),
+            "421" => (3, Variable(12)),
+            "422" | "424" | "426" => (3, Fixed(3)),
+            _ => return None,
+        },
+        // 90-99 企业内部使用
+        "90" | "91" | "92" | "93" | "94" | "95" | "96" | "97" | "98" | "99" => (2, Variable(90)),
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// GTIN 校验位：从右往左（不含校验位）奇数位乘 3、偶数位乘 1
+fn check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| u32::from(d - b'0') * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// 验证 EAN-8 / UPC-A / EAN-13 / ITF-14（GTIN-14）的校验位
+pub fn validate_gtin(code: &str) -> bool {
+    let bytes = code.as_bytes();
+    if !matches!(bytes.len(), 8 | 12 | 13 | 14) || !bytes.iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+    let (body, check) = bytes.split_at(bytes.len() - 1);
+    check_digit(body) == check[0] - b'0'
+}
+
+/// 补零为 14 位 GTIN；校验位错误时返回 `None`
+pub fn normalize_gtin(code: &str) -> Option<String> {
+    validate_gtin(code).then(|| format!("{:0>14}", code))
+}
+
+/// 同一 GTIN 在产品资料中可能的写法：14 位、EAN-13、UPC-A、EAN-8（只去掉前导零）
+pub fn gtin_variants(gtin: &str) -> Vec<String> {
+    let mut variants = vec![gtin.to_string()];
+    for len in [13, 12, 8] {
+        if gtin.len() > len && gtin[..gtin.len() - len].bytes().all(|b| b == b'0') {
+            variants.push(gtin[gtin.len() - len..].to_string());
+        }
+    }
+    variants
+}
+
+/// 解析 GS1 日期 `YYMMDD`：日为 00 表示当月最后一天，世纪按 GS1 规则取离当前年份最近的一个
+fn parse_gs1_date(value: &str, current_year: i32) -> Option<NaiveDate> {
+    if value.len() != 6 || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let yy: i32 = value[0..2].parse().ok()?;
+    let month: u32 = value[2..4].parse().ok()?;
+    let day: u32 = value[4..6].parse().ok()?;
+
+    let century = current_year - current_year.rem_euclid(100);
+    let year = match yy - current_year.rem_euclid(100) {
+        d if d >= 51 => century - 100 + yy,
+        d if d <= -50 => century + 100 + yy,
+        _ => century + yy,
+    };
+    if day == 0 {
+        let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+        return NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred_opt();
+    }
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// 把人工可读格式 `(01)...(10)...` 转为以 GS 分隔的原始格式
+fn from_human_readable(data: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let after = rest.strip_prefix('(')?;
+        let end = after.find(')')?;
+        let ai = &after[..end];
+        if !(2..=4).contains(&ai.len()) || !ai.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let value_end = after[end + 1..].find('(').map_or(after.len(), |i| end + 1 + i);
+        if !out.is_empty() {
+            out.push(GS);
+        }
+        out.push_str(ai);
+        out.push_str(&after[end + 1..value_end]);
+        rest = &after[value_end..];
+    }
+    Some(out)
+}
+
+/// 解析 GS1 元素串（不含码制标识符）
+pub fn parse_gs1(data: &str) -> Result<Gs1Data, BarcodeError> {
+    parse_gs1_at(data, Utc::now().year())
+}
+
+fn parse_gs1_at(data: &str, current_year: i32) -> Result<Gs1Data, BarcodeError> {
+    let owned;
+    let mut rest = if data.starts_with('(') {
+        owned = from_human_readable(data).ok_or(BarcodeError::Truncated)?;
+        owned.as_str()
+    } else {
+        data
+    };
+    rest = rest.trim_start_matches(GS);
+    if rest.is_empty() {
+        return Err(BarcodeError::Truncated);
+    }
+
+    let mut parsed = Gs1Data::default();
+    while !rest.is_empty() {
+        let (ai_len, length) = match ai_spec(rest) {
+            Some(spec) => spec,
+            None if rest.len() < 2 => return Err(BarcodeError::Truncated),
+            None => return Err(BarcodeError::UnknownAi(rest.chars().take(4).collect())),
+        };
+        let ai = &rest[..ai_len];
+        let body = &rest[ai_len..];
+        let (value, next) = match length {
+            AiLength::Fixed(n) => {
+                let value = body.get(..n).ok_or(BarcodeError::Truncated)?;
+                (value, &body[n..])
+            }
+            AiLength::Variable(_) => {
+                let end = body.find(GS).unwrap_or(body.len());
+                (&body[..end], &body[end..])
+            }
+        };
+        let invalid = || BarcodeError::InvalidValue { ai: ai.to_string(), value: value.to_string() };
+        if value.is_empty() || value.contains(GS) {
+            return Err(invalid());
+        }
+        if let AiLength::Variable(max) = length {
+            if value.chars().count() > max {
+                return Err(invalid());
+            }
+        }
+
+        match ai {
+            "01" | "02" => {
+                if !validate_gtin(value) {
+                    return Err(BarcodeError::InvalidCheckDigit);
+                }
+                // 02 是物流单元内所含商品的 GTIN，只在没有 01 时使用
+                if ai == "01" || parsed.gtin.is_none() {
+                    parsed.gtin = Some(value.to_string());
+                }
+            }
+            "10" => parsed.batch = Some(value.to_string()),
+            "11" | "17" => {
+                let date = parse_gs1_date(value, current_year).ok_or_else(invalid)?;
+                let date = Some(date.format("%Y-%m-%d").to_string());
+                if ai == "11" {
+                    parsed.production = date;
+                } else {
+                    parsed.expiry = date;
+                }
+            }
+            "21" => parsed.serial = Some(value.to_string()),
+            "30" | "37" => {
+                if !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                parsed.quantity = Some(value.parse().map_err(|_| invalid())?);
+            }
+            // 其他标识符只校验格式，不提取
+            _ => {}
+        }
+        rest = next.trim_start_matches(GS);
+    }
+    Ok(parsed)
+}
+
+/// 去掉 AIM 码制标识符（`]` + 码制字母 + 修饰符），返回标识符与剩余内容
+fn split_symbology_identifier(raw: &str) -> (Option<&str>, &str) {
+    match raw.get(..3) {
+        Some(id) if id.starts_with(']') => (Some(id), &raw[3..]),
+        _ => (None, raw),
+    }
+}
+
+/// 识别并解析扫码内容，解析失败时 `valid` 为 false 并附带原因，不会返回错误
+pub fn parse_barcode(raw: &str) -> ParsedBarcode {
+    let raw = raw.trim();
+    let (identifier, code) = split_symbology_identifier(raw);
+    let mut parsed = ParsedBarcode {
+        raw: raw.to_string(),
+        symbology: Symbology::Unknown,
+        code: code.trim_start_matches(GS).to_string(),
+        valid: false,
+        error: None,
+        gtin: None,
+        batch: None,
+        production: None,
+        expiry: None,
+        serial: None,
+        quantity: None,
+    };
+
+    // ]C1 GS1-128、]d2 GS1 DataMatrix、]Q3 GS1 QR、]e0 GS1 DataBar
+    let gs1_identifier = matches!(identifier, Some("]C1" | "]d2" | "]Q3" | "]e0"));
+    let all_digits = !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit());
+    let looks_gs1 = gs1_identifier
+        || code.contains(GS)
+        || (code.starts_with('(') && from_human_readable(code).is_some())
+        // 未开启码制标识符的扫码枪：以 (01)GTIN 开头的长数字串
+        || (all_digits && code.len() > 16 && code.starts_with("01") && validate_gtin(&code[2..16]));
+
+    if looks_gs1 {
+        parsed.symbology = Symbology::Gs1;
+        match parse_gs1(code) {
+            Ok(data) => {
+                parsed.valid = true;
+                parsed.gtin = data.gtin;
+                parsed.batch = data.batch;
+                parsed.production = data.production;
+                parsed.expiry = data.expiry;
+                parsed.serial = data.serial;
+                parsed.quantity = data.quantity;
+            }
+            Err(e) => parsed.error = Some(e.to_string()),
+        }
+        return parsed;
+    }
+
+    if all_digits && !identifier.is_some_and(|id| id.starts_with("]C")) {
+        let symbology = match code.len() {
+            8 => Some(Symbology::Ean8),
+            12 => Some(Symbology::UpcA),
+            13 => Some(Symbology::Ean13),
+            14 => Some(Symbology::Itf14),
+            _ => None,
+        };
+        if let Some(symbology) = symbology {
+            parsed.symbology = symbology;
+            parsed.gtin = normalize_gtin(code);
+            parsed.valid = parsed.gtin.is_some();
+            if !parsed.valid {
+                parsed.error = Some(BarcodeError::InvalidCheckDigit.to_string());
+            }
+            return parsed;
+        }
+    }
+
+    // Code128 可编码全部 ASCII 字符，校验字符已由扫码枪验证并去掉
+    if !code.is_empty() && code.is_ascii() && !code.bytes().any(|b| b.is_ascii_control()) {
+        parsed.symbology = Symbology::Code128;
+        parsed.valid = true;
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_digits() {
+        assert!(validate_gtin("4006381333931")); // EAN-13
+        assert!(validate_gtin("96385074")); // EAN-8
+        assert!(validate_gtin("036000291452")); // UPC-A
+        assert!(validate_gtin("10012345678902")); // ITF-14
+        assert!(!validate_gtin("4006381333932"));
+        assert!(!validate_gtin("40063813339"));
+        assert!(!validate_gtin("400638133393A"));
+
+        assert_eq!(normalize_gtin("036000291452").as_deref(), Some("00036000291452"));
+        assert_eq!(
+            gtin_variants("00036000291452"),
+            vec!["00036000291452", "0036000291452", "036000291452"]
+        );
+        assert_eq!(gtin_variants("00000096385074").last().map(String::as_str), Some("96385074"));
+
+        let ean = parse_barcode(" 4006381333931\r\n");
+        assert_eq!(ean.symbology, Symbology::Ean13);
+        assert!(ean.valid);
+        assert_eq!(ean.gtin.as_deref(), Some("04006381333931"));
+
+        let bad = parse_barcode("4006381333932");
+        assert_eq!(bad.symbology, Symbology::Ean13);
+        assert!(!bad.valid);
+        assert!(bad.gtin.is_none());
+
+        assert_eq!(parse_barcode("96385074").symbology, Symbology::Ean8);
+        assert_eq!(parse_barcode("036000291452").symbology, Symbology::UpcA);
+        assert_eq!(parse_barcode("10012345678902").symbology, Symbology::Itf14);
+    }
+
+    #[test]
+    fn test_code128() {
+        let parsed = parse_barcode("]C0SKU-00042/A");
+        assert_eq!(parsed.symbology, Symbology::Code128);
+        assert_eq!(parsed.code, "SKU-00042/A");
+        assert!(parsed.valid);
+        assert!(parsed.gtin.is_none());
+
+        // 带 Code128 标识符的 13 位数字不按 EAN 处理
+        assert_eq!(parse_barcode("]C04006381333932").symbology, Symbology::Code128);
+        assert_eq!(parse_barcode("P-1001").symbology, Symbology::Code128);
+        assert_eq!(parse_barcode("条码").symbology, Symbology::Unknown);
+    }
+
+    #[test]
+    fn test_gs1_element_strings() {
+        // GS1-128：定长 01/17 之后无需分隔符，可变长 10 以 GS 结束
+        let parsed = parse_barcode("]C101095011015300031726013110ABC123\u{1d}2112345\u{1d}3024");
+        assert_eq!(parsed.symbology, Symbology::Gs1);
+        assert!(parsed.valid, "{:?}", parsed.error);
+        assert_eq!(parsed.gtin.as_deref(), Some("09501101530003"));
+        assert_eq!(parsed.expiry.as_deref(), Some("2026-01-31"));
+        assert_eq!(parsed.batch.as_deref(), Some("ABC123"));
+        assert_eq!(parsed.serial.as_deref(), Some("12345"));
+        assert_eq!(parsed.quantity, Some(24));
+
+        // GS1 DataMatrix 以 GS 开头（FNC1），包含计量值 3103
+        let dm = parse_barcode("]d2\u{1d}0109501101530003310300125010L-7");
+        assert!(dm.valid, "{:?}", dm.error);
+        assert_eq!(dm.batch.as_deref(), Some("L-7"));
+
+        // 人工可读格式，日期 00 表示月末
+        let hri = parse_barcode("(01)09501101530003(11)260115(17)280200(10)B01");
+        assert!(hri.valid, "{:?}", hri.error);
+        assert_eq!(hri.production.as_deref(), Some("2026-01-15"));
+        assert_eq!(hri.expiry.as_deref(), Some("2028-02-29"));
+        assert_eq!(hri.batch.as_deref(), Some("B01"));
+
+        // 未开启码制标识符时按 01 开头的长数字串识别
+        let bare = parse_barcode("01095011015300031726013130100");
+        assert_eq!(bare.symbology, Symbology::Gs1);
+        assert_eq!(bare.quantity, Some(100));
+    }
+
+    #[test]
+    fn test_gs1_errors() {
+        assert_eq!(parse_gs1("0109501101530004"), Err(BarcodeError::InvalidCheckDigit));
+        assert_eq!(parse_gs1("01095011015300"), Err(BarcodeError::Truncated));
+        assert_eq!(parse_gs1("0109501101530003899"), Err(BarcodeError::UnknownAi("899".to_string())));
+        assert!(matches!(parse_gs1("010950110153000317991301"), Err(BarcodeError::InvalidValue { .. })));
+        assert!(matches!(parse_gs1("10ABCDEFGHIJKLMNOPQRSTU"), Err(BarcodeError::InvalidValue { .. })));
+
+        let parsed = parse_barcode("]C10109501101530004");
+        assert_eq!(parsed.symbology, Symbology::Gs1);
+        assert!(!parsed.valid);
+        assert_eq!(parsed.error.as_deref(), Some("校验位错误"));
+    }
+
+    #[test]
+    fn test_gs1_date_century() {
+        let date = |v| parse_gs1_date(v, 2026).map(|d| d.to_string());
+        assert_eq!(date("991231").as_deref(), Some("1999-12-31"));
+        assert_eq!(date("761231").as_deref(), Some("2076-12-31"));
+        assert_eq!(date("771231").as_deref(), Some("1977-12-31"));
+        assert_eq!(date("230100").as_deref(), Some("2023-01-31"));
+        assert_eq!(date("231301"), None);
+        assert_eq!(parse_gs1_date("991231", 2060).map(|d| d.year()), Some(2099));
+        assert_eq!(parse_gs1_date("050101", 2080).map(|d| d.year()), Some(2105));
+    }
+}
@@ -1,13 +1,31 @@
 //! 扫码枪模块 - 通过串口对接扫码枪
 
+pub mod barcode;
+#[cfg(unix)]
+pub mod virtual_port;
+pub mod wedge;
+
+pub use barcode::{gtin_variants, parse_barcode, BarcodeError, ParsedBarcode, Symbology};
+#[cfg(unix)]
+pub use virtual_port::VirtualScanner;
+pub use wedge::{WedgeDetector, WedgeKeyEvent};
+
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader};
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::io::{ErrorKind, Read};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread;
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use thiserror::Error;
 
+/// 串口读取超时，也是断开时读取线程退出的最长等待时间
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// 非阻塞读取（输入设备）没有数据时的等待间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// 单个条码的最大长度，超过后丢弃缓冲区（通常是波特率等配置不匹配读到了乱码）
+const MAX_BARCODE_LENGTH: usize = 512;
+
 #[derive(Error, Debug)]
 pub enum ScannerError {
     #[error("串口错误: {0}")]
@@ -20,30 +38,98 @@ pub enum ScannerError {
 
 pub type Result<T> = std::result::Result<T, ScannerError>;
 
-/// 扫码枪配置
+/// 扫码枪配置（与前端一致使用 camelCase，缺省字段取默认值）
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct ScannerConfig {
     pub enabled: bool,
+    /// serial 串口扫码枪 / wedge 键盘模式扫码枪
+    pub mode: String,
     pub port: String,
     pub baud_rate: u32,
     pub data_bits: u8,
     pub stop_bits: u8,
     pub parity: String,
+    /// 键盘模式的输入来源：为空时由应用窗口转发按键，否则为 Linux 输入设备路径
+    pub input_device: String,
+    /// 键盘模式下相邻按键的最大间隔（毫秒），超过视为人工输入
+    pub key_interval_ms: u64,
+    /// 键盘模式下条码的最小长度
+    pub min_length: usize,
 }
 
 impl Default for ScannerConfig {
     fn default() -> Self {
         Self {
             enabled: false,
+            mode: "serial".to_string(),
             port: String::new(),
             baud_rate: 9600,
             data_bits: 8,
             stop_bits: 1,
             parity: "none".to_string(),
+            input_device: String::new(),
+            key_interval_ms: 50,
+            min_length: 4,
         }
     }
 }
 
+/// 串口参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialSettings {
+    pub data_bits: DataBits,
+    pub stop_bits: StopBits,
+    pub parity: Parity,
+}
+
+impl ScannerConfig {
+    /// 校验配置并转换为串口参数
+    pub fn serial_settings(&self) -> Result<SerialSettings> {
+        if self.port.trim().is_empty() {
+            return Err(ScannerError::ConfigError("未指定串口".to_string()));
+        }
+        if self.baud_rate == 0 {
+            return Err(ScannerError::ConfigError("波特率必须大于 0".to_string()));
+        }
+        let data_bits = match self.data_bits {
+            5 => DataBits::Five,
+            6 => DataBits::Six,
+            7 => DataBits::Seven,
+            8 => DataBits::Eight,
+            n => return Err(ScannerError::ConfigError(format!("不支持的数据位: {}", n))),
+        };
+        let stop_bits = match self.stop_bits {
+            1 => StopBits::One,
+            2 => StopBits::Two,
+            n => return Err(ScannerError::ConfigError(format!("不支持的停止位: {}", n))),
+        };
+        let parity = match self.parity.to_lowercase().as_str() {
+            "" | "none" => Parity::None,
+            "odd" => Parity::Odd,
+            "even" => Parity::Even,
+            other => return Err(ScannerError::ConfigError(format!("不支持的校验位: {}", other))),
+        };
+        Ok(SerialSettings { data_bits, stop_bits, parity })
+    }
+
+    /// 是否为键盘模式
+    pub fn is_wedge(&self) -> bool {
+        self.mode.eq_ignore_ascii_case("wedge")
+    }
+
+    /// 校验键盘模式配置并创建按键识别器
+    pub fn wedge_detector(&self) -> Result<WedgeDetector> {
+        if !(1..=1000).contains(&self.key_interval_ms) {
+            return Err(ScannerError::ConfigError("按键间隔应在 1-1000 毫秒之间".to_string()));
+        }
+        if self.min_length == 0 || self.min_length > MAX_BARCODE_LENGTH {
+            return Err(ScannerError::ConfigError(format!("条码最小长度应在 1-{} 之间", MAX_BARCODE_LENGTH)));
+        }
+        Ok(WedgeDetector::new(Duration::from_millis(self.key_interval_ms), self.min_length))
+    }
+}
+
 /// 扫码结果
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanResult {
@@ -55,15 +141,26 @@ pub struct ScanResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScannerStatus {
     pub connected: bool,
+    /// serial / wedge
+    pub mode: String,
+    /// 串口，或键盘模式的输入设备（应用窗口转发按键时为空字符串）
     pub port: Option<String>,
+    /// 读取线程因错误退出时的原因（如设备被拔出）
+    pub last_error: Option<String>,
 }
 
+type ScanCallback = Box<dyn Fn(ScanResult) + Send + 'static>;
+
 /// 扫码枪管理器
 pub struct ScannerManager {
     config: Arc<Mutex<ScannerConfig>>,
     running: Arc<AtomicBool>,
     connected: Arc<AtomicBool>,
-    callback: Arc<Mutex<Option<Box<dyn Fn(ScanResult) + Send + 'static>>>>,
+    callback: Arc<Mutex<Option<ScanCallback>>>,
+    reader: Mutex<Option<JoinHandle<()>>>,
+    last_error: Arc<Mutex<Option<String>>>,
+    /// 键盘模式下由应用窗口转发按键时使用的识别器
+    window_wedge: Mutex<Option<WedgeDetector>>,
 }
 
 impl ScannerManager {
@@ -73,6 +170,9 @@ impl ScannerManager {
             running: Arc::new(AtomicBool::new(false)),
             connected: Arc::new(AtomicBool::new(false)),
             callback: Arc::new(Mutex::new(None)),
+            reader: Mutex::new(None),
+            last_error: Arc::new(Mutex::new(None)),
+            window_wedge: Mutex::new(None),
         }
     }
 
@@ -93,93 +193,144 @@ impl ScannerManager {
         *cb = Some(Box::new(callback));
     }
 
-    /// 连接扫码枪
+    /// 连接扫码枪（串口或键盘模式）
     pub fn connect(&self, config: ScannerConfig) -> Result<()> {
-        if self.running.load(Ordering::Relaxed) {
-            self.disconnect()?;
+        if config.is_wedge() {
+            return self.connect_wedge(config);
         }
+        let settings = config.serial_settings()?;
+        self.disconnect()?;
+
+        // 打开串口
+        let port = serialport::new(&config.port, config.baud_rate)
+            .data_bits(settings.data_bits)
+            .stop_bits(settings.stop_bits)
+            .parity(settings.parity)
+            .flow_control(FlowControl::None)
+            .timeout(READ_TIMEOUT)
+            .open()
+            .map_err(|e| ScannerError::SerialError(format!("打开串口失败: {}", e)))?;
 
         // 保存配置
         {
             let mut cfg = self.config.lock().unwrap();
-            *cfg = config.clone();
+            *cfg = config;
         }
+        let mut pending = Vec::new();
+        self.spawn_reader(port, move |bytes| split_lines(&mut pending, bytes));
+        Ok(())
+    }
 
-        // 打开串口
-        let port = serialport::new(&config.port, config.baud_rate)
-            .timeout(Duration::from_millis(100))
-            .open()
-            .map_err(|e| ScannerError::SerialError(format!("打开串口失败: {}", e)))?;
+    /// 键盘模式：指定了输入设备时后台读取设备事件，否则等待应用窗口转发按键
+    fn connect_wedge(&self, config: ScannerConfig) -> Result<()> {
+        let detector = config.wedge_detector()?;
+        self.disconnect()?;
+
+        let device = config.input_device.trim().to_string();
+        if device.is_empty() {
+            *self.window_wedge.lock().unwrap() = Some(detector);
+            *self.config.lock().unwrap() = config;
+            self.last_error.lock().unwrap().take();
+            self.connected.store(true, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        let file = wedge::open_input_device(&device)
+            .map_err(|e| ScannerError::SerialError(format!("打开输入设备失败: {}", e)))?;
+        *self.config.lock().unwrap() = config;
+        let mut decoder = wedge::EvdevDecoder::new(detector);
+        self.spawn_reader(file, move |bytes| decoder.feed(bytes));
+        Ok(())
+    }
+
+    /// 处理应用窗口转发的按键，返回识别出的条码数量
+    pub fn feed_keys(&self, events: &[WedgeKeyEvent]) -> Result<usize> {
+        let barcodes = match self.window_wedge.lock().unwrap().as_mut() {
+            Some(detector) => detector.push_events(events),
+            None => return Err(ScannerError::NotConnected),
+        };
+        let count = barcodes.len();
+        for barcode in barcodes {
+            emit_scan(&self.callback, barcode);
+        }
+        Ok(count)
+    }
 
-        self.running.store(true, Ordering::Relaxed);
-        self.connected.store(true, Ordering::Relaxed);
+    /// 启动读取线程：`decode` 把读到的字节转换为条码，读取超时时检查是否已断开
+    fn spawn_reader<R, D>(&self, mut port: R, mut decode: D)
+    where
+        R: Read + Send + 'static,
+        D: FnMut(&[u8]) -> Vec<String> + Send + 'static,
+    {
+        self.running.store(true, Ordering::SeqCst);
+        self.connected.store(true, Ordering::SeqCst);
+        self.last_error.lock().unwrap().take();
 
-        // 启动读取线程
         let running = Arc::clone(&self.running);
         let connected = Arc::clone(&self.connected);
         let callback = Arc::clone(&self.callback);
+        let last_error = Arc::clone(&self.last_error);
 
-        thread::spawn(move || {
-            let reader = BufReader::new(port);
-
-            for line in reader.lines() {
-                if !running.load(Ordering::Relaxed) {
-                    break;
-                }
+        let handle = thread::spawn(move || {
+            let mut chunk = [0u8; 256];
 
-                match line {
-                    Ok(barcode) => {
-                        let barcode = barcode.trim().to_string();
-                        if !barcode.is_empty() {
-                            let result = ScanResult {
-                                barcode,
-                                timestamp: chrono::Utc::now().to_rfc3339(),
-                            };
-
-                            if let Some(ref cb) = *callback.lock().unwrap() {
-                                cb(result);
-                            }
+            while running.load(Ordering::SeqCst) {
+                match port.read(&mut chunk) {
+                    Ok(0) => {
+                        *last_error.lock().unwrap() = Some("串口已关闭".to_string());
+                        break;
+                    }
+                    Ok(n) => {
+                        for barcode in decode(&chunk[..n]) {
+                            emit_scan(&callback, barcode);
                         }
                     }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
                     Err(e) => {
                         log::error!("读取扫码数据失败: {}", e);
-                        if e.kind() == std::io::ErrorKind::TimedOut {
-                            continue;
-                        }
+                        *last_error.lock().unwrap() = Some(e.to_string());
                         break;
                     }
                 }
             }
 
-            connected.store(false, Ordering::Relaxed);
-            running.store(false, Ordering::Relaxed);
+            connected.store(false, Ordering::SeqCst);
+            running.store(false, Ordering::SeqCst);
         });
-
-        Ok(())
+        *self.reader.lock().unwrap() = Some(handle);
     }
 
-    /// 断开扫码枪
+    /// 断开扫码枪，等待读取线程退出（串口随线程关闭）
     pub fn disconnect(&self) -> Result<()> {
-        self.running.store(false, Ordering::Relaxed);
-        self.connected.store(false, Ordering::Relaxed);
+        self.running.store(false, Ordering::SeqCst);
+        self.window_wedge.lock().unwrap().take();
+        if let Some(handle) = self.reader.lock().unwrap().take() {
+            if handle.join().is_err() {
+                log::error!("扫码读取线程异常退出");
+            }
+        }
+        self.connected.store(false, Ordering::SeqCst);
         Ok(())
     }
 
     /// 获取连接状态
     pub fn get_status(&self) -> ScannerStatus {
-        let connected = self.connected.load(Ordering::Relaxed);
-        let port = if connected {
-            self.config.lock().ok().map(|c| c.port.clone())
-        } else {
-            None
+        let connected = self.connected.load(Ordering::SeqCst);
+        let config = self.config.lock().unwrap();
+        let mode = if config.is_wedge() { "wedge" } else { "serial" };
+        let port = match (connected, config.is_wedge()) {
+            (false, _) => None,
+            (true, true) => Some(config.input_device.clone()),
+            (true, false) => Some(config.port.clone()),
         };
 
-        ScannerStatus { connected, port }
+        ScannerStatus { connected, mode: mode.to_string(), port, last_error: self.last_error.lock().unwrap().clone() }
     }
 
     /// 是否已连接
     pub fn is_connected(&self) -> bool {
-        self.connected.load(Ordering::Relaxed)
+        self.connected.load(Ordering::SeqCst)
     }
 }
 
@@ -189,6 +340,43 @@ impl Default for ScannerManager {
     }
 }
 
+impl Drop for ScannerManager {
+    fn drop(&mut self) {
+        self.disconnect().ok();
+    }
+}
+
+/// 通知一次扫码
+fn emit_scan(callback: &Mutex<Option<ScanCallback>>, barcode: String) {
+    let result = ScanResult {
+        barcode,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    };
+    if let Some(ref cb) = *callback.lock().unwrap() {
+        cb(result);
+    }
+}
+
+/// 把新读到的字节追加到缓冲区，返回其中完整的条码（以 `\r` 或 `\n` 结尾，去掉首尾空白）
+fn split_lines(pending: &mut Vec<u8>, bytes: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for &byte in bytes {
+        if byte == b'\r' || byte == b'\n' {
+            let line = String::from_utf8_lossy(pending).trim().to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+            pending.clear();
+        } else if pending.len() < MAX_BARCODE_LENGTH {
+            pending.push(byte);
+        } else {
+            log::warn!("扫码数据过长，已丢弃，请检查串口配置");
+            pending.clear();
+        }
+    }
+    lines
+}
+
 // 模拟扫码（用于测试和开发）
 pub fn simulate_scan(barcode: &str) -> ScanResult {
     ScanResult {
@@ -200,6 +388,8 @@ pub fn simulate_scan(barcode: &str) -> ScanResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::VecDeque;
+    use std::sync::mpsc;
 
     #[test]
     fn test_list_ports() {
@@ -213,4 +403,149 @@ mod tests {
         let status = manager.get_status();
         assert!(!status.connected);
     }
+
+    #[test]
+    fn test_config_parsing() {
+        let config: ScannerConfig = serde_json::from_value(serde_json::json!({
+            "enabled": true, "port": "/dev/ttyUSB0", "baudRate": 115200, "dataBits": 7, "stopBits": 2, "parity": "even"
+        }))
+        .unwrap();
+        assert_eq!(config.baud_rate, 115200);
+        assert_eq!(
+            config.serial_settings().unwrap(),
+            SerialSettings { data_bits: DataBits::Seven, stop_bits: StopBits::Two, parity: Parity::Even }
+        );
+
+        let defaults: ScannerConfig = serde_json::from_value(serde_json::json!({ "port": "COM3" })).unwrap();
+        assert_eq!(defaults.serial_settings().unwrap().data_bits, DataBits::Eight);
+
+        for (field, value) in [("dataBits", 9), ("stopBits", 3), ("baudRate", 0)] {
+            let mut raw = serde_json::json!({ "port": "COM3" });
+            raw[field] = value.into();
+            let config: ScannerConfig = serde_json::from_value(raw).unwrap();
+            assert!(matches!(config.serial_settings(), Err(ScannerError::ConfigError(_))), "{}", field);
+        }
+        let config = ScannerConfig { parity: "mark".to_string(), port: "COM3".to_string(), ..Default::default() };
+        assert!(config.serial_settings().is_err());
+        assert!(ScannerConfig::default().serial_settings().is_err());
+    }
+
+    /// 按顺序返回数据块与超时的模拟串口，数据用完后一直超时
+    struct MockPort(VecDeque<Option<&'static [u8]>>);
+
+    impl Read for MockPort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Some(bytes)) => {
+                    buf[..bytes.len()].copy_from_slice(bytes);
+                    Ok(bytes.len())
+                }
+                _ => {
+                    thread::sleep(Duration::from_millis(5));
+                    Err(std::io::Error::new(ErrorKind::TimedOut, "timeout"))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_thread_splits_and_stops() {
+        let manager = ScannerManager::new();
+        let (tx, rx) = mpsc::channel();
+        manager.set_callback(move |result| tx.send(result.barcode).unwrap());
+
+        // 条码被超时切成两段也能拼回
+        let mut pending = Vec::new();
+        manager.spawn_reader(
+            MockPort(VecDeque::from([
+                Some(&b"69012"[..]),
+                None,
+                Some(&b"34567892\r\n  \r\n"[..]),
+                Some(&b"ABC-1\nXYZ"[..]),
+            ])),
+            move |bytes| split_lines(&mut pending, bytes),
+        );
+        assert!(manager.is_connected());
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "6901234567892");
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "ABC-1");
+
+        manager.disconnect().unwrap();
+        assert!(!manager.is_connected());
+        assert!(manager.reader.lock().unwrap().is_none());
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn test_window_wedge_mode() {
+        let manager = ScannerManager::new();
+        let (tx, rx) = mpsc::channel();
+        manager.set_callback(move |result| tx.send(result.barcode).unwrap());
+
+        let key = |key: &str, timestamp: f64| WedgeKeyEvent { key: key.to_string(), timestamp };
+        assert!(matches!(manager.feed_keys(&[key("1", 0.0)]), Err(ScannerError::NotConnected)));
+
+        let config = ScannerConfig { mode: "wedge".to_string(), key_interval_ms: 0, ..Default::default() };
+        assert!(matches!(manager.connect(config), Err(ScannerError::ConfigError(_))));
+        manager.connect(ScannerConfig { mode: "wedge".to_string(), ..Default::default() }).unwrap();
+        let status = manager.get_status();
+        assert!(status.connected);
+        assert_eq!(status.mode, "wedge");
+
+        let events = [key("P", 10.0), key("-", 20.0), key("7", 30.0), key("7", 40.0), key("Enter", 50.0)];
+        assert_eq!(manager.feed_keys(&events).unwrap(), 1);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "P-77");
+        assert_eq!(manager.feed_keys(&[key("9", 500.0), key("Enter", 900.0)]).unwrap(), 0);
+
+        manager.disconnect().unwrap();
+        assert!(!manager.is_connected());
+        assert!(manager.feed_keys(&events).is_err());
+    }
+
+    /// 等待条件成立，最多 2 秒
+    #[cfg(unix)]
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        for _ in 0..200 {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_connect_virtual_scanner() {
+        let mut virtual_scanner = VirtualScanner::new().unwrap();
+        let manager = ScannerManager::new();
+        let (tx, rx) = mpsc::channel();
+        manager.set_callback(move |result| tx.send(result.barcode).unwrap());
+
+        let config = ScannerConfig { port: virtual_scanner.port_name().to_string(), baud_rate: 115200, ..Default::default() };
+        manager.connect(config).unwrap();
+        let status = manager.get_status();
+        assert!(status.connected);
+        assert_eq!(status.port.as_deref(), Some(virtual_scanner.port_name()));
+
+        virtual_scanner.scan("6901234567892").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "6901234567892");
+
+        // 分段到达的 GS1 数据
+        virtual_scanner.write_raw(b"]C10109501101530003").unwrap();
+        thread::sleep(READ_TIMEOUT + Duration::from_millis(50));
+        virtual_scanner.write_raw(b"10LOT-1\r").unwrap();
+        let barcode = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(parse_barcode(&barcode).batch.as_deref(), Some("LOT-1"));
+
+        // 重新连接会先停止原来的读取线程
+        manager.connect(ScannerConfig { port: virtual_scanner.port_name().to_string(), ..Default::default() }).unwrap();
+        virtual_scanner.scan("ABC-1").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "ABC-1");
+
+        // 关闭虚拟扫码枪相当于拔出设备
+        drop(virtual_scanner);
+        assert!(wait_until(|| !manager.is_connected()));
+        assert!(manager.get_status().last_error.is_some());
+        manager.disconnect().unwrap();
+    }
 }
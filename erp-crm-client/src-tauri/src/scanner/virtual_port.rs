@@ -0,0 +1,44 @@
+//! 虚拟扫码枪 - 用伪终端模拟串口扫码枪，无需硬件即可测试 `ScannerManager::connect`
+//!
+//! 伪终端的从端路径（如 `/dev/pts/3`）当作串口传给 `connect`，
+//! 向主端写入的数据就是扫码枪发出的数据；关闭虚拟扫码枪相当于拔出设备。
+
+use serialport::{SerialPort, TTYPort};
+use std::io::Write;
+
+use super::{Result, ScannerError};
+
+pub struct VirtualScanner {
+    master: TTYPort,
+    /// 保持从端打开，避免连接前主端写入的数据因无人打开从端而丢失
+    _slave: TTYPort,
+    port_name: String,
+}
+
+impl VirtualScanner {
+    pub fn new() -> Result<Self> {
+        let (master, slave) = TTYPort::pair().map_err(|e| ScannerError::SerialError(format!("创建伪终端失败: {}", e)))?;
+        let port_name = slave
+            .name()
+            .ok_or_else(|| ScannerError::SerialError("无法获取伪终端路径".to_string()))?;
+        Ok(Self { master, _slave: slave, port_name })
+    }
+
+    /// 传给 `ScannerConfig::port` 的串口路径
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    /// 模拟扫码：发送条码并以回车换行结束
+    pub fn scan(&mut self, barcode: &str) -> Result<()> {
+        self.write_raw(format!("{}\r\n", barcode).as_bytes())
+    }
+
+    /// 发送原始字节（可以只发送条码的一部分，测试分段到达的数据）
+    pub fn write_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.master
+            .write_all(bytes)
+            .and_then(|_| self.master.flush())
+            .map_err(|e| ScannerError::SerialError(e.to_string()))
+    }
+}
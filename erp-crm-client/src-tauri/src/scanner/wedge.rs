@@ -0,0 +1,291 @@
+//! 键盘模式扫码枪 - USB 扫码枪模拟键盘输入时，按按键间隔识别扫码
+//!
+//! 扫码枪在几十毫秒内连续“敲”完整个条码并以回车结束，人工输入的按键间隔要长得多。
+//! 输入来源有两种：应用窗口转发的按键（各平台通用），或 Linux 输入设备
+//! （`/dev/input/by-id/...-event-kbd`，独占读取，按键不会再进入其他窗口）。
+
+use serde::Deserialize;
+use std::time::Duration;
+
+use super::MAX_BARCODE_LENGTH;
+
+/// 按键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WedgeKey {
+    Char(char),
+    Enter,
+    /// 退格、Tab 等其他按键，会打断正在识别的条码
+    Other,
+}
+
+impl WedgeKey {
+    /// 按浏览器 `KeyboardEvent.key` 的取值转换
+    pub fn from_key_name(key: &str) -> Self {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            _ if key == "Enter" => WedgeKey::Enter,
+            (Some(c), None) if !c.is_control() => WedgeKey::Char(c),
+            _ => WedgeKey::Other,
+        }
+    }
+}
+
+/// 应用窗口转发的按键，`timestamp` 为 `KeyboardEvent.timeStamp`（毫秒）
+#[derive(Debug, Clone, Deserialize)]
+pub struct WedgeKeyEvent {
+    pub key: String,
+    pub timestamp: f64,
+}
+
+/// 按键速度识别：相邻按键间隔超过 `max_interval` 时重新开始，回车时长度达到 `min_length` 才算一次扫码
+#[derive(Debug, Clone)]
+pub struct WedgeDetector {
+    max_interval: Duration,
+    min_length: usize,
+    buffer: String,
+    last_key: Option<Duration>,
+}
+
+impl WedgeDetector {
+    pub fn new(max_interval: Duration, min_length: usize) -> Self {
+        Self { max_interval, min_length, buffer: String::new(), last_key: None }
+    }
+
+    /// 输入一个按键（`at` 为单调递增的时间戳），识别出完整条码时返回
+    pub fn push(&mut self, key: WedgeKey, at: Duration) -> Option<String> {
+        let too_slow = self.last_key.is_some_and(|last| at.saturating_sub(last) > self.max_interval);
+        if too_slow {
+            self.buffer.clear();
+        }
+        self.last_key = Some(at);
+
+        match key {
+            WedgeKey::Char(c) => {
+                if self.buffer.len() < MAX_BARCODE_LENGTH {
+                    self.buffer.push(c);
+                } else {
+                    self.buffer.clear();
+                }
+                None
+            }
+            WedgeKey::Enter => {
+                self.last_key = None;
+                let barcode = std::mem::take(&mut self.buffer);
+                let barcode = barcode.trim();
+                (barcode.chars().count() >= self.min_length).then(|| barcode.to_string())
+            }
+            WedgeKey::Other => {
+                self.buffer.clear();
+                None
+            }
+        }
+    }
+
+    /// 依次输入应用窗口转发的按键
+    pub fn push_events(&mut self, events: &[WedgeKeyEvent]) -> Vec<String> {
+        events
+            .iter()
+            .filter(|e| e.timestamp.is_finite() && e.timestamp >= 0.0)
+            .filter_map(|e| self.push(WedgeKey::from_key_name(&e.key), Duration::from_secs_f64(e.timestamp / 1000.0)))
+            .collect()
+    }
+}
+
+/// Linux `struct input_event`：timeval（秒、微秒）+ type + code + value
+#[cfg(target_pointer_width = "64")]
+type TimeField = i64;
+#[cfg(not(target_pointer_width = "64"))]
+type TimeField = i32;
+const TIME_FIELD_SIZE: usize = std::mem::size_of::<TimeField>();
+const INPUT_EVENT_SIZE: usize = TIME_FIELD_SIZE * 2 + 8;
+
+const EV_KEY: u16 = 1;
+const KEY_ENTER: u16 = 28;
+const KEY_KPENTER: u16 = 96;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_RIGHTSHIFT: u16 = 54;
+
+/// 按美式键盘布局把键码转换为字符
+fn keycode_char(code: u16, shift: bool) -> Option<char> {
+    let row = |first: u16, plain: &str, shifted: &str| {
+        let index = usize::from(code - first);
+        if shift { shifted.chars().nth(index) } else { plain.chars().nth(index) }
+    };
+    match code {
+        2..=13 => row(2, "1234567890-=", "!@#$%^&*()_+"),
+        16..=27 => row(16, "qwertyuiop[]", "QWERTYUIOP{}"),
+        30..=41 => row(30, "asdfghjkl;'`", "ASDFGHJKL:\"~"),
+        43 => row(43, "\\", "|"),
+        44..=53 => row(44, "zxcvbnm,./", "ZXCVBNM<>?"),
+        57 => Some(' '),
+        // 小键盘
+        71..=83 => "789-456+1230.".chars().nth(usize::from(code - 71)),
+        98 => Some('/'),
+        55 => Some('*'),
+        _ => None,
+    }
+}
+
+/// 解析 Linux 输入设备的原始事件流
+#[derive(Debug)]
+pub struct EvdevDecoder {
+    detector: WedgeDetector,
+    pending: Vec<u8>,
+    shift: bool,
+}
+
+impl EvdevDecoder {
+    pub fn new(detector: WedgeDetector) -> Self {
+        Self { detector, pending: Vec::new(), shift: false }
+    }
+
+    /// 追加读到的字节，返回其中识别出的条码（不完整的事件留到下次）
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut barcodes = Vec::new();
+        let complete = self.pending.len() - self.pending.len() % INPUT_EVENT_SIZE;
+        let events: Vec<u8> = self.pending.drain(..complete).collect();
+
+        for event in events.chunks_exact(INPUT_EVENT_SIZE) {
+            let field = |offset: usize, len: usize| &event[offset..offset + len];
+            let time = |offset: usize| {
+                let value = TimeField::from_ne_bytes(field(offset, TIME_FIELD_SIZE).try_into().unwrap());
+                u64::try_from(value).unwrap_or(0)
+            };
+            let at = Duration::from_secs(time(0)) + Duration::from_micros(time(TIME_FIELD_SIZE));
+            let kind = u16::from_ne_bytes(field(TIME_FIELD_SIZE * 2, 2).try_into().unwrap());
+            let code = u16::from_ne_bytes(field(TIME_FIELD_SIZE * 2 + 2, 2).try_into().unwrap());
+            let value = i32::from_ne_bytes(field(TIME_FIELD_SIZE * 2 + 4, 4).try_into().unwrap());
+            if kind != EV_KEY {
+                continue;
+            }
+
+            // value: 0 松开，1 按下，2 长按重复
+            if code == KEY_LEFTSHIFT || code == KEY_RIGHTSHIFT {
+                self.shift = value != 0;
+                continue;
+            }
+            if value == 0 {
+                continue;
+            }
+            let key = match code {
+                KEY_ENTER | KEY_KPENTER => WedgeKey::Enter,
+                _ => keycode_char(code, self.shift).map_or(WedgeKey::Other, WedgeKey::Char),
+            };
+            barcodes.extend(self.detector.push(key, at));
+        }
+        barcodes
+    }
+}
+
+/// 以非阻塞方式打开 Linux 输入设备并独占（EVIOCGRAB），扫码按键不再输入到其他窗口
+#[cfg(target_os = "linux")]
+pub fn open_input_device(path: &str) -> std::io::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    const EVIOCGRAB: u64 = 0x4004_4590;
+    let file = std::fs::OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+    // SAFETY: fd 在 file 的生命周期内有效，EVIOCGRAB 的参数是一个整数
+    if unsafe { libc::ioctl(file.as_raw_fd(), EVIOCGRAB as _, 1 as libc::c_int) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_input_device(_path: &str) -> std::io::Result<std::fs::File> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "仅 Linux 支持直接读取输入设备"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_detector_timing() {
+        fn scan(detector: &mut WedgeDetector, text: &str, start: u64, step: u64) -> Option<String> {
+            let mut at = start;
+            for c in text.chars() {
+                assert_eq!(detector.push(WedgeKey::Char(c), ms(at)), None);
+                at += step;
+            }
+            detector.push(WedgeKey::Enter, ms(at))
+        }
+        let mut detector = WedgeDetector::new(ms(30), 4);
+
+        assert_eq!(scan(&mut detector, "6901234567892", 0, 5).as_deref(), Some("6901234567892"));
+        // 人工输入：按键间隔过长
+        assert_eq!(scan(&mut detector, "1234", 1000, 200), None);
+        // 太短的快速输入（如快捷键后的回车）
+        assert_eq!(scan(&mut detector, "ab", 5000, 5), None);
+
+        // 手动输入的前缀不会混进随后的扫码
+        detector.push(WedgeKey::Char('x'), ms(9000));
+        assert_eq!(scan(&mut detector, "ABC-1", 9500, 8).as_deref(), Some("ABC-1"));
+
+        // 其他按键打断识别
+        detector.push(WedgeKey::Char('1'), ms(10000));
+        detector.push(WedgeKey::Char('2'), ms(10005));
+        detector.push(WedgeKey::Other, ms(10010));
+        assert_eq!(scan(&mut detector, "34", 10015, 5), None);
+    }
+
+    #[test]
+    fn test_window_events() {
+        assert_eq!(WedgeKey::from_key_name("Enter"), WedgeKey::Enter);
+        assert_eq!(WedgeKey::from_key_name("a"), WedgeKey::Char('a'));
+        assert_eq!(WedgeKey::from_key_name("Shift"), WedgeKey::Other);
+
+        let mut detector = WedgeDetector::new(ms(50), 4);
+        let events: Vec<WedgeKeyEvent> = ["S", "K", "U", "-", "9", "Enter"]
+            .iter()
+            .enumerate()
+            .map(|(i, key)| WedgeKeyEvent { key: key.to_string(), timestamp: 1234.5 + i as f64 * 12.0 })
+            .collect();
+        assert_eq!(detector.push_events(&events), vec!["SKU-9"]);
+    }
+
+    /// 构造一条 `input_event`
+    fn event(at_ms: u64, kind: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(INPUT_EVENT_SIZE);
+        bytes.extend_from_slice(&((at_ms / 1000) as TimeField).to_ne_bytes());
+        bytes.extend_from_slice(&((at_ms % 1000 * 1000) as TimeField).to_ne_bytes());
+        bytes.extend_from_slice(&kind.to_ne_bytes());
+        bytes.extend_from_slice(&code.to_ne_bytes());
+        bytes.extend_from_slice(&value.to_ne_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_evdev_decoder() {
+        let mut stream = Vec::new();
+        let mut at = 1_000;
+        let mut key = |code: u16, stream: &mut Vec<u8>| {
+            stream.extend(event(at, EV_KEY, code, 1));
+            stream.extend(event(at, 0, 0, 0)); // EV_SYN
+            stream.extend(event(at + 2, EV_KEY, code, 0));
+            at += 6;
+        };
+        // Shift + a b, '-', 1 2, 回车 => "AB-12"
+        stream.extend(event(1_000, EV_KEY, KEY_LEFTSHIFT, 1));
+        key(30, &mut stream);
+        key(48, &mut stream);
+        stream.extend(event(1_020, EV_KEY, KEY_LEFTSHIFT, 0));
+        key(12, &mut stream);
+        key(2, &mut stream);
+        key(3, &mut stream);
+        key(KEY_ENTER, &mut stream);
+
+        let mut decoder = EvdevDecoder::new(WedgeDetector::new(ms(50), 4));
+        // 事件被拆散读到也能拼回
+        let (first, rest) = stream.split_at(INPUT_EVENT_SIZE * 3 + 5);
+        assert!(decoder.feed(first).is_empty());
+        assert_eq!(decoder.feed(rest), vec!["AB-12"]);
+        assert!(decoder.pending.is_empty());
+    }
+}
@@ -0,0 +1,413 @@
+//! 表格文件读写 - CSV 与 Excel（XLSX）
+//!
+//! 读取时只取第一个工作表，第一行非空行作为表头；XLS、ODS 也能读取。
+//! 写入 XLSX 时直接生成最小的 SpreadsheetML 包（单个工作表、内联字符串、无样式），
+//! 写入 CSV 时带 UTF-8 BOM，Excel 打开中文不会乱码。
+
+use calamine::{open_workbook_auto, Data, Reader};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const UTF8_BOM: &str = "\u{feff}";
+/// Excel 工作表名称的最大长度
+const MAX_SHEET_NAME: usize = 31;
+
+#[derive(Error, Debug)]
+pub enum SpreadsheetError {
+    #[error("不支持的文件格式: {0}，请使用 CSV 或 XLSX")]
+    UnsupportedFormat(String),
+    #[error("文件中没有数据")]
+    Empty,
+    #[error("第 {0} 行不是 UTF-8 编码，请在 Excel 中另存为「CSV UTF-8」后重试")]
+    Encoding(usize),
+    #[error("读写文件失败: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CSV 解析失败: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Excel 解析失败: {0}")]
+    Excel(#[from] calamine::Error),
+    #[error("写入 XLSX 失败: {0}")]
+    Zip(#[from] zip::result::ZipError),
+}
+
+pub type SpreadsheetResult<T> = Result<T, SpreadsheetError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SheetFormat {
+    Csv,
+    Xlsx,
+}
+
+impl SheetFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Some(SheetFormat::Csv),
+            "xlsx" => Some(SheetFormat::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+/// 单元格：文本或数值，导出 XLSX 时数值写成数字单元格，可以直接求和
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+}
+
+impl Cell {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Cell::Empty => true,
+            Cell::Text(text) => text.trim().is_empty(),
+            Cell::Number(_) => false,
+        }
+    }
+}
+
+/// 数值按最短形式输出：整数不带小数点，长条码不会变成科学计数法
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Empty => Ok(()),
+            Cell::Text(text) => f.write_str(text),
+            Cell::Number(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Cell::Number(value)
+    }
+}
+
+impl From<i32> for Cell {
+    fn from(value: i32) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+impl From<usize> for Cell {
+    fn from(value: usize) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+/// 空值写成空单元格（如上期为 0 时的增长率）
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Cell::Empty, Into::into)
+    }
+}
+
+/// 一张表：表头与数据行
+#[derive(Debug, Clone, Default)]
+pub struct Sheet {
+    /// 工作表名称，只用于 XLSX
+    pub name: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+    /// 表头所在的行号（从 1 开始），数据第 i 行（从 0 开始）的行号为 `header_line + 1 + i`
+    pub header_line: usize,
+}
+
+impl Sheet {
+    pub fn new(name: &str, headers: &[&str]) -> Self {
+        Sheet {
+            name: name.to_string(),
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+            header_line: 1,
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    /// 数据行在文件中的行号，用于错误提示
+    pub fn line_of(&self, index: usize) -> usize {
+        self.header_line + 1 + index
+    }
+}
+
+/// 按扩展名读取表格文件
+pub fn read_sheet(path: &Path) -> SpreadsheetResult<Sheet> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    let (first_line, mut rows) = match extension.as_str() {
+        "csv" => (1, read_csv(path)?),
+        "xlsx" | "xlsm" | "xls" | "ods" => read_workbook(path)?,
+        other => return Err(SpreadsheetError::UnsupportedFormat(other.to_string())),
+    };
+    let header_index = rows.iter().position(|row| row.iter().any(|c| !c.is_empty())).ok_or(SpreadsheetError::Empty)?;
+    let data = rows.split_off(header_index + 1);
+    let headers = rows.pop().unwrap_or_default().iter().map(|c| c.to_string().trim().to_string()).collect();
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+    Ok(Sheet { name, headers, rows: data, header_line: first_line + header_index })
+}
+
+fn read_csv(path: &Path) -> SpreadsheetResult<Vec<Vec<Cell>>> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_path(path)?;
+    let mut rows = Vec::new();
+    for (index, record) in reader.byte_records().enumerate() {
+        let record = record?;
+        let mut row = Vec::with_capacity(record.len());
+        for field in record.iter() {
+            let text = std::str::from_utf8(field).map_err(|_| SpreadsheetError::Encoding(index + 1))?;
+            let text = if index == 0 { text.trim_start_matches(UTF8_BOM) } else { text };
+            row.push(if text.is_empty() { Cell::Empty } else { Cell::Text(text.to_string()) });
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// 返回第一行数据的行号与各行单元格（表格左上角可能不在 A1）
+fn read_workbook(path: &Path) -> SpreadsheetResult<(usize, Vec<Vec<Cell>>)> {
+    let mut workbook = open_workbook_auto(path)?;
+    let range = match workbook.worksheet_range_at(0) {
+        Some(range) => range?,
+        None => return Err(SpreadsheetError::Empty),
+    };
+    let first_line = range.start().map_or(1, |(row, _)| row as usize + 1);
+    let rows = range.rows().map(|row| row.iter().map(excel_cell).collect()).collect();
+    Ok((first_line, rows))
+}
+
+fn excel_cell(data: &Data) -> Cell {
+    match data {
+        Data::Empty | Data::Error(_) => Cell::Empty,
+        Data::Int(value) => Cell::Number(*value as f64),
+        Data::Float(value) => Cell::Number(*value),
+        Data::Bool(value) => Cell::Text(value.to_string()),
+        Data::DateTime(value) => Cell::Number(value.as_f64()),
+        Data::String(text) | Data::DateTimeIso(text) | Data::DurationIso(text) => Cell::Text(text.clone()),
+    }
+}
+
+pub fn write_sheet(path: &Path, format: SheetFormat, sheet: &Sheet) -> SpreadsheetResult<()> {
+    match format {
+        SheetFormat::Csv => write_csv(path, sheet),
+        SheetFormat::Xlsx => write_xlsx(path, sheet),
+    }
+}
+
+fn write_csv(path: &Path, sheet: &Sheet) -> SpreadsheetResult<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(UTF8_BOM.as_bytes())?;
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(file);
+    writer.write_record(&sheet.headers)?;
+    for row in &sheet.rows {
+        writer.write_record(row.iter().map(export_text))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_xlsx(path: &Path, sheet: &Sheet) -> SpreadsheetResult<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("[Content_Types].xml", options)?;
+    zip.write_all(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+        r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+        r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+        r#"<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+        r#"<Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+        r#"</Types>"#,
+    ).as_bytes())?;
+
+    zip.start_file("_rels/.rels", options)?;
+    zip.write_all(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>"#,
+        r#"</Relationships>"#,
+    ).as_bytes())?;
+
+    zip.start_file("xl/workbook.xml", options)?;
+    write!(
+        zip,
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">"#,
+            r#"<sheets><sheet name="{}" sheetId="1" r:id="rId1"/></sheets></workbook>"#,
+        ),
+        escape_xml(&sheet_name(&sheet.name)),
+    )?;
+
+    zip.start_file("xl/_rels/workbook.xml.rels", options)?;
+    zip.write_all(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>"#,
+        r#"</Relationships>"#,
+    ).as_bytes())?;
+
+    zip.start_file("xl/worksheets/sheet1.xml", options)?;
+    zip.write_all(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    ).as_bytes())?;
+    let headers: Vec<Cell> = sheet.headers.iter().map(|h| Cell::Text(h.clone())).collect();
+    for (index, row) in std::iter::once(&headers).chain(&sheet.rows).enumerate() {
+        let line = index + 1;
+        write!(zip, r#"<row r="{}">"#, line)?;
+        for (column, cell) in row.iter().enumerate() {
+            let reference = format!("{}{}", column_name(column), line);
+            match cell {
+                Cell::Empty => {}
+                Cell::Number(value) if value.is_finite() => write!(zip, r#"<c r="{}"><v>{}</v></c>"#, reference, value)?,
+                other => write!(zip, r#"<c r="{}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#, reference, escape_xml(&export_text(other)))?,
+            }
+        }
+        zip.write_all(b"</row>")?;
+    }
+    zip.write_all(b"</sheetData></worksheet>")?;
+
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// 导出时的单元格文本：以 `=`、`+`、`-`、`@`、制表符或回车开头的文本加 `'` 前缀，
+/// 避免被表格软件当作公式执行（CSV 注入）；数值单元格原样输出
+fn export_text(cell: &Cell) -> String {
+    match cell {
+        Cell::Text(text) if text.starts_with(['=', '+', '-', '@', '\t', '\r']) => format!("'{}", text),
+        other => other.to_string(),
+    }
+}
+
+/// 列号（从 0 开始）转为 A、B、…、Z、AA、AB…
+fn column_name(mut column: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (column % 26) as u8);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+/// 工作表名称不能包含 `[]:*?/\`，且不超过 31 个字符
+fn sheet_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+        .take(MAX_SHEET_NAME)
+        .collect();
+    if cleaned.trim().is_empty() { "Sheet1".to_string() } else { cleaned }
+}
+
+/// 转义 XML 特殊字符，去掉 XML 不允许的控制字符
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Sheet {
+        let mut sheet = Sheet::new("产品", &["编码", "名称", "条码", "售价"]);
+        sheet.push(vec!["P001".into(), "螺丝 <M4> & 垫片".into(), Cell::Number(6901234567892.0), 12.5.into()]);
+        sheet.push(vec!["P002".into(), "扳手,\"加长\"".into(), Cell::Empty, 30.into()]);
+        sheet
+    }
+
+    #[test]
+    fn test_round_trip_csv_and_xlsx() {
+        let dir = tempfile::tempdir().unwrap();
+        for format in [SheetFormat::Csv, SheetFormat::Xlsx] {
+            let path = dir.path().join(if format == SheetFormat::Csv { "out.csv" } else { "out.xlsx" });
+            write_sheet(&path, format, &sample()).unwrap();
+            let sheet = read_sheet(&path).unwrap();
+            assert_eq!(sheet.headers, vec!["编码", "名称", "条码", "售价"]);
+            assert_eq!(sheet.rows.len(), 2);
+            assert_eq!(sheet.rows[0][1].to_string(), "螺丝 <M4> & 垫片");
+            // 长条码不能变成科学计数法
+            assert_eq!(sheet.rows[0][2].to_string(), "6901234567892");
+            assert_eq!(sheet.rows[0][3].to_string(), "12.5");
+            assert_eq!(sheet.rows[1][1].to_string(), "扳手,\"加长\"");
+            assert_eq!(sheet.line_of(1), 3);
+        }
+    }
+
+    #[test]
+    fn test_formula_cells_neutralized() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sheet = Sheet::new("客户", &["名称", "备注", "余额"]);
+        sheet.push(vec!["=HYPERLINK(\"http://x\")".into(), "@SUM(A1)".into(), Cell::Number(-12.5)]);
+        sheet.push(vec!["+86 138".into(), "\t-1+1".into(), "-".into()]);
+        sheet.push(vec!["普通客户".into(), "a=b".into(), Cell::Number(3.0)]);
+        for format in [SheetFormat::Csv, SheetFormat::Xlsx] {
+            let path = dir.path().join(if format == SheetFormat::Csv { "out.csv" } else { "out.xlsx" });
+            write_sheet(&path, format, &sheet).unwrap();
+            let read = read_sheet(&path).unwrap();
+            let texts: Vec<Vec<String>> = read.rows.iter().map(|r| r.iter().map(|c| c.to_string()).collect()).collect();
+            assert_eq!(texts[0], vec!["'=HYPERLINK(\"http://x\")", "'@SUM(A1)", "-12.5"]);
+            assert_eq!(texts[1], vec!["'+86 138", "'\t-1+1", "'-"]);
+            assert_eq!(texts[2], vec!["普通客户", "a=b", "3"]);
+        }
+    }
+
+    #[test]
+    fn test_csv_header_after_blank_lines_and_non_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("in.csv");
+        std::fs::write(&path, "\u{feff},,\n编码,名称\nP001,螺丝\n").unwrap();
+        let sheet = read_sheet(&path).unwrap();
+        assert_eq!(sheet.headers, vec!["编码", "名称"]);
+        assert_eq!(sheet.line_of(0), 3);
+
+        // GBK 编码的「编码」
+        std::fs::write(&path, [0xb1, 0xe0, 0xc2, 0xeb, b'\n']).unwrap();
+        assert!(matches!(read_sheet(&path), Err(SpreadsheetError::Encoding(1))));
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(27), "AB");
+    }
+}
@@ -1,16 +1,37 @@
 //! 加密存储模块 - 使用 AES-256-GCM 加密本地数据
+//!
+//! 每个存储目录有一个 `keystore.json`，保存随机 salt 与密钥校验值：
+//! 密钥由密码经 Argon2 派生，解锁时先解密校验值，密码错误会被识别而不是读出乱码。
+//! 修改密码时用新 salt 重新派生密钥并重新加密全部数据。
 
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
-use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
+use argon2::Argon2;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use thiserror::Error;
 
+/// 密钥文件名
+const KEYSTORE_FILE: &str = "keystore.json";
+/// 修改密码时的临时后缀，提交前的文件都带这个后缀
+const PENDING_SUFFIX: &str = "new";
+/// 密钥校验值对应的明文
+const KEY_CHECK_PLAINTEXT: &[u8] = b"erp-crm-secure-storage";
+/// 存储键最大长度
+const MAX_KEY_LENGTH: usize = 128;
+/// 用户加密存储中保存数据库密钥的键
+const DATABASE_KEY_ENTRY: &str = "database_key";
+/// 更换数据库密钥期间保留的旧密钥
+const PREVIOUS_DATABASE_KEY_ENTRY: &str = "database_key.previous";
+/// 用户名到用户 ID 的索引（数据库加密锁定时登录需要据此找到加密存储）
+const VAULT_INDEX_FILE: &str = "users.json";
+
 #[derive(Error, Debug)]
 pub enum StorageError {
     #[error("加密失败: {0}")]
@@ -21,10 +42,28 @@ pub enum StorageError {
     IoError(#[from] std::io::Error),
     #[error("密钥派生失败: {0}")]
     KeyDerivationError(String),
+    #[error("密码错误，无法解锁加密存储")]
+    WrongPassword,
+    #[error("加密存储未解锁")]
+    Locked,
+    #[error("无效的存储键: {0}")]
+    InvalidKey(String),
+    #[error("密钥文件损坏: {0}")]
+    CorruptKeyStore(String),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
 
+/// 密钥文件内容
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyStore {
+    version: u32,
+    /// Base64 编码的随机 salt
+    salt: String,
+    /// Base64 编码的 nonce + 密文，解密结果应为 [`KEY_CHECK_PLAINTEXT`]
+    check: String,
+}
+
 /// 加密存储管理器
 pub struct SecureStorage {
     data_dir: PathBuf,
@@ -41,100 +80,180 @@ impl SecureStorage {
         }
     }
 
-    /// 使用密码初始化加密器
+    /// 是否已设置过密码（存在密钥文件）
+    pub fn is_initialized(&self) -> bool {
+        self.keystore_path().exists()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// 使用密码解锁；首次使用时生成随机 salt 与密钥校验值
     pub fn init_with_password(&mut self, password: &str) -> Result<()> {
-        let key = self.derive_key(password)?;
-        self.cipher = Some(Aes256Gcm::new_from_slice(&key).map_err(|e| {
-            StorageError::EncryptionError(format!("创建加密器失败: {}", e))
-        })?);
+        self.recover_pending()?;
+        let cipher = match self.read_keystore()? {
+            Some(keystore) => Self::open_keystore(&keystore, password)?,
+            None => {
+                let (keystore, cipher) = Self::new_keystore(password)?;
+                write_atomic(&self.keystore_path(), &serde_json::to_vec_pretty(&keystore).unwrap_or_default())?;
+                cipher
+            }
+        };
+        self.cipher = Some(cipher);
         Ok(())
     }
 
-    /// 使用 Argon2 派生密钥
-    fn derive_key(&self, password: &str) -> Result<[u8; 32]> {
-        // 使用固定的 salt（生产环境应该为每个用户生成唯一的 salt）
-        let salt = SaltString::from_b64("c2FsdHlzYWx0c2FsdHk")
-            .map_err(|e| StorageError::KeyDerivationError(format!("Salt 错误: {}", e)))?;
+    /// 清除内存中的密钥
+    pub fn lock(&mut self) {
+        self.cipher = None;
+    }
 
-        let argon2 = Argon2::default();
-        let hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|e| StorageError::KeyDerivationError(format!("哈希失败: {}", e)))?;
+    /// 修改密码：校验旧密码后用新 salt 重新加密全部数据
+    ///
+    /// 新数据先写入临时文件，替换密钥文件即为提交点；中途失败时下次解锁会自动回滚或完成替换。
+    pub fn change_password(&mut self, old_password: &str, new_password: &str) -> Result<()> {
+        self.recover_pending()?;
+        let Some(keystore) = self.read_keystore()? else {
+            return self.init_with_password(new_password);
+        };
+        let old_cipher = Self::open_keystore(&keystore, old_password)?;
+        let (new_keystore, new_cipher) = Self::new_keystore(new_password)?;
+
+        let mut pending = Vec::new();
+        for key in self.keys()? {
+            let path = self.entry_path(&key)?;
+            let plaintext = decrypt_with(&old_cipher, &decode(&fs::read_to_string(&path)?)?)?;
+            let pending_path = with_suffix(&path);
+            fs::write(&pending_path, BASE64.encode(encrypt_with(&new_cipher, &plaintext)?))?;
+            pending.push((pending_path, path));
+        }
+        let keystore_pending = with_suffix(&self.keystore_path());
+        fs::write(&keystore_pending, serde_json::to_vec_pretty(&new_keystore).unwrap_or_default())?;
 
-        let hash_output = hash.hash.ok_or_else(|| {
-            StorageError::KeyDerivationError("获取哈希输出失败".to_string())
-        })?;
+        fs::rename(&keystore_pending, self.keystore_path())?;
+        for (from, to) in pending {
+            fs::rename(from, to)?;
+        }
+        self.cipher = Some(new_cipher);
+        Ok(())
+    }
 
-        let bytes = hash_output.as_bytes();
+    /// 使用 Argon2 从密码和 salt 派生密钥
+    fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32]> {
         let mut key = [0u8; 32];
-        key.copy_from_slice(&bytes[..32]);
+        Argon2::default()
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|e| StorageError::KeyDerivationError(format!("哈希失败: {}", e)))?;
         Ok(key)
     }
 
-    /// 加密数据
-    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let cipher = self.cipher.as_ref().ok_or_else(|| {
-            StorageError::EncryptionError("加密器未初始化".to_string())
-        })?;
+    fn cipher_for(password: &str, salt: &[u8]) -> Result<Aes256Gcm> {
+        let key = Self::derive_key(password, salt)?;
+        Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| StorageError::EncryptionError(format!("创建加密器失败: {}", e)))
+    }
 
-        // 生成随机 nonce
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
+    fn new_keystore(password: &str) -> Result<(KeyStore, Aes256Gcm)> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = Self::cipher_for(password, &salt)?;
+        let check = BASE64.encode(encrypt_with(&cipher, KEY_CHECK_PLAINTEXT)?);
+        Ok((KeyStore { version: 1, salt: BASE64.encode(salt), check }, cipher))
+    }
 
-        // 加密
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| StorageError::EncryptionError(format!("加密失败: {}", e)))?;
+    fn open_keystore(keystore: &KeyStore, password: &str) -> Result<Aes256Gcm> {
+        let salt = BASE64
+            .decode(&keystore.salt)
+            .map_err(|e| StorageError::CorruptKeyStore(e.to_string()))?;
+        let check = BASE64
+            .decode(&keystore.check)
+            .map_err(|e| StorageError::CorruptKeyStore(e.to_string()))?;
+        let cipher = Self::cipher_for(password, &salt)?;
+        match decrypt_with(&cipher, &check) {
+            Ok(plaintext) if plaintext == KEY_CHECK_PLAINTEXT => Ok(cipher),
+            _ => Err(StorageError::WrongPassword),
+        }
+    }
 
-        // 组合 nonce + ciphertext
-        let mut result = nonce_bytes.to_vec();
-        result.extend(ciphertext);
-        Ok(result)
+    fn keystore_path(&self) -> PathBuf {
+        self.data_dir.join(KEYSTORE_FILE)
     }
 
-    /// 解密数据
-    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
-        let cipher = self.cipher.as_ref().ok_or_else(|| {
-            StorageError::DecryptionError("加密器未初始化".to_string())
-        })?;
+    fn read_keystore(&self) -> Result<Option<KeyStore>> {
+        let path = self.keystore_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        serde_json::from_slice(&fs::read(path)?)
+            .map(Some)
+            .map_err(|e| StorageError::CorruptKeyStore(e.to_string()))
+    }
+
+    /// 处理上次修改密码留下的临时文件：密钥文件未替换则丢弃，已替换则完成数据替换
+    fn recover_pending(&self) -> Result<()> {
+        let keystore_pending = with_suffix(&self.keystore_path());
+        let committed = !keystore_pending.exists();
+        if !committed {
+            fs::remove_file(&keystore_pending)?;
+        }
+        for entry in fs::read_dir(&self.data_dir)? {
+            let path = entry?.path();
+            let is_pending = path.extension().is_some_and(|ext| ext == PENDING_SUFFIX)
+                && path.file_stem().is_some_and(|stem| Path::new(stem).extension().is_some_and(|ext| ext == "enc"));
+            if !is_pending {
+                continue;
+            }
+            if committed {
+                fs::rename(&path, path.with_extension(""))?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
 
-        if encrypted.len() < 12 {
-            return Err(StorageError::DecryptionError("数据长度不足".to_string()));
+    /// 存储键对应的文件，只允许字母、数字、`_`、`-`、`.`，防止路径逃出存储目录
+    fn entry_path(&self, key: &str) -> Result<PathBuf> {
+        let valid = !key.is_empty()
+            && key.len() <= MAX_KEY_LENGTH
+            && !key.starts_with('.')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid {
+            return Err(StorageError::InvalidKey(key.to_string()));
         }
+        Ok(self.data_dir.join(format!("{}.enc", key)))
+    }
 
-        // 分离 nonce 和 ciphertext
-        let (nonce_bytes, ciphertext) = encrypted.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
+    /// 加密数据
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        encrypt_with(self.cipher.as_ref().ok_or(StorageError::Locked)?, plaintext)
+    }
 
-        // 解密
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| StorageError::DecryptionError(format!("解密失败: {}", e)))
+    /// 解密数据
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+        decrypt_with(self.cipher.as_ref().ok_or(StorageError::Locked)?, encrypted)
     }
 
     /// 存储加密数据到文件
     pub fn set(&self, key: &str, value: &str) -> Result<()> {
+        let file_path = self.entry_path(key)?;
         let encrypted = self.encrypt(value.as_bytes())?;
-        let encoded = BASE64.encode(&encrypted);
-        let file_path = self.data_dir.join(format!("{}.enc", key));
-        fs::write(file_path, encoded)?;
+        write_atomic(&file_path, BASE64.encode(&encrypted).as_bytes())?;
         Ok(())
     }
 
     /// 从文件读取并解密数据
     pub fn get(&self, key: &str) -> Result<Option<String>> {
-        let file_path = self.data_dir.join(format!("{}.enc", key));
+        let file_path = self.entry_path(key)?;
+        if self.cipher.is_none() {
+            return Err(StorageError::Locked);
+        }
         if !file_path.exists() {
             return Ok(None);
         }
 
-        let encoded = fs::read_to_string(file_path)?;
-        let encrypted = BASE64
-            .decode(encoded.trim())
-            .map_err(|e| StorageError::DecryptionError(format!("Base64 解码失败: {}", e)))?;
-
-        let decrypted = self.decrypt(&encrypted)?;
+        let decrypted = self.decrypt(&decode(&fs::read_to_string(file_path)?)?)?;
         let value = String::from_utf8(decrypted)
             .map_err(|e| StorageError::DecryptionError(format!("UTF-8 解码失败: {}", e)))?;
 
@@ -143,19 +262,34 @@ impl SecureStorage {
 
     /// 删除加密数据
     pub fn delete(&self, key: &str) -> Result<()> {
-        let file_path = self.data_dir.join(format!("{}.enc", key));
+        let file_path = self.entry_path(key)?;
         if file_path.exists() {
             fs::remove_file(file_path)?;
         }
         Ok(())
     }
 
+    /// 已保存的存储键
+    pub fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.data_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "enc") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    keys.push(stem.to_string());
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
     /// 清除所有加密数据
     pub fn clear(&self) -> Result<()> {
         for entry in fs::read_dir(&self.data_dir)? {
             let entry = entry?;
             let path = entry.path();
-            if path.extension().map_or(false, |ext| ext == "enc") {
+            if path.extension().is_some_and(|ext| ext == "enc") {
                 fs::remove_file(path)?;
             }
         }
@@ -163,6 +297,205 @@ impl SecureStorage {
     }
 }
 
+fn encrypt_with(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>> {
+    // 生成随机 nonce
+    let mut nonce_bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    // 加密
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|e| StorageError::EncryptionError(format!("加密失败: {}", e)))?;
+
+    // 组合 nonce + ciphertext
+    let mut result = nonce_bytes.to_vec();
+    result.extend(ciphertext);
+    Ok(result)
+}
+
+fn decrypt_with(cipher: &Aes256Gcm, encrypted: &[u8]) -> Result<Vec<u8>> {
+    if encrypted.len() < 12 {
+        return Err(StorageError::DecryptionError("数据长度不足".to_string()));
+    }
+
+    // 分离 nonce 和 ciphertext
+    let (nonce_bytes, ciphertext) = encrypted.split_at(12);
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    // 解密
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| StorageError::DecryptionError(format!("解密失败: {}", e)))
+}
+
+fn decode(encoded: &str) -> Result<Vec<u8>> {
+    BASE64
+        .decode(encoded.trim())
+        .map_err(|e| StorageError::DecryptionError(format!("Base64 解码失败: {}", e)))
+}
+
+/// 内部使用的存储键（数据库密钥），不允许通过存储命令读写
+pub fn is_reserved_key(key: &str) -> bool {
+    key.starts_with(DATABASE_KEY_ENTRY)
+}
+
+fn with_suffix(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(PENDING_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// 先写临时文件再替换，避免写到一半留下损坏的文件
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// 按用户划分的加密存储：`<root>/<用户 ID>/`，登录时用该用户的密码解锁，同一时间只解锁一个用户
+pub struct UserVaults {
+    root: PathBuf,
+    current: Mutex<Option<(String, SecureStorage)>>,
+}
+
+impl UserVaults {
+    pub fn new(root: PathBuf) -> Self {
+        fs::create_dir_all(&root).ok();
+        Self { root, current: Mutex::new(None) }
+    }
+
+    fn vault_dir(&self, user_id: &str) -> PathBuf {
+        self.root.join(user_id)
+    }
+
+    fn read_index(&self) -> std::collections::HashMap<String, String> {
+        fs::read(self.root.join(VAULT_INDEX_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn remember_user(&self, user_id: &str, username: &str) -> Result<()> {
+        let mut index = self.read_index();
+        if index.get(&username.to_lowercase()).map(String::as_str) != Some(user_id) {
+            index.insert(username.to_lowercase(), user_id.to_string());
+            write_atomic(&self.root.join(VAULT_INDEX_FILE), &serde_json::to_vec_pretty(&index).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+
+    /// 按用户名（不区分大小写）查找已有加密存储的用户 ID
+    pub fn user_id_for(&self, username: &str) -> Option<String> {
+        self.read_index().remove(&username.trim().to_lowercase())
+    }
+
+    /// 解锁用户的加密存储（首次使用时创建）
+    pub fn unlock(&self, user_id: &str, username: &str, password: &str) -> Result<()> {
+        let mut storage = SecureStorage::new(self.vault_dir(user_id));
+        storage.init_with_password(password)?;
+        self.remember_user(user_id, username)?;
+        *self.current.lock().unwrap() = Some((user_id.to_string(), storage));
+        Ok(())
+    }
+
+    /// 指定用户的加密存储是否已解锁
+    pub fn is_unlocked_for(&self, user_id: &str) -> bool {
+        self.current.lock().unwrap().as_ref().is_some_and(|(id, _)| id == user_id)
+    }
+
+    pub fn lock(&self) {
+        self.current.lock().unwrap().take();
+    }
+
+    /// 用新密码重建用户的加密存储（管理员创建用户或重置密码时），可同时写入数据库密钥
+    pub fn provision(&self, user_id: &str, username: &str, password: &str, database_key: Option<&[u8]>) -> Result<()> {
+        self.reset(user_id)?;
+        let mut storage = SecureStorage::new(self.vault_dir(user_id));
+        storage.init_with_password(password)?;
+        if let Some(key) = database_key {
+            storage.set(DATABASE_KEY_ENTRY, &BASE64.encode(key))?;
+        }
+        self.remember_user(user_id, username)
+    }
+
+    /// 从已解锁的存储读取数据库密钥，依次返回当前密钥和更换密钥期间保留的旧密钥
+    pub fn database_keys(&self, user_id: &str) -> Result<Vec<Vec<u8>>> {
+        self.with(user_id, |storage| {
+            let mut keys = Vec::new();
+            for entry in [DATABASE_KEY_ENTRY, PREVIOUS_DATABASE_KEY_ENTRY] {
+                if let Some(encoded) = storage.get(entry)? {
+                    keys.push(decode(&encoded)?);
+                }
+            }
+            Ok(keys)
+        })
+    }
+
+    /// 在已解锁的存储中保存数据库密钥；`keep_previous` 时保留原密钥直到 [`Self::drop_previous_database_key`]
+    pub fn set_database_key(&self, user_id: &str, key: &[u8], keep_previous: bool) -> Result<()> {
+        self.with(user_id, |storage| {
+            let encoded = BASE64.encode(key);
+            let current = storage.get(DATABASE_KEY_ENTRY)?;
+            if current.as_deref() == Some(encoded.as_str()) {
+                return Ok(());
+            }
+            if let (true, Some(previous)) = (keep_previous, current) {
+                storage.set(PREVIOUS_DATABASE_KEY_ENTRY, &previous)?;
+            }
+            storage.set(DATABASE_KEY_ENTRY, &encoded)
+        })
+    }
+
+    pub fn drop_previous_database_key(&self, user_id: &str) -> Result<()> {
+        self.with(user_id, |storage| storage.delete(PREVIOUS_DATABASE_KEY_ENTRY))
+    }
+
+    /// 清除用户自己保存的数据（保留数据库密钥）
+    pub fn clear_user_entries(&self, user_id: &str) -> Result<()> {
+        self.with(user_id, |storage| {
+            for key in storage.keys()? {
+                if !is_reserved_key(&key) {
+                    storage.delete(&key)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// 用户修改密码时重新加密其存储
+    pub fn change_password(&self, user_id: &str, old_password: &str, new_password: &str) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        match current.as_mut() {
+            Some((id, storage)) if id == user_id => storage.change_password(old_password, new_password),
+            _ => SecureStorage::new(self.vault_dir(user_id)).change_password(old_password, new_password),
+        }
+    }
+
+    /// 管理员重置密码后旧密钥无法恢复，删除该用户的加密存储
+    pub fn reset(&self, user_id: &str) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        if current.as_ref().is_some_and(|(id, _)| id == user_id) {
+            current.take();
+        }
+        let dir = self.vault_dir(user_id);
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// 在指定用户已解锁的存储上执行操作
+    pub fn with<T>(&self, user_id: &str, f: impl FnOnce(&SecureStorage) -> Result<T>) -> Result<T> {
+        match self.current.lock().unwrap().as_ref() {
+            Some((id, storage)) if id == user_id => f(storage),
+            _ => Err(StorageError::Locked),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,4 +525,103 @@ mod tests {
 
         assert_eq!(value, Some("test_value".to_string()));
     }
+
+    #[test]
+    fn test_wrong_password_and_key_validation() {
+        let dir = tempdir().unwrap();
+        let mut storage = SecureStorage::new(dir.path().to_path_buf());
+        assert!(matches!(storage.set("api_token", "secret"), Err(StorageError::Locked)));
+        storage.init_with_password("test_password").unwrap();
+        storage.set("api_token", "secret").unwrap();
+        for key in ["", "../escape", "a/b", ".hidden", "a\\b"] {
+            assert!(matches!(storage.set(key, "x"), Err(StorageError::InvalidKey(_))), "{:?}", key);
+        }
+
+        let mut reopened = SecureStorage::new(dir.path().to_path_buf());
+        assert!(matches!(reopened.init_with_password("wrong_password"), Err(StorageError::WrongPassword)));
+        assert!(!reopened.is_unlocked());
+        reopened.init_with_password("test_password").unwrap();
+        assert_eq!(reopened.get("api_token").unwrap().as_deref(), Some("secret"));
+
+        // 每个存储目录使用不同的随机 salt
+        let other_dir = tempdir().unwrap();
+        SecureStorage::new(other_dir.path().to_path_buf()).init_with_password("test_password").unwrap();
+        let salt = |dir: &Path| serde_json::from_slice::<KeyStore>(&fs::read(dir.join(KEYSTORE_FILE)).unwrap()).unwrap().salt;
+        assert_ne!(salt(dir.path()), salt(other_dir.path()));
+    }
+
+    #[test]
+    fn test_change_password_reencrypts() {
+        let dir = tempdir().unwrap();
+        let mut storage = SecureStorage::new(dir.path().to_path_buf());
+        storage.init_with_password("old_password").unwrap();
+        storage.set("a", "1").unwrap();
+        storage.set("b", "2").unwrap();
+        let before = fs::read_to_string(dir.path().join("a.enc")).unwrap();
+
+        assert!(matches!(storage.change_password("bad", "new_password"), Err(StorageError::WrongPassword)));
+        storage.change_password("old_password", "new_password").unwrap();
+        assert_ne!(fs::read_to_string(dir.path().join("a.enc")).unwrap(), before);
+        assert_eq!(storage.get("b").unwrap().as_deref(), Some("2"));
+
+        let mut reopened = SecureStorage::new(dir.path().to_path_buf());
+        assert!(matches!(reopened.init_with_password("old_password"), Err(StorageError::WrongPassword)));
+        reopened.init_with_password("new_password").unwrap();
+        assert_eq!(reopened.keys().unwrap(), vec!["a", "b"]);
+
+        // 提交前中断：临时文件被丢弃，旧密码仍然有效
+        fs::write(dir.path().join("a.enc.new"), "garbage").unwrap();
+        fs::write(dir.path().join("keystore.json.new"), "{}").unwrap();
+        let mut recovered = SecureStorage::new(dir.path().to_path_buf());
+        recovered.init_with_password("new_password").unwrap();
+        assert_eq!(recovered.get("a").unwrap().as_deref(), Some("1"));
+        assert!(!dir.path().join("a.enc.new").exists());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_database_encryption_with_vault_key() {
+        use crate::database::{generate_database_key, is_encrypted_file, Database, EncryptionError};
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let vaults = UserVaults::new(dir.path().join("secure"));
+        vaults.unlock("u1", "Admin", "admin-pass-1").unwrap();
+
+        let db = Database::new(db_path.clone()).unwrap();
+        db.set_sync_state("probe", "plaintext-secret").unwrap();
+        let key = generate_database_key();
+        vaults.set_database_key("u1", &key, false).unwrap();
+        db.encrypt_in_place(&key).unwrap();
+        assert!(is_encrypted_file(&db_path));
+        assert!(!fs::read(&db_path).unwrap().windows(16).any(|w| w == b"plaintext-secret"));
+        assert_eq!(db.get_sync_state("probe").unwrap().as_deref(), Some("plaintext-secret"));
+        assert!(matches!(db.encrypt_in_place(&key), Err(EncryptionError::AlreadyEncrypted)));
+        // 存储命令不能碰数据库密钥
+        vaults.with("u1", |s| s.set("api_token", "t")).unwrap();
+        vaults.clear_user_entries("u1").unwrap();
+        assert_eq!(vaults.database_keys("u1").unwrap(), vec![key.to_vec()]);
+        drop(db);
+
+        // 重新打开后锁定，用加密存储中的密钥解锁
+        let db = Database::new(db_path.clone()).unwrap();
+        assert!(db.is_locked());
+        assert!(matches!(db.unlock(&generate_database_key()), Err(EncryptionError::WrongKey)));
+        vaults.lock();
+        assert_eq!(vaults.user_id_for("admin").as_deref(), Some("u1"));
+        assert!(matches!(vaults.unlock("u1", "admin", "wrong-pass"), Err(StorageError::WrongPassword)));
+        vaults.unlock("u1", "admin", "admin-pass-1").unwrap();
+        let stored: [u8; 32] = vaults.database_keys("u1").unwrap()[0].as_slice().try_into().unwrap();
+        db.unlock(&stored).unwrap();
+        assert_eq!(db.get_sync_state("probe").unwrap().as_deref(), Some("plaintext-secret"));
+
+        // 更换密钥后旧密钥失效
+        let new_key = generate_database_key();
+        db.rekey(&new_key).unwrap();
+        drop(db);
+        let db = Database::new(db_path).unwrap();
+        assert!(matches!(db.unlock(&key), Err(EncryptionError::WrongKey)));
+        db.unlock(&new_key).unwrap();
+        assert_eq!(db.get_sync_state("probe").unwrap().as_deref(), Some("plaintext-secret"));
+    }
 }
@@ -1,12 +1,47 @@
 //! 数据同步模块 - 处理与企业服务器的数据同步
+//!
+//! 本地写入在同一事务中追加到 `sync_queue`（见 `database::outbox`）。同步时先把队列
+//! 中的变更以 [`SyncPacket`] 批量上传，再按保存的游标拉取服务器变更并在事务中应用。
+//!
+//! 服务器接口：
+//! - `POST sync/upload`，请求体 [`UploadRequest`]，返回 `ApiResponse<UploadResult>`
+//! - `GET sync/download?since=<游标>&limit=<数量>`，返回 `ApiResponse<DownloadBatch>`
+//!
+//! 客户、产品、订单带有服务器版本号。下载的变更命中仍有本地未上传修改的记录时，
+//! 以最近一次服务器数据（`sync_shadow`）为基准做字段级三方比较：双方修改了不同字段时
+//! 自动合并，修改了同一字段时按实体配置的 [`ConflictStrategy`] 处理，`Manual` 策略下
+//! 写入 `sync_conflicts` 等待用户处理。
+//!
+//! 进度、完成与失败通过 [`SyncEvent`] 回调通知调用方（应用中转发为 `sync:*` 事件）。
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::mpsc;
-use tokio::time::interval;
+use tokio::time::{interval, MissedTickBehavior};
+use chrono::Utc;
+use rand::Rng;
+
+use crate::database::{ChangeRecord, Database, DEVICE_ID_KEY, LAST_SYNC_AT_KEY, SYNC_CURSOR_KEY};
+
+/// 默认服务器地址
+pub const DEFAULT_API_BASE_URL: &str = "http://localhost:8080/api";
+/// 默认自动同步间隔（分钟）
+pub const DEFAULT_SYNC_INTERVAL_MINUTES: u64 = 5;
+
+/// 每批上传的变更数
+const UPLOAD_BATCH_SIZE: usize = 100;
+/// 每批下载的变更数
+const DOWNLOAD_BATCH_SIZE: usize = 500;
+/// 单次请求的最大尝试次数（网络错误与 5xx）
+const MAX_REQUEST_ATTEMPTS: u32 = 3;
+/// 被服务器拒绝的变更的首次重试间隔与上限
+const REJECT_RETRY_BASE: Duration = Duration::from_secs(30);
+const REJECT_RETRY_MAX: Duration = Duration::from_secs(60 * 60);
 
 #[derive(Error, Debug)]
 pub enum SyncError {
@@ -16,6 +51,8 @@ pub enum SyncError {
     ServerError(String),
     #[error("数据错误: {0}")]
     DataError(String),
+    #[error("数据库错误: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
     #[error("同步已在进行中")]
     AlreadySyncing,
 }
@@ -24,15 +61,19 @@ pub type Result<T> = std::result::Result<T, SyncError>;
 
 /// 同步状态
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SyncStatus {
     pub is_syncing: bool,
     pub last_sync_at: Option<String>,
     pub pending_changes: i64,
     pub sync_errors: Vec<SyncErrorItem>,
+    /// 未处理的同步冲突数
+    pub open_conflicts: i64,
 }
 
-/// 同步错误项
+/// 同步错误项（对应 `sync_queue` 中上传失败的记录）
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SyncErrorItem {
     pub id: String,
     pub entity: String,
@@ -43,46 +84,292 @@ pub struct SyncErrorItem {
     pub retry_count: i32,
 }
 
+impl From<ChangeRecord> for SyncErrorItem {
+    fn from(change: ChangeRecord) -> Self {
+        Self {
+            id: change.id.to_string(),
+            entity: change.entity_type,
+            entity_id: change.entity_id,
+            operation: change.operation,
+            error: change.last_error.unwrap_or_default(),
+            timestamp: change.created_at,
+            retry_count: change.retry_count,
+        }
+    }
+}
+
 /// 同步进度事件
 #[derive(Debug, Clone, Serialize)]
 pub struct SyncProgressEvent {
+    /// 0 ~ 100
     pub progress: f32,
     pub message: String,
 }
 
+/// 同步过程中的通知
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    Progress(SyncProgressEvent),
+    Complete(SyncReport),
+    Error(String),
+}
+
+type SyncEventHandler = Arc<dyn Fn(SyncEvent) + Send + Sync>;
+
+/// 同步期间持有，结束（包括任务被取消）时清除同步标记
+struct SyncingGuard(Arc<AtomicBool>);
+
+impl Drop for SyncingGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// 一次同步的结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    /// 服务器已接收的本地变更数
+    pub uploaded: usize,
+    /// 被服务器拒绝、等待重试的变更数
+    pub rejected: usize,
+    /// 已应用的服务器变更数
+    pub downloaded: usize,
+    /// 新产生的待处理冲突数
+    pub conflicts: usize,
+    /// 是否为全量同步
+    pub full: bool,
+}
+
+/// 冲突处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// 以服务器数据为准，丢弃本地修改
+    ServerWins,
+    /// 以本地修改为准，重新上传
+    ClientWins,
+    /// 比较修改时间，较新的一方生效
+    LastWriterWins,
+    /// 记录冲突，由用户处理
+    Manual,
+}
+
+impl ConflictStrategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "server_wins" => Some(Self::ServerWins),
+            "client_wins" => Some(Self::ClientWins),
+            "last_writer_wins" => Some(Self::LastWriterWins),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ServerWins => "server_wins",
+            Self::ClientWins => "client_wins",
+            Self::LastWriterWins => "last_writer_wins",
+            Self::Manual => "manual",
+        }
+    }
+
+    /// 默认策略：客户与订单由业务员手动处理，产品资料以总部为准
+    pub fn default_for(entity_type: &str) -> Self {
+        match entity_type {
+            "product" => Self::ServerWins,
+            _ => Self::Manual,
+        }
+    }
+}
+
+/// 参与冲突检测的实体
+pub const VERSIONED_ENTITIES: [&str; 3] = ["customer", "product", "order"];
+
+/// 不参与字段比较的元数据字段
+const META_FIELDS: [&str; 4] = ["id", "version", "created_at", "updated_at"];
+
+/// 单个字段的冲突
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldConflict {
+    pub field: String,
+    pub base: Value,
+    pub local: Value,
+    pub remote: Value,
+}
+
+/// 三方合并结果
+#[derive(Debug, Clone)]
+pub struct MergeOutcome {
+    /// 以服务器数据为底、叠加本地独有修改后的结果（冲突字段保留服务器值）
+    pub merged: Value,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+impl MergeOutcome {
+    /// 冲突字段改用本地值
+    pub fn prefer_local(mut self) -> Value {
+        for conflict in &self.conflicts {
+            self.merged[&conflict.field] = conflict.local.clone();
+        }
+        self.merged
+    }
+}
+
+/// 比较两个 JSON 值，数字统一按浮点比较（避免 `0` 与 `0.0` 被视为不同）
+fn same_value(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(Value::Number(x)), Some(Value::Number(y))) => x.as_f64() == y.as_f64(),
+        (a, b) => a == b,
+    }
+}
+
+/// 以 `base` 为共同祖先，对本地与服务器两份数据做字段级合并
+pub fn merge_fields(base: Option<&Value>, local: &Value, remote: &Value) -> MergeOutcome {
+    let empty = Map::new();
+    let base = base.and_then(Value::as_object).unwrap_or(&empty);
+    let local_fields = local.as_object().unwrap_or(&empty);
+    let remote_fields = remote.as_object().unwrap_or(&empty);
+
+    let mut merged = remote_fields.clone();
+    let mut conflicts = Vec::new();
+
+    for (field, local_value) in local_fields {
+        if META_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        let base_value = base.get(field);
+        let remote_value = remote_fields.get(field);
+
+        // 本地未修改：直接采用服务器值
+        if same_value(base_value, Some(local_value)) {
+            continue;
+        }
+        // 服务器未修改或双方改成相同的值：采用本地值
+        if same_value(base_value, remote_value) || same_value(remote_value, Some(local_value)) {
+            merged.insert(field.clone(), local_value.clone());
+            continue;
+        }
+        conflicts.push(FieldConflict {
+            field: field.clone(),
+            base: base_value.cloned().unwrap_or(Value::Null),
+            local: local_value.clone(),
+            remote: remote_value.cloned().unwrap_or(Value::Null),
+        });
+    }
+
+    MergeOutcome {
+        merged: Value::Object(merged),
+        conflicts,
+    }
+}
+
+/// 本地数据是否比服务器数据更新（按 `updated_at` 比较）
+pub fn local_is_newer(local: &Value, remote: &Value) -> bool {
+    let parse = |v: &Value| {
+        v["updated_at"]
+            .as_str()
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+    };
+    match (parse(local), parse(remote)) {
+        (Some(l), Some(r)) => l > r,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
 /// 同步管理器
 pub struct SyncManager {
+    db: Arc<Database>,
     api_base_url: String,
     is_syncing: Arc<AtomicBool>,
     sync_interval: Duration,
+    retry_base_delay: Duration,
     client: reqwest::Client,
+    on_event: Option<SyncEventHandler>,
 }
 
 impl SyncManager {
-    pub fn new(api_base_url: String, sync_interval_minutes: u64) -> Self {
+    pub fn new(db: Arc<Database>, api_base_url: String, sync_interval_minutes: u64) -> Self {
         Self {
+            db,
             api_base_url,
             is_syncing: Arc::new(AtomicBool::new(false)),
             sync_interval: Duration::from_secs(sync_interval_minutes * 60),
-            client: reqwest::Client::new(),
+            retry_base_delay: Duration::from_secs(1),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_default(),
+            on_event: None,
+        }
+    }
+
+    /// 设置同步事件回调
+    pub fn with_event_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(SyncEvent) + Send + Sync + 'static,
+    {
+        self.on_event = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn api_base_url(&self) -> &str {
+        &self.api_base_url
+    }
+
+    /// 自动同步间隔（分钟）
+    pub fn interval_minutes(&self) -> u64 {
+        self.sync_interval.as_secs() / 60
+    }
+
+    fn emit(&self, event: SyncEvent) {
+        if let Some(handler) = &self.on_event {
+            handler(event);
         }
     }
-        /// 构造 API 完整地址（仅拼接，不发起网络请求）
+
+    fn progress(&self, progress: f32, message: impl Into<String>) {
+        self.emit(SyncEvent::Progress(SyncProgressEvent {
+            progress,
+            message: message.into(),
+        }));
+    }
+
+    /// 设置请求失败后的首次重试间隔（之后按指数增长）
+    pub fn with_retry_base_delay(mut self, delay: Duration) -> Self {
+        self.retry_base_delay = delay;
+        self
+    }
+
+    /// 构造 API 完整地址
     fn endpoint(&self, path: &str) -> String {
         let base = self.api_base_url.trim_end_matches('/');
         let p = path.trim_start_matches('/');
         format!("{}/{}", base, p)
     }
 
-    /// 开始自动同步
+    /// 开始自动同步：立即同步一次，之后按间隔执行，直到收到停止信号或发送端被丢弃
+    ///
+    /// 停止信号会中断正在进行的同步；已提交的批次不受影响，未确认的变更下次重新上传。
     pub async fn start_auto_sync(&self, mut stop_rx: mpsc::Receiver<()>) {
         let mut interval = interval(self.sync_interval);
+        // 同步耗时超过间隔时顺延，不补发错过的同步
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    if let Err(e) = self.sync().await {
-                        log::error!("自动同步失败: {}", e);
+                    tokio::select! {
+                        result = self.sync() => {
+                            if let Err(e) = result {
+                                log::error!("自动同步失败: {}", e);
+                            }
+                        }
+                        _ = stop_rx.recv() => {
+                            log::info!("停止自动同步（中断进行中的同步）");
+                            break;
+                        }
                     }
                 }
                 _ = stop_rx.recv() => {
@@ -93,95 +380,269 @@ impl SyncManager {
         }
     }
 
-    /// 执行同步
-    pub async fn sync(&self) -> Result<()> {
-        // 检查是否已在同步
+    /// 执行增量同步
+    pub async fn sync(&self) -> Result<SyncReport> {
+        let _guard = self.begin()?;
+        let result = self.do_sync().await;
+        self.finish(result)
+    }
+
+    /// 强制全量同步：上传本地变更后，从服务器下载全部数据并在一个事务中重建本地业务数据
+    ///
+    /// 仍有未能上传的变更（被拒绝或存在未处理冲突）时中止，避免丢失本地修改。
+    pub async fn force_full_sync(&self) -> Result<SyncReport> {
+        let _guard = self.begin()?;
+        let result = self.do_full_sync().await;
+        self.finish(result)
+    }
+
+    fn begin(&self) -> Result<SyncingGuard> {
         if self.is_syncing.swap(true, Ordering::Relaxed) {
             return Err(SyncError::AlreadySyncing);
         }
+        Ok(SyncingGuard(Arc::clone(&self.is_syncing)))
+    }
 
-        let result = self.do_sync().await;
-
-        self.is_syncing.store(false, Ordering::Relaxed);
+    fn finish(&self, result: Result<SyncReport>) -> Result<SyncReport> {
+        match &result {
+            Ok(report) => {
+                self.progress(100.0, "同步完成");
+                self.emit(SyncEvent::Complete(report.clone()));
+            }
+            Err(e) => self.emit(SyncEvent::Error(e.to_string())),
+        }
         result
     }
 
     /// 实际同步逻辑
-    async fn do_sync(&self) -> Result<()> {
+    async fn do_sync(&self) -> Result<SyncReport> {
         log::info!("开始数据同步...");
+        self.progress(0.0, "开始同步...");
+        let mut report = SyncReport::default();
 
         // 1. 上传本地变更
-        self.upload_changes().await?;
+        self.upload_changes(&mut report).await?;
 
         // 2. 下载服务器变更
-        self.download_changes().await?;
+        self.download_changes(&mut report).await?;
 
-        log::info!("数据同步完成");
-        Ok(())
+        self.db.set_sync_state(LAST_SYNC_AT_KEY, &Utc::now().to_rfc3339())?;
+        log::info!(
+            "数据同步完成: 上传 {}，拒绝 {}，下载 {}，冲突 {}",
+            report.uploaded, report.rejected, report.downloaded, report.conflicts
+        );
+        Ok(report)
+    }
+
+    async fn do_full_sync(&self) -> Result<SyncReport> {
+        log::info!("开始全量同步...");
+        self.progress(0.0, "开始全量同步...");
+        let mut report = SyncReport {
+            full: true,
+            ..Default::default()
+        };
+
+        // 1. 先上传本地变更，重建前队列必须为空
+        self.upload_changes(&mut report).await?;
+        let remaining = self.db.count_pending_changes()?;
+        if remaining > 0 {
+            return Err(SyncError::DataError(format!(
+                "仍有 {} 条本地变更未能上传，请先处理同步错误和冲突",
+                remaining
+            )));
+        }
+
+        // 2. 从头下载全部数据
+        let mut changes = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let batch = self.fetch_batch(&cursor).await?;
+            let done = !batch.has_more || batch.changes.is_empty() || batch.cursor == cursor;
+            changes.extend(batch.changes);
+            cursor = batch.cursor;
+            self.progress(
+                (50.0 + changes.len() as f32 / 100.0).min(90.0),
+                format!("已下载 {} 条服务器数据", changes.len()),
+            );
+            if done {
+                break;
+            }
+        }
+
+        // 3. 重建本地数据
+        self.progress(90.0, "正在重建本地数据...");
+        report.downloaded = self.db.rebuild_from_remote(&changes, &cursor)?.applied;
+
+        self.db.set_sync_state(LAST_SYNC_AT_KEY, &Utc::now().to_rfc3339())?;
+        log::info!("全量同步完成: 上传 {}，下载 {}", report.uploaded, report.downloaded);
+        Ok(report)
     }
 
     /// 上传本地变更到服务器
-    async fn upload_changes(&self) -> Result<()> {
-        // 这里应该从数据库获取待同步的数据并上传
-        // 示例实现
-        // log::debug!("上传本地变更...");
-        // 仅构造请求以使用 api_base_url / client，避免 dead_code 警告（此处不发送网络请求）
+    async fn upload_changes(&self, report: &mut SyncReport) -> Result<()> {
+        let device_id = self.device_id()?;
         let url = self.endpoint("sync/upload");
-        let _req = self.client.post(&url);
-        log::debug!("上传本地变更... target={}", url);
+        let total = self.db.count_pending_changes()?.max(1) as f32;
+
+        loop {
+            let batch = self.db.pending_changes(UPLOAD_BATCH_SIZE)?;
+            if batch.is_empty() {
+                return Ok(());
+            }
+            log::debug!("上传本地变更 {} 条... target={}", batch.len(), url);
+
+            let request = UploadRequest {
+                device_id: device_id.clone(),
+                changes: batch.iter().map(SyncPacket::from).collect(),
+            };
+            let result: UploadResult = match self
+                .send_with_retry(|| self.client.post(&url).json(&request))
+                .await
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    // 网络或服务器故障：记录错误，变更留在队列中等待下次同步
+                    for change in &batch {
+                        self.db.fail_change(change.id, &e.to_string(), 0)?;
+                    }
+                    return Err(e);
+                }
+            };
+
+            let batch_ids: Vec<i64> = batch.iter().map(|c| c.id).collect();
+            let accepted: Vec<i64> = result
+                .accepted
+                .into_iter()
+                .filter(|id| batch_ids.contains(id))
+                .collect();
+            self.db.ack_changes(&accepted)?;
+            report.uploaded += accepted.len();
 
-        // 实际项目中应该:
-        // 1. 从 sync_queue 表获取待上传数据
-        // 2. 调用服务器 API 上传
-        // 3. 成功后从队列中删除
-        // 4. 失败则增加重试次数
+            let mut rejected = 0;
+            for item in result.rejected {
+                if let Some(change) = batch.iter().find(|c| c.id == item.change_id) {
+                    let retry_at = Utc::now().timestamp_millis()
+                        + reject_backoff(change.retry_count).as_millis() as i64;
+                    self.db.fail_change(change.id, &item.error, retry_at)?;
+                    rejected += 1;
+                }
+            }
+            report.rejected += rejected;
+            let done = (report.uploaded + report.rejected) as f32;
+            self.progress(
+                (done / total * 50.0).min(50.0),
+                format!("已上传 {} 条本地变更", report.uploaded),
+            );
 
-        Ok(())
+            // 服务器既未接收也未拒绝任何变更时停止，避免重复上传同一批
+            if accepted.is_empty() && rejected == 0 {
+                return Err(SyncError::ServerError("服务器未确认任何变更".to_string()));
+            }
+        }
     }
 
     /// 从服务器下载变更
-    async fn download_changes(&self) -> Result<()> {
-        // 这里应该从服务器拉取最新数据
-        // 示例实现
-        // log::debug!("下载服务器变更...");
-        // 仅构造请求以使用 api_base_url / client，避免 dead_code 警告（此处不发送网络请求）
-        let url = self.endpoint("sync/download");
-        let _req = self.client.get(&url);
-        log::debug!("下载服务器变更... target={}", url);
+    async fn download_changes(&self, report: &mut SyncReport) -> Result<()> {
+        let mut cursor = self.db.get_sync_state(SYNC_CURSOR_KEY)?.unwrap_or_default();
 
-        // 实际项目中应该:
-        // 1. 调用服务器 API 获取自上次同步后的变更
-        // 2. 合并到本地数据库
-        // 3. 处理冲突
+        loop {
+            let batch = self.fetch_batch(&cursor).await?;
 
-        Ok(())
-    }
+            let applied = self.db.apply_remote_changes(&batch.changes, &batch.cursor)?;
+            report.downloaded += applied.applied;
+            report.conflicts += applied.conflicts;
+            self.progress(
+                (50.0 + report.downloaded as f32 / 100.0).min(95.0),
+                format!("已下载 {} 条服务器变更", report.downloaded),
+            );
 
-    /// 强制全量同步
-    pub async fn force_full_sync(&self) -> Result<()> {
-        if self.is_syncing.swap(true, Ordering::Relaxed) {
-            return Err(SyncError::AlreadySyncing);
+            if !batch.has_more || batch.changes.is_empty() || batch.cursor == cursor {
+                return Ok(());
+            }
+            cursor = batch.cursor;
         }
+    }
 
-        log::info!("开始全量同步...");
+    /// 下载 `cursor` 之后的一批变更
+    async fn fetch_batch(&self, cursor: &str) -> Result<DownloadBatch> {
+        let url = self.endpoint("sync/download");
+        log::debug!("下载服务器变更... target={} since={}", url, cursor);
+        let limit = DOWNLOAD_BATCH_SIZE.to_string();
+        self.send_with_retry(|| {
+            self.client
+                .get(&url)
+                .query(&[("since", cursor), ("limit", limit.as_str())])
+        })
+        .await
+    }
 
-        // 1. 清除本地数据
-        // 2. 从服务器下载所有数据
-        // 3. 重建本地数据库
+    /// 发送请求，网络错误与 5xx 按指数退避加抖动重试
+    async fn send_with_retry<T, F>(&self, build: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let device_id = self.device_id()?;
+        let mut attempt = 1;
+
+        loop {
+            let error = match build().header("X-Device-Id", &device_id).send().await {
+                Ok(response) if response.status().is_server_error() => {
+                    SyncError::ServerError(format!("HTTP {}", response.status()))
+                }
+                Ok(response) if !response.status().is_success() => {
+                    return Err(SyncError::ServerError(format!("HTTP {}", response.status())));
+                }
+                Ok(response) => {
+                    let body: ApiResponse<T> = response
+                        .json()
+                        .await
+                        .map_err(|e| SyncError::DataError(e.to_string()))?;
+                    return match (body.success, body.data) {
+                        (true, Some(data)) => Ok(data),
+                        _ => Err(SyncError::ServerError(
+                            body.error.or(body.message).unwrap_or_else(|| "未知错误".to_string()),
+                        )),
+                    };
+                }
+                Err(e) => SyncError::NetworkError(e.to_string()),
+            };
 
-        self.is_syncing.store(false, Ordering::Relaxed);
-        log::info!("全量同步完成");
-        Ok(())
+            if attempt >= MAX_REQUEST_ATTEMPTS {
+                return Err(error);
+            }
+            let delay = self.retry_base_delay * 2u32.pow(attempt - 1);
+            let jitter = rand::thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+            log::warn!("同步请求失败（第 {} 次），稍后重试: {}", attempt, error);
+            tokio::time::sleep(delay + Duration::from_millis(jitter)).await;
+            attempt += 1;
+        }
     }
 
-    /// 获取同步状态
-    pub fn get_status(&self, pending_count: i64, errors: Vec<SyncErrorItem>) -> SyncStatus {
-        SyncStatus {
-            is_syncing: self.is_syncing.load(Ordering::Relaxed),
-            last_sync_at: None, // 应该从存储中读取
-            pending_changes: pending_count,
-            sync_errors: errors,
+    /// 本机设备 ID，首次使用时生成
+    fn device_id(&self) -> Result<String> {
+        if let Some(id) = self.db.get_sync_state(DEVICE_ID_KEY)? {
+            return Ok(id);
         }
+        let id = uuid::Uuid::new_v4().to_string();
+        self.db.set_sync_state(DEVICE_ID_KEY, &id)?;
+        Ok(id)
+    }
+
+    /// 获取同步状态（上次同步时间、待上传数与错误均读取自本地数据库）
+    pub fn status(&self) -> Result<SyncStatus> {
+        Ok(SyncStatus {
+            is_syncing: self.is_syncing(),
+            last_sync_at: self.db.get_sync_state(LAST_SYNC_AT_KEY)?,
+            pending_changes: self.db.count_pending_changes()?,
+            sync_errors: self
+                .db
+                .list_failed_changes()?
+                .into_iter()
+                .map(SyncErrorItem::from)
+                .collect(),
+            open_conflicts: self.db.count_open_conflicts()?,
+        })
     }
 
     /// 是否正在同步
@@ -190,6 +651,12 @@ impl SyncManager {
     }
 }
 
+/// 被拒绝变更的重试间隔：30 秒起按次数翻倍，最长 1 小时
+fn reject_backoff(retry_count: i32) -> Duration {
+    let exponent = retry_count.clamp(0, 16) as u32;
+    (REJECT_RETRY_BASE * 2u32.pow(exponent)).min(REJECT_RETRY_MAX)
+}
+
 /// API 请求响应
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiResponse<T> {
@@ -200,10 +667,391 @@ pub struct ApiResponse<T> {
 }
 
 /// 同步数据包
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncPacket {
+    /// 本地队列 ID，服务器确认时原样返回（下载的数据包为 0）
+    #[serde(default)]
+    pub change_id: i64,
     pub entity_type: String,
+    #[serde(default)]
+    pub entity_id: String,
     pub operation: String,
     pub data: serde_json::Value,
     pub timestamp: String,
 }
+
+impl From<&ChangeRecord> for SyncPacket {
+    fn from(change: &ChangeRecord) -> Self {
+        Self {
+            change_id: change.id,
+            entity_type: change.entity_type.clone(),
+            entity_id: change.entity_id.clone(),
+            operation: change.operation.clone(),
+            data: change.payload.clone(),
+            timestamp: change.created_at.clone(),
+        }
+    }
+}
+
+/// 上传请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadRequest {
+    pub device_id: String,
+    pub changes: Vec<SyncPacket>,
+}
+
+/// 上传结果
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadResult {
+    #[serde(default)]
+    pub accepted: Vec<i64>,
+    #[serde(default)]
+    pub rejected: Vec<RejectedChange>,
+}
+
+/// 被服务器拒绝的变更
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RejectedChange {
+    pub change_id: i64,
+    pub error: String,
+}
+
+/// 下载批次
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadBatch {
+    pub changes: Vec<SyncPacket>,
+    /// 本批之后的游标，下次请求时作为 `since`
+    pub cursor: String,
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{ConflictResolution, Customer};
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use tempfile::tempdir;
+
+    /// 本地替身服务器：记录上传的数据包，按脚本返回下载数据
+    struct StandInServer {
+        url: String,
+        uploads: Arc<Mutex<Vec<SyncPacket>>>,
+    }
+
+    impl StandInServer {
+        /// `failures` 为开头需要返回 503 的请求数
+        fn start(download: Vec<SyncPacket>, failures: usize) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let uploads = Arc::new(Mutex::new(Vec::new()));
+            let received = Arc::clone(&uploads);
+
+            std::thread::spawn(move || {
+                let mut remaining_failures = failures;
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let (status, response) = if remaining_failures > 0 {
+                        remaining_failures -= 1;
+                        ("503 Service Unavailable", json!({}))
+                    } else if request_line.starts_with("POST /sync/upload") {
+                        let request: UploadRequest = serde_json::from_slice(&body).unwrap();
+                        let (rejected, accepted): (Vec<_>, Vec<_>) =
+                            request.changes.iter().partition(|c| c.data["name"] == "REJECT");
+                        let result = json!({
+                            "accepted": accepted.iter().map(|c| c.change_id).collect::<Vec<_>>(),
+                            "rejected": rejected.iter().map(|c| json!({ "change_id": c.change_id, "error": "invalid" })).collect::<Vec<_>>(),
+                        });
+                        received.lock().unwrap().extend(request.changes);
+                        ("200 OK", json!({ "success": true, "data": result }))
+                    } else if request_line.contains("since=&") {
+                        let batch = json!({ "changes": download, "cursor": "c1", "has_more": false });
+                        ("200 OK", json!({ "success": true, "data": batch }))
+                    } else {
+                        let batch = json!({ "changes": [], "cursor": "c1", "has_more": false });
+                        ("200 OK", json!({ "success": true, "data": batch }))
+                    };
+
+                    let body = response.to_string();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                    .unwrap();
+                }
+            });
+
+            Self { url, uploads }
+        }
+    }
+
+    fn customer(id: &str, name: &str) -> Customer {
+        let now = Utc::now().to_rfc3339();
+        Customer {
+            id: id.to_string(), name: name.to_string(), company: String::new(), email: String::new(),
+            phone: String::new(), address: String::new(), category: "regular".to_string(),
+            status: "active".to_string(), contact_person: String::new(), credit_limit: 0.0,
+            balance: 0.0, remark: String::new(), source: String::new(),
+            created_at: now.clone(), updated_at: now, version: 0,
+        }
+    }
+
+    fn manager(db: &Arc<Database>, server: &StandInServer) -> SyncManager {
+        SyncManager::new(Arc::clone(db), server.url.clone(), 5)
+            .with_retry_base_delay(Duration::from_millis(10))
+    }
+
+    #[tokio::test]
+    async fn test_push_and_pull() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("test.db")).unwrap());
+        db.create_customer(&customer("local-1", "本地客户")).unwrap();
+        assert_eq!(db.count_pending_changes().unwrap(), 1);
+
+        let remote = SyncPacket {
+            change_id: 0,
+            entity_type: "customer".to_string(),
+            entity_id: "remote-1".to_string(),
+            operation: "create".to_string(),
+            data: serde_json::to_value(customer("remote-1", "总部客户")).unwrap(),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        let server = StandInServer::start(vec![remote], 0);
+
+        let report = manager(&db, &server).sync().await.unwrap();
+        assert_eq!(report.uploaded, 1);
+        assert_eq!(report.downloaded, 1);
+
+        let uploads = server.uploads.lock().unwrap();
+        assert_eq!(uploads[0].entity_id, "local-1");
+        assert_eq!(uploads[0].data["name"], Value::from("本地客户"));
+
+        // 服务器变更已应用且未回写到队列
+        assert_eq!(db.get_customer("remote-1").unwrap().unwrap().name, "总部客户");
+        assert_eq!(db.count_pending_changes().unwrap(), 0);
+        assert_eq!(db.get_sync_state(SYNC_CURSOR_KEY).unwrap().as_deref(), Some("c1"));
+        assert!(db.get_sync_state(LAST_SYNC_AT_KEY).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_rejection() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("test.db")).unwrap());
+        db.create_customer(&customer("ok-1", "正常客户")).unwrap();
+        db.create_customer(&customer("bad-1", "REJECT")).unwrap();
+
+        // 前两次请求返回 503，第三次成功
+        let server = StandInServer::start(Vec::new(), 2);
+        let report = manager(&db, &server).sync().await.unwrap();
+        assert_eq!(report.uploaded, 1);
+        assert_eq!(report.rejected, 1);
+
+        let failed = db.list_failed_changes().unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].entity_id, "bad-1");
+        assert_eq!(failed[0].retry_count, 1);
+
+        // 被拒绝的变更进入退避，同一客户后续的修改暂不上传，其他客户不受影响
+        db.update_customer(&customer("bad-1", "修改后")).unwrap();
+        db.update_customer(&customer("ok-1", "修改后")).unwrap();
+        let pending = db.pending_changes(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].entity_id, "ok-1");
+        assert_eq!(db.count_pending_changes().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_server_unreachable() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("test.db")).unwrap());
+        db.create_customer(&customer("c-1", "客户")).unwrap();
+
+        let manager = SyncManager::new(Arc::clone(&db), "http://127.0.0.1:1".to_string(), 5)
+            .with_retry_base_delay(Duration::from_millis(10));
+        assert!(matches!(manager.sync().await, Err(SyncError::NetworkError(_))));
+
+        // 变更保留在队列中，下次同步时重新上传
+        assert_eq!(db.count_pending_changes().unwrap(), 1);
+        assert_eq!(db.pending_changes(10).unwrap().len(), 1);
+        assert_eq!(db.list_failed_changes().unwrap()[0].retry_count, 1);
+    }
+
+    fn customer_packet(c: &Customer) -> SyncPacket {
+        SyncPacket {
+            change_id: 0,
+            entity_type: "customer".to_string(),
+            entity_id: c.id.clone(),
+            operation: "update".to_string(),
+            data: serde_json::to_value(c).unwrap(),
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn test_merge_fields() {
+        let base = json!({ "id": "1", "name": "甲", "phone": "1", "credit_limit": 100.0, "version": 1 });
+        let local = json!({ "id": "1", "name": "甲", "phone": "2", "credit_limit": 100, "version": 1 });
+        let remote = json!({ "id": "1", "name": "乙", "phone": "1", "credit_limit": 100.0, "version": 2 });
+
+        // 双方修改不同字段：自动合并，版本号取服务器
+        let outcome = merge_fields(Some(&base), &local, &remote);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(outcome.merged["name"], "乙");
+        assert_eq!(outcome.merged["phone"], "2");
+        assert_eq!(outcome.merged["version"], 2);
+
+        // 双方修改同一字段
+        let remote = json!({ "id": "1", "name": "甲", "phone": "3", "credit_limit": 100.0, "version": 2 });
+        let outcome = merge_fields(Some(&base), &local, &remote);
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(outcome.conflicts[0].field, "phone");
+        assert_eq!(outcome.prefer_local()["phone"], "2");
+    }
+
+    #[test]
+    fn test_conflict_detection_and_resolution() {
+        let dir = tempdir().unwrap();
+        let db = Database::new(dir.path().join("test.db")).unwrap();
+
+        let mut remote = customer("c-1", "总部客户");
+        remote.version = 1;
+        db.apply_remote_changes(&[customer_packet(&remote)], "c1").unwrap();
+
+        // 本地改电话、服务器改名称：自动合并后以新版本号重新上传
+        let mut local = db.get_customer("c-1").unwrap().unwrap();
+        local.phone = "138".to_string();
+        db.update_customer(&local).unwrap();
+        remote.name = "总部客户（改）".to_string();
+        remote.version = 2;
+        let summary = db.apply_remote_changes(&[customer_packet(&remote)], "c2").unwrap();
+        assert_eq!(summary.conflicts, 0);
+        let merged = db.get_customer("c-1").unwrap().unwrap();
+        assert_eq!((merged.name.as_str(), merged.phone.as_str(), merged.version), ("总部客户（改）", "138", 2));
+        let pending = db.pending_changes(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].payload["version"], 2);
+
+        // 双方修改同一字段：客户默认手动处理，保留本地数据并暂停上传
+        let mut local = merged;
+        local.name = "本地名称".to_string();
+        db.update_customer(&local).unwrap();
+        remote.name = "服务器名称".to_string();
+        remote.version = 3;
+        let summary = db.apply_remote_changes(&[customer_packet(&remote)], "c3").unwrap();
+        assert_eq!(summary.conflicts, 1);
+        assert_eq!(db.get_customer("c-1").unwrap().unwrap().name, "本地名称");
+        assert!(db.pending_changes(10).unwrap().is_empty());
+
+        let conflicts = db.list_conflicts(false).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].fields[0].field, "name");
+        assert_eq!(conflicts[0].fields[0].remote, "服务器名称");
+
+        // 保留本地：版本号更新为服务器版本后重新上传
+        let saved = db.resolve_conflict(&conflicts[0].id, &ConflictResolution::Local).unwrap().unwrap();
+        assert_eq!(saved["name"], "本地名称");
+        assert_eq!(saved["version"], 3);
+        assert_eq!(db.count_open_conflicts().unwrap(), 0);
+        let pending = db.pending_changes(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].payload["name"], "本地名称");
+
+        // 改为服务器优先后，同一字段的冲突直接采用服务器数据并丢弃本地修改
+        db.set_conflict_strategy("customer", ConflictStrategy::ServerWins).unwrap();
+        remote.name = "服务器再次修改".to_string();
+        remote.version = 4;
+        let summary = db.apply_remote_changes(&[customer_packet(&remote)], "c4").unwrap();
+        assert_eq!(summary.conflicts, 0);
+        assert_eq!(db.get_customer("c-1").unwrap().unwrap().name, "服务器再次修改");
+        assert_eq!(db.count_pending_changes().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_force_full_sync() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("test.db")).unwrap());
+        // 本地残留一条服务器上已不存在的数据
+        db.apply_remote_changes(&[customer_packet(&customer("stale-1", "旧客户"))], "old").unwrap();
+
+        let server = StandInServer::start(vec![customer_packet(&customer("remote-1", "总部客户"))], 0);
+        let report = manager(&db, &server).force_full_sync().await.unwrap();
+        assert!(report.full);
+        assert_eq!(report.downloaded, 1);
+
+        let customers = db.list_customers().unwrap();
+        assert_eq!(customers.len(), 1);
+        assert_eq!(customers[0].id, "remote-1");
+        assert_eq!(db.get_sync_state(SYNC_CURSOR_KEY).unwrap().as_deref(), Some("c1"));
+
+        // 有未能上传的变更时不重建
+        db.create_customer(&customer("bad-1", "REJECT")).unwrap();
+        assert!(matches!(manager(&db, &server).force_full_sync().await, Err(SyncError::DataError(_))));
+        assert!(db.get_customer("bad-1").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_auto_sync_events_and_stop() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("test.db")).unwrap());
+        db.create_customer(&customer("c-1", "客户")).unwrap();
+        let server = StandInServer::start(Vec::new(), 0);
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let manager = Arc::new(manager(&db, &server).with_event_handler(move |event| {
+            event_tx.send(event).ok();
+        }));
+
+        let (stop_tx, stop_rx) = mpsc::channel(1);
+        let task = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            async move { manager.start_auto_sync(stop_rx).await }
+        });
+
+        // 启动后立即同步一次，先报告进度再报告完成
+        let mut progress = Vec::new();
+        let report = loop {
+            match event_rx.recv().await.unwrap() {
+                SyncEvent::Progress(p) => progress.push(p.progress),
+                SyncEvent::Complete(report) => break report,
+                SyncEvent::Error(e) => panic!("同步失败: {}", e),
+            }
+        };
+        assert_eq!(report.uploaded, 1);
+        assert_eq!(progress.first(), Some(&0.0));
+        assert_eq!(progress.last(), Some(&100.0));
+
+        let status = manager.status().unwrap();
+        assert!(!status.is_syncing);
+        assert!(status.last_sync_at.is_some());
+        assert_eq!(status.pending_changes, 0);
+
+        // 丢弃发送端后任务退出
+        drop(stop_tx);
+        tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+    }
+}
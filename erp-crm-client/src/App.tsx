@@ -1,6 +1,7 @@
 import { BrowserRouter, Routes, Route } from 'react-router-dom';
 import { MainLayout } from '@/components/layout';
-import { DashboardPage, CustomersPage, InventoryPage, OrdersPage, ReportsPage, SettingsPage } from '@/pages';
+import { DashboardPage, CustomersPage, InventoryPage, OrdersPage, ReportsPage, SettingsPage, LoginPage } from '@/pages';
+import { authService } from '@/services/tauri';
 import { useOnlineStatus } from '@/hooks';
 import { useAppStore } from '@/store';
 import { useEffect } from 'react';
@@ -9,24 +10,22 @@ function AppContent() {
   // 监听网络状态
   useOnlineStatus();
 
-  // 模拟用户登录
-  const setCurrentUser = useAppStore((state) => state.setCurrentUser);
-  
+  // 恢复后端会话（应用未重启时刷新页面仍保持登录）
+  const { isAuthenticated, setCurrentUser, logout } = useAppStore();
+
   useEffect(() => {
-    // 模拟已登录用户
-    setCurrentUser({
-      id: 'user-1',
-      username: 'admin',
-      name: '管理员',
-      email: 'admin@example.com',
-      phone: '13800138000',
-      role: 'admin',
-      department: '管理部',
-      status: 'active',
-      createdAt: new Date().toISOString(),
-      updatedAt: new Date().toISOString(),
+    authService.getCurrentUser().then((res) => {
+      if (res.success && res.data) {
+        setCurrentUser(res.data);
+      } else {
+        logout();
+      }
     });
-  }, [setCurrentUser]);
+  }, [setCurrentUser, logout]);
+
+  if (!isAuthenticated) {
+    return <LoginPage />;
+  }
 
   return (
     <Routes>
@@ -2,6 +2,7 @@ import { useState, useRef, useEffect } from 'react';
 import { useAppStore } from '@/store';
 import { Avatar, Badge } from '@/components/common';
 import { formatRelativeTime } from '@/utils';
+import { authService } from '@/services/tauri';
 import {
   Bell,
   Search,
@@ -173,7 +174,7 @@ export function Header() {
                 </button>
                 <div className="my-2 border-t border-surface-100" />
                 <button
-                  onClick={logout}
+                  onClick={() => authService.logout().finally(logout)}
                   className="w-full flex items-center gap-3 px-3 py-2 rounded-lg text-danger-600 hover:bg-danger-50 transition-colors"
                 >
                   <LogOut className="h-4 w-4" />
@@ -3,7 +3,7 @@ import { listen } from '@tauri-apps/api/event';
 import { useAppStore, useInventoryStore } from '@/store';
 import { scannerService, syncService } from '@/services/tauri';
 import { debounce } from '@/utils';
-import type { ScanResult } from '@/types';
+import type { ScanResult, SyncProgress, SyncReport, WedgeKeyEvent } from '@/types';
 
 /**
  * 本地存储 Hook
@@ -336,13 +336,54 @@ export function useScanner() {
   };
 }
 
+/**
+ * 键盘模式扫码枪 Hook：缓存窗口按键，回车时交给后端按按键间隔识别
+ */
+export function useKeyboardWedge(enabled: boolean) {
+  const keysRef = useRef<WedgeKeyEvent[]>([]);
+
+  useEffect(() => {
+    if (!enabled) return;
+
+    const handleKeyDown = (event: KeyboardEvent) => {
+      const keys = keysRef.current;
+      keys.push({ key: event.key, timestamp: event.timeStamp });
+      if (keys.length > 512) keys.shift();
+      if (event.key === 'Enter') {
+        keysRef.current = [];
+        scannerService.sendWedgeKeys(keys).catch((error) => {
+          console.error('Failed to send wedge keys:', error);
+        });
+      }
+    };
+
+    window.addEventListener('keydown', handleKeyDown, true);
+    return () => {
+      window.removeEventListener('keydown', handleKeyDown, true);
+      keysRef.current = [];
+    };
+  }, [enabled]);
+}
+
 /**
  * 数据同步 Hook
  */
 export function useSync() {
-  const { syncStatus, setSyncStatus } = useAppStore();
+  const { syncStatus, setSyncStatus, config } = useAppStore();
   const addNotification = useAppStore((state) => state.addNotification);
 
+  // 从后端读取持久化的同步状态
+  const refreshStatus = useCallback(async () => {
+    try {
+      const response = await syncService.getSyncStatus();
+      if (response.success && response.data) {
+        setSyncStatus(response.data);
+      }
+    } catch (error) {
+      console.error('Failed to load sync status:', error);
+    }
+  }, [setSyncStatus]);
+
   // 监听同步事件
   useEffect(() => {
     let unlistenProgress: (() => void) | undefined;
@@ -351,26 +392,26 @@ export function useSync() {
 
     const setupListeners = async () => {
       try {
-        unlistenProgress = await listen<{ progress: number; message: string }>('sync:progress', (event) => {
-          setSyncStatus({ isSyncing: true });
-          console.log('Sync progress:', event.payload);
+        unlistenProgress = await listen<SyncProgress>('sync:progress', (event) => {
+          setSyncStatus({ isSyncing: event.payload.progress < 100, progress: event.payload });
         });
 
-        unlistenComplete = await listen('sync:complete', () => {
-          setSyncStatus({
-            isSyncing: false,
-            lastSyncAt: new Date().toISOString(),
-            pendingChanges: 0,
-          });
+        unlistenComplete = await listen<SyncReport>('sync:complete', (event) => {
+          const report = event.payload;
+          setSyncStatus({ isSyncing: false, progress: undefined });
+          refreshStatus();
           addNotification({
-            type: 'success',
-            title: '同步完成',
-            message: '数据已成功同步到服务器',
+            type: report.conflicts > 0 ? 'warning' : 'success',
+            title: report.full ? '全量同步完成' : '同步完成',
+            message: report.conflicts > 0
+              ? `上传 ${report.uploaded} 条，下载 ${report.downloaded} 条，${report.conflicts} 条冲突待处理`
+              : `上传 ${report.uploaded} 条，下载 ${report.downloaded} 条`,
           });
         });
 
         unlistenError = await listen<{ error: string }>('sync:error', (event) => {
-          setSyncStatus({ isSyncing: false });
+          setSyncStatus({ isSyncing: false, progress: undefined });
+          refreshStatus();
           addNotification({
             type: 'error',
             title: '同步失败',
@@ -383,19 +424,23 @@ export function useSync() {
     };
 
     setupListeners();
+    refreshStatus();
 
     return () => {
       if (unlistenProgress) unlistenProgress();
       if (unlistenComplete) unlistenComplete();
       if (unlistenError) unlistenError();
     };
-  }, [setSyncStatus, addNotification]);
+  }, [setSyncStatus, addNotification, refreshStatus]);
 
   // 开始同步
   const startSync = useCallback(async () => {
     try {
       setSyncStatus({ isSyncing: true });
-      await syncService.startSync();
+      const response = await syncService.startSync(config.apiBaseUrl, config.syncInterval);
+      if (!response.success) {
+        throw new Error(response.error);
+      }
     } catch (error) {
       setSyncStatus({ isSyncing: false });
       addNotification({
@@ -404,13 +449,22 @@ export function useSync() {
         message: error instanceof Error ? error.message : '无法开始同步',
       });
     }
-  }, [setSyncStatus, addNotification]);
+  }, [setSyncStatus, addNotification, config.apiBaseUrl, config.syncInterval]);
+
+  // 停止自动同步
+  const stopSync = useCallback(async () => {
+    await syncService.stopSync();
+    refreshStatus();
+  }, [refreshStatus]);
 
   // 强制全量同步
   const forceFullSync = useCallback(async () => {
     try {
       setSyncStatus({ isSyncing: true });
-      await syncService.forceFullSync();
+      const response = await syncService.forceFullSync();
+      if (!response.success) {
+        throw new Error(response.error);
+      }
     } catch (error) {
       setSyncStatus({ isSyncing: false });
       addNotification({
@@ -424,7 +478,9 @@ export function useSync() {
   return {
     syncStatus,
     startSync,
+    stopSync,
     forceFullSync,
+    refreshStatus,
   };
 }
 
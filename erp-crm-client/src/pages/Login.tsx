@@ -0,0 +1,63 @@
+import { useEffect, useState } from 'react';
+import { Card, Button, Input } from '@/components/common';
+import { useAppStore } from '@/store';
+import { authService } from '@/services/tauri';
+import { Lock, User as UserIcon } from 'lucide-react';
+
+export function LoginPage() {
+  const { config, setCurrentUser } = useAppStore();
+  const [needsSetup, setNeedsSetup] = useState(false);
+  const [username, setUsername] = useState('');
+  const [name, setName] = useState('');
+  const [password, setPassword] = useState('');
+  const [confirmPassword, setConfirmPassword] = useState('');
+  const [error, setError] = useState('');
+  const [isLoading, setIsLoading] = useState(false);
+
+  useEffect(() => {
+    authService.needsSetup().then((res) => {
+      if (res.success && res.data) setNeedsSetup(res.data.needsSetup);
+    });
+  }, []);
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setError('');
+    if (needsSetup && password !== confirmPassword) {
+      setError('两次输入的密码不一致');
+      return;
+    }
+    setIsLoading(true);
+    try {
+      const res = needsSetup
+        ? await authService.setupAdmin({ username, password, name })
+        : await authService.login(username, password);
+      if (res.success && res.data) {
+        setCurrentUser(res.data.user);
+      } else {
+        setError(res.error || '登录失败');
+      }
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="min-h-screen flex items-center justify-center bg-surface-50">
+      <Card className="w-full max-w-sm">
+        <h1 className="text-xl font-bold text-surface-900">{config.companyName}</h1>
+        <p className="text-surface-500 mt-1 mb-6">{needsSetup ? '首次使用，请创建管理员账号' : '请登录'}</p>
+        <form className="space-y-4" onSubmit={handleSubmit}>
+          <Input label="用户名" value={username} onChange={(e) => setUsername(e.target.value)} leftIcon={<UserIcon className="h-4 w-4" />} autoFocus />
+          {needsSetup && <Input label="姓名" value={name} onChange={(e) => setName(e.target.value)} />}
+          <Input label="密码" type="password" value={password} onChange={(e) => setPassword(e.target.value)} leftIcon={<Lock className="h-4 w-4" />} hint={needsSetup ? '至少 8 位' : undefined} />
+          {needsSetup && <Input label="确认密码" type="password" value={confirmPassword} onChange={(e) => setConfirmPassword(e.target.value)} />}
+          {error && <p className="text-sm text-danger-500">{error}</p>}
+          <Button type="submit" className="w-full" isLoading={isLoading} disabled={!username || !password}>
+            {needsSetup ? '创建并登录' : '登录'}
+          </Button>
+        </form>
+      </Card>
+    </div>
+  );
+}
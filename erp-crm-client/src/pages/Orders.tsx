@@ -3,9 +3,9 @@ import { Button, SearchInput, Select, Card, Table, Modal, StatusBadge, Paginatio
 import { useOrderStore } from '@/store';
 import { useModal, usePagination, useForm } from '@/hooks';
 import { formatDate, formatCurrency, getStatusText } from '@/utils';
-import { orderService, customerService } from '@/services/tauri';
+import { orderService, customerService, inventoryService } from '@/services/tauri';
 import { Plus, Download, Eye, Trash2, CheckCircle, XCircle, Truck } from 'lucide-react';
-import type { Order, OrderType, Customer } from '@/types';
+import type { Order, OrderType, Customer, Warehouse } from '@/types';
 
 const typeOptions = [
   { value: '', label: '全部类型' },
@@ -19,9 +19,11 @@ const statusOptions = [
   { value: 'draft', label: '草稿' },
   { value: 'pending', label: '待确认' },
   { value: 'confirmed', label: '已确认' },
+  { value: 'partially_shipped', label: '部分发货' },
   { value: 'shipped', label: '已发货' },
   { value: 'completed', label: '已完成' },
   { value: 'cancelled', label: '已取消' },
+  { value: 'returned', label: '已退货' },
 ];
 
 const tabs = [
@@ -54,6 +56,7 @@ const normalizeOrder = (raw: any): Order => {
     // 配送字段
     deliveryAddress: raw.deliveryAddress ?? raw.delivery_address ?? '',
     deliveryDate: raw.deliveryDate ?? raw.delivery_date ?? '',
+    warehouseId: raw.warehouseId ?? raw.warehouse_id ?? '',
 
     // 操作人
     operatorId: raw.operatorId ?? raw.operator_id ?? '',
@@ -69,6 +72,7 @@ export function OrdersPage() {
   const { searchKeyword, setSearchKeyword, filters, setFilters } = useOrderStore();
   const [orders, setOrders] = useState<Order[]>([]);
   const [customers, setCustomers] = useState<Customer[]>([]);
+  const [warehouses, setWarehouses] = useState<Warehouse[]>([]);
   const [selectedOrder, setSelectedOrder] = useState<Order | null>(null);
   const [activeTab, setActiveTab] = useState('all');
   const [isLoading, setIsLoading] = useState(true);
@@ -87,6 +91,7 @@ export function OrdersPage() {
     discountAmount: 0,
     payableAmount: 0,
     deliveryAddress: '',
+    warehouseId: '',
     remark: '',
   });
 
@@ -123,10 +128,22 @@ export function OrdersPage() {
     }
   }, []);
 
+  const loadWarehouses = useCallback(async () => {
+    try {
+      const result = await inventoryService.listWarehouses();
+      if (result.success && result.data) {
+        setWarehouses(result.data.filter((w) => w.status === 'active'));
+      }
+    } catch (error) {
+      console.error('加载仓库列表失败:', error);
+    }
+  }, []);
+
   useEffect(() => {
     loadOrders();
     loadCustomers();
-  }, [loadOrders, loadCustomers]);
+    loadWarehouses();
+  }, [loadOrders, loadCustomers, loadWarehouses]);
 
   const filteredOrders = useMemo(() => {
     return orders.filter((o) => {
@@ -138,8 +155,8 @@ export function OrdersPage() {
       const matchesStatus = !filters.status || o.status === filters.status;
       let matchesTab = true;
       if (activeTab === 'pending') matchesTab = ['draft', 'pending'].includes(o.status);
-      if (activeTab === 'processing') matchesTab = ['confirmed', 'shipped'].includes(o.status);
-      if (activeTab === 'completed') matchesTab = ['completed', 'cancelled'].includes(o.status);
+      if (activeTab === 'processing') matchesTab = ['confirmed', 'partially_shipped', 'shipped'].includes(o.status);
+      if (activeTab === 'completed') matchesTab = ['completed', 'cancelled', 'returned'].includes(o.status);
       return matchesSearch && matchesType && matchesStatus && matchesTab;
     });
   }, [orders, searchKeyword, filters, activeTab]);
@@ -163,6 +180,7 @@ export function OrdersPage() {
         discountAmount: form.values.discountAmount,
         payableAmount: form.values.payableAmount || form.values.totalAmount - form.values.discountAmount,
         deliveryAddress: form.values.deliveryAddress,
+        warehouseId: form.values.warehouseId,
         remark: form.values.remark,
       };
 
@@ -253,6 +271,7 @@ export function OrdersPage() {
           </>
         );
       case 'confirmed':
+      case 'shipped':
         return (
           <button onClick={() => handleComplete(order)} className="p-1.5 hover:bg-primary-50 rounded-lg" title="完成">
             <Truck className="h-4 w-4 text-primary-600" />
@@ -408,6 +427,15 @@ export function OrdersPage() {
             form.handleChange('discountAmount', discount);
             form.handleChange('payableAmount', form.values.totalAmount - discount);
           }} />
+          <Select
+            label={form.values.type === 'sale' ? '发货仓库' : '收货仓库'}
+            options={[
+              { value: '', label: '按产品所属仓库' },
+              ...warehouses.map(w => ({ value: w.id, label: w.name }))
+            ]}
+            value={form.values.warehouseId}
+            onChange={(v) => form.handleChange('warehouseId', v)}
+          />
           <div className="col-span-2">
             <Input label="收货地址" value={form.values.deliveryAddress} onChange={(e) => form.handleChange('deliveryAddress', e.target.value)} />
           </div>
@@ -438,6 +466,7 @@ export function OrdersPage() {
               <div><span className="text-surface-500">订单金额:</span> {formatCurrency(selectedOrder.totalAmount || 0)}</div>
               <div><span className="text-surface-500">优惠金额:</span> {formatCurrency(selectedOrder.discountAmount || 0)}</div>
               <div><span className="text-surface-500">应付金额:</span> <span className="font-semibold text-primary-600">{formatCurrency(selectedOrder.payableAmount || 0)}</span></div>
+              <div className="col-span-2"><span className="text-surface-500">仓库:</span> {warehouses.find(w => w.id === selectedOrder.warehouseId)?.name || selectedOrder.warehouseId || '按产品所属仓库'}</div>
               <div className="col-span-2"><span className="text-surface-500">收货地址:</span> {selectedOrder.deliveryAddress || '-'}</div>
               <div className="col-span-2"><span className="text-surface-500">备注:</span> {selectedOrder.remark || '无'}</div>
             </div>
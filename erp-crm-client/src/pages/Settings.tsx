@@ -32,7 +32,7 @@ const syncIntervalOptions = [
 export function SettingsPage() {
   const [activeTab, setActiveTab] = useState('general');
   const { config, setConfig, isOffline, syncStatus } = useAppStore();
-  const { startSync, forceFullSync } = useSync();
+  const { startSync, stopSync, forceFullSync } = useSync();
   const [isSaving, setIsSaving] = useState(false);
 
   const handleSave = async () => {
@@ -109,10 +109,14 @@ export function SettingsPage() {
                     <p className="font-medium mt-1">{syncStatus.syncErrors.length} 条</p>
                   </div>
                 </div>
+                {syncStatus.progress && (
+                  <p className="text-sm text-surface-500">{syncStatus.progress.message}（{Math.round(syncStatus.progress.progress)}%）</p>
+                )}
                 <Select label="同步间隔" options={syncIntervalOptions} value={String(config.syncInterval)} onChange={(v) => setConfig({ syncInterval: Number(v) })} />
                 <div className="flex gap-3">
                   <Button onClick={startSync} isLoading={syncStatus.isSyncing} leftIcon={<RefreshCw className="h-4 w-4" />}>立即同步</Button>
                   <Button variant="outline" onClick={forceFullSync} disabled={syncStatus.isSyncing}>全量同步</Button>
+                  {syncStatus.autoSync && <Button variant="ghost" onClick={stopSync}>停止自动同步</Button>}
                 </div>
               </div>
             </Card>
@@ -4,3 +4,4 @@ export { InventoryPage } from './Inventory';
 export { OrdersPage } from './Orders';
 export { ReportsPage } from './Reports';
 export { SettingsPage } from './Settings';
+export { LoginPage } from './Login';
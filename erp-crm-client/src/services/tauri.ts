@@ -3,16 +3,58 @@ import type {
   Customer,
   Product,
   Order,
+  OrderStatusChange,
+  Payment,
+  PaymentInput,
+  PaymentStatus,
+  AgingReport,
+  ItemShipment,
   User,
+  UserRole,
+  LoginResult,
+  RolePermissions,
+  PermissionModule,
+  PermissionAction,
+  AuditEntry,
   ApiResponse,
   PaginatedResponse,
   SearchParams,
   SyncStatus,
+  SyncConflict,
+  ConflictStrategy,
   DashboardStats,
   ScannerConfig,
   ScanResult,
+  WedgeKeyEvent,
   StockRecord,
+  StockBatchInput,
+  StockAvailability,
+  StockLevel,
+  BatchStock,
+  BatchTrace,
+  StockTransfer,
+  TransferItemInput,
+  TransferStatus,
+  Warehouse,
+  WarehouseLowStock,
+  OversellPolicy,
+  CostMethod,
+  InventoryReport,
+  OrderCostReport,
+  ReportGranularity,
+  SalesReport,
+  StockCheck,
   StockCheckItem,
+  StockCheckReport,
+  StockCheckStatus,
+  StockCountInput,
+  ImportColumns,
+  ImportEntity,
+  ImportReport,
+  ExportFilter,
+  ExportFormat,
+  ExportResult,
+  ReportExportType,
 } from '@/types';
 
 // ==================== Tauri 命令调用封装 ====================
@@ -32,10 +74,24 @@ async function tauriInvoke<T>(cmd: string, args?: Record<string, unknown>): Prom
 // ==================== 认证服务 ====================
 
 export const authService = {
+  /**
+   * 是否需要首次运行设置管理员
+   */
+  async needsSetup(): Promise<ApiResponse<{ needsSetup: boolean }>> {
+    return tauriInvoke('auth_needs_setup');
+  },
+
+  /**
+   * 首次运行创建管理员账号（创建后自动登录）
+   */
+  async setupAdmin(data: { username: string; password: string; name?: string; email?: string }): Promise<ApiResponse<LoginResult>> {
+    return tauriInvoke('auth_setup', { data });
+  },
+
   /**
    * 用户登录
    */
-  async login(username: string, password: string): Promise<ApiResponse<User>> {
+  async login(username: string, password: string): Promise<ApiResponse<LoginResult>> {
     return tauriInvoke('auth_login', { username, password });
   },
 
@@ -61,6 +117,43 @@ export const authService = {
   },
 };
 
+// ==================== 用户管理服务（管理员） ====================
+
+export const userService = {
+  async list(): Promise<ApiResponse<PaginatedResponse<User>>> {
+    return tauriInvoke('user_list');
+  },
+  async create(data: Partial<User> & { password: string }): Promise<ApiResponse<User>> {
+    return tauriInvoke('user_create', { data });
+  },
+  async update(id: string, data: Partial<User>): Promise<ApiResponse<User>> {
+    return tauriInvoke('user_update', { id, data });
+  },
+  async resetPassword(id: string, password: string): Promise<ApiResponse<void>> {
+    return tauriInvoke('user_reset_password', { id, password });
+  },
+};
+
+// ==================== 权限与审计服务 ====================
+
+export const rbacService = {
+  /**
+   * 角色权限表，不传角色时返回当前用户的权限
+   */
+  async getPermissions(role?: UserRole): Promise<ApiResponse<RolePermissions>> {
+    return tauriInvoke('rbac_get_permissions', { role });
+  },
+  async setPermissions(role: UserRole, module: PermissionModule, actions: PermissionAction[]): Promise<ApiResponse<RolePermissions>> {
+    return tauriInvoke('rbac_set_permissions', { role, module, actions });
+  },
+  async resetPermissions(role: UserRole): Promise<ApiResponse<RolePermissions>> {
+    return tauriInvoke('rbac_reset_permissions', { role });
+  },
+  async listAudit(onlyDenied = false, limit = 200): Promise<ApiResponse<{ items: AuditEntry[]; total: number }>> {
+    return tauriInvoke('audit_list', { onlyDenied, limit });
+  },
+};
+
 // ==================== 客户管理服务 ====================
 
 export const customerService = {
@@ -98,8 +191,24 @@ export const customerService = {
   async searchCustomers(keyword: string): Promise<ApiResponse<Customer[]>> {
     return tauriInvoke('customer_search', { keyword });
   },
-  async exportCustomers(format: 'xlsx' | 'csv'): Promise<ApiResponse<string>> {
-    return tauriInvoke('customer_export', { format });
+  /** 未指定 mapping 的字段按表头自动识别；dryRun 时只校验并返回预览，不写入 */
+  async importCustomers(path: string, mapping?: Record<string, string>, dryRun = false): Promise<ApiResponse<ImportReport<Customer>>> {
+    return tauriInvoke('import_customers', { path, mapping, dryRun });
+  },
+  /** format 未指定时按文件扩展名 */
+  async exportCustomers(path: string, format?: ExportFormat, filter?: ExportFilter): Promise<ApiResponse<ExportResult>> {
+    return tauriInvoke('customer_export', { path, format, filter });
+  },
+};
+
+// ==================== 导入服务 ====================
+
+export const importService = {
+  /**
+   * 读取导入文件的表头、可导入字段和自动识别的列对应关系
+   */
+  async getColumns(entity: ImportEntity, path: string): Promise<ApiResponse<ImportColumns>> {
+    return tauriInvoke('import_columns', { entity, path });
   },
 };
 
@@ -124,14 +233,73 @@ export const inventoryService = {
   async delete(id: string): Promise<ApiResponse<void>> {
     return tauriInvoke('product_delete', { id });
   },
-  async stockIn(productId: string, quantity: number, unitPrice: number, remark?: string): Promise<ApiResponse<StockRecord>> {
-    return tauriInvoke('stock_in', { productId, quantity, unitPrice, remark });
+  /** 按编码、条码匹配已有产品；库存数量只作为新建产品的期初库存 */
+  async importProducts(path: string, mapping?: Record<string, string>, dryRun = false): Promise<ApiResponse<ImportReport<Product>>> {
+    return tauriInvoke('import_products', { path, mapping, dryRun });
+  },
+  async exportProducts(path: string, format?: ExportFormat, filter?: ExportFilter): Promise<ApiResponse<ExportResult>> {
+    return tauriInvoke('product_export', { path, format, filter });
+  },
+  // 未指定仓库时记到产品的所属仓库和库位
+  async stockIn(productId: string, quantity: number, unitPrice: number, remark?: string, batch?: StockBatchInput, warehouseId?: string, location?: string): Promise<ApiResponse<StockRecord>> {
+    return tauriInvoke('stock_in', { productId, quantity, unitPrice, remark, batch, warehouseId, location });
+  },
+  async stockOut(productId: string, quantity: number, unitPrice: number, remark?: string, batch?: StockBatchInput, warehouseId?: string, location?: string): Promise<ApiResponse<StockRecord>> {
+    return tauriInvoke('stock_out', { productId, quantity, unitPrice, remark, batch, warehouseId, location });
+  },
+  async stockAdjust(productId: string, actualQuantity: number, remark?: string, warehouseId?: string, location?: string): Promise<ApiResponse<StockRecord>> {
+    return tauriInvoke('stock_adjust', { productId, actualQuantity, remark, warehouseId, location });
+  },
+  async getStockLevels(productId?: string, warehouseId?: string): Promise<ApiResponse<StockLevel[]>> {
+    return tauriInvoke('stock_levels', { productId, warehouseId });
+  },
+  async getBatches(productId?: string, warehouseId?: string): Promise<ApiResponse<BatchStock[]>> {
+    return tauriInvoke('stock_batches', { productId, warehouseId });
+  },
+  async traceBatch(batchNo: string, productId?: string): Promise<ApiResponse<BatchTrace>> {
+    return tauriInvoke('stock_trace_batch', { batchNo, productId });
+  },
+  async listWarehouses(): Promise<ApiResponse<Warehouse[]>> {
+    return tauriInvoke('warehouse_list');
+  },
+  async createWarehouse(data: Partial<Warehouse>): Promise<ApiResponse<Warehouse>> {
+    return tauriInvoke('warehouse_create', { data });
+  },
+  async updateWarehouse(id: string, data: Partial<Warehouse>): Promise<ApiResponse<Warehouse>> {
+    return tauriInvoke('warehouse_update', { id, data });
+  },
+  async deleteWarehouse(id: string): Promise<ApiResponse<void>> {
+    return tauriInvoke('warehouse_delete', { id });
+  },
+  async createTransfer(fromWarehouseId: string, toWarehouseId: string, items: TransferItemInput[], remark?: string): Promise<ApiResponse<StockTransfer>> {
+    return tauriInvoke('transfer_create', { data: { fromWarehouseId, toWarehouseId, items, remark } });
+  },
+  async receiveTransfer(id: string): Promise<ApiResponse<StockTransfer>> {
+    return tauriInvoke('transfer_receive', { id });
+  },
+  async cancelTransfer(id: string, reason?: string): Promise<ApiResponse<StockTransfer>> {
+    return tauriInvoke('transfer_cancel', { id, reason });
   },
-  async stockOut(productId: string, quantity: number, unitPrice: number, remark?: string): Promise<ApiResponse<StockRecord>> {
-    return tauriInvoke('stock_out', { productId, quantity, unitPrice, remark });
+  async getTransfer(id: string): Promise<ApiResponse<StockTransfer>> {
+    return tauriInvoke('transfer_get', { id });
   },
-  async stockAdjust(productId: string, actualQuantity: number, remark?: string): Promise<ApiResponse<StockRecord>> {
-    return tauriInvoke('stock_adjust', { productId, actualQuantity, remark });
+  async listTransfers(status?: TransferStatus): Promise<ApiResponse<StockTransfer[]>> {
+    return tauriInvoke('transfer_list', { status });
+  },
+  async getAvailability(productId: string): Promise<ApiResponse<StockAvailability>> {
+    return tauriInvoke('stock_availability', { productId });
+  },
+  async getOversellPolicy(): Promise<ApiResponse<OversellPolicy>> {
+    return tauriInvoke('stock_get_oversell_policy');
+  },
+  async setOversellPolicy(policy: OversellPolicy): Promise<ApiResponse<OversellPolicy>> {
+    return tauriInvoke('stock_set_oversell_policy', { policy });
+  },
+  async getCostMethod(): Promise<ApiResponse<CostMethod>> {
+    return tauriInvoke('stock_get_cost_method');
+  },
+  async setCostMethod(method: CostMethod): Promise<ApiResponse<CostMethod>> {
+    return tauriInvoke('stock_set_cost_method', { method });
   },
   // 兼容旧 API
   async getProducts(params: SearchParams): Promise<ApiResponse<PaginatedResponse<Product>>> {
@@ -161,25 +329,58 @@ export const inventoryService = {
   },
 
   /**
-   * 创建盘点单
+   * 创建盘点单，冻结仓库（可限定分类）的账面数量
    */
-  async createStockCheck(warehouseId: string): Promise<ApiResponse<{ id: string; code: string }>> {
-    return tauriInvoke('stock_check_create', { warehouseId });
+  async createStockCheck(warehouseId: string, category?: string, remark?: string): Promise<ApiResponse<StockCheck>> {
+    return tauriInvoke('stock_check_create', { warehouseId, category, remark });
   },
 
   /**
-   * 提交盘点结果
+   * 提交盘点结果（覆盖已录入的实盘数量）
    */
-  async submitStockCheck(id: string, items: StockCheckItem[]): Promise<ApiResponse<void>> {
+  async submitStockCheck(id: string, items: StockCountInput[]): Promise<ApiResponse<StockCheck>> {
     return tauriInvoke('stock_check_submit', { id, items });
   },
 
+  /**
+   * 扫码盘点，实盘数量累加
+   */
+  async scanStockCheck(id: string, barcode: string, quantity?: number, location?: string): Promise<ApiResponse<StockCheckItem>> {
+    return tauriInvoke('stock_check_scan', { id, barcode, quantity, location });
+  },
+  async getStockCheck(id: string): Promise<ApiResponse<StockCheck>> {
+    return tauriInvoke('stock_check_get', { id });
+  },
+  async listStockChecks(status?: StockCheckStatus): Promise<ApiResponse<StockCheck[]>> {
+    return tauriInvoke('stock_check_list', { status });
+  },
+  async getStockCheckReport(id: string): Promise<ApiResponse<StockCheckReport>> {
+    return tauriInvoke('stock_check_report', { id });
+  },
+
+  /**
+   * 审核过账，整单差异写入调整流水
+   */
+  async postStockCheck(id: string): Promise<ApiResponse<{ check: StockCheck; records: StockRecord[] }>> {
+    return tauriInvoke('stock_check_post', { id });
+  },
+  async cancelStockCheck(id: string, reason?: string): Promise<ApiResponse<StockCheck>> {
+    return tauriInvoke('stock_check_cancel', { id, reason });
+  },
+
   /**
    * 获取库存预警列表
    */
   async getLowStockProducts(): Promise<ApiResponse<Product[]>> {
     return tauriInvoke('product_low_stock');
   },
+
+  /**
+   * 获取某个仓库的库存预警，按该仓库的结存判断
+   */
+  async getWarehouseLowStock(warehouseId: string): Promise<ApiResponse<WarehouseLowStock[]>> {
+    return tauriInvoke('product_low_stock', { warehouseId });
+  },
 };
 
 // ==================== 订单管理服务 ====================
@@ -191,6 +392,7 @@ export const orderService = {
   async get(id: string): Promise<ApiResponse<Order>> {
     return tauriInvoke('order_get', { id });
   },
+  /** data.items 传 OrderItemInput[] 时订单数量与金额按明细汇总 */
   async create(data: Record<string, unknown>): Promise<ApiResponse<Order>> {
     return tauriInvoke('order_create', { data });
   },
@@ -200,6 +402,9 @@ export const orderService = {
   async delete(id: string): Promise<ApiResponse<void>> {
     return tauriInvoke('order_delete', { id });
   },
+  async exportOrders(path: string, format?: ExportFormat, filter?: ExportFilter): Promise<ApiResponse<ExportResult>> {
+    return tauriInvoke('order_export', { path, format, filter });
+  },
   async confirm(id: string): Promise<ApiResponse<Order>> {
     return tauriInvoke('order_confirm', { id });
   },
@@ -209,6 +414,16 @@ export const orderService = {
   async complete(id: string): Promise<ApiResponse<Order>> {
     return tauriInvoke('order_complete', { id });
   },
+  /** 不传 items 时发出全部未发货数量 */
+  async ship(id: string, items?: ItemShipment[], remark?: string): Promise<ApiResponse<Order>> {
+    return tauriInvoke('order_ship', { id, items, remark });
+  },
+  async returnOrder(id: string, reason: string): Promise<ApiResponse<Order>> {
+    return tauriInvoke('order_return', { id, reason });
+  },
+  async statusHistory(id: string): Promise<ApiResponse<OrderStatusChange[]>> {
+    return tauriInvoke('order_status_history', { id });
+  },
   // 兼容旧 API
   async getOrders(params: SearchParams): Promise<ApiResponse<PaginatedResponse<Order>>> {
     return this.list(params);
@@ -236,6 +451,18 @@ export const orderService = {
   },
 };
 
+// ==================== 收款服务 ====================
+
+export const paymentService = {
+  /** 登记收款或退款，同时更新订单已收金额与客户应收余额 */
+  async record(orderId: string, data: PaymentInput): Promise<ApiResponse<{ payment: Payment; order: Order; paymentStatus: PaymentStatus }>> {
+    return tauriInvoke('payment_record', { orderId, data });
+  },
+  async list(filter: { orderId?: string; customerId?: string } = {}): Promise<ApiResponse<Payment[]>> {
+    return tauriInvoke('payment_list', filter);
+  },
+};
+
 // ==================== 数据同步服务 ====================
 
 export const syncService = {
@@ -247,10 +474,10 @@ export const syncService = {
   },
 
   /**
-   * 开始同步
+   * 立即同步并启动自动同步（可同时更新服务器地址与同步间隔）
    */
-  async startSync(): Promise<ApiResponse<void>> {
-    return tauriInvoke('sync_start');
+  async startSync(apiBaseUrl?: string, intervalMinutes?: number): Promise<ApiResponse<void>> {
+    return tauriInvoke('sync_start', { apiBaseUrl, intervalMinutes });
   },
 
   /**
@@ -280,6 +507,38 @@ export const syncService = {
   async clearSyncErrors(): Promise<ApiResponse<void>> {
     return tauriInvoke('sync_clear_errors');
   },
+
+  /**
+   * 获取同步冲突
+   */
+  async listConflicts(includeResolved = false): Promise<ApiResponse<SyncConflict[]>> {
+    return tauriInvoke('sync_list_conflicts', { includeResolved });
+  },
+
+  /**
+   * 处理同步冲突，merge 时传入各冲突字段最终采用的值
+   */
+  async resolveConflict(
+    id: string,
+    resolution: 'local' | 'remote' | 'merge',
+    values?: Record<string, unknown>
+  ): Promise<ApiResponse<{ id: string; data: Record<string, unknown> | null }>> {
+    return tauriInvoke('sync_resolve_conflict', { id, resolution, values });
+  },
+
+  /**
+   * 获取各实体的冲突策略
+   */
+  async getConflictStrategies(): Promise<ApiResponse<Record<string, ConflictStrategy>>> {
+    return tauriInvoke('sync_get_conflict_strategies');
+  },
+
+  /**
+   * 设置实体的冲突策略
+   */
+  async setConflictStrategy(entityType: string, strategy: ConflictStrategy): Promise<ApiResponse<void>> {
+    return tauriInvoke('sync_set_conflict_strategy', { entityType, strategy });
+  },
 };
 
 // ==================== 扫码枪服务 ====================
@@ -309,10 +568,17 @@ export const scannerService = {
   /**
    * 获取扫码枪状态
    */
-  async getStatus(): Promise<ApiResponse<{ connected: boolean; port?: string }>> {
+  async getStatus(): Promise<ApiResponse<{ connected: boolean; mode: 'serial' | 'wedge'; port?: string; last_error?: string }>> {
     return tauriInvoke('scanner_status');
   },
 
+  /**
+   * 键盘模式：发送应用窗口收到的按键（以回车结束的一组）
+   */
+  async sendWedgeKeys(keys: WedgeKeyEvent[]): Promise<ApiResponse<number>> {
+    return tauriInvoke('scanner_wedge_keys', { keys });
+  },
+
   /**
    * 处理扫码结果
    */
@@ -352,6 +618,27 @@ export const storageService = {
     return tauriInvoke('storage_clear_secure');
   },
 
+  /**
+   * 数据库加密状态
+   */
+  async getDatabaseEncryption(): Promise<ApiResponse<{ supported: boolean; encrypted: boolean }>> {
+    return tauriInvoke('db_encryption_status');
+  },
+
+  /**
+   * 加密本地数据库（管理员）；`message` 列出还需登录一次以获得密钥的账号
+   */
+  async encryptDatabase(): Promise<ApiResponse<void>> {
+    return tauriInvoke('db_encrypt');
+  },
+
+  /**
+   * 更换数据库密钥（管理员）；`message` 列出还需登录一次以获得新密钥的账号
+   */
+  async rotateDatabaseKey(): Promise<ApiResponse<void>> {
+    return tauriInvoke('db_rotate_key');
+  },
+
   /**
    * 导出本地数据
    */
@@ -378,19 +665,40 @@ export const reportService = {
   },
 
   /**
-   * 获取销售报表
+   * 获取销售报表（日期含首尾）
    */
-  async getSalesReport(startDate: string, endDate: string): Promise<ApiResponse<unknown>> {
-    return tauriInvoke('report_sales', { startDate, endDate });
+  async getSalesReport(startDate: string, endDate: string, granularity?: ReportGranularity, top?: number): Promise<ApiResponse<SalesReport>> {
+    return tauriInvoke('report_sales', { startDate, endDate, granularity, top });
   },
 
   /**
    * 获取库存报表
    */
-  async getInventoryReport(): Promise<ApiResponse<unknown>> {
+  async getInventoryReport(): Promise<ApiResponse<InventoryReport>> {
     return tauriInvoke('report_inventory');
   },
 
+  /**
+   * 已完成销售订单的销售成本与毛利（日期含首尾，默认本月）
+   */
+  async getOrderCosts(startDate?: string, endDate?: string): Promise<ApiResponse<OrderCostReport>> {
+    return tauriInvoke('report_order_costs', { startDate, endDate });
+  },
+
+  /**
+   * 获取应收账龄报表
+   */
+  async getArAgingReport(asOf?: string): Promise<ApiResponse<AgingReport>> {
+    return tauriInvoke('report_ar_aging', { asOf });
+  },
+
+  /**
+   * 临期批次预警（days 天内到期，含已过期）
+   */
+  async getExpiringBatches(days = 30, warehouseId?: string): Promise<ApiResponse<{ days: number; items: BatchStock[] }>> {
+    return tauriInvoke('report_expiring_batches', { days, warehouseId });
+  },
+
   /**
    * 获取客户分析报表
    */
@@ -399,10 +707,17 @@ export const reportService = {
   },
 
   /**
-   * 导出报表
+   * 导出报表（日期含首尾，默认本月；库存报表与日期无关）
    */
-  async exportReport(reportType: string, format: 'xlsx' | 'pdf'): Promise<ApiResponse<string>> {
-    return tauriInvoke('report_export', { reportType, format });
+  async exportReport(
+    reportType: ReportExportType,
+    path: string,
+    format?: ExportFormat,
+    startDate?: string,
+    endDate?: string,
+    granularity?: ReportGranularity,
+  ): Promise<ApiResponse<ExportResult>> {
+    return tauriInvoke('report_export', { reportType, path, format, startDate, endDate, granularity });
   },
 };
 
@@ -92,35 +92,82 @@ export interface StockRecord extends BaseEntity {
   totalAmount: number;
   relatedOrderId?: string;
   warehouseId: string;
+  location?: string;
   operatorId: string;
   operatorName: string;
   remark: string;
+  batchNo?: string;
+  productionDate?: string;
+  expiryDate?: string;
 }
 
-export type StockRecordType = 'in' | 'out' | 'adjust' | 'transfer' | 'return';
+export type StockRecordType = 'in' | 'out' | 'adjust' | 'transfer_out' | 'transfer_in' | 'return';
 
 export interface StockCheck extends BaseEntity {
   code: string;
   warehouseId: string;
   warehouseName: string;
+  /** 限定盘点的产品分类，为空表示整个仓库 */
+  category: string;
   status: StockCheckStatus;
+  /** 列表接口不返回明细 */
   items: StockCheckItem[];
   operatorId: string;
   operatorName: string;
-  checkedAt?: string;
+  approverId: string;
+  approverName: string;
+  /** 过账或取消时间 */
+  checkedAt: string;
   remark: string;
 }
 
-export type StockCheckStatus = 'draft' | 'checking' | 'completed' | 'cancelled';
+export type StockCheckStatus = 'checking' | 'completed' | 'cancelled';
 
 export interface StockCheckItem {
+  id: string;
+  checkId: string;
   productId: string;
   productName: string;
   productCode: string;
+  barcode: string;
+  unit: string;
+  location: string;
+  /** 开始盘点时冻结的账面数量 */
   systemQuantity: number;
-  actualQuantity: number;
-  difference: number;
+  /** 未录入时为 null */
+  actualQuantity: number | null;
+  difference: number | null;
+  unitCost: number;
+  /** 差异金额，盘盈为正、盘亏为负 */
+  valueImpact: number;
   remark: string;
+  countedAt: string;
+}
+
+export interface StockCountInput {
+  itemId: string;
+  actualQuantity: number;
+  remark?: string;
+}
+
+export interface StockCheckReport {
+  id: string;
+  code: string;
+  warehouseId: string;
+  warehouseName: string;
+  status: StockCheckStatus;
+  summary: {
+    totalItems: number;
+    countedItems: number;
+    varianceItems: number;
+    gainQuantity: number;
+    lossQuantity: number;
+    gainValue: number;
+    lossValue: number;
+    netValue: number;
+  };
+  variances: StockCheckItem[];
+  uncounted: StockCheckItem[];
 }
 
 // ==================== 订单管理类型 ====================
@@ -139,13 +186,42 @@ export interface Order extends BaseEntity {
   paidAmount: number;
   deliveryAddress: string;
   deliveryDate?: string;
+  /** 发货/收货仓库，为空时按各产品的所属仓库 */
+  warehouseId?: string;
   remark: string;
   operatorId: string;
   operatorName: string;
 }
 
 export type OrderType = 'sale' | 'purchase' | 'return';
-export type OrderStatus = 'draft' | 'pending' | 'confirmed' | 'shipped' | 'completed' | 'cancelled';
+/** pending 为旧版本数据，按草稿处理 */
+export type OrderStatus =
+  | 'draft'
+  | 'pending'
+  | 'confirmed'
+  | 'partially_shipped'
+  | 'shipped'
+  | 'completed'
+  | 'cancelled'
+  | 'returned';
+
+/** 订单状态变更历史 */
+export interface OrderStatusChange {
+  id: string;
+  orderId: string;
+  fromStatus: OrderStatus;
+  toStatus: OrderStatus;
+  operatorId: string;
+  operatorName: string;
+  reason: string;
+  createdAt: string;
+}
+
+/** 分批发货的明细数量 */
+export interface ItemShipment {
+  itemId: string;
+  quantity: number;
+}
 
 export interface OrderItem {
   id: string;
@@ -158,6 +234,60 @@ export interface OrderItem {
   discount: number;
   amount: number;
   remark: string;
+  shippedQuantity?: number;
+}
+
+// ==================== 收款与应收类型 ====================
+
+export type PaymentType = 'payment' | 'refund';
+export type PaymentMethod = 'cash' | 'bank_transfer' | 'wechat' | 'alipay' | 'card' | 'other';
+/** 按已收金额计算的订单收款状态 */
+export type PaymentStatus = 'unpaid' | 'partially_paid' | 'paid';
+
+export interface Payment {
+  id: string;
+  orderId: string;
+  customerId: string;
+  paymentType: PaymentType;
+  /** 退款同样为正数 */
+  amount: number;
+  method: PaymentMethod;
+  reference: string;
+  remark: string;
+  operatorId: string;
+  operatorName: string;
+  paidAt: string;
+  createdAt: string;
+}
+
+export interface PaymentInput {
+  type: PaymentType;
+  amount: number;
+  method: PaymentMethod;
+  reference?: string;
+  remark?: string;
+  /** YYYY-MM-DD，默认当天 */
+  paidAt?: string;
+}
+
+/** 账龄分段：30 天内、31-60、61-90、90 天以上 */
+export interface AgingBuckets {
+  current: number;
+  days30: number;
+  days60: number;
+  days90Plus: number;
+  total: number;
+}
+
+export interface AgingRow extends AgingBuckets {
+  customerId: string;
+  customerName: string;
+}
+
+export interface AgingReport {
+  asOf: string;
+  rows: AgingRow[];
+  totals: AgingBuckets;
 }
 
 // ==================== 用户和权限类型 ====================
@@ -174,7 +304,39 @@ export interface User extends BaseEntity {
   lastLoginAt?: string;
 }
 
-export type UserRole = 'admin' | 'manager' | 'staff' | 'viewer';
+export type UserRole = 'admin' | 'manager' | 'staff' | 'warehouse' | 'viewer';
+
+export type PermissionModule = 'customers' | 'products' | 'orders' | 'stock' | 'reports' | 'settings';
+export type PermissionAction = 'view' | 'create' | 'update' | 'delete' | 'approve';
+
+export interface ModulePermission {
+  module: PermissionModule;
+  actions: PermissionAction[];
+  customized: boolean;
+}
+
+export interface RolePermissions {
+  role: UserRole;
+  permissions: ModulePermission[];
+}
+
+export interface AuditEntry {
+  id: number;
+  user_id?: string;
+  username?: string;
+  module: string;
+  action: string;
+  target: string;
+  result: 'allowed' | 'denied';
+  detail: string;
+  created_at: string;
+}
+
+export interface LoginResult {
+  user: User;
+  token: string;
+  expires_at: string;
+}
 
 // ==================== 系统配置类型 ====================
 
@@ -195,6 +357,8 @@ export interface ApiResponse<T> {
   data?: T;
   error?: string;
   message?: string;
+  /** UNAUTHENTICATED：未登录；PERMISSION_DENIED：无权限 */
+  code?: 'UNAUTHENTICATED' | 'PERMISSION_DENIED';
 }
 
 export interface PaginatedResponse<T> {
@@ -225,59 +389,382 @@ export interface SearchParams extends PaginationParams {
 
 // ==================== 报表类型 ====================
 
-export interface SalesReport {
-  period: string;
-  totalOrders: number;
-  totalAmount: number;
+/** 销售报表汇总粒度，week 为周一开始的自然周 */
+export type ReportGranularity = 'day' | 'week' | 'month';
+
+export interface SalesSummary {
+  orderCount: number;
+  quantity: number;
+  revenue: number;
+  /** 按产品成本价估算 */
+  cost: number;
+  grossProfit: number;
+  /** 毛利率（%） */
+  grossMargin: number;
   averageOrderValue: number;
+}
+
+export interface SalesReport {
+  startDate: string;
+  endDate: string;
+  granularity: ReportGranularity;
+  summary: SalesSummary;
+  /** 与上一个等长期间对比，增长率单位为 %，上期为 0 时为 null */
+  comparison: {
+    previousStart: string;
+    previousEnd: string;
+    previous: SalesSummary;
+    revenueGrowth: number | null;
+    orderGrowth: number | null;
+    grossProfitGrowth: number | null;
+    averageOrderValueGrowth: number | null;
+  };
+  /** period 为区间第一天，没有销售的区间也会列出 */
+  trend: {
+    period: string;
+    orderCount: number;
+    quantity: number;
+    revenue: number;
+    grossProfit: number;
+  }[];
   topProducts: {
     productId: string;
+    productCode: string;
     productName: string;
+    category: string;
+    orderCount: number;
     quantity: number;
-    amount: number;
+    revenue: number;
+    cost: number;
+    grossProfit: number;
+    grossMargin: number;
   }[];
   topCustomers: {
     customerId: string;
     customerName: string;
     orderCount: number;
-    amount: number;
+    revenue: number;
+    grossProfit: number;
+  }[];
+  categories: {
+    category: string;
+    productCount: number;
+    quantity: number;
+    revenue: number;
+    cost: number;
+    grossProfit: number;
+    grossMargin: number;
+  }[];
+  operators: {
+    operatorId: string;
+    operatorName: string;
+    orderCount: number;
+    revenue: number;
+    grossProfit: number;
   }[];
+  generatedAt: string;
 }
 
 export interface InventoryReport {
+  /** 计算库存金额使用的成本计价方法 */
+  method: CostMethod;
   totalProducts: number;
+  totalQuantity: number;
   totalValue: number;
   lowStockProducts: number;
   outOfStockProducts: number;
   categoryBreakdown: {
     category: string;
     productCount: number;
+    quantity: number;
     totalValue: number;
   }[];
-  stockMovement: {
-    date: string;
-    inQuantity: number;
-    outQuantity: number;
-  }[];
+  items: ProductValuation[];
+}
+
+/** 成本计价方法：moving_average 移动加权平均（默认），fifo 先进先出 */
+export type CostMethod = 'moving_average' | 'fifo';
+
+export interface ProductValuation {
+  productId: string;
+  productCode: string;
+  productName: string;
+  category: string;
+  quantity: number;
+  unitCost: number;
+  totalValue: number;
+}
+
+/** 已完成销售订单的销售成本与毛利 */
+export interface OrderCost {
+  orderId: string;
+  orderCode: string;
+  customerId: string;
+  customerName: string;
+  createdAt: string;
+  revenue: number;
+  cost: number;
+  grossProfit: number;
+  /** 毛利率（%） */
+  grossMargin: number;
+}
+
+export interface OrderCostReport {
+  startDate: string;
+  endDate: string;
+  revenue: number;
+  cost: number;
+  grossProfit: number;
+  grossMargin: number;
+  orders: OrderCost[];
+}
+
+// ==================== 导入导出类型 ====================
+
+export type ImportEntity = 'customer' | 'product';
+export type ExportFormat = 'csv' | 'xlsx';
+
+/** 可导入的字段，required 表示新建时必填 */
+export interface ImportField {
+  key: string;
+  label: string;
+  required: boolean;
+}
+
+/** 导入文件的表头与自动识别的列对应关系（字段 → 表头） */
+export interface ImportColumns {
+  headers: string[];
+  rows: number;
+  fields: ImportField[];
+  mapping: Record<string, string>;
+}
+
+/** 行号为文件中的行号 */
+export interface ImportRowError {
+  row: number;
+  field: string;
+  value: string;
+  message: string;
+}
+
+export interface ImportPreviewRow<T> {
+  row: number;
+  action: 'create' | 'update';
+  data: T;
+}
+
+export interface ImportReport<T> {
+  entity: ImportEntity;
+  dryRun: boolean;
+  columns: Record<string, string>;
+  total: number;
+  created: number;
+  updated: number;
+  /** 有错误而跳过的行数 */
+  skipped: number;
+  errors: ImportRowError[];
+  /** 前 200 行 */
+  preview: ImportPreviewRow<T>[];
+}
+
+/** 导出列表的筛选条件，日期为订单下单日期（含首尾） */
+export interface ExportFilter {
+  keyword?: string;
+  category?: string;
+  status?: string;
+  orderType?: string;
+  customerId?: string;
+  startDate?: string;
+  endDate?: string;
+}
+
+export type ReportExportType =
+  | 'sales_trend'
+  | 'sales_products'
+  | 'sales_customers'
+  | 'sales_categories'
+  | 'sales_operators'
+  | 'inventory'
+  | 'order_costs';
+
+export interface ExportResult {
+  path: string;
+  rows: number;
 }
 
 // ==================== 扫码枪类型 ====================
 
 export interface ScannerConfig {
   enabled: boolean;
+  /** serial 串口扫码枪 / wedge 键盘模式扫码枪，默认 serial */
+  mode?: 'serial' | 'wedge';
   port: string;
   baudRate: number;
   dataBits: number;
   stopBits: number;
   parity: 'none' | 'even' | 'odd';
+  /** 键盘模式输入来源：为空时由应用窗口转发按键，否则为 Linux 输入设备路径 */
+  inputDevice?: string;
+  /** 键盘模式相邻按键最大间隔（毫秒），默认 50 */
+  keyIntervalMs?: number;
+  /** 键盘模式条码最小长度，默认 4 */
+  minLength?: number;
+}
+
+export interface WedgeKeyEvent {
+  key: string;
+  timestamp: number;
+}
+
+export type BarcodeSymbology = 'ean13' | 'ean8' | 'upc_a' | 'itf14' | 'code128' | 'gs1' | 'unknown';
+
+export interface ParsedBarcode {
+  raw: string;
+  symbology: BarcodeSymbology;
+  code: string;
+  valid: boolean;
+  error?: string;
+  gtin?: string;
+  batch?: string;
+  /** 生产日期 YYYY-MM-DD（GS1 AI 11） */
+  production?: string;
+  expiry?: string;
+  serial?: string;
+  quantity?: number;
 }
 
 export interface ScanResult {
   barcode: string;
   timestamp: string;
+  parsed?: ParsedBarcode;
   product?: Product;
 }
 
+/** 出入库批次信息，传入 barcode 时从 GS1 字段中提取批号、生产日期与有效期 */
+export interface StockBatchInput {
+  batchNo?: string;
+  productionDate?: string;
+  expiryDate?: string;
+  barcode?: string;
+}
+
+/** 批次在某个仓库、库位的结存；daysToExpiry 已过期为负数，没有有效期时为 null */
+export interface BatchStock {
+  productId: string;
+  productCode: string;
+  productName: string;
+  warehouseId: string;
+  warehouseName: string;
+  location: string;
+  batchNo: string;
+  productionDate: string;
+  expiryDate: string;
+  quantity: number;
+  daysToExpiry: number | null;
+}
+
+export interface BatchOrder {
+  orderId: string;
+  orderCode: string;
+  orderType: OrderType;
+  status: OrderStatus;
+  customerId: string;
+  customerName: string;
+  productId: string;
+  /** 该批次随订单出库的数量 */
+  shipped: number;
+  /** 该批次随订单入库（采购收货、退货）的数量 */
+  received: number;
+  lastMovementAt: string;
+}
+
+/** 批次追溯 */
+export interface BatchTrace {
+  batchNo: string;
+  records: StockRecord[];
+  orders: BatchOrder[];
+  stock: BatchStock[];
+}
+
+/** 超卖策略：reject 可用库存不足时拒绝确认订单和出库，allow 允许库存为负 */
+export type OversellPolicy = 'reject' | 'allow';
+
+/** 可用库存 = 当前库存 - 调拨在途 - 已确认订单的预留 */
+export interface StockAvailability {
+  productId: string;
+  currentStock: number;
+  inTransit: number;
+  reserved: number;
+  available: number;
+}
+
+/** 产品在某个仓库、库位的结存 */
+export interface StockLevel {
+  productId: string;
+  productCode: string;
+  productName: string;
+  warehouseId: string;
+  warehouseName: string;
+  location: string;
+  quantity: number;
+  updatedAt: string;
+}
+
+/** 按仓库查询的库存预警，warehouseStock 为该仓库的结存 */
+export interface WarehouseLowStock extends Product {
+  warehouseId: string;
+  warehouseStock: number;
+}
+
+/** 调拨单：创建即从调出仓库出库（在途），收货后入调入仓库，在途时可取消 */
+export type TransferStatus = 'in_transit' | 'received' | 'cancelled';
+
+export interface StockTransferItem {
+  id: string;
+  transferId: string;
+  productId: string;
+  productName: string;
+  quantity: number;
+  fromLocation: string;
+  toLocation: string;
+  /** 调出时按先到期先出分配的批次，未分批为空 */
+  batchNo: string;
+  productionDate: string;
+  expiryDate: string;
+}
+
+export interface StockTransfer {
+  id: string;
+  code: string;
+  fromWarehouseId: string;
+  toWarehouseId: string;
+  status: TransferStatus;
+  remark: string;
+  operatorId: string;
+  operatorName: string;
+  createdAt: string;
+  updatedAt: string;
+  closedAt: string;
+  items: StockTransferItem[];
+}
+
+/** 库位为空时使用产品在该仓库的默认库位 */
+export interface TransferItemInput {
+  productId: string;
+  quantity: number;
+  fromLocation?: string;
+  toLocation?: string;
+}
+
+/** 创建订单时的明细，产品名称、编码、单位由后端带出 */
+export interface OrderItemInput {
+  productId: string;
+  quantity: number;
+  unitPrice?: number;
+  discount?: number;
+  remark?: string;
+}
+
 // ==================== 同步状态类型 ====================
 
 export interface SyncStatus {
@@ -285,6 +772,22 @@ export interface SyncStatus {
   isSyncing: boolean;
   pendingChanges: number;
   syncErrors: SyncError[];
+  openConflicts?: number;
+  autoSync?: boolean;
+  progress?: SyncProgress;
+}
+
+export interface SyncProgress {
+  progress: number; // 0 ~ 100
+  message: string;
+}
+
+export interface SyncReport {
+  uploaded: number;
+  rejected: number;
+  downloaded: number;
+  conflicts: number;
+  full: boolean;
 }
 
 export interface SyncError {
@@ -297,6 +800,29 @@ export interface SyncError {
   retryCount: number;
 }
 
+export type ConflictStrategy = 'server_wins' | 'client_wins' | 'last_writer_wins' | 'manual';
+
+export interface FieldConflict {
+  field: string;
+  base: unknown;
+  local: unknown;
+  remote: unknown;
+}
+
+export interface SyncConflict {
+  id: string;
+  entity_type: 'customer' | 'product' | 'order';
+  entity_id: string;
+  base_data: Record<string, unknown> | null;
+  local_data: Record<string, unknown> | null;
+  remote_data: Record<string, unknown> | null;
+  fields: FieldConflict[];
+  status: 'open' | 'resolved';
+  resolution: string | null;
+  created_at: string;
+  resolved_at: string | null;
+}
+
 // ==================== 通知类型 ====================
 
 export interface Notification {
@@ -318,8 +844,13 @@ export interface DashboardStats {
   lowStockProducts: number;
   todayOrders: number;
   todayRevenue: number;
+  monthlyOrders: number;
+  /** 本月 1 日至今的销售额 */
   monthlyRevenue: number;
-  monthlyGrowth: number;
+  /** 上月同期（相同天数）的销售额 */
+  previousMonthlyRevenue: number;
+  /** 环比增长（%），上月同期没有销售时为 null */
+  monthlyGrowth: number | null;
 }
 
 export interface DashboardChartData {
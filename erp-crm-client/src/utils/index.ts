@@ -208,9 +208,11 @@ export function getStatusColor(status: string): string {
     draft: 'gray',
     pending: 'warning',
     confirmed: 'primary',
+    partially_shipped: 'primary',
     shipped: 'primary',
     completed: 'success',
     cancelled: 'danger',
+    returned: 'warning',
     
     // 产品状态
     discontinued: 'gray',
@@ -239,9 +241,11 @@ export function getStatusText(status: string): string {
     draft: '草稿',
     pending: '待确认',
     confirmed: '已确认',
+    partially_shipped: '部分发货',
     shipped: '已发货',
     completed: '已完成',
     cancelled: '已取消',
+    returned: '已退货',
     
     // 产品状态
     discontinued: '已停产',
@@ -262,6 +266,15 @@ export function getStatusText(status: string): string {
     out: '出库',
     adjust: '调整',
     transfer: '调拨',
+    transfer_out: '调拨出库',
+    transfer_in: '调拨入库',
+
+    // 调拨单状态
+    in_transit: '在途',
+    received: '已收货',
+
+    // 盘点单状态
+    checking: '盘点中',
   };
   
   return textMap[status] || status;